@@ -1,6 +1,7 @@
 from __future__ import annotations
 
-from typing import List, Optional, Union, Tuple
+import os
+from typing import IO, Iterator, List, Optional, Union, Tuple
 
 
 class SearchParameters:
@@ -13,13 +14,15 @@ class SearchParameters:
 
         Keyword Arguments
         -------------------
-        max_anagram_distance: Union[int,float,Tuple[float,int]]
+        max_anagram_distance: Union[int,float,Tuple[float,int],str]
             Maximum anagram distance. The difference in characters (regardless of order)
-            Must be an integer expressing an absolute value, or float in range 0-1 expressing a ratio. Or a two-tuple expressing a ratio with an absolute limit (float, int)
+            Must be an integer expressing an absolute value, or float in range 0-1 expressing a ratio. Or a two-tuple expressing a ratio with an absolute limit (float, int).
+            Or a string with an integer prefixed by a plus sign (e.g. ``"+1"``), to derive it from the effective edit distance for each input plus that delta.
 
         max_edit_distance: Union[int,float,Tuple[float,int]]
             Maximum edit distance (levenshtein-damarau). The maximum edit distance according to Levenshtein-Damarau. Insertions, deletions, substitutions and transposition all have the same cost (1). It is recommended to set this value slightly lower than the maximum anagram distance.
             Must be an integer expressing an absolute value, or float in range 0-1 expressing a ratio. Or a two-tuple expressing a ratio with an absolute limit (float, int)
+            For very short inputs, absolute values are capped to half the input length, but the effective edit distance is always at least 1 (unless explicitly set to 0), for inputs of one or two characters this also holds for ratios.
 
         max_matches: int
             Number of matches to return per input (set to 0 for unlimited if you want to exhaustively return every possibility within the specified anagram and edit distance)
@@ -43,6 +46,10 @@ class SearchParameters:
         max_seq: int
             Maximum number of candidate sequences to take along to the language modelling stage
 
+        nbest: int
+            Number of alternative sequences (corrected sentences) to return for each sentence in :meth:`VariantModel.find_all_matches`,
+            ranked by decreasing score. Values of 1 or lower (the default) only return the best sequence. Can not exceed ``max_seq``.
+
         single_thread: bool
             Use only a single-thread instead of leveraging multiple cores (lowers resource use and
             performance)
@@ -60,6 +67,14 @@ class SearchParameters:
         contextrules_weight: float
             Weight attributed to the context rules model in finding the most likely sequence
 
+        lm_smoothing_logprob: float
+            Log-probability the language model assigns to unseen transitions and to transitions
+            involving out-of-vocabulary tokens. Only used if ``lm_add_k`` is 0.
+
+        lm_add_k: float
+            If non-zero, use add-k smoothing with this value for k in the language model, instead
+            of the fixed ``lm_smoothing_logprob``.
+
         freq_weight: float
             Weight attributed to the frequency information in frequency reranking, in relation to
             the similarity component. 0 = disabled)
@@ -70,12 +85,115 @@ class SearchParameters:
 
         unicodeoffsets: bool
             Output text offsets in unicode points rather than UTF-8 byte offsets
+
+        global_context_rules: bool
+            Apply the context rules once more over all matches, across hard boundaries, so that rules can also tag
+            sequences that span them. This does not affect which variants are selected.
+
+        neighbourhood_cache_size: int
+            Maximum number of anagram neighbourhoods to keep in a cache in the model, so repeated or similar inputs
+            are processed faster at the cost of memory. 0 = disabled (default).
+
+        freq_transform: str
+            How to transform the frequencies of the variants into frequency scores prior to normalization:
+            ``linear`` (default), ``log`` or ``rank``. The latter two dampen very skewed frequency distributions, making
+            ``freq_weight`` easier to tune.
+
+        freq_cap: int
+            Clip the absolute frequencies of the variants at this ceiling before they are transformed into frequency
+            scores, so extremely frequent entries (e.g. function words) do not win on frequency alone. 0 = no cap (default).
+
+        expansion_frequency: str
+            The frequency a matched variant gets when it is expanded to the item it is a variant of (e.g. for error lists):
+            ``min`` (default) for the minimum of both frequencies, ``product`` for their product, or ``target`` for the
+            frequency of the item referred to.
+
+        max_candidates: int
+            Maximum number of anagram values to consider as candidates for a single input, the nearest ones are kept.
+            Protects against pathological inputs with huge neighbourhoods. 0 = unlimited (default).
+
+        hard_max: int
+            Absolute maximum number of variants to return per input, which also applies if ``max_matches`` is 0 (unlimited).
+            Results beyond it are truncated after ranking and a warning is logged. 0 = no maximum (default).
+
+        ngram_min_length: int
+            Inputs of at least this many characters find their candidates by the character trigrams they share with lexicon
+            entries rather than by anagram distance, which is expensive for long inputs such as multi-word phrases.
+            No maximum edit distance applies to these candidates. Requires :meth:`VariantModel.set_ngram_index`. 0 = disabled (default).
+
+        ngram_min_overlap: float
+            The minimum overlap (Dice coefficient over the character trigrams, 0.0-1.0) for candidates found through
+            the n-gram index (see ``ngram_min_length``). Defaults to 0.5.
+
+        phonetic: bool
+            Also consider variants that share a phonetic code with the input, regardless of their anagram distance.
+            Requires a non-zero phonetic weight (see :class:`Weights`).
+
+        skip_unknown_scripts: bool
+            Pass runs of characters that are not covered by the alphabet (e.g. text in another script) through verbatim
+            as hard boundaries in :meth:`VariantModel.find_all_matches`, rather than attempting to correct them.
+
+        return_edits: bool
+            Return the edits that transform the input into each variant, as a list of dictionaries (with keys
+            ``type``, ``offset``, ``end``, ``input``, ``output``) under the ``edits`` key of each variant
+
+        transfer_case: bool
+            Transfer the casing pattern of the input (all-caps, title case or lowercase) to the text of the variants,
+            so e.g. ``Huys`` is corrected to ``Huis`` rather than to the lowercase lexicon form ``huis``
+
+        collapse_lemmas: bool
+            Collapse variants that share the same lemma (see ``lemma_column`` in :class:`VocabParams`), only the best
+            ranking inflection of each lemma is returned
+
+        sentence_confidence: bool
+            Attach confidence measures for the selected sequence to the first match of each sentence in
+            :meth:`VariantModel.find_all_matches`, so low-confidence sentences can be flagged for review
+
+        explain: bool
+            Return a breakdown of how the score of each variant was computed under the ``explanation`` key of each
+            variant, for diagnosing surprising rankings
+
+        min_length: int
+            Inputs shorter than this many characters are not matched at all: they yield no variants and are left
+            uncorrected by :meth:`VariantModel.find_all_matches`. 0 = no minimum (default).
+
+        skip_exact: bool
+            If the input occurs verbatim in the lexicon, return it as the sole variant with a perfect score rather than
+            searching for other variants. This speeds up cleaning mostly correct text.
+
+        preserve_numeric: float
+            Inputs of which at least this proportion of characters are decimal digits (in any script) are not matched
+            at all, so numbers such as years are not "corrected". 1.0 only affects inputs consisting entirely of
+            digits, 0.5 inputs that are at least half digits. 0.0 = disabled (default).
+
+        oov_policy: str
+            What to output in :meth:`VariantModel.find_all_matches` for matches for which no variant meets the thresholds
+            (out-of-vocabulary matches): ``keep`` keeps the original input (default), ``empty`` outputs nothing,
+            ``placeholder`` outputs ``<unk>``, or use ``placeholder:TEXT`` for a custom placeholder.
+
+        nonboundary_chars: str
+            Non-alphabetic characters that are not token boundaries but part of tokens (e.g. ``-/`` for
+            chemical nomenclature or code identifiers)
+
+        intraword_chars: str
+            Non-alphabetic characters that are part of a token if they occur between two alphabetic characters, and
+            token boundaries otherwise (e.g. ``'`` for contractions like ``don't`` and names like ``O'Brien``)
+
+        soft_boundary_chars: str
+            Characters that form a weak token boundary (defaults to ``'-_``)
+
+        hard_boundary_chars: str
+            Characters that always form a hard token boundary, no matches will span across these
+
+        whitespace_boundary: str
+            The strength of a token boundary consisting of a single whitespace character: ``weak``, ``normal`` (default) or ``hard``
         """
 
-        def get_max_anagram_distance(self) -> Union[int,float,Tuple[float,int]]:
+        def get_max_anagram_distance(self) -> Union[int,float,Tuple[float,int],str]:
             """
             Maximum anagram distance. The difference in characters (regardless of order)
-            Must be an integer expressing an absolute value, or float in range 0-1 expressing a ratio. Or a two-tuple expressing a ratio with an absolute limit (float, int)
+            Must be an integer expressing an absolute value, or float in range 0-1 expressing a ratio. Or a two-tuple expressing a ratio with an absolute limit (float, int).
+            Or a string with an integer prefixed by a plus sign (e.g. ``"+1"``), derived from the effective edit distance plus that delta.
             """
 
         def get_edit_distance(self) -> Union[int,float,Tuple[float,int]]:
@@ -106,6 +224,9 @@ class SearchParameters:
         def get_max_seq(self) -> int:
             """Maximum number of candidate sequences to take along to the language modelling stage"""
 
+        def get_nbest(self) -> int:
+            """Number of alternative sequences to return for each sentence"""
+
         def get_single_thread(self) -> bool:
             """Use only a single-thread instead of leveraging multiple cores (lowers resource use and
             performance)"""
@@ -123,6 +244,12 @@ class SearchParameters:
         def get_contextrules_weight(self) -> float:
             """Weight attributed to the context rules model in finding the most likely sequence"""
 
+        def get_lm_smoothing_logprob(self) -> float:
+            """Log-probability the language model assigns to unseen transitions"""
+
+        def get_lm_add_k(self) -> float:
+            """The value of k for add-k smoothing in the language model (0 = disabled)"""
+
         def get_freq_weight(self) -> float:
             """Weight attributed to the frequency information in frequency reranking, in relation to
             the similarity component. 0 = disabled)"""
@@ -134,6 +261,81 @@ class SearchParameters:
         def get_unicodeoffsets(self) -> bool:
             """Output text offsets in unicode points rather than UTF-8 byte offsets"""
 
+        def get_global_context_rules(self) -> bool:
+            """Apply the context rules once more over all matches, across hard boundaries"""
+
+        def get_neighbourhood_cache_size(self) -> int:
+            """Maximum number of anagram neighbourhoods to keep in a cache (0 = disabled)"""
+
+        def get_freq_transform(self) -> str:
+            """How to transform frequencies into frequency scores (``linear``, ``log`` or ``rank``)"""
+
+        def get_freq_cap(self) -> int:
+            """Clip the absolute frequencies of the variants at this ceiling (0 = no cap)"""
+
+        def get_expansion_frequency(self) -> str:
+            """The frequency a matched variant gets when it is expanded to the item it is a variant of (``min``, ``product`` or ``target``)"""
+
+        def get_max_candidates(self) -> int:
+            """Maximum number of anagram values to consider as candidates for a single input (0 = unlimited)"""
+
+        def get_hard_max(self) -> int:
+            """Absolute maximum number of variants to return per input, even if max_matches is 0 (0 = no maximum)"""
+
+        def get_ngram_min_length(self) -> int:
+            """Inputs of at least this many characters find their candidates through the n-gram index (0 = disabled)"""
+
+        def get_ngram_min_overlap(self) -> float:
+            """The minimum overlap (Dice coefficient) for candidates found through the n-gram index"""
+
+        def get_phonetic(self) -> bool:
+            """Also consider variants that share a phonetic code with the input"""
+
+        def get_skip_unknown_scripts(self) -> bool:
+            """Pass runs of characters that are not covered by the alphabet through verbatim"""
+
+        def get_return_edits(self) -> bool:
+            """Return the edits that transform the input into each variant"""
+
+        def get_transfer_case(self) -> bool:
+            """Transfer the casing pattern of the input to the text of the variants"""
+
+        def get_collapse_lemmas(self) -> bool:
+            """Collapse variants that share the same lemma"""
+
+        def get_sentence_confidence(self) -> bool:
+            """Attach confidence measures for the selected sequence to the first match of each sentence"""
+
+        def get_explain(self) -> bool:
+            """Return a breakdown of how the score of each variant was computed"""
+
+        def get_min_length(self) -> int:
+            """Inputs shorter than this many characters are not matched at all (0 = no minimum)"""
+
+        def get_skip_exact(self) -> bool:
+            """Return inputs that occur verbatim in the lexicon as the sole variant, without searching for others"""
+
+        def get_preserve_numeric(self) -> float:
+            """Inputs of which at least this proportion of characters are digits are not matched (0.0 = disabled)"""
+
+        def get_oov_policy(self) -> str:
+            """What to output for out-of-vocabulary matches: keep, empty, or placeholder:TEXT"""
+
+        def get_nonboundary_chars(self) -> str:
+            """Non-alphabetic characters that are not token boundaries but part of tokens"""
+
+        def get_intraword_chars(self) -> str:
+            """Non-alphabetic characters that are part of a token if they occur between two alphabetic characters"""
+
+        def get_soft_boundary_chars(self) -> str:
+            """Characters that form a weak token boundary"""
+
+        def get_hard_boundary_chars(self) -> str:
+            """Characters that always form a hard token boundary"""
+
+        def get_whitespace_boundary(self) -> str:
+            """The strength of a token boundary consisting of a single whitespace character"""
+
         def to_dict(self) -> dict:
             """Returns all parameters in a dictionary"""
 
@@ -157,7 +359,29 @@ class VocabParams:
             Frequency handling in case of duplicate items (may be across multiple lexicons), can be "sum","max","min","replace"
 
         vocabtype: str
-            "NONE", "INDEXED", "TRANSPARENT" or "LM"
+            "NONE", "INDEXED", "TRANSPARENT", "PHRASE", "LM" or "CORPUS". "PHRASE" marks all entries as atomic phrases:
+            multi-word entries are not split on spaces but treated as a single token (also in language modelling).
+            "CORPUS" uses the entries both for variant matching (like "INDEXED") and for language modelling (like "LM").
+
+        metadata_column: int
+            Column containing metadata for the entry (if any, 0-indexed), such as a lemma, a category or a source URL.
+            The metadata is passed on to the variants that are returned.
+
+        lemma_column: int
+            Column containing the lemma of the entry (if any, 0-indexed), for lexicons of inflected forms.
+            The lemma is passed on to the variants that are returned, see also ``collapse_lemmas`` in :class:`SearchParameters`.
+
+        delimiter: str
+            The (single) character that separates the columns, a tab by default. Set to ``,`` to read CSV files.
+
+        quoting: bool
+            Handle RFC 4180-style quoting: fields enclosed in double quotes may contain the delimiter, double quotes
+            within them are escaped by doubling them. Quoted fields can not span multiple lines.
+
+        weight: float
+            Weight of the lexicon: the distance score of variants from this lexicon is multiplied by it, so variants
+            from an authoritative lexicon (> 1.0) can be preferred over those from a noisy one (< 1.0). If a variant
+            occurs in multiple lexicons, the highest weight applies. Defaults to 1.0.
        """
 
 
@@ -186,6 +410,12 @@ class Weights:
 
         case: float
             Weight to assign to difference in case (lowercase/uppercase)
+
+        jarowinkler: float
+            Weight for the Jaro-Winkler similarity (disabled by default)
+
+        phonetic: float
+            Weight for phonetic similarity, i.e. sharing a Double Metaphone code (disabled by default). A phonetic index is only built if this is non-zero.
         """
 
         def get_ld(self) -> float:
@@ -203,6 +433,12 @@ class Weights:
         def get_case(self) -> float:
             """Returns the weight for the case differences"""
 
+        def get_jarowinkler(self) -> float:
+            """Returns the weight for the Jaro-Winkler similarity"""
+
+        def get_phonetic(self) -> float:
+            """Returns the weight for phonetic similarity"""
+
         def set_ld(self, value:float):
             """Sets the weight for the Levenshtein (or Damarau-Levenshtein) distance"""
 
@@ -218,20 +454,69 @@ class Weights:
         def set_case(self, value: float):
             """Sets the weight for the case differences"""
 
+        def set_jarowinkler(self, value: float):
+            """Sets the weight for the Jaro-Winkler similarity"""
+
+        def set_phonetic(self, value: float):
+            """Sets the weight for phonetic similarity"""
+
         def to_dict(self) -> dict:
-            """Returns all weights as a dictionary"""
+    """The text of the variant, as in the lexicon (with the casing of the input applied to it if ``transfer_case`` is enabled in the parameters)"""
+
+
+class VariantResult:
+    """A variant found for a given input, along with its scores. Instances are returned by the VariantModel and can not be instantiated directly."""
+
+    text: str
+    """The text of the variant, as in the lexicon"""
+
+    score: float
+    """The combined score of the variant"""
+
+    dist_score: float
+    """The distance score of the variant"""
+
+    freq_score: float
+    """The frequency score of the variant"""
+
+    via: Optional[str]
+    """The vocabulary item through which this variant was found (for variants found via a variant list), if any. This is the item that was originally matched, also if it was resolved over multiple transparent variants."""
+
+    lexicons: List[str]
+    """The names of the lexicons this variant occurs in"""
+
+    metadata: Optional[str]
+    """The metadata associated with this variant in the lexicon (see ``metadata_column`` in :class:`VocabParams`), if any"""
+
+    lemma: Optional[str]
+    """The lemma of this variant in the lexicon (see ``lemma_column`` in :class:`VocabParams`), if any"""
+
+    normalized: Optional[List[str]]
+    """The normalized form of the variant, only set if ``debug_normalization`` is enabled in the parameters"""
+
+    edits: Optional[List[dict]]
+    """The edit operations to get from the input to the variant, only set if ``return_edits`` is enabled in the parameters"""
+
+    explanation: Optional[dict]
+    """A breakdown of how the score of the variant was computed, only set if ``explain`` is enabled in the parameters.
+    Holds the individual similarity components (``distance``, a dictionary), the ``combined_score`` prior to applying
+    confusables and lexicon weights, the ``confusable_weight``, the ``lexicon_weight`` and, in search mode,
+    ``lm_perplexity`` and ``context_score`` of the selected sequence (if applicable)"""
+
+    def as_dict(self) -> dict:
+        """Returns the variant as a dictionary. For backward compatibility, keys can also be accessed directly on the instance as with a dictionary (e.g. ``variant['text']``)"""
 
 
 class VariantModel:
     """The VariantModel is the most high-level model of analiticcl, it holds all data required for variant matching."""
 
-    def __init__(self, alphabet_file: str, weights: Weights, debug: int = 0):
+    def __init__(self, alphabet_file: Union[str, os.PathLike], weights: Weights, debug: int = 0):
         """Instantiate a new variant model
 
         Parameters
         --------------
 
-        alphabet_file: str
+        alphabet_file: Union[str, os.PathLike]
             Path to the alphabet file to load for this model
 
         weights: Weights
@@ -241,51 +526,125 @@ class VariantModel:
             Debug level
         """
 
+    def add_alphabet_entries(self, entries: List[List[str]]):
+        """
+        Append entries to the alphabet, each entry is a list of strings that are treated as identical (like a line in an
+        alphabet file). This must be done before the model is built; vocabulary that was already added is normalized
+        again according to the extended alphabet. Raises a ``ValueError`` if the model was already built or if an entry
+        is empty or already in the alphabet.
+        """
+
     def build(self):
         """
         Build the anagram index (and secondary index) so the model
         is ready for variant matching
         """
 
+    def free(self):
+        """
+        Drop the underlying model and release its memory immediately rather than waiting for the
+        garbage collector. Any later call on this model raises a RuntimeError.
+        """
+
+    def close(self):
+        """Alias for free()"""
+
+    def __enter__(self) -> VariantModel:
+        """Use the model as a context manager, it is freed when leaving the ``with`` block"""
+
+    def __exit__(self, exc_type, exc_value, traceback) -> bool:
+        """Frees the model, exceptions are not suppressed"""
+
     def add_to_vocabulary(self, text: str, frequency: Optional[int], params: VocabParams):
         """
         Add an item to the vocabulary. This is a lower-level interface.
         """
 
-    def read_vocabulary(self, filename: str, params: VocabParams):
+    def read_vocabulary(self, filename: Union[str, os.PathLike, IO], params: VocabParams):
         """
         Load vocabulary (a lexicon or corpus-derived lexicon) from a TSV file
         May contain frequency information. This is a lower-level interface.
-        The parameters define what value can be read from what column
+        The parameters define what value can be read from what column.
+        The file is passed either as a path (``str`` or ``os.PathLike``) or as a file-like object (such as an open file
+        or an ``io.StringIO``/``io.BytesIO`` buffer) of which the contents are read entirely. The ``name`` attribute of a
+        file-like object (if any) is registered as the name of the lexicon, file-like objects are not decompressed.
         """
 
     def add_contextrule(self, pattern: str, score: float, tag: List[str], tagoffset: List[str]):
         pass
 
-    def read_lexicon(self, filename: str):
+    def read_lexicon(self, filename: Union[str, os.PathLike, IO]):
         """
         Higher order function to load a lexicon and make it available to the model.
-        Wraps around read_vocabulary() with default parameters.
+        Wraps around read_vocabulary() with default parameters, so also accepts a file-like object.
         """
 
-    def read_lm(self, filename: str):
+    def read_lm(self, filename: Union[str, os.PathLike, IO]):
         """
         Higher order function to load a language model and make it available to the model.
-        Wraps around read_vocabulary() with default parameters.
+        Wraps around read_vocabulary() with default parameters, so also accepts a file-like object.
         """
 
-    def read_variants(self, filename: str):
+    def read_corpus(self, filename: Union[str, os.PathLike, IO]):
+        """
+        Higher order function to load a corpus-derived frequency list that is used both as a lexicon (for variant
+        matching) and as a language model, so it only needs to be read once.
+        Wraps around read_vocabulary() with default parameters, so also accepts a file-like object.
+        """
+
+    def read_variants(self, filename: Union[str, os.PathLike, IO], transparent: bool):
         """
         Load a weighted variant list (set transparent to true if this is an error list and you
-        don't want the variants themselves to be returned when matching; i.e. they are transparent)
+        don't want the variants themselves to be returned when matching; i.e. they are transparent).
+        Like :meth:`read_vocabulary`, this also accepts a file-like object.
+        Each row may end with an extra column holding the name of a previously loaded lexicon prefixed by ``@``,
+        the row is then attributed to that lexicon rather than to the variant list itself.
         """
 
-    def read_confusiblelist(self, filename: str):
+    def read_confusiblelist(self, filename: Union[str, os.PathLike]):
         """
         Load a confusable list
         """
 
-    def read_contextrules(self, filename: str):
+    def read_affixlist(self, filename: Union[str, os.PathLike]):
+        """
+        Load an affix list: a TSV file with strippable affixes in the first column (``-jes`` for a suffix, ``ge-`` for a prefix)
+        and optionally weights in the second column
+        """
+
+    def add_to_affixes(self, affix: str, weight: float):
+        """
+        Add an affix rule: a suffix (e.g. ``-jes``) or prefix (e.g. ``ge-``) that may be stripped from the input, so the stem that
+        remains is matched against the lexicon as well. The scores of variants found for the stem are multiplied by the weight,
+        which must be in the range (0.0, 1.0]. Unlike confusables, affix rules only apply at the beginning or end of the input.
+        """
+
+    def add_keyboard_confusables(self, layout: Union[str,List[str]], weight: float):
+        """
+        Add confusables for substitutions between adjacent keys on a keyboard. The layout is either "qwerty", "azerty",
+        or a custom layout expressed as a list of rows (top to bottom)
+        """
+
+    def compute_confusable_weight(self, input: str, candidate: str) -> float:
+        """
+        Computes the weight the confusables assign to the given pair of an input and a candidate from the vocabulary
+        (a KeyError is raised if it is not in the vocabulary). This is the weight the distance score of the candidate
+        is multiplied by; it is 1.0 if no confusables apply, greater than 1.0 for favourable and smaller than 1.0 for
+        unfavourable confusables.
+        """
+
+    def read_substitution_costs(self, filename: Union[str, os.PathLike]):
+        """
+        Load substitution costs for the edit distance computation from a TSV file (two characters and a cost)
+        """
+
+    def add_substitution_cost(self, a: str, b: str, cost: float):
+        """
+        Set the cost of substituting character a for b (and vice versa) in the edit distance computation.
+        Both must be single characters in the alphabet. Arbitrary substitutions have cost 1.0.
+        """
+
+    def read_contextrules(self, filename: Union[str, os.PathLike]):
         """
         Load context rules from a TSV file
         """
@@ -293,14 +652,150 @@ class VariantModel:
     def __contains__(self, text: str):
         """Is this exact text in a loaded lexicon?"""
 
-    def find_variants(self, input: str, params: SearchParameters) -> List[dict]:
+    def vocabulary_size(self) -> int:
+        """Returns the number of entries in the vocabulary, this includes the special tokens (``<bos>``, ``<eos>``, ``<unk>``)"""
+
+    def alphabet_coverage_report(self) -> dict:
+        """Returns statistics on the characters in the (indexed) vocabulary that are not covered by the alphabet: a
+        dictionary with the counts of all such characters (``unknown_chars``), the number of entries that contain them
+        (``affected_entries``) and the texts of the entries that consist mostly of them (``dominated_entries``). Many
+        unknown characters are a sign of a wrong or incomplete alphabet."""
+
+    def iter_vocab(self) -> Iterator[dict]:
+        """Iterates over all entries in the vocabulary (including the special tokens), yielding a dictionary with the
+        keys ``text``, ``frequency``, ``tokencount``, ``lexicons`` and (if set) ``metadata`` and ``lemma`` for each entry"""
+
+    def lexicons(self) -> List[str]:
+        """Returns the names of all loaded lexicons, in the order in which they were loaded"""
+
+    def disable_lexicon(self, index: int):
+        """Disables the lexicon with the specified index (in the order of :meth:`lexicons`): variants that occur only in
+        disabled lexicons are no longer returned. This is cheap and can be undone using :meth:`enable_lexicon`, but the
+        disabled entries are still considered (and filtered out) for every query."""
+
+    def enable_lexicon(self, index: int):
+        """Enables a lexicon that was previously disabled using :meth:`disable_lexicon`"""
+
+    def remove_lexicon(self, index: int):
+        """Removes all entries of the lexicon with the specified index from the model, entries that occur in no other
+        lexicon are removed from the index. This is more expensive than :meth:`disable_lexicon` but queries no longer
+        pay for the removed entries. The removed lexicon retains its index (and name) in :meth:`lexicons`."""
+
+    def alphabet(self) -> List[List[str]]:
+        """Returns the alphabet as a list of lists of strings, each inner list holds all the representations of one
+        character in the alphabet"""
+
+    def find_variants(self, input: str, params: SearchParameters) -> List[VariantResult]:
         """Find variants in the vocabulary for a given string (in its totality), returns a list of variants with scores and their source lexicons"""
 
+    def score_pair(self, input: str, candidate: str) -> float:
+        """Computes the similarity score between two arbitrary strings using the weights of the model, without consulting the index.
+        This is the same score :meth:`find_variants` would assign to the candidate as a variant of the input (prior to frequency or context weighting),
+        using the default score combination. The candidate need not be in the lexicon. This is useful for ranking a small candidate set produced elsewhere."""
+
+    def decode_sequence(self, lattice: List[Tuple[str, List[Tuple[str, float]]]], params: SearchParameters) -> List[int]:
+        """Decodes the most likely sequence through a lattice of candidates produced elsewhere, using the same decoder as :meth:`find_all_matches`
+        (candidate cost, language model and context rules, weighted as configured in the parameters).
+        The lattice holds, for each input token, a tuple of the input text and a list of ``(candidate, cost)`` tuples, where the cost ranges from 0.0 (best) to 1.0 (worst).
+        Candidates that are not in the vocabulary are considered out-of-vocabulary.
+        Returns the index of the selected candidate for each position (0 for positions without candidates)."""
+
+    def best_variant(self, input: str, params: SearchParameters) -> Optional[VariantResult]:
+        """Returns the best (top-ranked) variant for the input, or ``None`` if no variant meets the thresholds in the parameters (notably ``score_threshold``)"""
+
     def find_variants_par(self, input: List[str], params: SearchParameters) -> List[dict]:
-        """Find variants in the vocabulary for all multiple string items at once, provided in in the input list. Returns a list of variants with scores and their source lexicons. Will use parallellisation under the hood."""
+        """Find variants in the vocabulary for all multiple string items at once, provided in in the input list. Returns a list of dictionaries with the ``input`` and its ``variants`` (a list of :class:`VariantResult`). Will use parallellisation under the hood."""
+
+    def precompute(self, queries: List[str], params: SearchParameters) -> dict:
+        """Computes the anagram neighbourhoods of a known set of queries up front (in parallel) and adds them to the
+        neighbourhood cache, so subsequent lookups with the same parameters are served from the cache. This is intended
+        for batch jobs where the queries are known in advance. Requires ``neighbourhood_cache_size`` to be set in the
+        parameters, large enough to hold all neighbourhoods.
+        Returns a dictionary with statistics: the number of ``queries``, the number of neighbourhoods that were ``computed``,
+        the number of queries that were already ``cached`` (or share a neighbourhood with another query), the number of
+        queries that were ``skipped`` (e.g. empty ones) and the ``duration`` in seconds."""
 
     def find_all_matches(self, text: str, params: SearchParameters) -> List[dict]:
-        """Searches a text and returns all highest-ranking variants found in the text"""
+        """Searches a text and returns all highest-ranking variants found in the text.
+        Each match is a dictionary with the matched ``input``, its ``offset`` and its ``variants`` (a list of :class:`VariantResult`).
+        If ``consolidate_matches`` is disabled in the parameters, all n-gram matches (including overlapping ones)
+        are returned rather than a single most likely sequence, the ``offset`` and ``n`` (number of tokens) keys
+        can then be used to make a selection.
+        Besides ``offset`` (which follows the ``unicodeoffsets`` parameter), each match always carries both a ``byteoffset``
+        (UTF-8 bytes) and a ``charoffset`` (unicode points), each a dictionary with ``begin`` and ``end`` keys.
+        Matches tagged by context rules carry a ``tags`` key: a list of dictionaries with the tag name (``tag``) and
+        the sequence number of the match in the tagged sequence (``seqnr``).
+        Matches for which no variant meets the thresholds carry ``"oov": True`` and an ``output`` key with the text to output
+        according to the ``oov_policy`` parameter (``None`` if nothing should be output).
+        If the ``nbest`` parameter is larger than 1, the first match of each sentence carries an ``alternatives`` key: a list of
+        dictionaries with the ``text`` of the sentence according to an alternative sequence, its combined ``score``, its ``variant_cost``
+        and (if applicable) its ``lm_perplexity`` and ``context_score``, ranked by decreasing score.
+        If the ``sentence_confidence`` parameter is set, the first match of each sentence carries a ``confidence`` key: a
+        dictionary with the mean ``score`` of the selected variants (weighted by the number of tokens they cover, out-of-vocabulary
+        tokens count as 0.0) and (if applicable) the ``lm_perplexity`` and ``context_score`` of the selected sequence."""
+
+    def correct_text(self, text: str, params: SearchParameters) -> str:
+        """Searches a text like :meth:`find_all_matches` and returns the corrected text as a plain string.
+        Every match is replaced by its output (the selected variant, or whatever the ``oov_policy`` prescribes
+        for matches without a variant), all text in between matches is retained verbatim."""
+
+    def set_casefold(self, casefold: bool):
+        """
+        Enable or disable case-insensitive matching: lexicon entries and input are lowercased prior to matching,
+        whereas the returned variants keep the casing of the lexicon. This should be called before loading any lexicons.
+        This corresponds to the `--casefold` option for the CLI version
+        """
+
+    def set_case_first_char(self, case_first_char: bool):
+        """
+        Only compare the casing of the first character of the input and a variant for the case component of the score
+        (as earlier versions did), rather than the fraction of aligned characters with the same casing.
+        This corresponds to the `--case-first-char` option for the CLI version
+        """
+
+    def set_distinct_unknowns(self, distinct_unknowns: bool):
+        """
+        Encode each distinct character that is not in the alphabet with its own symbol for the edit distance computation,
+        rather than all as the same unknown character, so differing unknown characters count as a substitution.
+        This favours precision over recall on noisy (multilingual) input.
+        This corresponds to the `--distinct-unknowns` option for the CLI version
+        """
+
+    def set_check_alphabet_coverage(self, check: bool):
+        """
+        Check how well the alphabet covers the vocabulary when building the model, and warn about characters that are
+        not in the alphabet and about entries that consist mostly of such characters.
+        This corresponds to the `--check-alphabet` option for the CLI version
+        """
+
+    def set_ngram_index(self, enabled: bool):
+        """
+        Enable or disable the character n-gram index, an alternative candidate generator for long inputs (see the
+        ``ngram_min_length`` search parameter). The index is built by :meth:`build`, or immediately if the model was already built.
+        This corresponds to the `--ngram-candidates` option for the CLI version
+        """
+
+    def set_insertions(self, enabled: bool):
+        """
+        Enable or disable the secondary sorted index (enabled by default). Without it, the model uses less memory and
+        queries are faster, but variants that are longer than the input (i.e. that need insertions) are never found, only
+        deletions and substitutions are. Call this prior to :meth:`build`.
+        This corresponds to the `--no-insertions` option for the CLI version
+        """
+
+    def set_lm_smoothing(self, smoothing: str):
+        """
+        Configure the smoothing method for the language model: ``fixed`` (the default, unseen transitions get a fixed
+        log-probability or add-k smoothing as configured in the :class:`SearchParameters`), ``witten-bell`` or ``kneser-ney``.
+        This corresponds to the `--lm-smoothing` option for the CLI version
+        """
+
+    def set_num_threads(self, num_threads: int):
+        """
+        Use a dedicated thread pool with the specified number of threads for all parallel processing by this model,
+        rather than the global one (which is controlled by the ``RAYON_NUM_THREADS`` environment variable).
+        A value of 0 lets the system decide.
+        """
 
     def set_confusables_before_pruning(self):
         """
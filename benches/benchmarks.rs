@@ -17,6 +17,14 @@ pub fn benchmarks(c: &mut Criterion) {
         }));
     }
 
+    let matcher = AlphabetMatcher::new(&alphabet);
+    for input in inputs {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("anahash_matcher",format!("input {} chars",input.chars().count())), &input, |b, input| b.iter(||{
+            input.anahash(&matcher)
+        }));
+    }
+
     group.finish();
 
     let mut group = c.benchmark_group("anahash_edit");
@@ -156,6 +164,33 @@ pub fn benchmarks(c: &mut Criterion) {
         model.build()
     }));
 
+    //most of the anagram neighbourhood of a transposition is pruned on character counts alone
+    let mut model = VariantModel::new_with_alphabet(get_test_alphabet().0, Weights::default(), 0);
+    for item in &["house","hose","horse","mouse","blouse","houses","mouses","hoes","shoe","shore","whose"] {
+        model.add_to_vocabulary(item,None,&params);
+    }
+    model.build();
+    let searchparams = get_test_searchparams()
+        .with_edit_distance(DistanceThreshold::Absolute(1))
+        .with_anagram_distance(DistanceThreshold::Absolute(3));
+
+    c.bench_function("model_find_variants_transposition", |b| b.iter(||{
+        model.find_variants(black_box("ohuse"), &searchparams)
+    }));
+
+    //the pre-filter must be much cheaper than the distance computation it saves
+    let query = "ohuse".normalize_to_alphabet(&alphabet);
+    let query_counts = symbol_counts(&query);
+    let candidate = "blouse".normalize_to_alphabet(&alphabet);
+
+    c.bench_function("distance_damerau_levenshtein", |b| b.iter(||{
+        damerau_levenshtein(black_box(&query), black_box(&candidate), 1)
+    }));
+
+    c.bench_function("distance_multiset_difference", |b| b.iter(||{
+        multiset_difference(black_box(&query), &query_counts, black_box(&candidate))
+    }));
+
 }
 
 /*
@@ -12,6 +12,6 @@ model = VariantModel(os.path.join(basedir,"examples","simple.alphabet.tsv"), Wei
 model.read_lexicon(os.path.join(basedir, "examples","eng.aspell.lexicon"))
 model.build()
 result = model.find_variants("udnerstand", SearchParameters(max_edit_distance=3))
-print(json.dumps(result, ensure_ascii=False, indent=4))
+print(json.dumps(result, ensure_ascii=False, indent=4, default=lambda variant: variant.as_dict()))
 results = model.find_all_matches("I do not udnerstand the probleem", SearchParameters(max_edit_distance=3,max_ngram=1))
-print(json.dumps(results, ensure_ascii=False, indent=4))
+print(json.dumps(results, ensure_ascii=False, indent=4, default=lambda variant: variant.as_dict()))
@@ -1,7 +1,7 @@
-use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::exceptions::{PyKeyError, PyRuntimeError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::*;
-use rayon::prelude::*;
+use std::path::PathBuf;
 use std::str::FromStr;
 //use pyo3::wrap_pymodule;
 
@@ -47,6 +47,16 @@ impl PyWeights {
                             instance.weights.case = value
                         }
                     }
+                    "jarowinkler" => {
+                        if let Ok(Some(value)) = value.extract() {
+                            instance.weights.jarowinkler = value
+                        }
+                    }
+                    "phonetic" => {
+                        if let Ok(Some(value)) = value.extract() {
+                            instance.weights.phonetic = value
+                        }
+                    }
                     _ => eprintln!("Ignored unknown kwargs option {}", key),
                 }
             }
@@ -74,6 +84,14 @@ impl PyWeights {
     fn get_case(&self) -> PyResult<f64> {
         Ok(self.weights.case)
     }
+    #[getter]
+    fn get_jarowinkler(&self) -> PyResult<f64> {
+        Ok(self.weights.jarowinkler)
+    }
+    #[getter]
+    fn get_phonetic(&self) -> PyResult<f64> {
+        Ok(self.weights.phonetic)
+    }
 
     #[setter]
     fn set_ld(&mut self, value: f64) -> PyResult<()> {
@@ -100,6 +118,16 @@ impl PyWeights {
         self.weights.case = value;
         Ok(())
     }
+    #[setter]
+    fn set_jarowinkler(&mut self, value: f64) -> PyResult<()> {
+        self.weights.jarowinkler = value;
+        Ok(())
+    }
+    #[setter]
+    fn set_phonetic(&mut self, value: f64) -> PyResult<()> {
+        self.weights.phonetic = value;
+        Ok(())
+    }
 
     fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
         let dict = PyDict::new_bound(py);
@@ -108,6 +136,8 @@ impl PyWeights {
         dict.set_item("prefix", self.get_prefix()?)?;
         dict.set_item("suffix", self.get_suffix()?)?;
         dict.set_item("case", self.get_case()?)?;
+        dict.set_item("jarowinkler", self.get_jarowinkler()?)?;
+        dict.set_item("phonetic", self.get_phonetic()?)?;
         Ok(dict)
     }
 }
@@ -126,13 +156,57 @@ fn extract_distance_threshold<'py>(
         if let Ok(v) = libanaliticcl::DistanceThreshold::from_str(v.extract()?) {
             Ok(v)
         } else {
-            Err(PyValueError::new_err(format!("Unable to convert from string ({}). Must be an integer expressing an absolute value, or float in range 0-1 expressing a ratio. Or a two-tuple expression a ratio with an absolute limit (float;int)",v)))
+            Err(PyValueError::new_err(format!("Unable to convert from string ({}). Must be an integer expressing an absolute value, or float in range 0-1 expressing a ratio. Or a two-tuple expression a ratio with an absolute limit (float;int). Or an integer prefixed by a plus sign expressing a delta relative to the edit distance",v)))
         }
     } else {
         Err(PyValueError::new_err("Must be an integer expressing an absolute value, or float in range 0-1 expressing a ratio. Or a two-tuple expression a ratio with an absolute limit (float, int)"))
     }
 }
 
+fn extract_score_combination(value: &str) -> PyResult<libanaliticcl::ScoreCombination> {
+    libanaliticcl::ScoreCombination::from_str(value)
+        .map_err(|e| PyValueError::new_err(format!("{}", e)))
+}
+
+fn extract_expansion_frequency(value: &str) -> PyResult<libanaliticcl::ExpansionFrequency> {
+    libanaliticcl::ExpansionFrequency::from_str(value)
+        .map_err(|e| PyValueError::new_err(format!("{}", e)))
+}
+
+fn extract_freq_transform(value: &str) -> PyResult<libanaliticcl::FrequencyTransform> {
+    libanaliticcl::FrequencyTransform::from_str(value)
+        .map_err(|e| PyValueError::new_err(format!("{}", e)))
+}
+
+fn extract_oov_policy(value: &str) -> PyResult<libanaliticcl::OovPolicy> {
+    libanaliticcl::OovPolicy::from_str(value).map_err(|e| PyValueError::new_err(format!("{}", e)))
+}
+
+fn extract_protected_patterns(patterns: Vec<String>) -> PyResult<Vec<regex::Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            regex::Regex::new(pattern).map_err(|e| {
+                PyValueError::new_err(format!("Invalid protected pattern ({}): {}", pattern, e))
+            })
+        })
+        .collect()
+}
+
+fn extract_boundary_strength(value: &str) -> PyResult<libanaliticcl::BoundaryStrength> {
+    libanaliticcl::BoundaryStrength::from_str(value)
+        .map_err(|e| PyValueError::new_err(format!("{}", e)))
+}
+
+fn boundary_strength_to_str(value: libanaliticcl::BoundaryStrength) -> &'static str {
+    match value {
+        libanaliticcl::BoundaryStrength::None => "none",
+        libanaliticcl::BoundaryStrength::Weak => "weak",
+        libanaliticcl::BoundaryStrength::Normal => "normal",
+        libanaliticcl::BoundaryStrength::Hard => "hard",
+    }
+}
+
 #[pyclass(dict, name = "SearchParameters")]
 #[derive(Default, Clone)]
 pub struct PySearchParameters {
@@ -189,6 +263,11 @@ impl PySearchParameters {
                         }
                         Err(v) => eprintln!("{}", v),
                     },
+                    "nbest" => match value.extract() {
+                        Ok(Some(value)) => instance.data.nbest = value,
+                        Ok(None) => eprintln!("No value specified for nbest parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
                     "stop_at_exact_match" => {
                         if let Ok(Some(value)) = value.extract() {
                             if value {
@@ -205,6 +284,159 @@ impl PySearchParameters {
                         Ok(None) => eprintln!("No value specified for single_thread parameter"),
                         Err(v) => eprintln!("{}", v),
                     },
+                    "score_combination" => match value.extract::<Option<String>>() {
+                        Ok(Some(value)) => {
+                            instance.data.score_combination = extract_score_combination(&value)?
+                        }
+                        Ok(None) => {
+                            eprintln!("No value specified for score_combination parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "freq_transform" => match value.extract::<Option<String>>() {
+                        Ok(Some(value)) => {
+                            instance.data.freq_transform = extract_freq_transform(&value)?
+                        }
+                        Ok(None) => eprintln!("No value specified for freq_transform parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "freq_cap" => match value.extract() {
+                        Ok(Some(value)) => instance.data.freq_cap = value,
+                        Ok(None) => eprintln!("No value specified for freq_cap parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "expansion_frequency" => match value.extract::<Option<String>>() {
+                        Ok(Some(value)) => {
+                            instance.data.expansion_frequency = extract_expansion_frequency(&value)?
+                        }
+                        Ok(None) => {
+                            eprintln!("No value specified for expansion_frequency parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "debug_normalization" => match value.extract() {
+                        Ok(Some(value)) => instance.data.debug_normalization = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for debug_normalization parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "phonetic" => match value.extract() {
+                        Ok(Some(value)) => instance.data.phonetic = value,
+                        Ok(None) => eprintln!("No value specified for phonetic parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "return_edits" => match value.extract() {
+                        Ok(Some(value)) => instance.data.return_edits = value,
+                        Ok(None) => eprintln!("No value specified for return_edits parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "transfer_case" => match value.extract() {
+                        Ok(Some(value)) => instance.data.transfer_case = value,
+                        Ok(None) => eprintln!("No value specified for transfer_case parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "collapse_lemmas" => match value.extract() {
+                        Ok(Some(value)) => instance.data.collapse_lemmas = value,
+                        Ok(None) => eprintln!("No value specified for collapse_lemmas parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "sentence_confidence" => match value.extract() {
+                        Ok(Some(value)) => instance.data.sentence_confidence = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for sentence_confidence parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "explain" => match value.extract() {
+                        Ok(Some(value)) => instance.data.explain = value,
+                        Ok(None) => eprintln!("No value specified for explain parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "skip_exact" => match value.extract() {
+                        Ok(Some(value)) => instance.data.skip_exact = value,
+                        Ok(None) => eprintln!("No value specified for skip_exact parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "preserve_numeric" => match value.extract() {
+                        Ok(Some(value)) => instance.data.preserve_numeric = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for preserve_numeric parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "oov_policy" => match value.extract::<Option<String>>() {
+                        Ok(Some(value)) => instance.data.oov_policy = extract_oov_policy(&value)?,
+                        Ok(None) => eprintln!("No value specified for oov_policy parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "min_length" => match value.extract() {
+                        Ok(Some(value)) => instance.data.min_length = value,
+                        Ok(None) => eprintln!("No value specified for min_length parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "nonboundary_chars" => match value.extract::<Option<String>>() {
+                        Ok(Some(value)) => {
+                            instance.data.boundary_config.nonboundary = value.chars().collect()
+                        }
+                        Ok(None) => {
+                            eprintln!("No value specified for nonboundary_chars parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "intraword_chars" => match value.extract::<Option<String>>() {
+                        Ok(Some(value)) => {
+                            instance.data.boundary_config.intraword = value.chars().collect()
+                        }
+                        Ok(None) => {
+                            eprintln!("No value specified for intraword_chars parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "soft_boundary_chars" => match value.extract::<Option<String>>() {
+                        Ok(Some(value)) => {
+                            instance.data.boundary_config.soft = value.chars().collect()
+                        }
+                        Ok(None) => {
+                            eprintln!("No value specified for soft_boundary_chars parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "hard_boundary_chars" => match value.extract::<Option<String>>() {
+                        Ok(Some(value)) => {
+                            instance.data.boundary_config.hard = value.chars().collect()
+                        }
+                        Ok(None) => {
+                            eprintln!("No value specified for hard_boundary_chars parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "whitespace_boundary" => match value.extract::<Option<String>>() {
+                        Ok(Some(value)) => {
+                            instance.data.boundary_config.whitespace =
+                                extract_boundary_strength(&value)?
+                        }
+                        Ok(None) => {
+                            eprintln!("No value specified for whitespace_boundary parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "protected_patterns" => match value.extract::<Option<Vec<String>>>() {
+                        Ok(Some(value)) => {
+                            instance.data.protected_patterns = extract_protected_patterns(value)?
+                        }
+                        Ok(None) => {
+                            eprintln!("No value specified for protected_patterns parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "skip_unknown_scripts" => match value.extract() {
+                        Ok(Some(value)) => instance.data.skip_unknown_scripts = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for skip_unknown_scripts parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
                     "unicodeoffsets" => match value.extract() {
                         Ok(Some(value)) => instance.data.unicodeoffsets = value,
                         Ok(None) => {
@@ -229,6 +461,18 @@ impl PySearchParameters {
                         }
                         Err(v) => eprintln!("{}", v),
                     },
+                    "lm_smoothing_logprob" => match value.extract() {
+                        Ok(Some(value)) => instance.data.lm_smoothing_logprob = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for lm_smoothing_logprob parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "lm_add_k" => match value.extract() {
+                        Ok(Some(value)) => instance.data.lm_add_k = value,
+                        Ok(None) => eprintln!("No value specified for lm_add_k parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
                     "variantmodel_weight" => match value.extract() {
                         Ok(Some(value)) => instance.data.variantmodel_weight = value,
                         Ok(None) => {
@@ -243,6 +487,46 @@ impl PySearchParameters {
                         }
                         Err(v) => eprintln!("{}", v),
                     },
+                    "global_context_rules" => match value.extract() {
+                        Ok(Some(value)) => instance.data.global_context_rules = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for global_context_rules parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "neighbourhood_cache_size" => match value.extract() {
+                        Ok(Some(value)) => instance.data.neighbourhood_cache_size = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for neighbourhood_cache_size parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "ngram_min_length" => match value.extract() {
+                        Ok(Some(value)) => instance.data.ngram_min_length = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for ngram_min_length parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "ngram_min_overlap" => match value.extract() {
+                        Ok(Some(value)) => instance.data.ngram_min_overlap = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for ngram_min_overlap parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "hard_max" => match value.extract() {
+                        Ok(Some(value)) => instance.data.hard_max = value,
+                        Ok(None) => eprintln!("No value specified for hard_max parameter"),
+                        Err(v) => eprintln!("{}", v),
+                    },
+                    "max_candidates" => match value.extract() {
+                        Ok(Some(value)) => instance.data.max_candidates = value,
+                        Ok(None) => {
+                            eprintln!("No value specified for max_candidates parameter")
+                        }
+                        Err(v) => eprintln!("{}", v),
+                    },
                     "consolidate_matches" => match value.extract() {
                         Ok(Some(value)) => instance.data.consolidate_matches = value,
                         Ok(None) => {
@@ -270,6 +554,9 @@ impl PySearchParameters {
                 dict.set_item("limit", limit)?;
                 Ok(dict.into_any())
             }
+            libanaliticcl::DistanceThreshold::DerivedFromEdit(delta) => {
+                Ok(format!("+{}", delta).into_py(py).into_bound(py))
+            }
         }
     }
     #[getter]
@@ -285,6 +572,9 @@ impl PySearchParameters {
                 dict.set_item("limit", limit)?;
                 Ok(dict.into_any())
             }
+            libanaliticcl::DistanceThreshold::DerivedFromEdit(delta) => {
+                Ok(format!("+{}", delta).into_py(py).into_bound(py))
+            }
         }
     }
     #[getter]
@@ -308,6 +598,10 @@ impl PySearchParameters {
         Ok(self.data.max_seq)
     }
     #[getter]
+    fn get_nbest(&self) -> PyResult<usize> {
+        Ok(self.data.nbest)
+    }
+    #[getter]
     fn get_single_thread(&self) -> PyResult<bool> {
         Ok(self.data.single_thread)
     }
@@ -332,6 +626,38 @@ impl PySearchParameters {
         Ok(self.data.contextrules_weight)
     }
     #[getter]
+    fn get_lm_smoothing_logprob(&self) -> PyResult<f32> {
+        Ok(self.data.lm_smoothing_logprob)
+    }
+    #[getter]
+    fn get_lm_add_k(&self) -> PyResult<f32> {
+        Ok(self.data.lm_add_k)
+    }
+    #[getter]
+    fn get_global_context_rules(&self) -> PyResult<bool> {
+        Ok(self.data.global_context_rules)
+    }
+    #[getter]
+    fn get_neighbourhood_cache_size(&self) -> PyResult<usize> {
+        Ok(self.data.neighbourhood_cache_size)
+    }
+    #[getter]
+    fn get_max_candidates(&self) -> PyResult<usize> {
+        Ok(self.data.max_candidates)
+    }
+    #[getter]
+    fn get_hard_max(&self) -> PyResult<usize> {
+        Ok(self.data.hard_max)
+    }
+    #[getter]
+    fn get_ngram_min_length(&self) -> PyResult<usize> {
+        Ok(self.data.ngram_min_length)
+    }
+    #[getter]
+    fn get_ngram_min_overlap(&self) -> PyResult<f64> {
+        Ok(self.data.ngram_min_overlap)
+    }
+    #[getter]
     fn get_consolidate_matches(&self) -> PyResult<bool> {
         Ok(self.data.consolidate_matches)
     }
@@ -339,6 +665,112 @@ impl PySearchParameters {
     fn get_unicodeoffsets(&self) -> PyResult<bool> {
         Ok(self.data.unicodeoffsets)
     }
+    #[getter]
+    fn get_debug_normalization(&self) -> PyResult<bool> {
+        Ok(self.data.debug_normalization)
+    }
+    #[getter]
+    fn get_phonetic(&self) -> PyResult<bool> {
+        Ok(self.data.phonetic)
+    }
+    #[getter]
+    fn get_return_edits(&self) -> PyResult<bool> {
+        Ok(self.data.return_edits)
+    }
+    #[getter]
+    fn get_transfer_case(&self) -> PyResult<bool> {
+        Ok(self.data.transfer_case)
+    }
+    #[getter]
+    fn get_collapse_lemmas(&self) -> PyResult<bool> {
+        Ok(self.data.collapse_lemmas)
+    }
+    #[getter]
+    fn get_sentence_confidence(&self) -> PyResult<bool> {
+        Ok(self.data.sentence_confidence)
+    }
+    #[getter]
+    fn get_explain(&self) -> PyResult<bool> {
+        Ok(self.data.explain)
+    }
+    #[getter]
+    fn get_min_length(&self) -> PyResult<usize> {
+        Ok(self.data.min_length)
+    }
+    #[getter]
+    fn get_skip_exact(&self) -> PyResult<bool> {
+        Ok(self.data.skip_exact)
+    }
+    #[getter]
+    fn get_preserve_numeric(&self) -> PyResult<f32> {
+        Ok(self.data.preserve_numeric)
+    }
+    #[getter]
+    fn get_oov_policy(&self) -> PyResult<String> {
+        Ok(self.data.oov_policy.to_string())
+    }
+    #[getter]
+    fn get_nonboundary_chars(&self) -> PyResult<String> {
+        Ok(self.data.boundary_config.nonboundary.iter().collect())
+    }
+    #[getter]
+    fn get_intraword_chars(&self) -> PyResult<String> {
+        Ok(self.data.boundary_config.intraword.iter().collect())
+    }
+    #[getter]
+    fn get_soft_boundary_chars(&self) -> PyResult<String> {
+        Ok(self.data.boundary_config.soft.iter().collect())
+    }
+    #[getter]
+    fn get_hard_boundary_chars(&self) -> PyResult<String> {
+        Ok(self.data.boundary_config.hard.iter().collect())
+    }
+    #[getter]
+    fn get_whitespace_boundary(&self) -> PyResult<&str> {
+        Ok(boundary_strength_to_str(
+            self.data.boundary_config.whitespace,
+        ))
+    }
+    #[getter]
+    fn get_protected_patterns(&self) -> PyResult<Vec<String>> {
+        Ok(self
+            .data
+            .protected_patterns
+            .iter()
+            .map(|pattern| pattern.as_str().to_owned())
+            .collect())
+    }
+    #[getter]
+    fn get_skip_unknown_scripts(&self) -> PyResult<bool> {
+        Ok(self.data.skip_unknown_scripts)
+    }
+    #[getter]
+    fn get_score_combination(&self) -> PyResult<&str> {
+        Ok(match self.data.score_combination {
+            libanaliticcl::ScoreCombination::ArithmeticMean => "arithmetic",
+            libanaliticcl::ScoreCombination::GeometricMean => "geometric",
+        })
+    }
+    #[getter]
+    fn get_freq_transform(&self) -> PyResult<&str> {
+        Ok(match self.data.freq_transform {
+            libanaliticcl::FrequencyTransform::Linear => "linear",
+            libanaliticcl::FrequencyTransform::Log => "log",
+            libanaliticcl::FrequencyTransform::Rank => "rank",
+        })
+    }
+    #[getter]
+    fn get_freq_cap(&self) -> PyResult<u32> {
+        Ok(self.data.freq_cap)
+    }
+    #[getter]
+    fn get_expansion_frequency(&self) -> PyResult<&str> {
+        Ok(match self.data.expansion_frequency {
+            libanaliticcl::ExpansionFrequency::Minimum => "min",
+            libanaliticcl::ExpansionFrequency::Product => "product",
+            libanaliticcl::ExpansionFrequency::Target => "target",
+        })
+    }
 
     #[setter]
     fn set_max_anagram_distance<'py>(&mut self, value: &Bound<'py, PyAny>) -> PyResult<()> {
@@ -368,6 +800,11 @@ impl PySearchParameters {
         Ok(())
     }
     #[setter]
+    fn set_nbest(&mut self, value: usize) -> PyResult<()> {
+        self.data.nbest = value;
+        Ok(())
+    }
+    #[setter]
     fn set_single_thread(&mut self, value: bool) -> PyResult<()> {
         self.data.single_thread = value;
         Ok(())
@@ -393,6 +830,16 @@ impl PySearchParameters {
         Ok(())
     }
     #[setter]
+    fn set_lm_smoothing_logprob(&mut self, value: f32) -> PyResult<()> {
+        self.data.lm_smoothing_logprob = value;
+        Ok(())
+    }
+    #[setter]
+    fn set_lm_add_k(&mut self, value: f32) -> PyResult<()> {
+        self.data.lm_add_k = value;
+        Ok(())
+    }
+    #[setter]
     fn set_variantmodel_weight(&mut self, value: f32) -> PyResult<()> {
         self.data.variantmodel_weight = value;
         Ok(())
@@ -405,54 +852,256 @@ impl PySearchParameters {
     }
 
     #[setter]
-    fn set_unicodeoffsets(&mut self, value: bool) -> PyResult<()> {
-        self.data.unicodeoffsets = value;
+    fn set_global_context_rules(&mut self, value: bool) -> PyResult<()> {
+        self.data.global_context_rules = value;
         Ok(())
     }
 
     #[setter]
-    fn set_stop_at_exact_match(&mut self, value: bool) -> PyResult<()> {
-        if value {
-            self.data.stop_criterion = libanaliticcl::StopCriterion::StopAtExactMatch;
-        } else {
-            self.data.stop_criterion = libanaliticcl::StopCriterion::Exhaustive;
-        };
+    fn set_neighbourhood_cache_size(&mut self, value: usize) -> PyResult<()> {
+        self.data.neighbourhood_cache_size = value;
         Ok(())
     }
 
-    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
-        let dict = PyDict::new_bound(py);
-        dict.set_item("max_anagram_distance", self.get_max_anagram_distance(py)?)?;
-        dict.set_item("max_edit_distance", self.get_max_edit_distance(py)?)?;
-        dict.set_item("max_matches", self.get_max_matches()?)?;
-        dict.set_item("score_threshold", self.get_score_threshold()?)?;
-        dict.set_item("cutoff_threshold", self.get_cutoff_threshold()?)?;
-        dict.set_item("max_ngram", self.get_max_ngram()?)?;
-        dict.set_item("max_seq", self.get_max_seq()?)?;
-        dict.set_item("single_thread", self.get_single_thread()?)?;
-        dict.set_item("context_weight", self.get_context_weight()?)?;
-        dict.set_item("freq_weight", self.get_freq_weight()?)?;
-        dict.set_item("lm_weight", self.get_lm_weight()?)?;
-        dict.set_item("contextrules_weight", self.get_contextrules_weight()?)?;
-        dict.set_item("variantmodel_weight", self.get_variantmodel_weight()?)?;
-        dict.set_item("consolidate_matches", self.get_consolidate_matches()?)?;
-        dict.set_item("unicodeoffsets", self.get_unicodeoffsets()?)?;
-        Ok(dict)
+    #[setter]
+    fn set_max_candidates(&mut self, value: usize) -> PyResult<()> {
+        self.data.max_candidates = value;
+        Ok(())
     }
-}
 
-#[pyclass(dict, name = "VocabParams")]
-#[derive(Default, Clone)]
-pub struct PyVocabParams {
-    data: libanaliticcl::VocabParams,
-}
+    #[setter]
+    fn set_hard_max(&mut self, value: usize) -> PyResult<()> {
+        self.data.hard_max = value;
+        Ok(())
+    }
 
-#[pymethods]
-impl PyVocabParams {
-    #[new]
-    #[pyo3(signature = (**kwargs))]
-    fn new<'py>(kwargs: Option<&Bound<'py, PyDict>>) -> PyResult<Self> {
-        let mut instance = Self::default();
+    #[setter]
+    fn set_ngram_min_length(&mut self, value: usize) -> PyResult<()> {
+        self.data.ngram_min_length = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_ngram_min_overlap(&mut self, value: f64) -> PyResult<()> {
+        self.data.ngram_min_overlap = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_unicodeoffsets(&mut self, value: bool) -> PyResult<()> {
+        self.data.unicodeoffsets = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_debug_normalization(&mut self, value: bool) -> PyResult<()> {
+        self.data.debug_normalization = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_phonetic(&mut self, value: bool) -> PyResult<()> {
+        self.data.phonetic = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_return_edits(&mut self, value: bool) -> PyResult<()> {
+        self.data.return_edits = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_transfer_case(&mut self, value: bool) -> PyResult<()> {
+        self.data.transfer_case = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_collapse_lemmas(&mut self, value: bool) -> PyResult<()> {
+        self.data.collapse_lemmas = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_sentence_confidence(&mut self, value: bool) -> PyResult<()> {
+        self.data.sentence_confidence = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_explain(&mut self, value: bool) -> PyResult<()> {
+        self.data.explain = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_min_length(&mut self, value: usize) -> PyResult<()> {
+        self.data.min_length = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_skip_exact(&mut self, value: bool) -> PyResult<()> {
+        self.data.skip_exact = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_preserve_numeric(&mut self, value: f32) -> PyResult<()> {
+        self.data.preserve_numeric = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_oov_policy(&mut self, value: String) -> PyResult<()> {
+        self.data.oov_policy = extract_oov_policy(&value)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_nonboundary_chars(&mut self, value: String) -> PyResult<()> {
+        self.data.boundary_config.nonboundary = value.chars().collect();
+        Ok(())
+    }
+
+    #[setter]
+    fn set_intraword_chars(&mut self, value: String) -> PyResult<()> {
+        self.data.boundary_config.intraword = value.chars().collect();
+        Ok(())
+    }
+
+    #[setter]
+    fn set_soft_boundary_chars(&mut self, value: String) -> PyResult<()> {
+        self.data.boundary_config.soft = value.chars().collect();
+        Ok(())
+    }
+
+    #[setter]
+    fn set_hard_boundary_chars(&mut self, value: String) -> PyResult<()> {
+        self.data.boundary_config.hard = value.chars().collect();
+        Ok(())
+    }
+
+    #[setter]
+    fn set_whitespace_boundary(&mut self, value: String) -> PyResult<()> {
+        self.data.boundary_config.whitespace = extract_boundary_strength(&value)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_protected_patterns(&mut self, value: Vec<String>) -> PyResult<()> {
+        self.data.protected_patterns = extract_protected_patterns(value)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_skip_unknown_scripts(&mut self, value: bool) -> PyResult<()> {
+        self.data.skip_unknown_scripts = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_score_combination(&mut self, value: String) -> PyResult<()> {
+        self.data.score_combination = extract_score_combination(&value)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_freq_transform(&mut self, value: String) -> PyResult<()> {
+        self.data.freq_transform = extract_freq_transform(&value)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_freq_cap(&mut self, value: u32) -> PyResult<()> {
+        self.data.freq_cap = value;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_expansion_frequency(&mut self, value: String) -> PyResult<()> {
+        self.data.expansion_frequency = extract_expansion_frequency(&value)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_stop_at_exact_match(&mut self, value: bool) -> PyResult<()> {
+        if value {
+            self.data.stop_criterion = libanaliticcl::StopCriterion::StopAtExactMatch;
+        } else {
+            self.data.stop_criterion = libanaliticcl::StopCriterion::Exhaustive;
+        };
+        Ok(())
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("max_anagram_distance", self.get_max_anagram_distance(py)?)?;
+        dict.set_item("max_edit_distance", self.get_max_edit_distance(py)?)?;
+        dict.set_item("max_matches", self.get_max_matches()?)?;
+        dict.set_item("score_threshold", self.get_score_threshold()?)?;
+        dict.set_item("cutoff_threshold", self.get_cutoff_threshold()?)?;
+        dict.set_item("max_ngram", self.get_max_ngram()?)?;
+        dict.set_item("max_seq", self.get_max_seq()?)?;
+        dict.set_item("nbest", self.get_nbest()?)?;
+        dict.set_item("single_thread", self.get_single_thread()?)?;
+        dict.set_item("context_weight", self.get_context_weight()?)?;
+        dict.set_item("freq_weight", self.get_freq_weight()?)?;
+        dict.set_item("lm_weight", self.get_lm_weight()?)?;
+        dict.set_item("contextrules_weight", self.get_contextrules_weight()?)?;
+        dict.set_item("lm_smoothing_logprob", self.get_lm_smoothing_logprob()?)?;
+        dict.set_item("lm_add_k", self.get_lm_add_k()?)?;
+        dict.set_item("variantmodel_weight", self.get_variantmodel_weight()?)?;
+        dict.set_item("consolidate_matches", self.get_consolidate_matches()?)?;
+        dict.set_item("global_context_rules", self.get_global_context_rules()?)?;
+        dict.set_item(
+            "neighbourhood_cache_size",
+            self.get_neighbourhood_cache_size()?,
+        )?;
+        dict.set_item("max_candidates", self.get_max_candidates()?)?;
+        dict.set_item("hard_max", self.get_hard_max()?)?;
+        dict.set_item("ngram_min_length", self.get_ngram_min_length()?)?;
+        dict.set_item("ngram_min_overlap", self.get_ngram_min_overlap()?)?;
+        dict.set_item("unicodeoffsets", self.get_unicodeoffsets()?)?;
+        dict.set_item("score_combination", self.get_score_combination()?)?;
+        dict.set_item("freq_transform", self.get_freq_transform()?)?;
+        dict.set_item("freq_cap", self.get_freq_cap()?)?;
+        dict.set_item("expansion_frequency", self.get_expansion_frequency()?)?;
+        dict.set_item("protected_patterns", self.get_protected_patterns()?)?;
+        dict.set_item("skip_unknown_scripts", self.get_skip_unknown_scripts()?)?;
+        dict.set_item("debug_normalization", self.get_debug_normalization()?)?;
+        dict.set_item("phonetic", self.get_phonetic()?)?;
+        dict.set_item("return_edits", self.get_return_edits()?)?;
+        dict.set_item("transfer_case", self.get_transfer_case()?)?;
+        dict.set_item("collapse_lemmas", self.get_collapse_lemmas()?)?;
+        dict.set_item("sentence_confidence", self.get_sentence_confidence()?)?;
+        dict.set_item("explain", self.get_explain()?)?;
+        dict.set_item("min_length", self.get_min_length()?)?;
+        dict.set_item("skip_exact", self.get_skip_exact()?)?;
+        dict.set_item("preserve_numeric", self.get_preserve_numeric()?)?;
+        dict.set_item("oov_policy", self.get_oov_policy()?)?;
+        dict.set_item("nonboundary_chars", self.get_nonboundary_chars()?)?;
+        dict.set_item("intraword_chars", self.get_intraword_chars()?)?;
+        dict.set_item("soft_boundary_chars", self.get_soft_boundary_chars()?)?;
+        dict.set_item("hard_boundary_chars", self.get_hard_boundary_chars()?)?;
+        dict.set_item("whitespace_boundary", self.get_whitespace_boundary()?)?;
+        Ok(dict)
+    }
+}
+
+#[pyclass(dict, name = "VocabParams")]
+#[derive(Default, Clone)]
+pub struct PyVocabParams {
+    data: libanaliticcl::VocabParams,
+}
+
+#[pymethods]
+impl PyVocabParams {
+    #[new]
+    #[pyo3(signature = (**kwargs))]
+    fn new<'py>(kwargs: Option<&Bound<'py, PyDict>>) -> PyResult<Self> {
+        let mut instance = Self::default();
         if let Some(kwargs) = kwargs {
             for (key, value) in kwargs {
                 match key.downcast()?.extract()? {
@@ -471,6 +1120,31 @@ impl PyVocabParams {
                             instance.data.index = value
                         }
                     }
+                    "metadata_column" => {
+                        if let Ok(Some(value)) = value.extract() {
+                            instance.data.metadata_column = value
+                        }
+                    }
+                    "lemma_column" => {
+                        if let Ok(Some(value)) = value.extract() {
+                            instance.data.lemma_column = value
+                        }
+                    }
+                    "delimiter" => {
+                        if let Ok(Some(value)) = value.extract() {
+                            instance.data.delimiter = value
+                        }
+                    }
+                    "quoting" => {
+                        if let Ok(Some(value)) = value.extract() {
+                            instance.data.quoting = value
+                        }
+                    }
+                    "weight" => {
+                        if let Ok(Some(value)) = value.extract() {
+                            instance.data.weight = value
+                        }
+                    }
                     "freqhandling" => match value.downcast()?.extract()? {
                         "sum" => {
                             instance.data.freq_handling = libanaliticcl::FrequencyHandling::Sum
@@ -489,6 +1163,17 @@ impl PyVocabParams {
                             value
                         ),
                     },
+                    "compression" => match value.downcast()?.extract()? {
+                        "auto" => instance.data.compression = libanaliticcl::Compression::Auto,
+                        "none" => instance.data.compression = libanaliticcl::Compression::None,
+                        "gzip" => instance.data.compression = libanaliticcl::Compression::Gzip,
+                        "xz" => instance.data.compression = libanaliticcl::Compression::Xz,
+                        "bzip2" => instance.data.compression = libanaliticcl::Compression::Bzip2,
+                        _ => eprintln!(
+                            "WARNING: Ignored unknown value for VocabParams.compression ({})",
+                            value
+                        ),
+                    },
                     "vocabtype" => match value.downcast()?.extract()? {
                         "NONE" => instance.data.vocab_type = libanaliticcl::VocabType::NONE,
                         "INDEXED" => instance.data.vocab_type = libanaliticcl::VocabType::INDEXED,
@@ -496,7 +1181,12 @@ impl PyVocabParams {
                             instance.data.vocab_type = libanaliticcl::VocabType::TRANSPARENT
                                 | libanaliticcl::VocabType::INDEXED
                         }
+                        "PHRASE" => {
+                            instance.data.vocab_type =
+                                libanaliticcl::VocabType::PHRASE | libanaliticcl::VocabType::INDEXED
+                        }
                         "LM" => instance.data.vocab_type = libanaliticcl::VocabType::LM,
+                        "CORPUS" => instance.data.vocab_type = libanaliticcl::VocabType::CORPUS,
                         _ => eprintln!(
                             "WARNING: Ignored unknown value for VocabParams.vocabtype ({})",
                             value
@@ -523,6 +1213,26 @@ impl PyVocabParams {
     fn get_index(&self) -> PyResult<u8> {
         Ok(self.data.index)
     }
+    #[getter]
+    fn get_metadata_column(&self) -> PyResult<Option<u8>> {
+        Ok(self.data.metadata_column)
+    }
+    #[getter]
+    fn get_lemma_column(&self) -> PyResult<Option<u8>> {
+        Ok(self.data.lemma_column)
+    }
+    #[getter]
+    fn get_delimiter(&self) -> PyResult<char> {
+        Ok(self.data.delimiter)
+    }
+    #[getter]
+    fn get_quoting(&self) -> PyResult<bool> {
+        Ok(self.data.quoting)
+    }
+    #[getter]
+    fn get_weight(&self) -> PyResult<f64> {
+        Ok(self.data.weight)
+    }
 
     #[setter]
     fn set_text_column(&mut self, value: u8) -> PyResult<()> {
@@ -539,48 +1249,306 @@ impl PyVocabParams {
         self.data.index = value;
         Ok(())
     }
+    #[setter]
+    fn set_metadata_column(&mut self, value: Option<u8>) -> PyResult<()> {
+        self.data.metadata_column = value;
+        Ok(())
+    }
+    #[setter]
+    fn set_lemma_column(&mut self, value: Option<u8>) -> PyResult<()> {
+        self.data.lemma_column = value;
+        Ok(())
+    }
+    #[setter]
+    fn set_delimiter(&mut self, value: char) -> PyResult<()> {
+        self.data.delimiter = value;
+        Ok(())
+    }
+    #[setter]
+    fn set_quoting(&mut self, value: bool) -> PyResult<()> {
+        self.data.quoting = value;
+        Ok(())
+    }
+    #[setter]
+    fn set_weight(&mut self, value: f64) -> PyResult<()> {
+        self.data.weight = value;
+        Ok(())
+    }
+}
+
+/// A variant found for an input, along with its scores
+#[pyclass(name = "VariantResult")]
+pub struct PyVariantResult {
+    text: String,
+    score: f64,
+    dist_score: f64,
+    freq_score: f64,
+    via: Option<String>,
+    lexicons: Vec<String>,
+    metadata: Option<String>,
+    lemma: Option<String>,
+    normalized: Option<Vec<String>>,
+    edits: Option<Vec<libanaliticcl::EditOp>>,
+    explanation: Option<libanaliticcl::ScoreExplanation>,
+}
+
+#[pymethods]
+impl PyVariantResult {
+    #[getter]
+    fn get_text(&self) -> &str {
+        self.text.as_str()
+    }
+    #[getter]
+    fn get_score(&self) -> f64 {
+        self.score
+    }
+    #[getter]
+    fn get_dist_score(&self) -> f64 {
+        self.dist_score
+    }
+    #[getter]
+    fn get_freq_score(&self) -> f64 {
+        self.freq_score
+    }
+    #[getter]
+    fn get_via(&self) -> Option<&str> {
+        self.via.as_deref()
+    }
+    #[getter]
+    fn get_lexicons(&self) -> Vec<&str> {
+        self.lexicons.iter().map(|s| s.as_str()).collect()
+    }
+    #[getter]
+    fn get_metadata(&self) -> Option<&str> {
+        self.metadata.as_deref()
+    }
+    #[getter]
+    fn get_lemma(&self) -> Option<&str> {
+        self.lemma.as_deref()
+    }
+    #[getter]
+    fn get_normalized(&self) -> Option<Vec<&str>> {
+        self.normalized
+            .as_ref()
+            .map(|normalized| normalized.iter().map(|s| s.as_str()).collect())
+    }
+    #[getter]
+    fn get_edits<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyList>>> {
+        if let Some(edits) = &self.edits {
+            let editlist = PyList::empty_bound(py);
+            for edit in edits.iter() {
+                let editdict = PyDict::new_bound(py);
+                editdict.set_item("type", edit.optype.as_str())?;
+                editdict.set_item("offset", edit.offset)?;
+                editdict.set_item("end", edit.end)?;
+                editdict.set_item("input", edit.input.as_str())?;
+                editdict.set_item("output", edit.output.as_str())?;
+                editlist.append(editdict)?;
+            }
+            Ok(Some(editlist))
+        } else {
+            Ok(None)
+        }
+    }
+    #[getter]
+    fn get_explanation<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        if let Some(explanation) = &self.explanation {
+            let distance = &explanation.distance;
+            let distdict = PyDict::new_bound(py);
+            distdict.set_item("ld", distance.ld)?;
+            distdict.set_item("weighted_ld", distance.weighted_ld)?;
+            distdict.set_item("lcs", distance.lcs)?;
+            distdict.set_item("prefixlen", distance.prefixlen)?;
+            distdict.set_item("suffixlen", distance.suffixlen)?;
+            distdict.set_item("samecase", distance.samecase)?;
+            distdict.set_item("casesimilarity", distance.casesimilarity)?;
+            distdict.set_item("jarowinkler", distance.jarowinkler)?;
+            distdict.set_item("phonetic", distance.phonetic)?;
+            let dict = PyDict::new_bound(py);
+            dict.set_item("distance", distdict)?;
+            dict.set_item("combined_score", explanation.combined_score)?;
+            dict.set_item("confusable_weight", explanation.confusable_weight)?;
+            dict.set_item("lexicon_weight", explanation.lexicon_weight)?;
+            if let Some(lm_perplexity) = explanation.lm_perplexity {
+                dict.set_item("lm_perplexity", lm_perplexity)?;
+            }
+            if let Some(context_score) = explanation.context_score {
+                dict.set_item("context_score", context_score)?;
+            }
+            Ok(Some(dict))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the variant as a dictionary, as returned by earlier versions
+    fn as_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("text", self.get_text())?;
+        dict.set_item("score", self.score)?;
+        dict.set_item("dist_score", self.dist_score)?;
+        dict.set_item("freq_score", self.freq_score)?;
+        if let Some(via) = self.get_via() {
+            dict.set_item("via", via)?;
+        }
+        dict.set_item("lexicons", self.get_lexicons())?;
+        if let Some(metadata) = self.get_metadata() {
+            dict.set_item("metadata", metadata)?;
+        }
+        if let Some(lemma) = self.get_lemma() {
+            dict.set_item("lemma", lemma)?;
+        }
+        if let Some(normalized) = self.get_normalized() {
+            dict.set_item("normalized", normalized)?;
+        }
+        if let Some(edits) = self.get_edits(py)? {
+            dict.set_item("edits", edits)?;
+        }
+        if let Some(explanation) = self.get_explanation(py)? {
+            dict.set_item("explanation", explanation)?;
+        }
+        Ok(dict)
+    }
+
+    /// Dictionary-style access, for backward compatibility
+    fn __getitem__<'py>(&self, key: &str, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        match self.as_dict(py)?.get_item(key)? {
+            Some(value) => Ok(value),
+            None => Err(PyKeyError::new_err(key.to_owned())),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "VariantResult(text={:?}, score={}, dist_score={}, freq_score={})",
+            self.text, self.score, self.dist_score, self.freq_score
+        )
+    }
+}
+
+/// Converts a filename passed as `str` or `os.PathLike` to a string
+fn path_to_string(path: PathBuf) -> PyResult<String> {
+    path.into_os_string()
+        .into_string()
+        .map_err(|_| PyValueError::new_err("Filename is not valid unicode"))
+}
+
+/// A source to load data from: a filename (`str` or `os.PathLike`) or a file-like object
+enum Source {
+    File(String),
+    /// The contents of a file-like object, read in its entirety, along with its name
+    Stream {
+        name: String,
+        data: Vec<u8>,
+    },
+}
+
+impl Source {
+    fn extract(obj: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(path) = obj.extract::<PathBuf>() {
+            return Ok(Self::File(path_to_string(path)?));
+        }
+        if obj.hasattr("read")? {
+            let content = obj.call_method0("read")?;
+            let data = if let Ok(text) = content.extract::<String>() {
+                text.into_bytes()
+            } else if let Ok(bytes) = content.downcast::<PyBytes>() {
+                bytes.as_bytes().to_vec()
+            } else {
+                return Err(PyTypeError::new_err(
+                    "read() of a file-like object should return str or bytes",
+                ));
+            };
+            let name = obj
+                .getattr("name")
+                .and_then(|name| name.extract::<String>())
+                .unwrap_or_else(|_| "<stream>".to_owned());
+            return Ok(Self::Stream { name, data });
+        }
+        Err(PyTypeError::new_err(
+            "Expected a filename (str or os.PathLike) or a file-like object",
+        ))
+    }
 }
 
 #[pyclass(dict, name = "VariantModel")]
 pub struct PyVariantModel {
-    model: libanaliticcl::VariantModel,
+    /// The underlying model, None once it has been freed
+    model: Option<libanaliticcl::VariantModel>,
 }
 
 impl PyVariantModel {
-    fn variantresult_to_dict<'py>(
+    fn model(&self) -> PyResult<&libanaliticcl::VariantModel> {
+        self.model
+            .as_ref()
+            .ok_or_else(|| PyRuntimeError::new_err("The model has been freed"))
+    }
+
+    fn model_mut(&mut self) -> PyResult<&mut libanaliticcl::VariantModel> {
+        self.model
+            .as_mut()
+            .ok_or_else(|| PyRuntimeError::new_err("The model has been freed"))
+    }
+
+    /// Loads vocabulary from a filename or from the contents of a file-like object
+    fn read_vocabulary_source(
+        &mut self,
+        source: Source,
+        params: &libanaliticcl::VocabParams,
+    ) -> PyResult<()> {
+        let result = match source {
+            Source::File(filename) => self.model_mut()?.read_vocabulary(&filename, params),
+            Source::Stream { name, data } => {
+                self.model_mut()?
+                    .read_vocabulary_from_reader(&name, data.as_slice(), params)
+            }
+        };
+        result.map_err(|e| PyRuntimeError::new_err(format!("{}", e)))
+    }
+
+    fn variantresult_to_py(
         &self,
         result: &libanaliticcl::VariantResult,
         freq_weight: f32,
-        py: Python<'py>,
-    ) -> PyResult<Bound<'py, PyDict>> {
-        let dict = PyDict::new_bound(py);
-        let vocabvalue = self
-            .model
+        debug_normalization: bool,
+    ) -> PyResult<PyVariantResult> {
+        let model = self.model()?;
+        let vocabvalue = model
             .get_vocab(result.vocab_id)
             .expect("getting vocab by id");
-        dict.set_item("text", vocabvalue.text.as_str())?;
-        dict.set_item("score", result.score(freq_weight))?;
-        dict.set_item("dist_score", result.dist_score)?;
-        dict.set_item("freq_score", result.freq_score)?;
-        if let Some(via_id) = result.via {
-            let viavalue = self.model.get_vocab(via_id).expect("getting vocab by id");
-            dict.set_item("via", viavalue.text.as_str())?;
-        }
-        let lexicons: Vec<&str> = self
-            .model
-            .lexicons
-            .iter()
-            .enumerate()
-            .filter_map(|(i, name)| {
-                if vocabvalue.in_lexicon(i as u8) {
-                    Some(name.as_str())
-                } else {
-                    None
-                }
-            })
-            .collect();
-        dict.set_item("lexicons", lexicons)?;
-        Ok(dict)
+        Ok(PyVariantResult {
+            text: model.variant_text(result).to_owned(),
+            score: result.score(freq_weight),
+            dist_score: result.dist_score,
+            freq_score: result.freq_score,
+            via: result.via.map(|via_id| {
+                model
+                    .get_vocab(via_id)
+                    .expect("getting vocab by id")
+                    .text
+                    .clone()
+            }),
+            lexicons: model
+                .result_lexicons(result)
+                .into_iter()
+                .map(|name| name.to_owned())
+                .collect(),
+            metadata: vocabvalue.metadata.clone(),
+            lemma: vocabvalue.lemma.clone(),
+            normalized: if debug_normalization {
+                Some(
+                    model
+                        .decompose_normstring(&vocabvalue.norm)
+                        .into_iter()
+                        .map(|s| s.to_owned())
+                        .collect(),
+                )
+            } else {
+                None
+            },
+            edits: result.edits.clone(),
+            explanation: result.explanation.clone(),
+        })
     }
 }
 
@@ -588,19 +1556,97 @@ impl PyVariantModel {
 impl PyVariantModel {
     #[new]
     #[pyo3(signature = (alphabet_file, weights, debug = 0))]
-    fn new(alphabet_file: &str, weights: PyRef<PyWeights>, debug: u8) -> Self {
-        Self {
-            model: libanaliticcl::VariantModel::new(alphabet_file, weights.weights.clone(), debug),
-        }
+    fn new(alphabet_file: PathBuf, weights: PyRef<PyWeights>, debug: u8) -> PyResult<Self> {
+        Ok(Self {
+            model: Some(libanaliticcl::VariantModel::new(
+                &path_to_string(alphabet_file)?,
+                weights.weights.clone(),
+                debug,
+            )),
+        })
     }
 
     /// Build the anagram index (and secondary index) so the model
     /// is ready for variant matching
     fn build(&mut self) -> PyResult<()> {
-        self.model.build();
+        self.model_mut()?.build();
         Ok(())
     }
 
+    /// Drop the underlying model and release its memory immediately rather than waiting for the
+    /// garbage collector. Any later call on this model raises a RuntimeError. Calling this more
+    /// than once is harmless.
+    fn free(&mut self) {
+        self.model = None;
+    }
+
+    /// Alias for free()
+    fn close(&mut self) {
+        self.free();
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyResult<PyRef<'_, Self>> {
+        slf.model()?;
+        Ok(slf)
+    }
+
+    /// Frees the model when leaving a `with` block, exceptions are not suppressed
+    fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> bool {
+        self.free();
+        false
+    }
+
+    /// Instantiate a new variant model, explicitly passing an alphabet (a list of lists of
+    /// strings) rather than loading one from file
+    #[staticmethod]
+    #[pyo3(signature = (alphabet, weights, debug = 0))]
+    fn from_alphabet(alphabet: Vec<Vec<String>>, weights: PyRef<PyWeights>, debug: u8) -> Self {
+        Self {
+            model: Some(libanaliticcl::VariantModel::new_with_alphabet(
+                alphabet,
+                weights.weights.clone(),
+                debug,
+            )),
+        }
+    }
+
+    /// Append entries (a list of lists of strings) to the alphabet. This must be done before the
+    /// model is built, vocabulary that was already added is normalized again.
+    fn add_alphabet_entries(&mut self, entries: Vec<Vec<String>>) -> PyResult<()> {
+        self.model_mut()?
+            .add_alphabet_entries(entries)
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))
+    }
+
+    /// Load a previously built and saved model from file, the alphabet file must match the
+    /// alphabet the model was built with. There is no need to call build() afterwards.
+    #[staticmethod]
+    #[pyo3(signature = (filename, alphabet_file, debug = 0))]
+    fn load(filename: PathBuf, alphabet_file: PathBuf, debug: u8) -> PyResult<Self> {
+        match libanaliticcl::VariantModel::load(
+            &path_to_string(filename)?,
+            &path_to_string(alphabet_file)?,
+            debug,
+        ) {
+            Ok(model) => Ok(Self { model: Some(model) }),
+            Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
+        }
+    }
+
+    /// Save the built model to a single binary file, so it can be loaded again later without
+    /// needing to rebuild it
+    fn save(&self, filename: PathBuf) -> PyResult<()> {
+        match self.model()?.save(&path_to_string(filename)?) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
+        }
+    }
+
     /// Add an item to the vocabulary. This is a lower-level interface.
     #[pyo3(signature = (text,frequency,params))]
     pub fn add_to_vocabulary(
@@ -609,18 +1655,33 @@ impl PyVariantModel {
         frequency: Option<u32>,
         params: PyRef<PyVocabParams>,
     ) -> PyResult<()> {
-        self.model.add_to_vocabulary(text, frequency, &params.data);
+        self.model_mut()?
+            .add_to_vocabulary(text, frequency, &params.data);
+        Ok(())
+    }
+
+    /// Add an item to the vocabulary of a model that has already been built, updating the
+    /// index incrementally so there is no need to call build() again
+    pub fn insert_built(
+        &mut self,
+        text: &str,
+        frequency: Option<u32>,
+        params: PyRef<PyVocabParams>,
+    ) -> PyResult<()> {
+        self.model_mut()?
+            .insert_built(text, frequency, &params.data);
         Ok(())
     }
 
-    /// Load vocabulary (a lexicon or corpus-derived lexicon) from a TSV file
+    /// Load vocabulary (a lexicon or corpus-derived lexicon) from a TSV file (a filename or a file-like object)
     /// May contain frequency information. This is a lower-level interface.
     /// The parameters define what value can be read from what column
-    fn read_vocabulary(&mut self, filename: &str, params: PyRef<PyVocabParams>) -> PyResult<()> {
-        match self.model.read_vocabulary(filename, &params.data) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
-        }
+    fn read_vocabulary(
+        &mut self,
+        filename: &Bound<'_, PyAny>,
+        params: PyRef<PyVocabParams>,
+    ) -> PyResult<()> {
+        self.read_vocabulary_source(Source::extract(filename)?, &params.data)
     }
 
     fn add_contextrule(
@@ -632,7 +1693,10 @@ impl PyVariantModel {
     ) -> PyResult<()> {
         let tag: Vec<&str> = tag.iter().map(|s| s.as_str()).collect();
         let tagoffset: Vec<&str> = tagoffset.iter().map(|s| s.as_str()).collect();
-        match self.model.add_contextrule(pattern, score, tag, tagoffset) {
+        match self
+            .model_mut()?
+            .add_contextrule(pattern, score, tag, tagoffset)
+        {
             Ok(_) => Ok(()),
             Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
         }
@@ -640,60 +1704,224 @@ impl PyVariantModel {
 
     /// Higher order function to load a lexicon and make it available to the model.
     /// Wraps around read_vocabulary() with default parameters.
-    fn read_lexicon(&mut self, filename: &str) -> PyResult<()> {
+    fn read_lexicon(&mut self, filename: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.read_vocabulary_source(
+            Source::extract(filename)?,
+            &libanaliticcl::VocabParams::default(),
+        )
+    }
+
+    /// Higher order function to load a language model and make it available to the model.
+    /// Wraps around read_vocabulary() with default parameters.
+    fn read_lm(&mut self, filename: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.read_vocabulary_source(
+            Source::extract(filename)?,
+            &libanaliticcl::VocabParams::default().with_vocab_type(libanaliticcl::VocabType::LM),
+        )
+    }
+
+    /// Higher order function to load a corpus-derived frequency list that is used both as a lexicon
+    /// and as a language model. Wraps around read_vocabulary() with default parameters.
+    fn read_corpus(&mut self, filename: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.read_vocabulary_source(
+            Source::extract(filename)?,
+            &libanaliticcl::VocabParams::default()
+                .with_vocab_type(libanaliticcl::VocabType::CORPUS),
+        )
+    }
+
+    ///Load a weighted variant list (set transparent to true if this is an error list and you
+    ///don't want the variants themselves to be returned when matching; i.e. they are transparent)
+    fn read_variants(&mut self, filename: &Bound<'_, PyAny>, transparent: bool) -> PyResult<()> {
+        let params = libanaliticcl::VocabParams::default();
+        let result = match Source::extract(filename)? {
+            Source::File(filename) => {
+                self.model_mut()?
+                    .read_variants(&filename, Some(&params), transparent)
+            }
+            Source::Stream { name, data } => self.model_mut()?.read_variants_from_reader(
+                &name,
+                data.as_slice(),
+                Some(&params),
+                transparent,
+            ),
+        };
+        result.map_err(|e| PyRuntimeError::new_err(format!("{}", e)))
+    }
+
+    ///Load a confusable list
+    fn read_confusablelist(&mut self, filename: PathBuf) -> PyResult<()> {
         match self
-            .model
-            .read_vocabulary(filename, &libanaliticcl::VocabParams::default())
+            .model_mut()?
+            .read_confusablelist(&path_to_string(filename)?)
         {
             Ok(_) => Ok(()),
             Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
         }
     }
 
-    /// Higher order function to load a language model and make it available to the model.
-    /// Wraps around read_vocabulary() with default parameters.
-    fn read_lm(&mut self, filename: &str) -> PyResult<()> {
-        match self.model.read_vocabulary(
-            filename,
-            &libanaliticcl::VocabParams::default().with_vocab_type(libanaliticcl::VocabType::LM),
-        ) {
+    ///Load an affix list
+    fn read_affixlist(&mut self, filename: PathBuf) -> PyResult<()> {
+        match self.model_mut()?.read_affixlist(&path_to_string(filename)?) {
             Ok(_) => Ok(()),
             Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
         }
     }
 
-    ///Load a weighted variant list (set transparent to true if this is an error list and you
-    ///don't want the variants themselves to be returned when matching; i.e. they are transparent)
-    fn read_variants(&mut self, filename: &str, transparent: bool) -> PyResult<()> {
-        match self.model.read_variants(
-            filename,
-            Some(&libanaliticcl::VocabParams::default()),
-            transparent,
-        ) {
+    /// Add an affix rule: a suffix (e.g. `-jes`) or prefix (e.g. `ge-`) that may be stripped from the input
+    /// so the stem is matched as well, the scores of variants found for the stem are multiplied by the weight
+    fn add_to_affixes(&mut self, affix: &str, weight: f64) -> PyResult<()> {
+        match self.model_mut()?.add_to_affixes(affix, weight) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(PyValueError::new_err(format!("{}", e))),
+        }
+    }
+
+    /// Add confusables for substitutions between adjacent keys on a keyboard layout. The layout
+    /// is either "qwerty", "azerty", or a custom layout as a list of rows (top to bottom)
+    fn add_keyboard_confusables(&mut self, layout: Bound<PyAny>, weight: f64) -> PyResult<()> {
+        let layout = if let Ok(rows) = layout.extract::<Vec<String>>() {
+            libanaliticcl::KeyboardLayout::Custom(rows)
+        } else {
+            layout
+                .extract::<String>()?
+                .parse::<libanaliticcl::KeyboardLayout>()
+                .map_err(|e| PyValueError::new_err(format!("{}", e)))?
+        };
+        match self.model_mut()?.add_keyboard_confusables(layout, weight) {
             Ok(_) => Ok(()),
             Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
         }
     }
 
-    ///Load a confusable list
-    fn read_confusablelist(&mut self, filename: &str) -> PyResult<()> {
-        match self.model.read_confusablelist(filename) {
+    /// Computes the weight the confusables assign to the pair of the input and the candidate, which
+    /// must be in the vocabulary. Returns 1.0 if no confusables apply
+    fn compute_confusable_weight(&self, input: &str, candidate: &str) -> PyResult<f64> {
+        let model = self.model()?;
+        let candidate = model.unicode_normalization.normalize(candidate);
+        match model.encoder.get(&*candidate) {
+            Some(vocab_id) => Ok(model.compute_confusable_weight(input, *vocab_id)),
+            None => Err(PyKeyError::new_err(candidate.into_owned())),
+        }
+    }
+
+    /// Load substitution costs for the edit distance computation from a TSV file
+    fn read_substitution_costs(&mut self, filename: PathBuf) -> PyResult<()> {
+        match self
+            .model_mut()?
+            .read_substitution_costs(&path_to_string(filename)?)
+        {
             Ok(_) => Ok(()),
             Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
         }
     }
 
+    /// Set the cost of substituting one character for another (in both directions) in the edit
+    /// distance computation
+    fn add_substitution_cost(&mut self, a: &str, b: &str, cost: f64) -> PyResult<()> {
+        match self.model_mut()?.add_substitution_cost(a, b, cost) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(PyValueError::new_err(format!("{}", e))),
+        }
+    }
+
     /// Load context rules from a TSV file
-    fn read_contextrules(&mut self, filename: &str) -> PyResult<()> {
-        match self.model.read_contextrules(filename) {
+    fn read_contextrules(&mut self, filename: PathBuf) -> PyResult<()> {
+        match self
+            .model_mut()?
+            .read_contextrules(&path_to_string(filename)?)
+        {
             Ok(_) => Ok(()),
             Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
         }
     }
 
     ///Is this exact text in a loaded lexicon?
-    fn __contains__(&self, text: &str) -> bool {
-        self.model.has(text)
+    fn __contains__(&self, text: &str) -> PyResult<bool> {
+        Ok(self.model()?.has(text))
+    }
+
+    /// Returns the number of entries in the vocabulary, this includes the special tokens (`<bos>`,
+    /// `<eos>`, `<unk>`)
+    fn vocabulary_size(&self) -> PyResult<usize> {
+        Ok(self.model()?.decoder.len())
+    }
+
+    /// Returns statistics on the characters in the (indexed) vocabulary that are not covered by the
+    /// alphabet: a dictionary with the counts of all such characters (`unknown_chars`), the number
+    /// of entries that contain them (`affected_entries`) and the entries that consist mostly of
+    /// them (`dominated_entries`)
+    fn alphabet_coverage_report<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let model = self.model()?;
+        let report = model.alphabet_coverage_report();
+        let dict = PyDict::new_bound(py);
+        dict.set_item("unknown_chars", report.unknown_chars)?;
+        dict.set_item("affected_entries", report.affected_entries)?;
+        dict.set_item(
+            "dominated_entries",
+            report
+                .dominated_entries
+                .iter()
+                .map(|vocab_id| model.decoder[*vocab_id as usize].text.as_str())
+                .collect::<Vec<_>>(),
+        )?;
+        Ok(dict)
+    }
+
+    /// Iterates over all entries in the vocabulary (including the special tokens), yielding a
+    /// dictionary with the text, frequency, token count, lexicons and metadata of each entry
+    fn iter_vocab<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        let model = self.model()?;
+        let entries = PyList::empty_bound(py);
+        for vocabvalue in model.decoder.iter() {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("text", vocabvalue.text.as_str())?;
+            dict.set_item("frequency", vocabvalue.frequency)?;
+            dict.set_item("tokencount", vocabvalue.tokencount)?;
+            dict.set_item("lexicons", model.vocab_lexicons(vocabvalue))?;
+            if let Some(metadata) = vocabvalue.metadata.as_ref() {
+                dict.set_item("metadata", metadata.as_str())?;
+            }
+            if let Some(lemma) = vocabvalue.lemma.as_ref() {
+                dict.set_item("lemma", lemma.as_str())?;
+            }
+            entries.append(dict)?;
+        }
+        entries.as_any().iter()
+    }
+
+    /// Returns the names of all loaded lexicons, in the order in which they were loaded
+    fn lexicons(&self) -> PyResult<Vec<String>> {
+        Ok(self.model()?.lexicons.clone())
+    }
+
+    /// Disables the lexicon with the specified index, variants that occur only in disabled
+    /// lexicons are no longer returned. This is cheap and can be undone using enable_lexicon()
+    fn disable_lexicon(&mut self, index: u8) -> PyResult<()> {
+        self.model_mut()?
+            .disable_lexicon(index)
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))
+    }
+
+    /// Enables a lexicon that was previously disabled
+    fn enable_lexicon(&mut self, index: u8) -> PyResult<()> {
+        self.model_mut()?
+            .enable_lexicon(index)
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))
+    }
+
+    /// Removes all entries of the lexicon with the specified index from the model, updating
+    /// the index incrementally
+    fn remove_lexicon(&mut self, index: u8) -> PyResult<()> {
+        self.model_mut()?
+            .remove_lexicon(index)
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))
+    }
+
+    /// Returns the alphabet as a list of lists of strings, each inner list holds all the
+    /// representations of one character in the alphabet
+    fn alphabet(&self) -> PyResult<Vec<Vec<String>>> {
+        Ok(self.model()?.alphabet.clone())
     }
 
     /// Find variants in the vocabulary for a given string (in its totality), returns a list of variants with scores and their source lexicons
@@ -704,14 +1932,94 @@ impl PyVariantModel {
         py: Python<'py>,
     ) -> PyResult<Bound<'py, PyList>> {
         let pyresults = PyList::empty_bound(py);
-        let results = self.model.find_variants(input, &params.data);
+        let results = self.model()?.find_variants(input, &params.data);
         for result in results {
-            let dict = self.variantresult_to_dict(&result, params.data.freq_weight, py)?;
-            pyresults.append(dict)?;
+            let variant = self.variantresult_to_py(
+                &result,
+                params.data.freq_weight,
+                params.data.debug_normalization,
+            )?;
+            pyresults.append(Bound::new(py, variant)?)?;
         }
         Ok(pyresults)
     }
 
+    /// Decodes the most likely sequence through a lattice of candidates from an external source.
+    /// The lattice is a list with, for each input token, a tuple of the input text and a list of
+    /// (candidate text, cost) tuples, where the cost ranges from 0.0 (best) to 1.0 (worst).
+    /// Candidates that are not in the vocabulary are considered out-of-vocabulary. Returns the index
+    /// of the selected candidate for each position.
+    fn decode_sequence(
+        &self,
+        lattice: Vec<(String, Vec<(String, f32)>)>,
+        params: PyRef<PySearchParameters>,
+    ) -> PyResult<Vec<usize>> {
+        let model = self.model()?;
+        let lattice: Vec<libanaliticcl::TokenHypotheses> = lattice
+            .into_iter()
+            .map(|(text, candidates)| {
+                candidates.into_iter().fold(
+                    libanaliticcl::TokenHypotheses::new(text),
+                    |hypotheses, (candidate, cost)| {
+                        hypotheses.with_candidate(model.encoder.get(&candidate).copied(), cost)
+                    },
+                )
+            })
+            .collect();
+        Ok(model.decode_sequence(&lattice, &params.data))
+    }
+
+    /// Computes the similarity score between two arbitrary strings using the weights of the model,
+    /// without consulting the index. This is the same score find_variants() would assign to the
+    /// candidate as a variant of the input (prior to frequency or context weighting)
+    fn score_pair(&self, input: &str, candidate: &str) -> PyResult<f64> {
+        Ok(self.model()?.score_pair(input, candidate))
+    }
+
+    /// Returns the best (top-ranked) variant for the input, or None if no variant meets the
+    /// thresholds in the parameters (notably score_threshold)
+    fn best_variant(
+        &self,
+        input: &str,
+        params: PyRef<PySearchParameters>,
+    ) -> PyResult<Option<PyVariantResult>> {
+        self.model()?
+            .find_variants(input, &params.data)
+            .first()
+            .map(|result| {
+                self.variantresult_to_py(
+                    result,
+                    params.data.freq_weight,
+                    params.data.debug_normalization,
+                )
+            })
+            .transpose()
+    }
+
+    /// Learn variants from the input strings; all variants that are found (and meet the thresholds)
+    /// are stored in the model rather than returned. Returns the number of variants learned.
+    /// Will use parallellisation under the hood unless single_thread is set in the parameters.
+    #[pyo3(signature = (input, params, strict = false, auto_build = true))]
+    fn learn_variants(
+        &mut self,
+        input: Vec<String>,
+        params: PyRef<PySearchParameters>,
+        strict: bool,
+        auto_build: bool,
+    ) -> PyResult<usize> {
+        Ok(self
+            .model_mut()?
+            .learn_variants(&input, &params.data, strict, auto_build))
+    }
+
+    /// Save all weighted variants held by the model (e.g. after learn_variants()) to a TSV file
+    fn save_variants(&self, filename: PathBuf) -> PyResult<()> {
+        match self.model()?.save_variants(&path_to_string(filename)?) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(PyRuntimeError::new_err(format!("{}", e))),
+        }
+    }
+
     /// Find variants in the vocabulary for all multiple string items at once, provided in in the input list. Returns a list of variants with scores and their source lexicons. Will use parallellisation under the hood.
     fn find_variants_par<'py>(
         &self,
@@ -719,24 +2027,20 @@ impl PyVariantModel {
         params: PyRef<PySearchParameters>,
         py: Python<'py>,
     ) -> PyResult<Bound<'py, PyList>> {
-        let params_data = &params.data;
-        let output: Vec<(&str, Vec<libanaliticcl::VariantResult>)> = input
-            .par_iter()
-            .map(|input_str| {
-                (
-                    input_str.as_str(),
-                    self.model.find_variants(input_str, params_data),
-                )
-            })
-            .collect();
+        let inputs: Vec<&str> = input.iter().map(|input_str| input_str.as_str()).collect();
+        let output = self.model()?.find_variants_batch(&inputs, &params.data);
         let results = PyList::empty_bound(py);
         for (input_str, variants) in output {
             let odict = PyDict::new_bound(py);
             let olist = PyList::empty_bound(py);
             odict.set_item("input", input_str)?;
             for result in variants {
-                let dict = self.variantresult_to_dict(&result, params.data.freq_weight, py)?;
-                olist.append(dict)?;
+                let variant = self.variantresult_to_py(
+                    &result,
+                    params.data.freq_weight,
+                    params.data.debug_normalization,
+                )?;
+                olist.append(Bound::new(py, variant)?)?;
             }
             odict.set_item("variants", olist)?;
             results.append(odict)?;
@@ -744,6 +2048,29 @@ impl PyVariantModel {
         Ok(results)
     }
 
+    /// Computes the anagram neighbourhoods of a known set of queries up front (in parallel) and adds
+    /// them to the neighbourhood cache, so subsequent lookups with the same parameters are served
+    /// from the cache. Requires neighbourhood_cache_size to be set in the parameters. Returns a
+    /// dictionary with statistics: the number of `queries`, the number of neighbourhoods that were
+    /// `computed`, the number of queries that were already `cached`, the number of queries that
+    /// were `skipped` and the `duration` in seconds.
+    fn precompute<'py>(
+        &self,
+        queries: Vec<String>,
+        params: PyRef<PySearchParameters>,
+        py: Python<'py>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let queries: Vec<&str> = queries.iter().map(|query| query.as_str()).collect();
+        let stats = self.model()?.precompute(&queries, &params.data);
+        let dict = PyDict::new_bound(py);
+        dict.set_item("queries", stats.queries)?;
+        dict.set_item("computed", stats.computed)?;
+        dict.set_item("cached", stats.cached)?;
+        dict.set_item("skipped", stats.skipped)?;
+        dict.set_item("duration", stats.duration.as_secs_f64())?;
+        Ok(dict)
+    }
+
     ///Searches a text and returns all highest-ranking variants found in the text
     fn find_all_matches<'py>(
         &self,
@@ -752,7 +2079,8 @@ impl PyVariantModel {
         py: Python<'py>,
     ) -> PyResult<Bound<'py, PyList>> {
         let params_data = &params.data;
-        let matches = self.model.find_all_matches(text, params_data);
+        let model = self.model()?;
+        let matches = model.find_all_matches(text, params_data);
         let results = PyList::empty_bound(py);
         for m in matches {
             let odict = PyDict::new_bound(py);
@@ -761,36 +2089,93 @@ impl PyVariantModel {
             offsetdict.set_item("begin", m.offset.begin)?;
             offsetdict.set_item("end", m.offset.end)?;
             odict.set_item("offset", offsetdict)?;
+            if let Some(byteoffset) = &m.byteoffset {
+                let offsetdict = PyDict::new_bound(py);
+                offsetdict.set_item("begin", byteoffset.begin)?;
+                offsetdict.set_item("end", byteoffset.end)?;
+                odict.set_item("byteoffset", offsetdict)?;
+            }
+            if let Some(charoffset) = &m.charoffset {
+                let offsetdict = PyDict::new_bound(py);
+                offsetdict.set_item("begin", charoffset.begin)?;
+                offsetdict.set_item("end", charoffset.end)?;
+                odict.set_item("charoffset", offsetdict)?;
+            }
+            odict.set_item("sentence_index", m.sentence_index)?;
+            odict.set_item("token_index", m.token_index)?;
+            odict.set_item("n", m.n)?;
+            if m.oov {
+                odict.set_item("oov", true)?;
+                odict.set_item("output", model.match_output(&m, params_data))?;
+            }
+            if let Some(normalized) = &m.normalized {
+                odict.set_item("normalized", model.decompose_normstring(normalized))?;
+            }
             if !m.tag.is_empty() {
                 let taglist = PyList::empty_bound(py);
                 let seqnrlist = PyList::empty_bound(py);
-                for (tagindex, seqnr) in m.tag.iter().zip(m.seqnr.iter()) {
-                    taglist.append(
-                        self.model
-                            .tags
-                            .get(*tagindex as usize)
-                            .expect("Tag must exist"),
-                    )?;
+                let tags = PyList::empty_bound(py);
+                for (tag, seqnr) in model.match_tags(&m) {
+                    taglist.append(tag)?;
                     seqnrlist.append(seqnr)?;
+                    let tagdict = PyDict::new_bound(py);
+                    tagdict.set_item("tag", tag)?;
+                    tagdict.set_item("seqnr", seqnr)?;
+                    tags.append(tagdict)?;
                 }
                 odict.set_item("tag", taglist)?;
                 odict.set_item("seqnr", seqnrlist)?;
+                odict.set_item("tags", tags)?;
+            }
+            if !m.alternatives.is_empty() {
+                let alternatives = PyList::empty_bound(py);
+                for alternative in m.alternatives.iter() {
+                    let altdict = PyDict::new_bound(py);
+                    altdict.set_item("text", alternative.text.as_str())?;
+                    altdict.set_item("score", alternative.score)?;
+                    altdict.set_item("variant_cost", alternative.variant_cost)?;
+                    if let Some(lm_perplexity) = alternative.lm_perplexity {
+                        altdict.set_item("lm_perplexity", lm_perplexity)?;
+                    }
+                    if let Some(context_score) = alternative.context_score {
+                        altdict.set_item("context_score", context_score)?;
+                    }
+                    alternatives.append(altdict)?;
+                }
+                odict.set_item("alternatives", alternatives)?;
+            }
+            if let Some(confidence) = m.confidence.as_ref() {
+                let confdict = PyDict::new_bound(py);
+                confdict.set_item("score", confidence.score)?;
+                if let Some(lm_perplexity) = confidence.lm_perplexity {
+                    confdict.set_item("lm_perplexity", lm_perplexity)?;
+                }
+                if let Some(context_score) = confidence.context_score {
+                    confdict.set_item("context_score", context_score)?;
+                }
+                odict.set_item("confidence", confdict)?;
             }
             let olist = PyList::empty_bound(py);
             if let Some(variants) = m.variants {
                 if let Some(selected) = m.selected {
                     if let Some(result) = variants.get(selected) {
-                        let dict =
-                            self.variantresult_to_dict(&result, params.data.freq_weight, py)?;
-                        olist.append(dict)?;
+                        let variant = self.variantresult_to_py(
+                            &result,
+                            params.data.freq_weight,
+                            params.data.debug_normalization,
+                        )?;
+                        olist.append(Bound::new(py, variant)?)?;
                     }
                 }
                 for (i, result) in variants.iter().enumerate() {
                     if m.selected.is_none() || m.selected.unwrap() != i {
                         //output all others
-                        let dict =
-                            self.variantresult_to_dict(&result, params.data.freq_weight, py)?;
-                        olist.append(dict)?;
+                        let variant = self.variantresult_to_py(
+                            &result,
+                            params.data.freq_weight,
+                            params.data.debug_normalization,
+                        )?;
+                        olist.append(Bound::new(py, variant)?)?;
                     }
                 }
             }
@@ -800,18 +2185,107 @@ impl PyVariantModel {
         Ok(results)
     }
 
+    ///Searches a text and returns the corrected text, in which every match is replaced by its
+    ///output and all other text is retained verbatim
+    fn correct_text(&self, text: &str, params: PyRef<PySearchParameters>) -> PyResult<String> {
+        Ok(self.model()?.correct_text(text, &params.data))
+    }
+
     /// Configure the model to match against known confusables prior to pruning on maximum weight.
     /// This corresponds to the `--early-confusables` option for the CLI version
-    fn set_confusables_before_pruning(&mut self) {
-        self.model.set_confusables_before_pruning();
+    fn set_confusables_before_pruning(&mut self) -> PyResult<()> {
+        self.model_mut()?.set_confusables_before_pruning();
+        Ok(())
+    }
+
+    /// Configure the Unicode normalization to apply to lexicon entries and input, may be "none", "nfc" or "nfd".
+    /// This should be called before loading any lexicons.
+    /// This corresponds to the `--unicode-normalization` option for the CLI version
+    fn set_unicode_normalization(&mut self, normalization: &str) -> PyResult<()> {
+        let normalization = normalization
+            .parse::<libanaliticcl::UnicodeNormalization>()
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))?;
+        self.model_mut()?.set_unicode_normalization(normalization);
+        Ok(())
+    }
+
+    /// Enable or disable case-insensitive matching, the returned variants keep the casing of the lexicon.
+    /// This should be called before loading any lexicons.
+    /// This corresponds to the `--casefold` option for the CLI version
+    fn set_casefold(&mut self, casefold: bool) -> PyResult<()> {
+        self.model_mut()?.set_casefold(casefold);
+        Ok(())
+    }
+
+    /// Only compare the casing of the first character for the case component of the score, rather
+    /// than the fraction of aligned characters with the same casing.
+    /// This corresponds to the `--case-first-char` option for the CLI version
+    fn set_case_first_char(&mut self, case_first_char: bool) -> PyResult<()> {
+        self.model_mut()?.set_case_first_char(case_first_char);
+        Ok(())
+    }
+
+    /// Encode each distinct character that is not in the alphabet with its own symbol for the
+    /// edit distance computation, rather than all as the same unknown character.
+    /// This corresponds to the `--distinct-unknowns` option for the CLI version
+    fn set_distinct_unknowns(&mut self, distinct_unknowns: bool) -> PyResult<()> {
+        self.model_mut()?.set_distinct_unknowns(distinct_unknowns);
+        Ok(())
+    }
+
+    /// Check how well the alphabet covers the vocabulary when building the model, and warn
+    /// about characters that are not in the alphabet.
+    /// This corresponds to the `--check-alphabet` option for the CLI version
+    fn set_check_alphabet_coverage(&mut self, check: bool) -> PyResult<()> {
+        self.model_mut()?.set_check_alphabet_coverage(check);
+        Ok(())
+    }
+
+    /// Enable or disable the character n-gram index, an alternative candidate generator for long
+    /// inputs (see the ngram_min_length search parameter). The index is built by build(), or
+    /// immediately if the model was already built.
+    /// This corresponds to the `--ngram-candidates` option for the CLI version
+    fn set_ngram_index(&mut self, enabled: bool) -> PyResult<()> {
+        self.model_mut()?.set_ngram_index(enabled);
+        Ok(())
+    }
+
+    /// Enable or disable the secondary sorted index (enabled by default). Without it, variants that
+    /// are longer than the input (i.e. that need insertions) are never found, only deletions and
+    /// substitutions are. This saves memory and query time.
+    /// This corresponds to the `--no-insertions` option for the CLI version
+    fn set_insertions(&mut self, enabled: bool) -> PyResult<()> {
+        self.model_mut()?.set_insertions(enabled);
+        Ok(())
+    }
+
+    /// Configure the smoothing method for the language model, may be "fixed", "witten-bell" or "kneser-ney".
+    /// This corresponds to the `--lm-smoothing` option for the CLI version
+    fn set_lm_smoothing(&mut self, smoothing: &str) -> PyResult<()> {
+        let smoothing = smoothing
+            .parse::<libanaliticcl::LmSmoothing>()
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))?;
+        self.model_mut()?.set_lm_smoothing(smoothing);
+        Ok(())
+    }
+
+    /// Use a dedicated thread pool with the specified number of threads for all parallel
+    /// processing by this model (0 lets the system decide)
+    fn set_num_threads(&mut self, num_threads: usize) -> PyResult<()> {
+        self.model_mut()?
+            .set_num_threads(num_threads)
+            .map_err(|e| PyRuntimeError::new_err(format!("{}", e)))
     }
 }
 
 #[pymodule]
 fn analiticcl(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    //messages are governed by the debug level of the model, write them to stderr as before
+    libanaliticcl::set_logger(Box::new(libanaliticcl::StderrLogger));
     m.add_class::<PyWeights>()?;
     m.add_class::<PySearchParameters>()?;
     m.add_class::<PyVocabParams>()?;
     m.add_class::<PyVariantModel>()?;
+    m.add_class::<PyVariantResult>()?;
     Ok(())
 }
@@ -1,6 +1,9 @@
+import io
+import pathlib
+import tempfile
 import unittest
 
-from analiticcl import VariantModel, Weights, SearchParameters
+from analiticcl import VariantModel, Weights, SearchParameters, VocabParams
 from icecream import ic
 
 LEXICON_AMPHIBIANS = 'tests/amphibians.tsv'
@@ -25,6 +28,262 @@ class AnaliticclPythonBindingTests(unittest.TestCase):
         self.assert_result(results[3], 'snake', LEXICON_REPTILES)
         self.assert_result(results[4], 'toad', LEXICON_AMPHIBIANS)
 
+    def test_from_alphabet(self):
+        alphabet = [[c, c.upper()] for c in "abcdefghijklmnopqrstuvwxyz"]
+        model = VariantModel.from_alphabet(alphabet, Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.build()
+        results = model.find_variants("salamandre", SearchParameters(max_edit_distance=3))
+        self.assertEqual(results[0]['text'], 'salamander')
+
+    def test_variant_result_attributes(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.build()
+        results = model.find_variants("salamandre", SearchParameters(max_edit_distance=3))
+        best = results[0]
+        self.assertEqual(best.text, 'salamander')
+        self.assertEqual(best.lexicons, [LEXICON_AMPHIBIANS])
+        self.assertIsNone(best.via)
+        self.assertEqual(best.as_dict()['text'], best.text)
+        self.assertEqual(best['score'], best.score)
+
+    def test_explain(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.build()
+        results = model.find_variants("salamandre", SearchParameters(max_edit_distance=3))
+        self.assertIsNone(results[0].explanation)
+        results = model.find_variants("salamandre", SearchParameters(max_edit_distance=3, explain=True))
+        explanation = results[0].explanation
+        self.assertEqual(explanation['distance']['ld'], 2)
+        self.assertEqual(explanation['confusable_weight'], 1.0)
+        self.assertAlmostEqual(explanation['combined_score'], results[0].dist_score)
+
+    def test_disable_lexicon(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.read_lexicon(LEXICON_REPTILES)
+        model.build()
+        params = SearchParameters(max_edit_distance=3)
+        model.disable_lexicon(0)
+        self.assertNotIn('salamander', [v.text for v in model.find_variants("salamandre", params)])
+        model.enable_lexicon(0)
+        self.assertEqual(model.find_variants("salamandre", params)[0].text, 'salamander')
+        model.remove_lexicon(0)
+        self.assertNotIn('salamander', [v.text for v in model.find_variants("salamandre", params)])
+
+    def test_best_variant(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.build()
+        self.assertEqual(model.best_variant("salamandre", SearchParameters(max_edit_distance=3)).text, 'salamander')
+        self.assertIsNone(model.best_variant("salamandre", SearchParameters(max_edit_distance=3, score_threshold=0.99)))
+
+    def test_oov_policy(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.build()
+        matches = model.find_all_matches("salamander qqqqqqqq", SearchParameters(max_edit_distance=3, oov_policy="placeholder:???"))
+        self.assertEqual(len(matches), 2)
+        self.assertNotIn('oov', matches[0])
+        self.assertTrue(matches[1]['oov'])
+        self.assertEqual(matches[1]['output'], '???')
+
+    def test_score_pair(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        self.assertEqual(model.score_pair("salamander", "salamander"), 1.0)
+        self.assertTrue(model.score_pair("salamandre", "salamander") > model.score_pair("salamandre", "newt"))
+
+    def test_decode_sequence(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        for text in ("I", "sink", "think"):
+            model.add_to_vocabulary(text, 2, VocabParams())
+        model.build()
+        lattice = [("I", [("I", 0.0)]), ("tink", [("sink", 0.6), ("think", 0.1), ("tink", 0.9)]), ("qqq", [])]
+        self.assertEqual(model.decode_sequence(lattice, SearchParameters()), [0, 1, 0])
+
+    def test_nbest(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        for text in ("I", "sink", "think"):
+            model.add_to_vocabulary(text, 2, VocabParams())
+        model.build()
+        matches = model.find_all_matches("I tink", SearchParameters(nbest=2))
+        alternatives = matches[0]['alternatives']
+        self.assertEqual(len(alternatives), 2)
+        self.assertEqual(alternatives[0]['text'], "I think")
+        self.assertTrue(alternatives[0]['score'] >= alternatives[1]['score'])
+
+    def test_correct_text(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        for text in ("I", "sink", "think"):
+            model.add_to_vocabulary(text, 2, VocabParams())
+        model.build()
+        self.assertEqual(model.correct_text("I tink.", SearchParameters()), "I think.")
+
+    def test_transfer_case(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.add_to_vocabulary("huis", 2, VocabParams())
+        model.build()
+        self.assertEqual(model.find_variants("Huys", SearchParameters(transfer_case=True))[0].text, "Huis")
+        self.assertEqual(model.find_variants("HUYS", SearchParameters(transfer_case=True))[0].text, "HUIS")
+        self.assertEqual(model.find_variants("Huys", SearchParameters())[0].text, "huis")
+
+    def test_affixes(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.add_to_vocabulary("huis", 2, VocabParams())
+        model.add_to_affixes("-jes", 0.9)
+        model.build()
+        result = model.find_variants("huisjes", SearchParameters())
+        self.assertEqual(result[0].text, "huis")
+        self.assertTrue(result[0].score <= 0.9)
+        with self.assertRaises(ValueError):
+            model.add_to_affixes("jes", 0.9)
+
+    def test_read_lexicon_sources(self):
+        model = VariantModel(pathlib.Path("../../examples/simple.alphabet.tsv"), Weights(), debug=False)
+        model.read_lexicon(pathlib.Path(LEXICON_AMPHIBIANS))
+        model.read_lexicon(io.StringIO("lizard\nsnake\n"))
+        with open(LEXICON_REPTILES, 'rb') as f:
+            model.read_lexicon(f)
+        model.build()
+        self.assertEqual(model.lexicons(), [LEXICON_AMPHIBIANS, "<stream>", LEXICON_REPTILES])
+        self.assertTrue("salamander" in model)
+        self.assertTrue("lizard" in model)
+        with self.assertRaises(TypeError):
+            model.read_lexicon(42)
+
+    def test_read_corpus(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_corpus(io.StringIO("<bos>\t2\n<eos>\t2\nthe\t2\nfrog\t1\ntoad\t1\nthe frog\t1\nthe toad\t1\n"))
+        model.build()
+        self.assertTrue("frog" in model)
+        self.assertEqual(model.find_variants("frogg", SearchParameters())[0].text, "frog")
+
+    def test_compute_confusable_weight(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.build()
+        self.assertEqual(model.compute_confusable_weight("frgo", "frog"), 1.0)
+        model.add_keyboard_confusables("qwerty", 1.1)
+        self.assertAlmostEqual(model.compute_confusable_weight("frpg", "frog"), 1.1)
+        with self.assertRaises(KeyError):
+            model.compute_confusable_weight("frog", "unicorn")
+
+    def test_distinct_unknowns(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.set_distinct_unknowns(True)
+        model.add_to_vocabulary("frogα", None, VocabParams())
+        model.add_to_vocabulary("frogβ", None, VocabParams())
+        model.build()
+        results = model.find_variants("frogα", SearchParameters())
+        self.assertEqual(results[0].text, "frogα")
+        self.assertLess(results[1].score, 1.0)
+
+    def test_lemmas(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_vocabulary(io.StringIO("frogs\t5\tfrog\nfrog\t10\tfrog\nfog\t3\tfog\n"), VocabParams(lemma_column=2))
+        model.build()
+        results = model.find_variants("frogz", SearchParameters(collapse_lemmas=True))
+        self.assertIn(results[0].text, ("frog", "frogs"))
+        self.assertEqual(results[0].lemma, "frog")
+        self.assertEqual(len([result for result in results if result.lemma == "frog"]), 1)
+
+    def test_no_insertions(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.set_insertions(False)
+        for text in ("frog", "fog", "frig", "frogs"):
+            model.add_to_vocabulary(text, None, VocabParams())
+        model.build()
+        texts = [result.text for result in model.find_variants("frog", SearchParameters(max_matches=0))]
+        self.assertIn("fog", texts)
+        self.assertIn("frig", texts)
+        self.assertNotIn("frogs", texts)
+
+    def test_precompute(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.build()
+        params = SearchParameters(neighbourhood_cache_size=10)
+        stats = model.precompute(["salamander", "tooad"], params)
+        self.assertEqual(stats["queries"], 2)
+        self.assertEqual(stats["computed"], 2)
+        self.assertEqual(model.find_variants("tooad", params)[0].text, "toad")
+
+    def test_contextrules(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        with tempfile.TemporaryDirectory() as tmpdir:
+            rulesfile = pathlib.Path(tmpdir) / "rules.tsv"
+            rulesfile.write_text("frog ; toad\t1.5\tpair\n")
+            model.read_contextrules(rulesfile)
+        model.build()
+        params = SearchParameters(nbest=2)
+        matches = model.find_all_matches("frog toad", params)
+        self.assertEqual(matches[0]["tags"], [{"tag": "pair", "seqnr": 0}])
+        self.assertEqual(matches[1]["tags"], [{"tag": "pair", "seqnr": 1}])
+        self.assertEqual(matches[1]["tag"], ["pair"])
+        self.assertEqual(matches[1]["seqnr"], [1])
+        self.assertEqual(matches[1]["offset"], {"begin": 5, "end": 9})
+        alternatives = matches[0]["alternatives"]
+        self.assertEqual(alternatives[0]["text"], "frog toad")
+        self.assertEqual(alternatives[0]["context_score"], 1.5)
+        params.contextrules_weight = 2.0
+        self.assertEqual(params.contextrules_weight, 2.0)
+        weighted = model.find_all_matches("frog toad", params)[0]["alternatives"]
+        self.assertEqual(weighted[0]["score"], 1.0)
+        self.assertGreater(weighted[1]["score"], alternatives[1]["score"])
+
+    def test_sentence_confidence(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.build()
+        matches = model.find_all_matches("frog toad", SearchParameters(sentence_confidence=True))
+        self.assertEqual(matches[0]["confidence"]["score"], 1.0)
+        self.assertNotIn("confidence", matches[1])
+
+    def test_intraword_chars(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.add_to_vocabulary("don't", None, VocabParams())
+        model.add_to_vocabulary("know", None, VocabParams())
+        model.build()
+        params = SearchParameters(intraword_chars="'")
+        self.assertEqual(params.intraword_chars, "'")
+        matches = model.find_all_matches("don't know", params)
+        self.assertEqual([match["input"] for match in matches], ["don't", "know"])
+
+    def test_add_alphabet_entries(self):
+        model = VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False)
+        model.add_to_vocabulary("λόγος", None, VocabParams())
+        model.add_alphabet_entries([["λ"], ["ό"], ["γ"], ["ο"], ["ς", "σ"]])
+        model.build()
+        self.assertEqual(model.find_variants("λόγοσ", SearchParameters())[0].text, "λόγος")
+        with self.assertRaises(ValueError):
+            model.add_alphabet_entries([["δ"]])
+
+    def test_free(self):
+        with VariantModel("../../examples/simple.alphabet.tsv", Weights(), debug=False) as model:
+            model.read_lexicon(LEXICON_AMPHIBIANS)
+            model.build()
+            self.assertTrue("salamander" in model)
+        with self.assertRaises(RuntimeError):
+            model.find_variants("salamander", SearchParameters())
+        model.free() #freeing twice is harmless
+        with self.assertRaises(RuntimeError):
+            model.vocabulary_size()
+
+    def test_introspection(self):
+        alphabet = [[c, c.upper()] for c in "abcdefghijklmnopqrstuvwxyz"]
+        model = VariantModel.from_alphabet(alphabet, Weights(), debug=False)
+        model.read_lexicon(LEXICON_AMPHIBIANS)
+        model.read_lexicon(LEXICON_REPTILES)
+        self.assertEqual(model.alphabet(), alphabet)
+        self.assertEqual(model.lexicons(), [LEXICON_AMPHIBIANS, LEXICON_REPTILES])
+        entries = list(model.iter_vocab())
+        self.assertEqual(len(entries), model.vocabulary_size())
+        frog = next(entry for entry in entries if entry['text'] == 'frog')
+        self.assertEqual(frog['lexicons'], [LEXICON_AMPHIBIANS])
+
     def assert_result(self, result, orig_term, lexicon, lex_term=None):
         if not lex_term:
             lex_term = orig_term
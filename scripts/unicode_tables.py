@@ -0,0 +1,97 @@
+#!/usr/bin/env python3
+
+"""Generates the Unicode normalization tables in src/unicode/tables.rs from the Unicode Character Database.
+
+Download UnicodeData.txt and CompositionExclusions.txt for the desired Unicode version from
+https://www.unicode.org/Public/<version>/ucd/ and run:
+
+    python3 scripts/unicode_tables.py 14.0.0 UnicodeData.txt CompositionExclusions.txt > src/unicode/tables.rs
+"""
+
+import sys
+
+
+def parse_unicodedata(filename):
+    """Returns the canonical combining classes and canonical decompositions by code point"""
+    classes = {}
+    decompositions = {}
+    with open(filename, encoding="utf-8") as f:
+        for line in f:
+            fields = line.strip().split(";")
+            if len(fields) < 6:
+                continue
+            cp = int(fields[0], 16)
+            ccc = int(fields[3])
+            if ccc:
+                classes[cp] = ccc
+            # compatibility decompositions start with a <tag>, we only need canonical ones
+            if fields[5] and not fields[5].startswith("<"):
+                decompositions[cp] = [int(x, 16) for x in fields[5].split()]
+    return classes, decompositions
+
+
+def parse_exclusions(filename):
+    """Returns the code points that are explicitly excluded from composition"""
+    exclusions = set()
+    with open(filename, encoding="utf-8") as f:
+        for line in f:
+            line = line.split("#")[0].strip()
+            if line:
+                exclusions.add(int(line, 16))
+    return exclusions
+
+
+def main():
+    if len(sys.argv) != 4:
+        print(__doc__, file=sys.stderr)
+        sys.exit(2)
+    version = sys.argv[1]
+    classes, decompositions = parse_unicodedata(sys.argv[2])
+    exclusions = parse_exclusions(sys.argv[3])
+
+    print("// Unicode normalization tables, generated by scripts/unicode_tables.py from the Unicode")
+    print(f"// Character Database version {version} (UnicodeData.txt and CompositionExclusions.txt).")
+    print("// Do not edit by hand, run the script again to update them.")
+    print()
+
+    print("/// Canonical decompositions: (character, first, second), second is 0 for singleton decompositions. Sorted by character.")
+    print("pub(super) const CANONICAL_DECOMPOSITIONS: &[(u32, u32, u32)] = &[")
+    for cp, decomposition in sorted(decompositions.items()):
+        first = decomposition[0]
+        second = decomposition[1] if len(decomposition) > 1 else 0
+        print(f"    (0x{cp:04X}, 0x{first:04X}, 0x{second:04X}),")
+    print("];")
+    print()
+
+    # primary composites: pairs not excluded from composition (full composition exclusion covers
+    # the explicit exclusions, singletons and decompositions that start with a non-starter)
+    compositions = []
+    for cp, decomposition in decompositions.items():
+        if len(decomposition) != 2 or cp in exclusions:
+            continue
+        if classes.get(cp, 0) != 0 or classes.get(decomposition[0], 0) != 0:
+            continue
+        compositions.append((decomposition[0], decomposition[1], cp))
+    print("/// Primary composites: (first, second, composite). Sorted by first and second.")
+    print("pub(super) const CANONICAL_COMPOSITIONS: &[(u32, u32, u32)] = &[")
+    for first, second, cp in sorted(compositions):
+        print(f"    (0x{first:04X}, 0x{second:04X}, 0x{cp:04X}),")
+    print("];")
+    print()
+
+    # ranges of consecutive code points with the same class
+    ranges = []
+    for cp, ccc in sorted(classes.items()):
+        if ranges and ranges[-1][1] == cp - 1 and ranges[-1][2] == ccc:
+            ranges[-1][1] = cp
+        else:
+            ranges.append([cp, cp, ccc])
+    print("/// Canonical combining classes: (first, last, class). Sorted, characters not covered have class 0.")
+    print("pub(super) const COMBINING_CLASSES: &[(u32, u32, u8)] = &[")
+    for first, last, ccc in ranges:
+        print(f"    (0x{first:04X}, 0x{last:04X}, {ccc}),")
+    print("];")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,91 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+
+/// Whether an affix rule strips a prefix or a suffix
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AffixType {
+    Prefix,
+    Suffix,
+}
+
+/// A strippable affix with a weight, used to match inflected or derived inputs against their stem
+/// in the lexicon (e.g. `huisjes` against `huis` by stripping the suffix `jes`). Unlike
+/// confusables, which apply to arbitrary edits, affix rules only apply at the beginning (prefixes)
+/// or end (suffixes) of the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AffixRule {
+    /// The affix itself, without the hyphen marking where the stem attaches
+    pub affix: String,
+    pub affixtype: AffixType,
+    /// The score of variants found for the stripped input is multiplied by this weight (in the range `(0.0, 1.0]`)
+    pub weight: f64,
+}
+
+impl AffixRule {
+    /// Parses an affix rule from its notation, in which a hyphen marks where the stem attaches:
+    /// `-jes` denotes a suffix and `ge-` a prefix
+    pub fn new(affix: &str, weight: f64) -> Result<AffixRule, Error> {
+        let (affix, affixtype) = if let Some(suffix) = affix.strip_prefix('-') {
+            (suffix, AffixType::Suffix)
+        } else if let Some(prefix) = affix.strip_suffix('-') {
+            (prefix, AffixType::Prefix)
+        } else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Affix {} should start with a hyphen (suffix) or end with one (prefix)",
+                    affix
+                ),
+            ));
+        };
+        if affix.is_empty() || affix.contains(char::is_whitespace) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Affix {} should be non-empty and can not contain whitespace",
+                    affix
+                ),
+            ));
+        }
+        if !(weight > 0.0 && weight <= 1.0) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Weight of affix {} should be in the range (0.0, 1.0], got {}",
+                    affix, weight
+                ),
+            ));
+        }
+        Ok(AffixRule {
+            affix: affix.to_string(),
+            affixtype,
+            weight,
+        })
+    }
+
+    /// Strips the affix from the text and returns the remaining stem, or `None` if the rule does
+    /// not apply. The rule only applies if the stem is a non-empty part of the same word as the affix.
+    pub fn strip<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let stem = match self.affixtype {
+            AffixType::Suffix => text.strip_suffix(self.affix.as_str())?,
+            AffixType::Prefix => text.strip_prefix(self.affix.as_str())?,
+        };
+        let adjacent = match self.affixtype {
+            AffixType::Suffix => stem.chars().last(),
+            AffixType::Prefix => stem.chars().next(),
+        };
+        match adjacent {
+            Some(c) if !c.is_whitespace() => Some(stem),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AffixRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.affixtype {
+            AffixType::Suffix => write!(f, "-{}", self.affix),
+            AffixType::Prefix => write!(f, "{}-", self.affix),
+        }
+    }
+}
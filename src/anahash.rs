@@ -1,85 +1,227 @@
 use ibig::UBig;
 use num_traits::{One, Zero};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashSet;
+use std::fmt;
+use std::ops::{Add, Mul};
 
 use crate::iterators::*;
 use crate::types::*;
 
-///Trait for objects that can be anahashed (string-like)
+/// Matches strings against the entries of an alphabet. This is implemented directly for an
+/// [`Alphabet`], which scans all entries at every position, and by [`AlphabetMatcher`], which is
+/// prebuilt from an alphabet and is much faster for large alphabets. Both give identical results.
+pub trait AlphabetLookup {
+    /// Matches the alphabet against the text at the specified byte position, returns the index of
+    /// the alphabet entry and the length (in bytes) of the matching string. Matching is greedy in
+    /// the order of the alphabet: if multiple strings match, the first one in the alphabet wins.
+    fn match_at(&self, text: &str, bytepos: usize) -> Option<(CharIndexType, usize)>;
+
+    /// The number of entries in the alphabet (not counting the unknown character)
+    fn alphabet_len(&self) -> usize;
+}
+
+impl AlphabetLookup for Alphabet {
+    fn match_at(&self, text: &str, bytepos: usize) -> Option<(CharIndexType, usize)> {
+        for (seqnr, chars) in self.iter().enumerate() {
+            for element in chars.iter().filter(|element| !element.is_empty()) {
+                if let Some(slice) = text.get(bytepos..bytepos + element.len()) {
+                    if slice == element {
+                        return Some((seqnr as CharIndexType, element.len()));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn alphabet_len(&self) -> usize {
+        self.len()
+    }
+}
+
+/// A byte-level trie over all strings of an alphabet, so the alphabet can be matched against a
+/// text at any position in a single pass over the bytes that follow it, rather than by testing
+/// every alphabet entry in turn. Build it once with [`AlphabetMatcher::new()`] and pass it instead
+/// of the alphabet to the methods of [`Anahashable`]. It must be rebuilt if the alphabet changes.
+#[derive(Clone, Debug, Default)]
+pub struct AlphabetMatcher {
+    nodes: Vec<AlphabetTrieNode>,
+    alphabet_len: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct AlphabetTrieNode {
+    /// Children by byte, sorted
+    children: Vec<(u8, u32)>,
+    /// If a string of the alphabet ends here: its rank in alphabet order (lower wins) and the
+    /// index of its alphabet entry
+    entry: Option<(u32, CharIndexType)>,
+}
+
+impl AlphabetMatcher {
+    pub fn new(alphabet: &Alphabet) -> Self {
+        let mut matcher = Self {
+            nodes: vec![AlphabetTrieNode::default()],
+            alphabet_len: alphabet.len(),
+        };
+        let mut rank: u32 = 0;
+        for (seqnr, chars) in alphabet.iter().enumerate() {
+            for element in chars.iter().filter(|element| !element.is_empty()) {
+                let mut node = 0;
+                for byte in element.bytes() {
+                    node = match matcher.nodes[node]
+                        .children
+                        .binary_search_by_key(&byte, |(b, _)| *b)
+                    {
+                        Ok(pos) => matcher.nodes[node].children[pos].1 as usize,
+                        Err(pos) => {
+                            let child = matcher.nodes.len();
+                            matcher.nodes.push(AlphabetTrieNode::default());
+                            matcher.nodes[node]
+                                .children
+                                .insert(pos, (byte, child as u32));
+                            child
+                        }
+                    };
+                }
+                //if a string occurs multiple times in the alphabet, the first occurrence wins
+                if matcher.nodes[node].entry.is_none() {
+                    matcher.nodes[node].entry = Some((rank, seqnr as CharIndexType));
+                }
+                rank += 1;
+            }
+        }
+        matcher
+    }
+}
+
+impl AlphabetLookup for AlphabetMatcher {
+    fn match_at(&self, text: &str, bytepos: usize) -> Option<(CharIndexType, usize)> {
+        let mut best: Option<(u32, CharIndexType, usize)> = None;
+        let mut node = 0;
+        for (i, byte) in text.as_bytes()[bytepos..].iter().enumerate() {
+            node = match self.nodes[node]
+                .children
+                .binary_search_by_key(byte, |(b, _)| *b)
+            {
+                Ok(pos) => self.nodes[node].children[pos].1 as usize,
+                Err(_) => break,
+            };
+            if let Some((rank, seqnr)) = self.nodes[node].entry {
+                if best.is_none_or(|(bestrank, ..)| rank < bestrank) {
+                    best = Some((rank, seqnr, i + 1));
+                }
+            }
+        }
+        best.map(|(_, seqnr, len)| (seqnr, len))
+    }
+
+    fn alphabet_len(&self) -> usize {
+        self.alphabet_len
+    }
+}
+
+///Trait for objects that can be anahashed (string-like). The alphabet may be passed as an
+///[`Alphabet`] or, for speed, as a prebuilt [`AlphabetMatcher`].
 pub trait Anahashable {
-    fn anahash(&self, alphabet: &Alphabet) -> AnaValue;
-    fn normalize_to_alphabet(&self, alphabet: &Alphabet) -> NormString;
+    fn anahash(&self, alphabet: &(impl AlphabetLookup + ?Sized)) -> AnaValue;
+    fn normalize_to_alphabet(&self, alphabet: &(impl AlphabetLookup + ?Sized)) -> NormString {
+        self.normalize_to_alphabet_with(alphabet, false)
+    }
+    /// Normalize via the alphabet, if `distinct_unknowns` is set each distinct character that is
+    /// not covered by the alphabet is encoded with its own symbol (see [`unknown_symbol()`])
+    /// rather than all with the same one
+    fn normalize_to_alphabet_with(
+        &self,
+        alphabet: &(impl AlphabetLookup + ?Sized),
+        distinct_unknowns: bool,
+    ) -> NormString;
+    /// Returns all characters that are not covered by the alphabet (in order of appearance)
+    fn unknown_chars(&self, alphabet: &(impl AlphabetLookup + ?Sized)) -> Vec<char>;
 }
 
 impl Anahashable for str {
     ///Compute the anahash for a given string, according to the alphabet
-    fn anahash(&self, alphabet: &Alphabet) -> AnaValue {
+    fn anahash(&self, alphabet: &(impl AlphabetLookup + ?Sized)) -> AnaValue {
         let mut hash: AnaValue = AnaValue::empty();
-        let mut skip = 0;
+        let mut resume = 0;
         for (bytepos, _c) in self.char_indices() {
-            if skip > 0 {
-                skip -= 1;
+            if bytepos < resume {
                 continue;
             }
-            let mut matched = false;
-            'abciter: for (seqnr, chars) in alphabet.iter().enumerate() {
-                for element in chars.iter() {
-                    let charlen = element.chars().count();
-                    let bytelen = element.len();
-                    if let Some(slice) = self.get(bytepos..bytepos + bytelen) {
-                        if slice == element {
-                            let charvalue = AnaValue::character(seqnr as CharIndexType);
-                            hash = hash.insert(&charvalue);
-                            matched = true;
-                            skip = charlen - 1;
-                            break 'abciter;
-                        }
-                    }
-                }
-            }
-            if !matched {
+            let seqnr = if let Some((seqnr, bytelen)) = alphabet.match_at(self, bytepos) {
+                resume = bytepos + bytelen;
+                seqnr
+            } else {
                 //Highest one is reserved for UNK
-                let charvalue = AnaValue::character(alphabet.len() as CharIndexType);
-                hash = hash.insert(&charvalue);
-            }
+                alphabet.alphabet_len() as CharIndexType
+            };
+            hash = hash.insert(&AnaValue::character(seqnr));
         }
         hash
     }
 
     ///Normalize a string via the alphabet
-    fn normalize_to_alphabet(&self, alphabet: &Alphabet) -> NormString {
+    fn normalize_to_alphabet_with(
+        &self,
+        alphabet: &(impl AlphabetLookup + ?Sized),
+        distinct_unknowns: bool,
+    ) -> NormString {
         let mut result = Vec::with_capacity(self.chars().count());
-        let mut skip = 0;
-        for (bytepos, _c) in self.char_indices() {
-            if skip > 0 {
-                skip -= 1;
+        let mut resume = 0;
+        for (bytepos, c) in self.char_indices() {
+            if bytepos < resume {
                 continue;
             }
             //does greedy matching in order of appearance in the alphabet file
-            let mut matched = false;
-            'abciter: for (seqnr, chars) in alphabet.iter().enumerate() {
-                for element in chars.iter() {
-                    let charlen = element.chars().count();
-                    let bytelen = element.len();
-                    if let Some(slice) = self.get(bytepos..bytepos + bytelen) {
-                        if slice == element {
-                            result.push(seqnr as CharIndexType);
-                            matched = true;
-                            skip = charlen - 1;
-                            break 'abciter;
-                        }
-                    }
-                }
-            }
-            if !matched {
+            if let Some((seqnr, bytelen)) = alphabet.match_at(self, bytepos) {
+                result.push(seqnr);
+                resume = bytepos + bytelen;
+            } else if distinct_unknowns {
+                result.push(unknown_symbol(c, alphabet.alphabet_len()));
+            } else {
                 //Highest one is reserved for UNK
-                result.push(alphabet.len() as CharIndexType + 1);
+                result.push(alphabet.alphabet_len() as CharIndexType + 1);
+            }
+        }
+        result
+    }
+
+    fn unknown_chars(&self, alphabet: &(impl AlphabetLookup + ?Sized)) -> Vec<char> {
+        let mut result = Vec::new();
+        let mut resume = 0;
+        for (bytepos, c) in self.char_indices() {
+            if bytepos < resume {
+                continue;
+            }
+            //same greedy matching as normalize_to_alphabet()
+            if let Some((_, bytelen)) = alphabet.match_at(self, bytepos) {
+                resume = bytepos + bytelen;
+            } else {
+                result.push(c);
             }
         }
         result
     }
 }
 
+/// Returns the symbol that encodes an unknown character (one not covered by the alphabet) in a
+/// normalized string when distinct unknowns are enabled. The symbols are taken from the range
+/// following the one reserved for UNK (`alphabet_len + 1`), distinct characters get distinct
+/// symbols as far as that range allows, beyond that they share symbols. Falls back to the UNK
+/// symbol if the alphabet leaves no room.
+pub fn unknown_symbol(c: char, alphabet_len: usize) -> CharIndexType {
+    let unk = alphabet_len + 1;
+    let first = unk + 1;
+    let available = (CharIndexType::MAX as usize + 1).saturating_sub(first);
+    if available == 0 {
+        unk.min(CharIndexType::MAX as usize) as CharIndexType
+    } else {
+        (first + (c as usize) % available) as CharIndexType
+    }
+}
+
 /// This trait can be applied to types
 /// that can function as anahashes.
 /// It can be implemented  for integer types.
@@ -136,37 +278,169 @@ pub trait Anahash: One + Zero {
     }
 }
 
+impl AnaValue {
+    /// Convert a big integer to an anagram value, using the small representation if it fits
+    fn from_ubig(value: UBig) -> AnaValue {
+        match u128::try_from(&value) {
+            Ok(value) => AnaValue::Small(value),
+            Err(_) => AnaValue::Big(value),
+        }
+    }
+
+    /// Returns the value as a big integer
+    pub fn to_ubig(&self) -> UBig {
+        match self {
+            AnaValue::Small(value) => UBig::from(*value),
+            AnaValue::Big(value) => value.clone(),
+        }
+    }
+
+    /// Returns the (estimated) number of bytes this value occupies on the heap
+    pub fn heap_size(&self) -> usize {
+        match self {
+            AnaValue::Small(_) => 0,
+            AnaValue::Big(value) => value.bit_len().div_ceil(64) * 8,
+        }
+    }
+}
+
+impl From<u128> for AnaValue {
+    fn from(value: u128) -> AnaValue {
+        AnaValue::Small(value)
+    }
+}
+
+impl From<usize> for AnaValue {
+    fn from(value: usize) -> AnaValue {
+        AnaValue::Small(value as u128)
+    }
+}
+
+impl From<UBig> for AnaValue {
+    fn from(value: UBig) -> AnaValue {
+        AnaValue::from_ubig(value)
+    }
+}
+
+impl fmt::Display for AnaValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnaValue::Small(value) => write!(f, "{}", value),
+            AnaValue::Big(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Debug for AnaValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Serialised as a big integer regardless of the representation
+impl Serialize for AnaValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_ubig().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AnaValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        UBig::deserialize(deserializer).map(AnaValue::from_ubig)
+    }
+}
+
+impl Mul for AnaValue {
+    type Output = AnaValue;
+
+    fn mul(self, other: AnaValue) -> AnaValue {
+        if let (AnaValue::Small(a), AnaValue::Small(b)) = (&self, &other) {
+            if let Some(product) = a.checked_mul(*b) {
+                return AnaValue::Small(product);
+            }
+        }
+        AnaValue::from_ubig(self.to_ubig() * other.to_ubig())
+    }
+}
+
+impl Add for AnaValue {
+    type Output = AnaValue;
+
+    fn add(self, other: AnaValue) -> AnaValue {
+        if let (AnaValue::Small(a), AnaValue::Small(b)) = (&self, &other) {
+            if let Some(sum) = a.checked_add(*b) {
+                return AnaValue::Small(sum);
+            }
+        }
+        AnaValue::from_ubig(self.to_ubig() + other.to_ubig())
+    }
+}
+
+impl One for AnaValue {
+    fn one() -> AnaValue {
+        AnaValue::Small(1)
+    }
+}
+
+impl Zero for AnaValue {
+    fn zero() -> AnaValue {
+        AnaValue::Small(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self == &AnaValue::Small(0)
+    }
+}
+
 impl Anahash for AnaValue {
     /// Computes the Anagram value for the n'th entry in the alphabet
     fn character(seqnr: CharIndexType) -> AnaValue {
-        UBig::from(PRIMES[seqnr as usize])
+        AnaValue::Small(PRIMES[seqnr as usize] as u128)
     }
 
     /// Insert the characters represented by the anagram value, returning the result
     fn insert(&self, value: &AnaValue) -> AnaValue {
-        if self == &AnaValue::zero() {
+        if self.is_zero() {
             value.clone()
         } else {
-            self * value
+            self.clone() * value.clone()
         }
     }
 
     /// Delete the characters represented by the anagram value, returning the result
     /// Returns None of the anagram was not found
     fn delete(&self, value: &AnaValue) -> Option<AnaValue> {
-        if self.contains(value) {
-            Some(self / value)
-        } else {
-            None
+        match (self, value) {
+            (AnaValue::Small(a), AnaValue::Small(b)) => {
+                if b <= a && a % b == 0 {
+                    Some(AnaValue::Small(a / b))
+                } else {
+                    None
+                }
+            }
+            _ => {
+                if self.contains(value) {
+                    Some(AnaValue::from_ubig(self.to_ubig() / value.to_ubig()))
+                } else {
+                    None
+                }
+            }
         }
     }
 
     /// Tests if the anagram value contains the specified anagram value
     fn contains(&self, value: &AnaValue) -> bool {
-        if value > self {
-            false
-        } else {
-            (self % value) == AnaValue::zero()
+        match (self, value) {
+            (AnaValue::Small(a), AnaValue::Small(b)) => b <= a && a % b == 0,
+            //a big value can never be contained in a small one
+            (AnaValue::Small(_), AnaValue::Big(_)) => false,
+            (AnaValue::Big(a), _) => {
+                if value > self {
+                    false
+                } else {
+                    (a % value.to_ubig()) == UBig::from(0u8)
+                }
+            }
         }
     }
 
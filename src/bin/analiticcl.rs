@@ -3,45 +3,132 @@ extern crate rayon;
 
 use clap::{App, Arg, SubCommand};
 use rayon::prelude::*;
+use regex::Regex;
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
 use std::process::exit;
-use std::time::SystemTime;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
 use analiticcl::*;
 
 #[derive(Debug)]
 enum Resource<'a> {
     Lexicon(&'a str),
+    Corpus(&'a str),
     VariantList(&'a str),
     ErrorList(&'a str),
 }
 
+/// Splits an input line into the text to process, taken from the specified (1-indexed) column,
+/// and all other columns, which are passed through as-is.
+fn split_input_column(line: &str, input_column: Option<usize>) -> (&str, Vec<&str>) {
+    if let Some(input_column) = input_column {
+        let mut text = "";
+        let mut passthrough = Vec::new();
+        for (i, column) in line.split('\t').enumerate() {
+            if i + 1 == input_column {
+                text = column;
+            } else {
+                passthrough.push(column);
+            }
+        }
+        (text, passthrough)
+    } else {
+        (line, Vec::new())
+    }
+}
+
+/// Options that determine what is included in the output of the query, search and serve subcommands
+#[derive(Clone, Copy, Debug)]
+struct OutputOptions {
+    /// Output the lexicons each variant was found in
+    lexmatch: bool,
+    /// Output the group of tied variants each variant belongs to
+    ties: bool,
+    /// Output the frequency and token count of each variant
+    freq: bool,
+    /// Output the normalized representation of the input and of each variant (from the search parameters)
+    debug_normalization: bool,
+    /// The frequency weight used to compute the scores that are output (from the search parameters)
+    freq_weight: f32,
+}
+
+impl OutputOptions {
+    fn new(args: &clap::ArgMatches, searchparams: &SearchParameters) -> Self {
+        Self {
+            lexmatch: args.is_present("output-lexmatch"),
+            ties: args.is_present("output-ties"),
+            freq: args.is_present("output-freq"),
+            debug_normalization: searchparams.debug_normalization,
+            freq_weight: searchparams.freq_weight,
+        }
+    }
+
+    /// Returns the options with those that derive from the search parameters updated
+    fn with_searchparams(&self, searchparams: &SearchParameters) -> Self {
+        Self {
+            debug_normalization: searchparams.debug_normalization,
+            freq_weight: searchparams.freq_weight,
+            ..*self
+        }
+    }
+}
+
+/// Options that determine how the input of the query and search subcommands is read
+#[derive(Clone, Copy, Debug)]
+struct InputOptions {
+    /// Take the input from this (1-indexed) column, passing all other columns through
+    column: Option<usize>,
+    /// Show progress every so many inputs
+    progress: bool,
+    /// In search mode, join lines with a space rather than a newline
+    newline_as_space: bool,
+    /// In search mode, process each line on its own
+    per_line: bool,
+}
+
 fn output_matches_as_tsv(
     model: &VariantModel,
     input: &str,
+    passthrough: &[&str],
     variants: Option<&Vec<VariantResult>>,
     selected: Option<usize>,
     offset: Option<Offset>,
-    output_lexmatch: bool,
-    freq_weight: f32,
+    output: &OutputOptions,
 ) {
+    for column in passthrough.iter() {
+        print!("{}\t", column);
+    }
     print!("{}", input);
     if let Some(offset) = offset {
         print!("\t{}:{}", offset.begin, offset.end);
     }
     if let Some(variants) = variants {
+        let tiegroups = if output.ties {
+            Some(tie_groups(variants, output.freq_weight))
+        } else {
+            None
+        };
         if let Some(selected) = selected {
             //output selected value before all others
             if let Some(result) = variants.get(selected) {
-                output_result_as_tsv(&model, &result, output_lexmatch, freq_weight);
+                output_result_as_tsv(
+                    &model,
+                    &result,
+                    tiegroups.as_ref().map(|x| x[selected]),
+                    output,
+                );
             }
         }
         for (i, result) in variants.iter().enumerate() {
             if selected.is_none() || selected.unwrap() != i {
                 //output all others
-                output_result_as_tsv(&model, &result, output_lexmatch, freq_weight);
+                output_result_as_tsv(&model, &result, tiegroups.as_ref().map(|x| x[i]), output);
             }
         }
     }
@@ -51,139 +138,391 @@ fn output_matches_as_tsv(
 fn output_result_as_tsv(
     model: &VariantModel,
     result: &VariantResult,
-    output_lexmatch: bool,
-    freq_weight: f32,
+    tiegroup: Option<usize>,
+    output: &OutputOptions,
 ) {
     let vocabvalue = model
         .get_vocab(result.vocab_id)
         .expect("getting vocab by id");
-    print!("\t{}\t{}\t", vocabvalue.text, result.score(freq_weight));
-    if output_lexmatch {
-        let lexicons: Vec<&str> = model
-            .lexicons
-            .iter()
-            .enumerate()
-            .filter_map(|(i, name)| {
-                if vocabvalue.in_lexicon(i as u8) {
-                    Some(name.as_str())
-                } else {
-                    None
-                }
-            })
-            .collect();
+    print!(
+        "\t{}\t{}\t",
+        model.variant_text(result),
+        result.score(output.freq_weight)
+    );
+    if output.lexmatch {
+        let lexicons = model.result_lexicons(result);
         print!("\t\"{}\"", lexicons.join(";"));
     }
+    if output.freq {
+        print!("\t{}\t{}", vocabvalue.frequency, vocabvalue.tokencount);
+    }
+    if let Some(tiegroup) = tiegroup {
+        print!("\t{}", tiegroup);
+    }
 }
 
-fn output_matches_as_json(
+/// Formats a match in search mode as a single line with fixed columns (CoNLL-style): the
+/// passthrough columns (if any), the input, its offset, the best variant, its score and the lexicons
+/// it was found in. Empty values are represented by an underscore.
+fn match_to_conll(
     model: &VariantModel,
-    input: &str,
-    variants: Option<&Vec<VariantResult>>,
-    selected: Option<usize>,
-    offset: Option<Offset>,
-    output_lexmatch: bool,
-    freq_weight: f32,
-    seqnr: usize,
-    tag: Vec<u16>,
-    tag_seqnr: Vec<u8>,
-) {
-    if seqnr > 1 {
-        print!("    ,")
+    result_match: &Match,
+    passthrough: &[&str],
+    searchparams: &SearchParameters,
+) -> String {
+    let mut line = String::new();
+    for column in passthrough.iter() {
+        line += column;
+        line.push('\t');
+    }
+    line += &format!(
+        "{}\t{}:{}",
+        result_match.text, result_match.offset.begin, result_match.offset.end
+    );
+    if let Some(result) = result_match.solution() {
+        let lexicons = model.result_lexicons(result);
+        line += &format!(
+            "\t{}\t{}\t{}",
+            model.variant_text(result),
+            result.score(searchparams.freq_weight),
+            if lexicons.is_empty() {
+                "_".to_string()
+            } else {
+                lexicons.join(";")
+            }
+        );
+    } else if let (true, OovPolicy::Placeholder(placeholder)) =
+        (result_match.oov, &searchparams.oov_policy)
+    {
+        line += &format!("\t{}\t_\t_", placeholder);
     } else {
-        print!("    ")
+        line += "\t_\t_\t_";
     }
-    print!("{{ \"input\": \"{}\"", input.replace("\"", "\\\"").as_str());
-    if let Some(offset) = offset {
-        print!(", \"begin\": {}, \"end\": {}", offset.begin, offset.end);
+    line
+}
+
+/// Writes a single JSON array to standard output, one element at a time, so results can be streamed
+struct JsonArrayWriter {
+    first: bool,
+}
+
+impl JsonArrayWriter {
+    fn begin() -> Self {
+        println!("[");
+        Self { first: true }
     }
-    if !tag.is_empty() {
-        print!(", \"tag\": [");
-        for (i, (tag, _tag_seqnr)) in tag.iter().zip(tag_seqnr.iter()).enumerate() {
-            if i > 0 {
-                print!(",")
-            }
-            print!(
-                "\"{}\"",
-                model
-                    .tags
-                    .get(*tag as usize)
-                    .expect("tag must exist in model")
-            );
-        }
-        print!("], \"seqnr\": [ ");
-        for (i, (_tag, tag_seqnr)) in tag.iter().zip(tag_seqnr.iter()).enumerate() {
-            if i > 0 {
-                print!(",")
-            }
-            print!("{}", tag_seqnr);
+
+    fn write(&mut self, item: &impl Serialize) {
+        if !self.first {
+            println!(",");
         }
-        print!("]");
+        self.first = false;
+        print!(
+            "{}",
+            serde_json::to_string(item).expect("serialising to json")
+        );
     }
-    if let Some(variants) = variants {
-        println!(", \"variants\": [ ");
-        let mut wroteoutput = false;
-        if let Some(selected) = selected {
-            if let Some(result) = variants.get(selected) {
-                if wroteoutput {
-                    println!(",");
-                }
-                output_result_as_json(&model, &result, output_lexmatch, freq_weight);
-                wroteoutput = true;
-            }
+
+    fn end(self) {
+        if !self.first {
+            println!();
         }
-        for (i, result) in variants.iter().enumerate() {
-            if selected.is_none() || selected.unwrap() != i {
-                //output all others
-                if wroteoutput {
-                    println!(",");
-                }
-                output_result_as_json(&model, &result, output_lexmatch, freq_weight);
-                wroteoutput = true;
-            }
+        println!("]");
+    }
+}
+
+#[derive(Serialize)]
+struct OffsetJson {
+    begin: usize,
+    end: usize,
+}
+
+impl From<&Offset> for OffsetJson {
+    fn from(offset: &Offset) -> Self {
+        Self {
+            begin: offset.begin,
+            end: offset.end,
         }
-        println!("");
-        println!("    ] }}");
+    }
+}
+
+#[derive(Serialize)]
+struct EditJson<'a> {
+    #[serde(rename = "type")]
+    optype: &'a str,
+    offset: usize,
+    end: usize,
+    input: &'a str,
+    output: &'a str,
+}
+
+/// A single variant in the JSON output
+#[derive(Serialize)]
+struct ResultJson<'a> {
+    text: &'a str,
+    score: f64,
+    dist_score: f64,
+    freq_score: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    via: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lemma: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lexicons: Option<Vec<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokencount: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tiegroup: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalized: Option<Vec<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edits: Option<Vec<EditJson<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<&'a ScoreExplanation>,
+}
+
+/// A single input (or match in search mode) with its variants in the JSON output
+#[derive(Serialize)]
+struct MatchJson<'a> {
+    input: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    columns: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalized: Option<Vec<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    begin: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    byteoffset: Option<OffsetJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    charoffset: Option<OffsetJson>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tag: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    seqnr: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variants: Option<Vec<ResultJson<'a>>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    oov: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<SequenceAlternative>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confidence: Option<SentenceConfidence>,
+}
+
+fn match_to_json<'a>(
+    model: &'a VariantModel,
+    input: &'a str,
+    passthrough: &[&'a str],
+    variants: Option<&'a Vec<VariantResult>>,
+    selected: Option<usize>,
+    output: &OutputOptions,
+) -> MatchJson<'a> {
+    let normalized = if output.debug_normalization {
+        Some(model.decompose_normstring(&input.normalize_to_alphabet(model.alphabet_matcher())))
     } else {
-        println!(" }}");
+        None
+    };
+    let variants = variants.map(|variants| {
+        let tiegroups = if output.ties {
+            Some(tie_groups(variants, output.freq_weight))
+        } else {
+            None
+        };
+        //output the selected value before all others
+        let order = selected
+            .filter(|selected| *selected < variants.len())
+            .into_iter()
+            .chain((0..variants.len()).filter(|i| selected != Some(*i)));
+        order
+            .map(|i| {
+                result_to_json(
+                    model,
+                    &variants[i],
+                    tiegroups.as_ref().map(|x| x[i]),
+                    output,
+                )
+            })
+            .collect()
+    });
+    MatchJson {
+        input,
+        columns: passthrough.to_vec(),
+        normalized,
+        begin: None,
+        end: None,
+        byteoffset: None,
+        charoffset: None,
+        tag: Vec::new(),
+        seqnr: Vec::new(),
+        variants,
+        oov: false,
+        alternatives: Vec::new(),
+        confidence: None,
     }
 }
 
-fn output_result_as_json(
-    model: &VariantModel,
-    result: &VariantResult,
-    output_lexmatch: bool,
-    freq_weight: f32,
-) {
+/// Converts a match found in search mode (see [`VariantModel::find_all_matches()`]) to JSON
+fn search_match_to_json<'a>(
+    model: &'a VariantModel,
+    result_match: &'a Match,
+    passthrough: &[&'a str],
+    output: &OutputOptions,
+) -> MatchJson<'a> {
+    MatchJson {
+        begin: Some(result_match.offset.begin),
+        end: Some(result_match.offset.end),
+        byteoffset: result_match.byteoffset.as_ref().map(OffsetJson::from),
+        charoffset: result_match.charoffset.as_ref().map(OffsetJson::from),
+        tag: result_match
+            .tag
+            .iter()
+            .map(|tag| {
+                model
+                    .tags
+                    .get(*tag as usize)
+                    .expect("tag must exist in model")
+                    .as_str()
+            })
+            .collect(),
+        seqnr: result_match.seqnr.clone(),
+        oov: result_match.oov,
+        alternatives: result_match.alternatives.clone(),
+        confidence: result_match.confidence.clone(),
+        ..match_to_json(
+            model,
+            result_match.text,
+            passthrough,
+            result_match.variants.as_ref(),
+            result_match.selected,
+            output,
+        )
+    }
+}
+
+fn result_to_json<'a>(
+    model: &'a VariantModel,
+    result: &'a VariantResult,
+    tiegroup: Option<usize>,
+    output: &OutputOptions,
+) -> ResultJson<'a> {
     let vocabvalue = model
         .get_vocab(result.vocab_id)
         .expect("getting vocab by id");
-    print!(
-        "        {{ \"text\": \"{}\", \"score\": {}",
-        vocabvalue.text.replace("\"", "\\\""),
-        result.score(freq_weight)
-    );
-    print!(", \"dist_score\": {}", result.dist_score);
-    print!(", \"freq_score\": {}", result.freq_score);
-    if let Some(via_id) = result.via {
-        let viavalue = model.get_vocab(via_id).expect("getting vocab by id");
-        print!(", \"via\": \"{}\"", viavalue.text.replace("\"", "\\\""));
-    }
-    if output_lexmatch {
-        let lexicons: Vec<String> = model
-            .lexicons
-            .iter()
-            .enumerate()
-            .filter_map(|(i, name)| {
-                if vocabvalue.in_lexicon(i as u8) {
-                    Some(format!("\"{}\"", name.replace("\"", "\\\"")))
-                } else {
-                    None
-                }
-            })
-            .collect();
-        print!(", \"lexicons\": [ {} ]", lexicons.join(", "));
+    ResultJson {
+        text: model.variant_text(result),
+        score: result.score(output.freq_weight),
+        dist_score: result.dist_score,
+        freq_score: result.freq_score,
+        via: result.via.map(|via_id| {
+            model
+                .get_vocab(via_id)
+                .expect("getting vocab by id")
+                .text
+                .as_str()
+        }),
+        metadata: vocabvalue.metadata.as_deref(),
+        lemma: vocabvalue.lemma.as_deref(),
+        lexicons: if output.lexmatch {
+            Some(model.result_lexicons(result))
+        } else {
+            None
+        },
+        frequency: if output.freq {
+            Some(vocabvalue.frequency)
+        } else {
+            None
+        },
+        tokencount: if output.freq {
+            Some(vocabvalue.tokencount)
+        } else {
+            None
+        },
+        tiegroup,
+        normalized: if output.debug_normalization {
+            Some(model.decompose_normstring(&vocabvalue.norm))
+        } else {
+            None
+        },
+        edits: result.edits.as_ref().map(|edits| {
+            edits
+                .iter()
+                .map(|edit| EditJson {
+                    optype: edit.optype.as_str(),
+                    offset: edit.offset,
+                    end: edit.end,
+                    input: edit.input.as_str(),
+                    output: edit.output.as_str(),
+                })
+                .collect()
+        }),
+        explanation: result.explanation.as_ref(),
     }
-    print!(" }}");
+}
+
+/// A single candidate in the evaluation dump (JSON Lines), see [`eval_dump_hook()`]
+#[derive(Serialize)]
+struct EvalRowJson<'a> {
+    input: &'a str,
+    rank: usize,
+    text: &'a str,
+    score: f64,
+    dist_score: f64,
+    freq_score: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    via: Option<&'a str>,
+    lexicons: Vec<&'a str>,
+    frequency: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<&'a ScoreExplanation>,
+}
+
+/// Returns an evaluation hook that writes the complete ranked list of candidates for each input to
+/// the specified file, one JSON object per line per candidate. The candidates of an input are
+/// always written together.
+fn eval_dump_hook(filename: &str) -> EvalHook {
+    let file = Mutex::new(
+        File::create(filename)
+            .unwrap_or_else(|e| panic!("Unable to create evaluation dump {}: {}", filename, e)),
+    );
+    Box::new(move |eval: &EvalCandidates| {
+        let mut rows = String::new();
+        for (i, result) in eval.candidates.iter().enumerate() {
+            let vocabvalue = eval
+                .model
+                .get_vocab(result.vocab_id)
+                .expect("getting vocab by id");
+            let row = EvalRowJson {
+                input: eval.input,
+                rank: i + 1,
+                text: vocabvalue.text.as_str(),
+                score: result.score(eval.freq_weight),
+                dist_score: result.dist_score,
+                freq_score: result.freq_score,
+                via: result.via.map(|via_id| {
+                    eval.model
+                        .get_vocab(via_id)
+                        .expect("getting vocab by id")
+                        .text
+                        .as_str()
+                }),
+                lexicons: eval.model.result_lexicons(result),
+                frequency: vocabvalue.frequency,
+                explanation: result.explanation.as_ref(),
+            };
+            rows += &serde_json::to_string(&row).expect("serialising to json");
+            rows.push('\n');
+        }
+        file.lock()
+            .expect("locking evaluation dump")
+            .write_all(rows.as_bytes())
+            .expect("writing evaluation dump");
+    })
 }
 
 ///auxiliary function outputting a single variant
@@ -267,146 +606,146 @@ fn output_weighted_variants_as_tsv(model: &VariantModel, multioutput: bool) {
     }
 }
 
-///auxiliary function outputting a single variant
-fn output_weighted_variant_as_json(
-    text: &str,
+#[derive(Serialize)]
+struct WeightedVariantJson<'a> {
+    text: &'a str,
     score: f64,
     freq: u32,
-    lexindex: u32,
-    multioutput: bool,
-    outfiles: &mut HashMap<u8, File>,
-    model: &VariantModel,
-) {
-    if multioutput {
-        for lexindex in model.lexicons.iter().enumerate().filter_map(|(i, _name)| {
-            if lexindex as usize & (1 << i) == 1 << i {
-                Some(i)
-            } else {
-                None
-            }
-        }) {
-            let lexindex = lexindex as u8;
-            let f = if let Some(f) = outfiles.get_mut(&lexindex) {
-                f
-            } else {
-                let filename: String = format!(
-                    "{}.variants.json",
-                    model
-                        .lexicons
-                        .get(lexindex as usize)
-                        .expect("lexindex must exist")
-                );
-                if let Ok(f) = File::create(filename.as_str()) {
-                    outfiles.insert(lexindex, f);
-                    outfiles
-                        .get_mut(&lexindex)
-                        .expect("outfile must be prepared")
-                } else {
-                    panic!("unable to write to {}", filename.as_str());
-                }
-            };
-            f.write(
-                format!(
-                    "        {{ \"text\": \"{}\",  \"score\": {}, \"freq\": {} }}, ",
-                    text.replace("\"", "\\\""),
-                    freq,
-                    score
-                )
-                .as_bytes(),
-            )
-            .expect("error writing to file");
+}
+
+/// Weighted variants per reference text, serialised as a JSON object that retains the order of the references
+struct WeightedVariantsJson<'a>(Vec<(&'a str, Vec<WeightedVariantJson<'a>>)>);
+
+impl Serialize for WeightedVariantsJson<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (text, variants) in self.0.iter() {
+            map.serialize_entry(text, variants)?;
         }
-    } else {
-        println!(
-            "        {{ \"text\": \"{}\", \"score\": {}, \"freq\": {} }}, ",
-            text.replace("\"", "\\\""),
-            score,
-            freq
-        );
+        map.end()
     }
 }
 
-/// Outputs weighted variants stored in the model as tsv
-fn output_weighted_variants_as_json(model: &VariantModel, multioutput: bool) {
-    let mut outfiles: HashMap<u8, File> = HashMap::new();
-    let mut first;
-    println!("{{");
+/// Outputs weighted variants stored in the model as json
+fn output_weighted_variants_as_json(
+    writer: &mut JsonArrayWriter,
+    model: &VariantModel,
+    multioutput: bool,
+) {
+    let mut all = WeightedVariantsJson(Vec::new());
+    //only used with multioutput, variants per lexicon index
+    let mut perlexicon: HashMap<u8, WeightedVariantsJson> = HashMap::new();
     for vocabitem in model.decoder.iter() {
-        first = true;
         if let Some(variants) = &vocabitem.variants {
-            for variant in variants {
-                if let VariantReference::ReferenceFor((vocab_id, score)) = variant {
-                    if first {
-                        println!(
-                            "    \"{}\": [ ",
-                            vocabitem.text.replace("\"", "\\\"").as_str()
-                        );
-                        first = false;
+            let variants: Vec<(&VocabValue, f64)> = variants
+                .iter()
+                .filter_map(|variant| {
+                    if let VariantReference::ReferenceFor((vocab_id, score)) = variant {
+                        let variantitem = model
+                            .decoder
+                            .get(*vocab_id as usize)
+                            .expect("vocab id must exist");
+                        Some((variantitem, *score))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            if variants.is_empty() {
+                continue;
+            }
+            if multioutput {
+                for (variantitem, score) in variants {
+                    for lexindex in variantitem.lexindex_as_vec() {
+                        let entries = &mut perlexicon
+                            .entry(lexindex)
+                            .or_insert_with(|| WeightedVariantsJson(Vec::new()))
+                            .0;
+                        if entries.last().map(|(text, _)| *text) != Some(vocabitem.text.as_str()) {
+                            entries.push((vocabitem.text.as_str(), Vec::new()));
+                        }
+                        entries
+                            .last_mut()
+                            .expect("entry must exist")
+                            .1
+                            .push(WeightedVariantJson {
+                                text: variantitem.text.as_str(),
+                                score,
+                                freq: variantitem.frequency,
+                            });
                     }
-                    let variantitem = model
-                        .decoder
-                        .get(*vocab_id as usize)
-                        .expect("vocab id must exist");
-                    output_weighted_variant_as_json(
-                        &variantitem.text,
-                        *score,
-                        variantitem.frequency,
-                        variantitem.lexindex,
-                        multioutput,
-                        &mut outfiles,
-                        model,
-                    );
                 }
+            } else {
+                all.0.push((
+                    vocabitem.text.as_str(),
+                    variants
+                        .into_iter()
+                        .map(|(variantitem, score)| WeightedVariantJson {
+                            text: variantitem.text.as_str(),
+                            score,
+                            freq: variantitem.frequency,
+                        })
+                        .collect(),
+                ));
             }
         }
-        if !first {
-            println!("    ]");
+    }
+    if multioutput {
+        for (lexindex, variants) in perlexicon {
+            let filename: String = format!(
+                "{}.variants.json",
+                model
+                    .lexicons
+                    .get(lexindex as usize)
+                    .expect("lexindex must exist")
+            );
+            let f = File::create(filename.as_str())
+                .unwrap_or_else(|_| panic!("unable to write to {}", filename.as_str()));
+            serde_json::to_writer_pretty(f, &variants).expect("error writing to file");
         }
+    } else {
+        writer.write(&all);
     }
-    println!("}}")
 }
 
 fn process(
     model: &VariantModel,
     inputstream: impl Read,
     searchparams: &SearchParameters,
-    output_lexmatch: bool,
-    json: bool,
-    progress: bool,
+    output: &OutputOptions,
+    input: &InputOptions,
+    mut json: Option<&mut JsonArrayWriter>,
 ) {
     let mut seqnr = 0;
     let f_buffer = BufReader::new(inputstream);
     let mut progresstime = SystemTime::now();
     for line in f_buffer.lines() {
-        if let Ok(input) = line {
+        if let Ok(line) = line {
             seqnr += 1;
-            if progress && seqnr % 1000 == 1 {
+            if input.progress && seqnr % 1000 == 1 {
                 progresstime = show_progress(seqnr, progresstime, 1000);
             }
-            let variants = model.find_variants(&input, searchparams);
-            if json {
-                output_matches_as_json(
+            let (text, passthrough) = split_input_column(&line, input.column);
+            let variants = model.find_variants(text, searchparams);
+            if let Some(writer) = json.as_deref_mut() {
+                writer.write(&match_to_json(
                     model,
-                    &input,
+                    text,
+                    &passthrough,
                     Some(&variants),
                     Some(0),
-                    None,
-                    output_lexmatch,
-                    searchparams.freq_weight,
-                    seqnr,
-                    vec![],
-                    vec![],
-                );
+                    output,
+                ));
             } else {
                 //Normal output mode
                 output_matches_as_tsv(
                     model,
-                    &input,
+                    text,
+                    &passthrough,
                     Some(&variants),
                     Some(0),
                     None,
-                    output_lexmatch,
-                    searchparams.freq_weight,
+                    output,
                 );
             }
         }
@@ -419,9 +758,9 @@ fn process_par(
     model: &VariantModel,
     inputstream: impl Read,
     searchparams: &SearchParameters,
-    output_lexmatch: bool,
-    json: bool,
-    progress: bool,
+    output: &OutputOptions,
+    input: &InputOptions,
+    mut json: Option<&mut JsonArrayWriter>,
 ) -> io::Result<()> {
     let mut seqnr = 0;
     let f_buffer = BufReader::new(inputstream);
@@ -442,39 +781,38 @@ fn process_par(
             }
         }
         let batchsize = batch.len();
-        let output: Vec<_> = batch
+        let results: Vec<_> = batch
             .par_iter()
-            .map(|input| (input, model.find_variants(&input, searchparams)))
+            .map(|line| {
+                let (text, passthrough) = split_input_column(line, input.column);
+                (text, passthrough, model.find_variants(text, searchparams))
+            })
             .collect();
-        for (input, variants) in output {
+        for (text, passthrough, variants) in results {
             seqnr += 1;
-            if json {
-                output_matches_as_json(
+            if let Some(writer) = json.as_deref_mut() {
+                writer.write(&match_to_json(
                     model,
-                    &input,
+                    text,
+                    &passthrough,
                     Some(&variants),
                     Some(0),
-                    None,
-                    output_lexmatch,
-                    searchparams.freq_weight,
-                    seqnr,
-                    vec![],
-                    vec![],
-                );
+                    output,
+                ));
             } else {
                 //Normal output mode
                 output_matches_as_tsv(
                     model,
-                    &input,
+                    text,
+                    &passthrough,
                     Some(&variants),
                     Some(0),
                     None,
-                    output_lexmatch,
-                    searchparams.freq_weight,
+                    output,
                 );
             }
         }
-        if progress {
+        if input.progress {
             progresstime = show_progress(seqnr, progresstime, batchsize);
         }
     }
@@ -486,7 +824,7 @@ fn process_learn(
     inputstream: impl Read,
     searchparams: &SearchParameters,
     iterations: u8,
-    json: bool,
+    json: Option<&mut JsonArrayWriter>,
     multioutput: bool,
     strict: bool,
     newline_as_space: bool,
@@ -548,8 +886,8 @@ fn process_learn(
             }
         }
     }
-    if json {
-        output_weighted_variants_as_json(model, multioutput);
+    if let Some(writer) = json {
+        output_weighted_variants_as_json(writer, model, multioutput);
     } else {
         output_weighted_variants_as_tsv(model, multioutput);
     }
@@ -562,11 +900,10 @@ fn process_search(
     model: &VariantModel,
     inputstream: impl Read,
     searchparams: &SearchParameters,
-    output_lexmatch: bool,
-    json: bool,
-    progress: bool,
-    newline_as_space: bool,
-    per_line: bool,
+    output: &OutputOptions,
+    input: &InputOptions,
+    mut json: Option<&mut JsonArrayWriter>,
+    conll: bool,
 ) {
     let mut seqnr = 0;
     let mut prevseqnr = 0;
@@ -574,15 +911,20 @@ fn process_search(
     let mut progresstime = SystemTime::now();
     let mut line_iter = f_buffer.lines();
     let mut eof = false;
+    //when reading from a column, each line is necessarily processed on its own
+    let per_line = input.per_line || input.column.is_some();
     while !eof {
         let mut batch = String::new();
+        let mut passthrough: Vec<String> = Vec::new();
         for i in 0..MAX_BATCHSIZE_SEARCH {
-            if let Some(Ok(input)) = line_iter.next() {
+            if let Some(Ok(line)) = line_iter.next() {
+                let (text, columns) = split_input_column(&line, input.column);
+                passthrough = columns.into_iter().map(|s| s.to_owned()).collect();
                 if i > 0 {
-                    batch.push(if newline_as_space { ' ' } else { '\n' });
+                    batch.push(if input.newline_as_space { ' ' } else { '\n' });
                 }
-                let empty = input.is_empty();
-                batch.extend(input.chars());
+                let empty = text.is_empty();
+                batch.extend(text.chars());
                 if empty || per_line {
                     //an empty line is a good breakpoint for a batch
                     break;
@@ -596,45 +938,318 @@ fn process_search(
             }
         }
         //parallellisation will occur inside this method:
-        let output = model.find_all_matches(&batch, searchparams);
-        if seqnr > 0 && !output.is_empty() {
+        let matches = model.find_all_matches(&batch, searchparams);
+        let passthrough: Vec<&str> = passthrough.iter().map(|s| s.as_str()).collect();
+        if seqnr > 0 && !matches.is_empty() {
             println!();
         }
-        for result_match in output {
+        let mut sentence_index = 0;
+        for result_match in matches {
             seqnr += 1;
-            if json {
-                output_matches_as_json(
-                    model,
-                    result_match.text,
-                    result_match.variants.as_ref(),
-                    result_match.selected,
-                    Some(result_match.offset),
-                    output_lexmatch,
-                    searchparams.freq_weight,
-                    seqnr,
-                    result_match.tag,
-                    result_match.seqnr,
+            if conll {
+                if result_match.sentence_index != sentence_index {
+                    //blank line between sentences
+                    println!();
+                    sentence_index = result_match.sentence_index;
+                }
+                println!(
+                    "{}",
+                    match_to_conll(model, &result_match, &passthrough, searchparams)
                 );
+            } else if let Some(writer) = json.as_deref_mut() {
+                writer.write(&search_match_to_json(
+                    model,
+                    &result_match,
+                    &passthrough,
+                    output,
+                ));
             } else {
                 //Normal output mode
                 output_matches_as_tsv(
                     model,
                     result_match.text,
+                    &passthrough,
                     result_match.variants.as_ref(),
                     result_match.selected,
                     Some(result_match.offset),
-                    output_lexmatch,
-                    searchparams.freq_weight,
+                    output,
                 );
             }
         }
-        if progress {
+        if input.progress {
             progresstime = show_progress(seqnr, progresstime, seqnr - prevseqnr);
         }
         prevseqnr = seqnr;
     }
 }
 
+/// Maximum size of the body of a request in server mode
+const MAX_REQUEST_SIZE: usize = 16 * 1024 * 1024;
+
+/// Maximum length of the request line and of each header line of a request in server mode
+const MAX_HEADER_LINE: usize = 8 * 1024;
+
+/// Maximum number of headers of a request in server mode
+const MAX_HEADERS: usize = 100;
+
+/// Time a client in server mode may take to send its complete request, or to receive any part of the response
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Reads a request from a client in server mode, failing once the deadline for the whole request
+/// has passed, so a client that trickles in its request can not hold up the server
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Request deadline exceeded",
+            ));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Decodes a percent-encoded component of a query string, `+` encodes a space
+fn percent_decode(component: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid percent-encoding: {}", component);
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                //from_str_radix would also accept a sign, so check the digits explicitly
+                let hex = component
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+                    .ok_or_else(invalid)?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).map_err(|_| invalid())
+}
+
+/// Overrides search parameters from the query string of a request in server mode
+fn apply_query_parameters(searchparams: &mut SearchParameters, query: &str) -> Result<(), String> {
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let (key, value) = (percent_decode(key)?, percent_decode(value)?);
+        let invalid = || format!("Invalid value for {}: {}", key, value);
+        match key.as_str() {
+            "max_anagram_distance" => {
+                searchparams.max_anagram_distance = value.parse().map_err(|_| invalid())?
+            }
+            "max_edit_distance" => {
+                searchparams.max_edit_distance = value.parse().map_err(|_| invalid())?
+            }
+            "max_matches" => searchparams.max_matches = value.parse().map_err(|_| invalid())?,
+            "score_threshold" => {
+                searchparams.score_threshold = value.parse().map_err(|_| invalid())?
+            }
+            "cutoff_threshold" => {
+                searchparams.cutoff_threshold = value.parse().map_err(|_| invalid())?
+            }
+            "max_ngram" => searchparams.max_ngram = value.parse().map_err(|_| invalid())?,
+            "freq_weight" => searchparams.freq_weight = value.parse().map_err(|_| invalid())?,
+            "unicodeoffsets" => {
+                searchparams.unicodeoffsets = value.parse().map_err(|_| invalid())?
+            }
+            "oov_policy" => searchparams.oov_policy = value.parse().map_err(|_| invalid())?,
+            "nbest" => searchparams.nbest = value.parse().map_err(|_| invalid())?,
+            "sentence_confidence" => {
+                searchparams.sentence_confidence = value.parse().map_err(|_| invalid())?
+            }
+            _ => return Err(format!("Unknown parameter: {}", key)),
+        }
+    }
+    Ok(())
+}
+
+/// Reads a single line of the head of an HTTP request, which may not exceed [`MAX_HEADER_LINE`]
+fn read_header_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    reader
+        .take(MAX_HEADER_LINE as u64 + 1)
+        .read_line(&mut line)?;
+    if line.len() > MAX_HEADER_LINE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Request header line too long",
+        ));
+    }
+    Ok(line)
+}
+
+/// Reads a single HTTP request, returns the method, the request target and the body
+fn read_http_request(reader: &mut impl BufRead) -> io::Result<(String, String, Vec<u8>)> {
+    let requestline = read_header_line(reader)?;
+    let mut fields = requestline.split_whitespace();
+    let (method, target) = match (fields.next(), fields.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Malformed HTTP request line",
+            ))
+        }
+    };
+    let mut content_length = 0;
+    let mut headercount = 0;
+    loop {
+        let header = read_header_line(reader)?;
+        if header.trim().is_empty() {
+            break;
+        }
+        headercount += 1;
+        if headercount > MAX_HEADERS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Too many request headers",
+            ));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Invalid Content-Length")
+                })?;
+            }
+        }
+    }
+    if content_length > MAX_REQUEST_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Request body too large",
+        ));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok((method, target, body))
+}
+
+fn write_http_response(stream: &mut impl Write, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn json_error(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+/// Handles a single request in server mode
+fn handle_connection(
+    model: &VariantModel,
+    stream: TcpStream,
+    searchparams: &SearchParameters,
+    output: &OutputOptions,
+) -> io::Result<()> {
+    //a client that stalls must not block the server indefinitely
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(DeadlineReader {
+        stream: &stream,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    });
+    let mut stream = &stream;
+    let (method, target, body) = match read_http_request(&mut reader) {
+        Ok(request) => request,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            return write_http_response(
+                &mut stream,
+                "400 Bad Request",
+                &json_error(&e.to_string()),
+            );
+        }
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            return write_http_response(
+                &mut stream,
+                "408 Request Timeout",
+                &json_error("Timed out waiting for the request"),
+            );
+        }
+        Err(e) => return Err(e),
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+    if path != "/" {
+        return write_http_response(&mut stream, "404 Not Found", &json_error("Not found"));
+    }
+    if method != "POST" {
+        return write_http_response(
+            &mut stream,
+            "405 Method Not Allowed",
+            &json_error("Only POST is supported, post the text to search in the request body"),
+        );
+    }
+    let text = match String::from_utf8(body) {
+        Ok(text) => text,
+        Err(_) => {
+            return write_http_response(
+                &mut stream,
+                "400 Bad Request",
+                &json_error("Request body must be valid UTF-8"),
+            )
+        }
+    };
+    let mut searchparams = searchparams.clone();
+    if let Err(e) = apply_query_parameters(&mut searchparams, query) {
+        return write_http_response(&mut stream, "400 Bad Request", &json_error(&e));
+    }
+    let output = output.with_searchparams(&searchparams);
+    let matches = model.find_all_matches(&text, &searchparams);
+    let json: Vec<MatchJson> = matches
+        .iter()
+        .map(|m| search_match_to_json(model, m, &[], &output))
+        .collect();
+    write_http_response(
+        &mut stream,
+        "200 OK",
+        &serde_json::to_string(&json).expect("serialising to json"),
+    )
+}
+
+/// Server mode: serves the loaded model over HTTP, handling one request at a time
+fn serve(
+    model: &VariantModel,
+    bind: &str,
+    searchparams: &SearchParameters,
+    output: &OutputOptions,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(bind)?;
+    eprintln!("Listening on http://{}/ (POST text to search)", bind);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle_connection(model, stream, searchparams, output) {
+                    eprintln!("ERROR: {}", e);
+                }
+            }
+            Err(e) => eprintln!("ERROR: {}", e),
+        }
+    }
+    Ok(())
+}
+
 fn show_progress(seqnr: usize, lasttime: SystemTime, batchsize: usize) -> SystemTime {
     let now = SystemTime::now();
     if lasttime >= now || seqnr <= 1 {
@@ -662,7 +1277,39 @@ pub fn common_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
         .takes_value(true)
         .number_of_values(1)
         .multiple(true)
-        .required_unless("variants"));
+        .required_unless_one(&["variants", "corpus"]));
+    args.push(Arg::with_name("corpus")
+        .long("corpus")
+        .help("Corpus-derived frequency list that is used both as a lexicon for variant matching (like --lexicon) and as language model (like --lm), so it only needs to be read once. The format is the same as for --lexicon, entries may be ngrams (space separated tokens) with the absolute frequency count in the second column. This option may be used multiple times.")
+        .takes_value(true)
+        .number_of_values(1)
+        .multiple(true));
+    args.push(Arg::with_name("lexicon-weight")
+        .long("lexicon-weight")
+        .help("Weight of a lexicon, the distance score of variants from that lexicon is multiplied by it, so variants from an authoritative lexicon (> 1.0) can be preferred over those from a noisy one (< 1.0). May be used multiple times, the weights apply to the lexicons (--lexicon) in the order they are specified; lexicons without a weight get 1.0. If a variant occurs in multiple lexicons, the highest weight applies.")
+        .takes_value(true)
+        .number_of_values(1)
+        .multiple(true));
+    args.push(Arg::with_name("metadata-column")
+        .long("metadata-column")
+        .help("Column (0-indexed) in the lexicons that holds metadata for each entry (e.g. a lemma, a category or a source URL), the metadata is passed on to the variants in the output.")
+        .takes_value(true));
+    args.push(Arg::with_name("lemma-column")
+        .long("lemma-column")
+        .help("Column (0-indexed) in the lexicons that holds the lemma of each entry, for lexicons of inflected forms. The lemma is included with the variants in the JSON output, see also --collapse-lemmas.")
+        .takes_value(true));
+    args.push(Arg::with_name("delimiter")
+        .long("delimiter")
+        .help("The character that separates the columns in lexicons, variant lists and language models, e.g. ',' for CSV files or '|'. Use '\\t' for a tab (the default).")
+        .takes_value(true));
+    args.push(Arg::with_name("quoting")
+        .long("quoting")
+        .help("Handle (RFC 4180-style) quoting in lexicons, variant lists and language models: fields enclosed in double quotes may contain the delimiter, double quotes within them are escaped by doubling them.")
+        .required(false));
+    args.push(Arg::with_name("phrases")
+        .long("phrases")
+        .help("Treat multi-word entries in the lexicons as atomic phrases rather than as n-grams, i.e. spaces in lexicon entries are not considered token separators.")
+        .required(false));
     args.push(Arg::with_name("variants")
         .long("variants")
         .short("V")
@@ -692,10 +1339,58 @@ pub fn common_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
         .number_of_values(1)
         .multiple(true)
         .takes_value(true));
+    args.push(Arg::with_name("affixes")
+        .long("affixes")
+        .help("Affix list with weights. This is an optional TSV file with strippable affixes in the first column, in which a hyphen marks where the stem attaches (e.g. '-jes' for a suffix, 'ge-' for a prefix), and weights in the second column. Inputs with such an affix are also matched with the affix stripped, the scores of variants found that way are multiplied by the weight, which must be in the range (0.0, 1.0].")
+        .number_of_values(1)
+        .multiple(true)
+        .takes_value(true));
+    args.push(Arg::with_name("keyboard-confusables")
+        .long("keyboard-confusables")
+        .help("Automatically add confusables for substitutions between adjacent keys on the specified keyboard layout")
+        .takes_value(true)
+        .possible_values(&["qwerty", "azerty"]));
+    args.push(
+        Arg::with_name("keyboard-confusables-weight")
+            .long("keyboard-confusables-weight")
+            .help("Weight for the confusables added by --keyboard-confusables")
+            .takes_value(true)
+            .default_value("1.1"),
+    );
+    args.push(Arg::with_name("substitution-costs")
+        .long("substitution-costs")
+        .help("Substitution costs for the edit distance computation. This is a TSV file with two characters (as defined in the alphabet) in the first two columns and the cost of substituting one for the other in the third column (1.0 is the cost of an arbitrary substitution).")
+        .takes_value(true));
     args.push(Arg::with_name("early-confusables")
         .long("early-confusables")
         .help("Process the confusables before pruning rather than after, may lead to more accurate results but has a performance impact")
         .required(false));
+    args.push(Arg::with_name("unicode-normalization")
+        .long("unicode-normalization")
+        .help("Apply Unicode normalization to the lexicon entries and the input prior to matching. This is useful when input mixes precomposed and decomposed characters (e.g. from different OCR engines).")
+        .takes_value(true)
+        .possible_values(&["none", "nfc", "nfd"])
+        .default_value("none"));
+    args.push(Arg::with_name("casefold")
+        .long("casefold")
+        .help("Match case-insensitively by lowercasing the lexicon entries and the input prior to matching, the variants that are returned retain the casing of the lexicon.")
+        .required(false));
+    args.push(Arg::with_name("distinct-unknowns")
+        .long("distinct-unknowns")
+        .help("Encode each distinct character that is not in the alphabet with its own symbol for the edit distance computation, rather than encoding all of them as the same unknown character. Differing unknown characters then count as a substitution, which prevents false merges on noisy (multilingual) input at the cost of some recall.")
+        .required(false));
+    args.push(Arg::with_name("no-insertions")
+        .long("no-insertions")
+        .help("Do not build the secondary sorted index, which saves memory and query time but restricts the edit operations that can be reached: variants that are longer than the input (i.e. that need insertions) are never found, only deletions and substitutions are.")
+        .required(false));
+    args.push(Arg::with_name("case-first-char")
+        .long("case-first-char")
+        .help("Only compare the casing of the first character of the input and a variant for the case component of the score (as earlier versions did), rather than the fraction of aligned characters with the same casing.")
+        .required(false));
+    args.push(Arg::with_name("check-alphabet")
+        .long("check-alphabet")
+        .help("Check how well the alphabet covers the lexicons and warn about characters that are not in the alphabet, and about entries that consist mostly of such characters (which suggests a wrong or incomplete alphabet).")
+        .required(false));
     args.push(Arg::with_name("contextrules")
         .long("contextrules")
         .short("R")
@@ -709,6 +1404,14 @@ pub fn common_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
             .help("Output the matching lexicon name for each variant match")
             .required(false),
     );
+    args.push(Arg::with_name("output-ties")
+        .long("output-ties")
+        .help("Output the tie group for each variant match. Variants with an identical score share the same (numbered) tie group, their relative order within the group is not meaningful.")
+        .required(false));
+    args.push(Arg::with_name("output-freq")
+        .long("output-freq")
+        .help("Output the absolute frequency and the token count of the lexicon entry of each variant match")
+        .required(false));
     args.push(
         Arg::with_name("json")
             .long("json")
@@ -716,6 +1419,10 @@ pub fn common_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
             .help("Output json instead of tsv")
             .required(false),
     );
+    args.push(Arg::with_name("eval-dump")
+        .long("eval-dump")
+        .help("Write the complete ranked list of candidates for each input, with all score components, to the specified file for evaluation against a gold standard (e.g. to compute recall@k). The candidates are written before they are truncated by --max-matches, --hard-max and --cutoff-threshold. The file is in JSON Lines format, with one object per candidate holding the input, the rank, the variant and its scores. Use --single-thread to write the inputs in the order they are read.")
+        .takes_value(true));
     args.push(
         Arg::with_name("progress")
             .long("progress")
@@ -735,6 +1442,36 @@ pub fn common_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
         .takes_value(true)
         .default_value("0.25")
         .required(false));
+    args.push(Arg::with_name("score-combination")
+        .long("score-combination")
+        .help("How to combine the individual weighted similarity components into a single score: 'arithmetic' (weighted arithmetic mean) or 'geometric' (weighted geometric mean, penalizes any single weak component more harshly)")
+        .takes_value(true)
+        .possible_values(&["arithmetic", "geometric"])
+        .default_value("arithmetic")
+        .required(false));
+    args.push(Arg::with_name("freq-transform")
+        .long("freq-transform")
+        .help("How to transform the frequencies of the variants into frequency scores, prior to normalization: 'linear' (the absolute frequency), 'log' (the logarithm of the frequency) or 'rank' (the rank of the frequency amongst the variants). The latter two make --freq-ranking behave more sensibly for lexicons with very skewed frequency distributions")
+        .takes_value(true)
+        .possible_values(&["linear", "log", "rank"])
+        .default_value("linear")
+        .required(false));
+    args.push(Arg::with_name("freq-cap")
+        .long("freq-cap")
+        .help("Clip the absolute frequencies of the variants at this ceiling before they are transformed into frequency scores, so extremely frequent entries such as function words do not win on frequency alone (0=no cap, default)")
+        .takes_value(true)
+        .required(false));
+    args.push(Arg::with_name("expansion-frequency")
+        .long("expansion-frequency")
+        .help("The frequency a matched variant gets when it is expanded to the item it is a variant of (e.g. for error lists): the minimum of both frequencies (min, default), their product (product), or the frequency of the item referred to (target)")
+        .takes_value(true)
+        .possible_values(&["min", "product", "target"])
+        .default_value("min")
+        .required(false));
+    args.push(Arg::with_name("phonetic")
+        .long("phonetic")
+        .help("Also consider variants that sound the same as the input (i.e. share a Double Metaphone code), regardless of their anagram distance. Requires a non-zero --weight-phonetic.")
+        .required(false));
     args.push(Arg::with_name("cutoff-threshold")
         .long("cutoff-threshold")
         .short("T")
@@ -797,16 +1534,30 @@ pub fn common_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
             .takes_value(true)
             .default_value("0.125"),
     );
+    args.push(
+        Arg::with_name("weight-jarowinkler")
+            .long("weight-jarowinkler")
+            .help("Weight attributed to the Jaro-Winkler similarity in scoring, this favours matches with a common prefix and may help for short words and names. Disabled (0.0) by default.")
+            .takes_value(true)
+            .default_value("0.0"),
+    );
+    args.push(
+        Arg::with_name("weight-phonetic")
+            .long("weight-phonetic")
+            .help("Weight attributed to phonetic similarity (sharing a Double Metaphone code) in scoring. Setting this builds an additional phonetic index. Disabled (0.0) by default.")
+            .takes_value(true)
+            .default_value("0.0"),
+    );
     args.push(Arg::with_name("max-anagram-distance")
         .long("max-anagram-distance")
         .short("k")
-        .help("Maximum anagram distance. Can either be an absolute value (integer), or a ratio of the input length (float between 0.0 and 1.0), or a combination of a ratio with an absolute maximum, separated by a semicolon (ratio;limit). The anagram distance impacts the size of the search space. Each insertion or deletion has cost 1, substitutions can not be separately tracked so they counts as 2 (deletion+insertion). It is therefore recommended to set this value slightly higher than the max edit distance.")
+        .help("Maximum anagram distance. Can either be an absolute value (integer), or a ratio of the input length (float between 0.0 and 1.0), or a combination of a ratio with an absolute maximum, separated by a semicolon (ratio;limit). The anagram distance impacts the size of the search space. Each insertion or deletion has cost 1, substitutions can not be separately tracked so they counts as 2 (deletion+insertion). It is therefore recommended to set this value slightly higher than the max edit distance. You can also derive it from the effective edit distance for each input by specifying a delta prefixed by a plus sign (e.g. +1).")
         .takes_value(true)
         .default_value("3"));
     args.push(Arg::with_name("max-edit-distance")
         .long("max-edit-distance")
         .short("d")
-        .help("Maximum edit distance (levenshtein-damerau). The maximum edit distance according to Levenshtein-Damarau. Can either be an absolute value (integer), or a ratio of the input length (float between 0.0 and 1.0), or a combination of a ratio with an absolute maximum, separated by a semicolon (ratio;limit). When a ratio is expressed, longer inputs use a higher edit distance than shorter ones. Insertions, deletions, substitutions and transposition all have the same cost (1). It is recommended to set this value slightly lower than the maximum anagram distance.")
+        .help("Maximum edit distance (levenshtein-damerau). The maximum edit distance according to Levenshtein-Damarau. Can either be an absolute value (integer), or a ratio of the input length (float between 0.0 and 1.0), or a combination of a ratio with an absolute maximum, separated by a semicolon (ratio;limit). When a ratio is expressed, longer inputs use a higher edit distance than shorter ones. For very short inputs, absolute values are capped to half the input length, but the effective edit distance is always at least 1 (unless explicitly set to 0), for inputs of one or two characters this also holds for ratios. Insertions, deletions, substitutions and transposition all have the same cost (1). It is recommended to set this value slightly lower than the maximum anagram distance.")
         .takes_value(true)
         .default_value("2"));
     args.push(Arg::with_name("max-matches")
@@ -821,6 +1572,81 @@ pub fn common_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
             .short("u")
             .help("Output all text offsets in unicode points rather than UTF-8 byte offsets"),
     );
+    args.push(Arg::with_name("input-column")
+        .long("input-column")
+        .help("Read the input to process from the specified column (1-indexed) of tab-separated input, rather than taking entire lines. All other columns are passed through to the output as-is (preceding the input column in TSV output). In search mode, this implies --per-line.")
+        .takes_value(true)
+        .required(false));
+    args.push(Arg::with_name("debug-normalization")
+        .long("debug-normalization")
+        .help("Include the normalized representation of the input and of each candidate, i.e. the alphabet characters the model actually compares, in the output. This is useful for diagnosing why an expected match did not occur (e.g. a character not in the alphabet). Only has effect on JSON output.")
+        .required(false));
+    args.push(Arg::with_name("return-edits")
+        .long("return-edits")
+        .help("Include the edits (insertions, deletions, substitutions) that transform the input into each variant in the output. Only has effect on JSON output.")
+        .required(false));
+    args.push(Arg::with_name("transfer-case")
+        .long("transfer-case")
+        .help("Transfer the casing pattern of the input (all-caps, title case or lowercase) to the variants, so an input like 'Huys' is corrected to 'Huis' rather than to the lowercase lexicon form 'huis'.")
+        .required(false));
+    args.push(Arg::with_name("collapse-lemmas")
+        .long("collapse-lemmas")
+        .help("Collapse variants that share the same lemma (see --lemma-column), only the best ranking inflection of each lemma is returned.")
+        .required(false));
+    args.push(Arg::with_name("sentence-confidence")
+        .long("sentence-confidence")
+        .help("(Search mode only) Attach a confidence field to the first match of each sentence in the JSON output, with the mean score of the selected variants (out-of-vocabulary tokens count as 0), and the language model perplexity and context rules score of the selected sequence (if applicable). This can be used to flag low-quality sentences for review.")
+        .required(false));
+    args.push(Arg::with_name("explain")
+        .long("explain")
+        .help("Include a breakdown of how the score of each variant was computed in the output: the individual similarity components, their combined score, the confusable weight and (in search mode) the language model perplexity and context rules score of the selected sequence. This is useful for diagnosing surprising rankings. Only has effect on JSON output.")
+        .required(false));
+    args.push(Arg::with_name("neighbourhood-cache")
+        .long("neighbourhood-cache")
+        .help("Keep up to this many computed anagram neighbourhoods in a cache, so repeated or similar inputs are processed faster at the cost of memory (0=disabled, default)")
+        .takes_value(true)
+        .required(false));
+    args.push(Arg::with_name("max-candidates")
+        .long("max-candidates")
+        .help("Maximum number of anagram values to consider as candidates for a single input, the nearest ones are kept. Protects against pathological inputs with huge neighbourhoods (0=unlimited, default)")
+        .takes_value(true)
+        .required(false));
+    args.push(Arg::with_name("hard-max")
+        .long("hard-max")
+        .help("Absolute maximum number of variants to return per input, which also applies if --max-matches is 0 (unlimited); results beyond it are truncated after ranking and a warning is issued. Protects against huge result sets with large lexicons and loose thresholds (0=no maximum, default)")
+        .takes_value(true)
+        .required(false));
+    args.push(Arg::with_name("ngram-candidates")
+        .long("ngram-candidates")
+        .help("Find the candidates for inputs of at least this many characters by the character trigrams they share with lexicon entries, rather than by anagram distance, which is expensive for long inputs such as multi-word phrases. This builds an extra trigram index. No maximum edit distance applies to these candidates (0=disabled, default)")
+        .takes_value(true)
+        .required(false));
+    args.push(Arg::with_name("ngram-overlap")
+        .long("ngram-overlap")
+        .help("The minimum overlap (Dice coefficient over the character trigrams, 0.0-1.0) for candidates found with --ngram-candidates")
+        .takes_value(true)
+        .default_value("0.5")
+        .required(false));
+    args.push(Arg::with_name("skip-exact")
+        .long("skip-exact")
+        .help("If an input occurs verbatim in the lexicon, output it as the sole variant with a perfect score rather than searching for other variants. This speeds up processing of mostly correct text")
+        .required(false));
+    args.push(Arg::with_name("preserve-numeric")
+        .long("preserve-numeric")
+        .help("Do not attempt to match inputs of which at least this proportion of characters are digits (in any script), they yield no variants (and are left uncorrected in search mode). This prevents nonsense corrections of numbers such as years. Set to 1.0 to only preserve inputs consisting entirely of digits, 0.5 to preserve inputs that are at least half digits (0.0=disabled, default)")
+        .takes_value(true)
+        .required(false));
+    args.push(Arg::with_name("oov-policy")
+        .long("oov-policy")
+        .help("What to output in search mode for tokens for which no variant meets the thresholds (out-of-vocabulary tokens): 'keep' keeps the original input (default), 'empty' outputs nothing, 'placeholder' outputs a placeholder (<unk>), use 'placeholder:TEXT' for a custom placeholder. Out-of-vocabulary tokens are marked in the JSON output and the placeholder is shown in the CoNLL output")
+        .takes_value(true)
+        .default_value("keep")
+        .required(false));
+    args.push(Arg::with_name("min-length")
+        .long("min-length")
+        .help("Do not attempt to match inputs shorter than this many characters, they yield no variants (and are left uncorrected in search mode). Very short inputs produce many candidates but rarely meaningful corrections (0=no minimum, default)")
+        .takes_value(true)
+        .required(false));
     args.push(
         Arg::with_name("files")
             .help("Input files")
@@ -833,6 +1659,12 @@ pub fn common_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
 
 pub fn search_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
     let mut args: Vec<Arg> = Vec::new();
+    args.push(Arg::with_name("output-format")
+            .long("output-format")
+            .help("Output format. 'conll' outputs one match per line with fixed columns (input, offset, best variant, score, lexicons) and a blank line between sentences. 'json' is the same as --json.")
+            .takes_value(true)
+            .possible_values(&["tsv", "json", "conll"])
+            .default_value("tsv"));
     args.push(Arg::with_name("per-line")
             .long("per-line")
             .help("Will process per line; assumes each line holds a complete unit (e.g. sentence or paragraph) and that n-grams never cross line boundaires"));
@@ -853,9 +1685,14 @@ pub fn search_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
             .help("Maximum number of candidate sequences to take along to the language modelling stage")
             .takes_value(true)
             .default_value("250"));
+    args.push(Arg::with_name("nbest")
+            .long("nbest")
+            .help("Return this many alternative sequences (corrected sentences) with their scores for each sentence, ranked by decreasing score. Only in JSON output. Can not exceed --max-seq.")
+            .takes_value(true)
+            .default_value("1"));
     args.push(Arg::with_name("lm")
             .long("lm")
-            .help("Language model, a corpus-derived list of n-grams with absolute frequency counts. This is a TSV file containing the the ngram in the first column (space character acts as token separator), and the absolute frequency count in the second column. It is also recommended it contains the special tokens <bos> (begin of sentence) and <eos> end of sentence. The items in this list are NOT used for variant matching, use --corpus or even --lexicon instead if you want to also match against these items. Conversely, files provides through --lexicon and --corpus and other options are NOT used for language modelling.")
+            .help("Language model, a corpus-derived list of n-grams with absolute frequency counts. This is a TSV file containing the the ngram in the first column (space character acts as token separator), and the absolute frequency count in the second column. It is also recommended it contains the special tokens <bos> (begin of sentence) and <eos> end of sentence. The items in this list are NOT used for variant matching, use --corpus instead if you want to also match against these items. Conversely, files provided through --lexicon and other options are NOT used for language modelling.")
             .takes_value(true)
             .number_of_values(1)
             .multiple(true));
@@ -882,11 +1719,66 @@ pub fn search_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
             .help("Weight attributed to the context rules model in finding the most likely sequence in search mode")
             .takes_value(true)
             .default_value("1.0"));
+    args.push(Arg::with_name("global-contextrules")
+            .long("global-contextrules")
+            .help("Apply the context rules once more over all matches of the input, across hard boundaries, so that rules can also tag sequences that span them (e.g. a name split by a line break). This does not affect which variants are selected. Only relevant for search mode.")
+            .required(false));
     args.push(Arg::with_name("weight-context")
             .long("weight-context")
             .help("For rescoring against input context using a language model: weight attributed to the language model in relation to the variant model. (0=disabled, default, 1.0=equal weight, 0.5=half as strong as the variant model). Setting this forces consideration of input context in an earlier stage. Only relevant for search mode.")
             .takes_value(true)
             .default_value("0.0"));
+    args.push(Arg::with_name("lm-smoothing")
+            .long("lm-smoothing")
+            .help("Smoothing method for the language model: 'fixed' assigns a fixed log-probability to unseen transitions (see --lm-smoothing-logprob and --lm-add-k), 'witten-bell' and 'kneser-ney' interpolate with lower-order statistics, which tends to work better for sparse corpora. Only relevant for search mode.")
+            .takes_value(true)
+            .possible_values(&["fixed", "witten-bell", "kneser-ney"])
+            .default_value("fixed"));
+    args.push(Arg::with_name("lm-smoothing-logprob")
+            .long("lm-smoothing-logprob")
+            .help("Log-probability the language model assigns to unseen transitions (bigrams) and to transitions involving out-of-vocabulary tokens. Lower values penalise unseen transitions more strongly. Only relevant for search mode.")
+            .takes_value(true)
+            .allow_hyphen_values(true));
+    args.push(Arg::with_name("lm-add-k")
+            .long("lm-add-k")
+            .help("Use add-k smoothing for the language model with the specified value for k (e.g. 0.01), instead of a fixed log-probability for unseen transitions (0=disabled, default). Only relevant for search mode.")
+            .takes_value(true)
+            .default_value("0.0"));
+    args.push(Arg::with_name("protect")
+            .long("protect")
+            .help("Regular expression for inline markup (e.g. '<[^>]+>' for XML-like tags or '\\[[^\\]]+\\]' for bracketed annotations) that should be protected: text matching it is passed through verbatim and never corrected, the surrounding text is still processed normally. This option may be used multiple times.")
+            .takes_value(true)
+            .number_of_values(1)
+            .multiple(true));
+    args.push(Arg::with_name("skip-unknown-scripts")
+            .long("skip-unknown-scripts")
+            .help("Pass runs of characters that are not covered by the alphabet (e.g. text in another script) through verbatim as hard boundaries, rather than attempting to correct them")
+            .required(false));
+    args.push(Arg::with_name("nonboundary-chars")
+            .long("nonboundary-chars")
+            .help("Non-alphabetic characters that should not be considered token boundaries but part of tokens (e.g. '-/' for chemical nomenclature or code identifiers)")
+            .takes_value(true));
+    args.push(Arg::with_name("intraword-chars")
+            .long("intraword-chars")
+            .help("Non-alphabetic characters that are part of a token if they occur between two alphabetic characters and boundaries otherwise (e.g. \"'\" for contractions like \"don't\" and names like \"O'Brien\", or \"'.\" to also keep abbreviations like \"U.S.\" together, except for the final period)")
+            .takes_value(true));
+    args.push(Arg::with_name("soft-boundary-chars")
+            .long("soft-boundary-chars")
+            .help("Characters that form a weak token boundary, which the system is inclined to ignore")
+            .takes_value(true)
+            .default_value("'-_"));
+    args.push(Arg::with_name("hard-boundary-chars")
+            .long("hard-boundary-chars")
+            .help("Characters that always form a hard token boundary, no matches will span across these")
+            .takes_value(true));
+    args.push(
+        Arg::with_name("whitespace-boundary")
+            .long("whitespace-boundary")
+            .help("The strength of a token boundary consisting of a single whitespace character")
+            .takes_value(true)
+            .possible_values(&["weak", "normal", "hard"])
+            .default_value("normal"),
+    );
     args.push(Arg::with_name("allow-overlap")
             .long("allow-overlap")
             .help("Do not consolidate multiple matches by finding a most likely sequence, but simply return all matches as-is, even if they overlap.")
@@ -894,6 +1786,96 @@ pub fn search_arguments<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
     args
 }
 
+/// Returns the parameters for reading all input files, and those for reading lexicons specifically
+fn vocab_params(args: &clap::ArgMatches) -> (VocabParams, VocabParams) {
+    let mut file_params = VocabParams::default();
+    if let Some(delimiter) = args.value_of("delimiter") {
+        file_params = file_params.with_delimiter(match delimiter {
+            "\\t" | "tab" => '\t',
+            delimiter => {
+                let mut chars = delimiter.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => panic!("Delimiter must be a single character"),
+                }
+            }
+        });
+    }
+    if args.is_present("quoting") {
+        file_params = file_params.with_quoting();
+    }
+
+    let mut lexicon_params = file_params.clone();
+    if let Some(metadata_column) = args.value_of("metadata-column") {
+        lexicon_params = lexicon_params.with_metadata_column(
+            metadata_column
+                .parse::<u8>()
+                .expect("Metadata column must be an integer"),
+        );
+    }
+    if let Some(lemma_column) = args.value_of("lemma-column") {
+        lexicon_params = lexicon_params.with_lemma_column(
+            lemma_column
+                .parse::<u8>()
+                .expect("Lemma column must be an integer"),
+        );
+    }
+    if args.is_present("phrases") {
+        lexicon_params = lexicon_params.with_vocab_type(VocabType::INDEXED | VocabType::PHRASE);
+    }
+    (file_params, lexicon_params)
+}
+
+/// Checks all input files (alphabet, lexicons, variant lists, error lists and confusable lists)
+/// without building a model, and outputs all problems found. Returns the exit code.
+fn check_files(args: &clap::ArgMatches) -> i32 {
+    let (file_params, lexicon_params) = vocab_params(args);
+    let mut checks: Vec<(&str, Result<Vec<FileProblem>, io::Error>)> = Vec::new();
+    let alphabet = args.value_of("alphabet").unwrap();
+    checks.push((alphabet, check_alphabet(alphabet)));
+    for filename in args
+        .values_of("lexicon")
+        .into_iter()
+        .flatten()
+        .chain(args.values_of("corpus").into_iter().flatten())
+    {
+        checks.push((filename, check_vocabulary(filename, &lexicon_params)));
+    }
+    for filename in args
+        .values_of("variants")
+        .into_iter()
+        .flatten()
+        .chain(args.values_of("errors").into_iter().flatten())
+    {
+        checks.push((filename, check_variants(filename, &file_params)));
+    }
+    for filename in args.values_of("confusables").into_iter().flatten() {
+        checks.push((filename, check_confusablelist(filename)));
+    }
+    let mut failed = false;
+    for (filename, result) in checks {
+        match result {
+            Ok(problems) if problems.is_empty() => eprintln!("OK: {}", filename),
+            Ok(problems) => {
+                eprintln!("FAILED: {} ({} problem(s))", filename, problems.len());
+                for problem in problems {
+                    println!("{}", problem);
+                }
+                failed = true;
+            }
+            Err(e) => {
+                eprintln!("FAILED: {} ({})", filename, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        1
+    } else {
+        0
+    }
+}
+
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
 fn main() {
@@ -916,12 +1898,28 @@ fn main() {
                             .about("Test whether the input can be encoded with the given alphabet")
                             .args(&common_arguments())
                     )
+                    .subcommand(
+                        SubCommand::with_name("check")
+                            .about("Check the alphabet, lexicons, variant lists, error lists and confusable lists for problems (with line numbers), without building the model")
+                            .args(&common_arguments())
+                    )
                     .subcommand(
                         SubCommand::with_name("search")
                             .about("Search entire text input and find and output all possible matches")
                             .args(&common_arguments())
                             .args(&search_arguments())
                     )
+                    .subcommand(
+                        SubCommand::with_name("serve")
+                            .about("Load the model once and serve it over HTTP. POST text to / and receive all matches (as in search mode) as JSON. Search parameters may be overridden per request in the query string, e.g. /?max_edit_distance=2. Requests are handled one at a time, a client has 30 seconds to send its complete request.")
+                            .args(&common_arguments())
+                            .args(&search_arguments())
+                            .arg(Arg::with_name("bind")
+                                .long("bind")
+                                .help("Address and port to listen on")
+                                .takes_value(true)
+                                .default_value("127.0.0.1:8080"))
+                    )
                     .subcommand(
                         SubCommand::with_name("learn")
                             .about("Learn variants from the input data. Outputs a (weighted) variant list.")
@@ -949,6 +1947,13 @@ fn main() {
                         .required(false))
                     .get_matches();
 
+    set_logger(Box::new(StderrLogger));
+
+    if let Some(args) = rootargs.subcommand_matches("check") {
+        eprintln!("Checking input files...");
+        exit(check_files(args));
+    }
+
     eprintln!("Initializing model...");
 
     let args = if let Some(args) = rootargs.subcommand_matches("query") {
@@ -961,6 +1966,8 @@ fn main() {
         args
     } else if let Some(args) = rootargs.subcommand_matches("testinput") {
         args
+    } else if let Some(args) = rootargs.subcommand_matches("serve") {
+        args
     } else {
         eprintln!("No command specified, please see analiticcl --help");
         exit(2);
@@ -992,6 +1999,16 @@ fn main() {
             .unwrap()
             .parse::<f64>()
             .expect("Weights should be a floating point value"),
+        jarowinkler: args
+            .value_of("weight-jarowinkler")
+            .unwrap()
+            .parse::<f64>()
+            .expect("Weights should be a floating point value"),
+        phonetic: args
+            .value_of("weight-phonetic")
+            .unwrap()
+            .parse::<f64>()
+            .expect("Weights should be a floating point value"),
     };
 
     let mut model = VariantModel::new(
@@ -1004,14 +2021,38 @@ fn main() {
             .expect("Debug level should be integer in range 0-4"),
     );
 
+    model.set_unicode_normalization(
+        args.value_of("unicode-normalization")
+            .unwrap()
+            .parse::<UnicodeNormalization>()
+            .expect("Unicode normalization should be 'none', 'nfc' or 'nfd'"),
+    );
+    model.set_casefold(args.is_present("casefold"));
+    model.set_case_first_char(args.is_present("case-first-char"));
+    model.set_distinct_unknowns(args.is_present("distinct-unknowns"));
+    model.set_check_alphabet_coverage(args.is_present("check-alphabet"));
+    if args.value_of("ngram-candidates").unwrap_or("0") != "0" {
+        model.set_ngram_index(true);
+    }
+    if args.is_present("no-insertions") {
+        model.set_insertions(false);
+    }
+    if let Some(value) = args.value_of("lm-smoothing") {
+        model.set_lm_smoothing(
+            value
+                .parse::<LmSmoothing>()
+                .expect("LM smoothing should be 'fixed', 'witten-bell' or 'kneser-ney'"),
+        );
+    }
+
     if rootargs.subcommand_matches("testinput").is_some() {
         eprintln!("Testing whether input can be fully encoded...");
         let stdin = io::stdin();
         let f_buffer = BufReader::new(stdin);
         for line in f_buffer.lines() {
             if let Ok(input) = line {
-                let av: AnaValue = input.anahash(&model.alphabet);
-                let normstring: NormString = input.normalize_to_alphabet(&model.alphabet);
+                let av: AnaValue = input.anahash(model.alphabet_matcher());
+                let normstring: NormString = input.normalize_to_alphabet(model.alphabet_matcher());
                 if av.contains(&AnaValue::character(model.alphabet_size() - 1)) {
                     eprintln!("UNKNOWN: {}\t{}\t{:?}", input, av, normstring);
                 } else {
@@ -1034,6 +2075,13 @@ fn main() {
             resources.push((index, Resource::Lexicon(filename)));
         }
     }
+    if args.is_present("corpus") {
+        let corpora = args.values_of("corpus").unwrap().collect::<Vec<&str>>();
+        let corpus_indices = args.indices_of("corpus").unwrap().collect::<Vec<usize>>();
+        for (filename, index) in corpora.iter().zip(corpus_indices) {
+            resources.push((index, Resource::Corpus(filename)));
+        }
+    }
     if args.is_present("variants") {
         let variantlists = args.values_of("variants").unwrap().collect::<Vec<&str>>();
         let variantlist_indices = args.indices_of("variants").unwrap().collect::<Vec<usize>>();
@@ -1053,16 +2101,43 @@ fn main() {
     //sort by index
     resources.sort_by_key(|x| x.0);
 
+    let (file_params, lexicon_params) = vocab_params(args);
+
+    let lexicon_weights: Vec<f64> = args
+        .values_of("lexicon-weight")
+        .into_iter()
+        .flatten()
+        .map(|weight| {
+            weight
+                .parse::<f64>()
+                .expect("Lexicon weights should be a floating point value")
+        })
+        .collect();
+    let mut lexicon_count = 0;
+
     for (_, resource) in resources {
         match resource {
-            Resource::Lexicon(filename) => model
-                .read_vocabulary(filename, &VocabParams::default())
-                .expect(&format!("Error reading lexicon {}", filename)),
+            Resource::Lexicon(filename) => {
+                let weight = lexicon_weights.get(lexicon_count).copied().unwrap_or(1.0);
+                lexicon_count += 1;
+                model
+                    .read_vocabulary(filename, &lexicon_params.clone().with_weight(weight))
+                    .expect(&format!("Error reading lexicon {}", filename))
+            }
+            Resource::Corpus(filename) => model
+                .read_vocabulary(
+                    filename,
+                    &VocabParams {
+                        vocab_type: lexicon_params.vocab_type | VocabType::CORPUS,
+                        ..lexicon_params.clone()
+                    },
+                )
+                .unwrap_or_else(|e| panic!("Error reading corpus {}: {}", filename, e)),
             Resource::VariantList(filename) => model
-                .read_variants(filename, Some(&VocabParams::default()), false)
+                .read_variants(filename, Some(&file_params), false)
                 .expect(&format!("Error reading weighted variant list {}", filename)),
             Resource::ErrorList(filename) => model
-                .read_variants(filename, Some(&VocabParams::default()), true)
+                .read_variants(filename, Some(&file_params), true)
                 .expect(&format!("Error reading weighted variant list {}", filename)),
         }
     }
@@ -1074,7 +2149,7 @@ fn main() {
                     filename,
                     &VocabParams {
                         vocab_type: VocabType::LM,
-                        ..Default::default()
+                        ..file_params.clone()
                     },
                 )
                 .expect(&format!("Error reading lm {}", filename));
@@ -1094,6 +2169,37 @@ fn main() {
         }
     }
 
+    if args.is_present("affixes") {
+        eprintln!("Loading affix lists...");
+        for filename in args.values_of("affixes").unwrap().collect::<Vec<&str>>() {
+            model
+                .read_affixlist(filename)
+                .unwrap_or_else(|e| panic!("Error reading affix list {}: {}", filename, e));
+        }
+    }
+
+    if let Some(layout) = args.value_of("keyboard-confusables") {
+        eprintln!("Adding keyboard confusables...");
+        model
+            .add_keyboard_confusables(
+                layout
+                    .parse::<KeyboardLayout>()
+                    .expect("Keyboard layout should be 'qwerty' or 'azerty'"),
+                args.value_of("keyboard-confusables-weight")
+                    .unwrap()
+                    .parse::<f64>()
+                    .expect("Keyboard confusables weight should be a floating point value"),
+            )
+            .expect("Error adding keyboard confusables");
+    }
+
+    if let Some(filename) = args.value_of("substitution-costs") {
+        eprintln!("Loading substitution costs...");
+        model
+            .read_substitution_costs(filename)
+            .unwrap_or_else(|e| panic!("Error reading substitution costs {}: {}", filename, e));
+    }
+
     if args.is_present("contextrules") {
         eprintln!("Loading context rules...");
         for filename in args
@@ -1111,9 +2217,19 @@ fn main() {
     eprintln!("Building model...");
     model.build();
 
-    let output_lexmatch = args.is_present("output-lexmatch");
+    let input_column: Option<usize> = args.value_of("input-column").map(|value| {
+        let column = value
+            .parse::<usize>()
+            .expect("Input column should be an integer (1-indexed)");
+        if column == 0 {
+            eprintln!("ERROR: Input column is 1-indexed, 0 is not valid");
+            exit(2);
+        }
+        column
+    });
     let progress = args.is_present("progress");
-    let json = args.is_present("json");
+    let json = args.is_present("json") || args.value_of("output-format") == Some("json");
+    let conll = args.value_of("output-format") == Some("conll");
 
     //settings for Search mode
     let perline = args.is_present("per-line");
@@ -1167,12 +2283,93 @@ fn main() {
         } else {
             1.0
         },
+        lm_smoothing_logprob: if let Some(value) = args.value_of("lm-smoothing-logprob") {
+            value.parse::<f32>().expect("LM smoothing log-probability should be a (negative) floating point number")
+        } else {
+            TRANSITION_SMOOTHING_LOGPROB
+        },
+        lm_add_k: if let Some(value) = args.value_of("lm-add-k") {
+            value.parse::<f32>().expect("LM add-k value should be a floating point number")
+        } else {
+            0.0
+        },
         max_seq: if args.is_present("max-seq") {
             args.value_of("max-seq").unwrap().parse::<usize>().expect("max-seq must be an integer")
         } else {
             250
         },
-        unicodeoffsets: args.is_present("unicode-offsets")
+        nbest: args.value_of("nbest").unwrap_or("1").parse::<usize>().expect("nbest must be an integer"),
+        unicodeoffsets: args.is_present("unicode-offsets"),
+        score_combination: args.value_of("score-combination").unwrap().parse::<ScoreCombination>().expect("Score combination should be 'arithmetic' or 'geometric'"),
+        freq_transform: args.value_of("freq-transform").unwrap().parse::<FrequencyTransform>().expect("Frequency transform should be 'linear', 'log' or 'rank'"),
+        freq_cap: if let Some(value) = args.value_of("freq-cap") {
+            value.parse::<u32>().expect("Frequency cap should be an integer")
+        } else {
+            0
+        },
+        expansion_frequency: args.value_of("expansion-frequency").unwrap().parse::<ExpansionFrequency>().expect("Expansion frequency should be 'min', 'product' or 'target'"),
+        debug_normalization: args.is_present("debug-normalization"),
+        phonetic: args.is_present("phonetic"),
+        return_edits: args.is_present("return-edits"),
+        transfer_case: args.is_present("transfer-case"),
+        collapse_lemmas: args.is_present("collapse-lemmas"),
+        sentence_confidence: args.is_present("sentence-confidence"),
+        global_context_rules: args.is_present("global-contextrules"),
+        neighbourhood_cache_size: if let Some(value) = args.value_of("neighbourhood-cache") {
+            value.parse::<usize>().expect("Neighbourhood cache size should be an integer")
+        } else {
+            0
+        },
+        explain: args.is_present("explain"),
+        skip_exact: args.is_present("skip-exact"),
+        oov_policy: args.value_of("oov-policy").unwrap().parse::<OovPolicy>().expect("OOV policy should be 'keep', 'empty', 'placeholder' or 'placeholder:TEXT'"),
+        preserve_numeric: if let Some(value) = args.value_of("preserve-numeric") {
+            value.parse::<f32>().expect("Numeric proportion should be a floating point number (between 0 and 1)")
+        } else {
+            0.0
+        },
+        min_length: if let Some(value) = args.value_of("min-length") {
+            value.parse::<usize>().expect("Minimum length should be an integer")
+        } else {
+            0
+        },
+        ngram_min_length: if let Some(value) = args.value_of("ngram-candidates") {
+            value.parse::<usize>().expect("Minimum length for n-gram candidates should be an integer")
+        } else {
+            0
+        },
+        ngram_min_overlap: args.value_of("ngram-overlap").unwrap().parse::<f64>().expect("N-gram overlap should be a floating point number (between 0 and 1)"),
+        hard_max: if let Some(value) = args.value_of("hard-max") {
+            value.parse::<usize>().expect("Hard maximum should be an integer")
+        } else {
+            0
+        },
+        max_candidates: if let Some(value) = args.value_of("max-candidates") {
+            value.parse::<usize>().expect("Maximum number of candidates should be an integer")
+        } else {
+            0
+        },
+        //the boundary options are only available for the search subcommand
+        boundary_config: BoundaryConfig {
+            nonboundary: args.value_of("nonboundary-chars").unwrap_or("").chars().collect(),
+            intraword: args.value_of("intraword-chars").unwrap_or("").chars().collect(),
+            soft: args.value_of("soft-boundary-chars").unwrap_or("'-_").chars().collect(),
+            hard: args.value_of("hard-boundary-chars").unwrap_or("").chars().collect(),
+            whitespace: args.value_of("whitespace-boundary").unwrap_or("normal").parse::<BoundaryStrength>().expect("Whitespace boundary strength should be 'weak', 'normal' or 'hard'"),
+        },
+        protected_patterns: if let Some(patterns) = args.values_of("protect") {
+            patterns.map(|pattern| Regex::new(pattern).expect("Protected pattern should be a valid regular expression")).collect()
+        } else {
+            Vec::new()
+        },
+        skip_unknown_scripts: args.is_present("skip-unknown-scripts"),
+    };
+    let output = OutputOptions::new(args, &searchparams);
+    let input = InputOptions {
+        column: input_column,
+        progress,
+        newline_as_space: !retain_linebreaks,
+        per_line: perline,
     };
 
     if searchparams.cutoff_threshold < 1.0 && searchparams.cutoff_threshold != 0.0 {
@@ -1187,9 +2384,16 @@ fn main() {
         model.set_confusables_before_pruning();
     }
 
+    if let Some(filename) = args.value_of("eval-dump") {
+        model.set_eval_hook(Some(eval_dump_hook(filename)));
+    }
+
     if rootargs.subcommand_matches("index").is_some() {
         eprintln!("Computing and outputting anagram index...");
-        for (anahash, indexnode) in model.index.iter() {
+        //sorted, so the output is the same on every run
+        let mut index: Vec<_> = model.index.iter().collect();
+        index.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        for (anahash, indexnode) in index {
             if !indexnode.instances.is_empty() {
                 print!("{}", anahash);
                 for instance in indexnode.instances.iter() {
@@ -1202,6 +2406,14 @@ fn main() {
                 println!()
             }
         }
+    } else if rootargs.subcommand_matches("serve").is_some() {
+        serve(
+            &model,
+            args.value_of("bind").unwrap(),
+            &searchparams,
+            &output,
+        )
+        .expect("Server error");
     } else {
         //query or collect
 
@@ -1213,9 +2425,11 @@ fn main() {
             eprintln!("Collecting variants...");
         }
 
-        if json {
-            println!("[");
-        }
+        let mut jsonwriter = if json {
+            Some(JsonArrayWriter::begin())
+        } else {
+            None
+        };
 
         let files: Vec<_> = if args.is_present("files") {
             args.values_of("files").unwrap().collect()
@@ -1237,7 +2451,7 @@ fn main() {
                             stdin,
                             &searchparams,
                             iterations,
-                            json,
+                            jsonwriter.as_mut(),
                             args.is_present("multi-output"),
                             args.is_present("strict"),
                             !retain_linebreaks,
@@ -1250,11 +2464,10 @@ fn main() {
                             &model,
                             stdin,
                             &searchparams,
-                            output_lexmatch,
-                            json,
-                            progress,
-                            !retain_linebreaks,
-                            perline,
+                            &output,
+                            &input,
+                            jsonwriter.as_mut(),
+                            conll,
                         );
                     } else if searchparams.single_thread {
                         eprintln!("(accepting standard input; enter input to match, one per line)");
@@ -1262,9 +2475,9 @@ fn main() {
                             &model,
                             stdin,
                             &searchparams,
-                            output_lexmatch,
-                            json,
-                            progress,
+                            &output,
+                            &input,
+                            jsonwriter.as_mut(),
                         );
                     } else {
                         eprintln!("(accepting standard input; enter input to match, one per line, output may be delayed until end of input due to parallellisation)");
@@ -1273,9 +2486,9 @@ fn main() {
                             &model,
                             stdin,
                             &searchparams,
-                            output_lexmatch,
-                            json,
-                            progress,
+                            &output,
+                            &input,
+                            jsonwriter.as_mut(),
                         )
                         .expect("I/O Error");
                     }
@@ -1294,7 +2507,7 @@ fn main() {
                             f,
                             &searchparams,
                             iterations,
-                            json,
+                            jsonwriter.as_mut(),
                             args.is_present("multi-output"),
                             args.is_present("strict"),
                             !retain_linebreaks,
@@ -1306,25 +2519,398 @@ fn main() {
                             &model,
                             f,
                             &searchparams,
-                            output_lexmatch,
-                            json,
-                            progress,
-                            !retain_linebreaks,
-                            perline,
+                            &output,
+                            &input,
+                            jsonwriter.as_mut(),
+                            conll,
                         );
                     } else if searchparams.single_thread {
-                        process(&model, f, &searchparams, output_lexmatch, json, progress);
+                        process(
+                            &model,
+                            f,
+                            &searchparams,
+                            &output,
+                            &input,
+                            jsonwriter.as_mut(),
+                        );
                     } else {
                         //normal parallel behaviour
-                        process_par(&model, f, &searchparams, output_lexmatch, json, progress)
-                            .expect("I/O Error");
+                        process_par(
+                            &model,
+                            f,
+                            &searchparams,
+                            &output,
+                            &input,
+                            jsonwriter.as_mut(),
+                        )
+                        .expect("I/O Error");
                     }
                 }
             }
         }
 
-        if json {
-            println!("]");
+        if let Some(writer) = jsonwriter {
+            writer.end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analiticcl::test::*;
+
+    fn get_test_model() -> VariantModel {
+        let (alphabet, _alphabet_size) = get_test_alphabet();
+        let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 0);
+        for text in ["salamander", "frog", "toad", "toads"] {
+            model.add_to_vocabulary(text, Some(2), &VocabParams::default());
+        }
+        model.build();
+        model
+    }
+
+    fn get_test_output() -> OutputOptions {
+        OutputOptions {
+            lexmatch: false,
+            ties: false,
+            freq: false,
+            debug_normalization: false,
+            freq_weight: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_split_input_column() {
+        let line = "id1\tsalamandre\tnote";
+        assert_eq!(split_input_column(line, None), (line, vec![]));
+        assert_eq!(
+            split_input_column(line, Some(2)),
+            ("salamandre", vec!["id1", "note"])
+        );
+        assert_eq!(
+            split_input_column(line, Some(1)),
+            ("id1", vec!["salamandre", "note"])
+        );
+    }
+
+    #[test]
+    fn test_split_input_column_missing() {
+        //a column beyond the end of the line yields empty input, all columns are passed through
+        assert_eq!(
+            split_input_column("id1\tnote", Some(3)),
+            ("", vec!["id1", "note"])
+        );
+        //an empty column yields empty input
+        assert_eq!(
+            split_input_column("id1\t\tnote", Some(2)),
+            ("", vec!["id1", "note"])
+        );
+    }
+
+    #[test]
+    fn test_match_to_json() {
+        let model = get_test_model();
+        let variants = model.find_variants("salamandre", &get_test_searchparams());
+        let json = serde_json::to_value(match_to_json(
+            &model,
+            "salamandre",
+            &["id1"],
+            Some(&variants),
+            Some(0),
+            &get_test_output(),
+        ))
+        .expect("serialising to json");
+        assert_eq!(json["input"], "salamandre");
+        assert_eq!(json["columns"], serde_json::json!(["id1"]));
+        assert_eq!(json["variants"][0]["text"], "salamander");
+        assert_eq!(
+            json["variants"][0]["score"].as_f64(),
+            Some(variants[0].score(0.0))
+        );
+        //optional fields are omitted rather than serialised as null
+        for key in ["begin", "end", "byteoffset", "tag", "oov", "alternatives"] {
+            assert!(json.get(key).is_none(), "unexpected key {}", key);
+        }
+        for key in ["lexicons", "frequency", "tiegroup", "normalized"] {
+            assert!(
+                json["variants"][0].get(key).is_none(),
+                "unexpected key {}",
+                key
+            );
         }
     }
+
+    #[test]
+    fn test_match_to_json_selected_first() {
+        let model = get_test_model();
+        let variants = model.find_variants("toad", &get_test_searchparams());
+        assert!(variants.len() >= 2);
+        let json = serde_json::to_value(match_to_json(
+            &model,
+            "toad",
+            &[],
+            Some(&variants),
+            Some(1),
+            &get_test_output(),
+        ))
+        .expect("serialising to json");
+        //the selected variant is output before all others
+        assert_eq!(
+            json["variants"][0]["text"],
+            model.variant_text(&variants[1])
+        );
+        assert_eq!(
+            json["variants"][1]["text"],
+            model.variant_text(&variants[0])
+        );
+        assert!(json.get("columns").is_none());
+    }
+
+    #[test]
+    fn test_search_match_to_json() {
+        let model = get_test_model();
+        let matches = model.find_all_matches("the salamandre", &get_test_searchparams());
+        let result_match = matches
+            .iter()
+            .find(|m| m.text == "salamandre")
+            .expect("match for salamandre");
+        let json = serde_json::to_value(search_match_to_json(
+            &model,
+            result_match,
+            &[],
+            &get_test_output(),
+        ))
+        .expect("serialising to json");
+        assert_eq!(json["input"], "salamandre");
+        assert_eq!(json["begin"], 4);
+        assert_eq!(json["end"], 14);
+        assert_eq!(
+            json["byteoffset"],
+            serde_json::json!({"begin": 4, "end": 14})
+        );
+        assert_eq!(
+            json["charoffset"],
+            serde_json::json!({"begin": 4, "end": 14})
+        );
+        assert_eq!(json["variants"][0]["text"], "salamander");
+    }
+
+    #[test]
+    fn test_match_to_conll() {
+        let model = get_test_model();
+        let searchparams = get_test_searchparams();
+        let matches = model.find_all_matches("the salamandre", &searchparams);
+        let lines: Vec<String> = matches
+            .iter()
+            .map(|m| match_to_conll(&model, m, &["doc1"], &searchparams))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        //no variant found for this word, so empty columns are represented by underscores
+        assert_eq!(lines[0], "doc1\tthe\t0:3\t_\t_\t_");
+        let result = matches[1].solution().expect("solution");
+        assert_eq!(
+            lines[1],
+            format!(
+                "doc1\tsalamandre\t4:14\tsalamander\t{}\t_",
+                result.score(searchparams.freq_weight)
+            )
+        );
+    }
+
+    #[test]
+    fn test_match_to_conll_oov_placeholder() {
+        let model = get_test_model();
+        let searchparams = SearchParameters {
+            oov_policy: OovPolicy::Placeholder("<unk>".to_string()),
+            ..get_test_searchparams()
+        };
+        let matches = model.find_all_matches("the", &searchparams);
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].oov);
+        assert_eq!(
+            match_to_conll(&model, &matches[0], &[], &searchparams),
+            "the\t0:3\t<unk>\t_\t_"
+        );
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("abc"), Ok("abc".to_string()));
+        assert_eq!(percent_decode("a+b"), Ok("a b".to_string()));
+        assert_eq!(percent_decode("a%2Bb"), Ok("a+b".to_string()));
+        assert_eq!(
+            percent_decode("placeholder%3a%3F"),
+            Ok("placeholder:?".to_string())
+        );
+        assert_eq!(percent_decode("caf%C3%A9"), Ok("caf\u{e9}".to_string()));
+        assert!(percent_decode("%zz").is_err());
+        assert!(percent_decode("%4").is_err());
+        assert!(percent_decode("%").is_err());
+        //a sign is not a hex digit
+        assert!(percent_decode("%+F").is_err());
+        assert!(percent_decode("%-1").is_err());
+        //not valid UTF-8
+        assert!(percent_decode("%FF").is_err());
+    }
+
+    #[test]
+    fn test_apply_query_parameters() {
+        let mut searchparams = get_test_searchparams();
+        assert!(apply_query_parameters(
+            &mut searchparams,
+            "max_matches=3&freq_weight=0.5&oov_policy=placeholder%3A%3F"
+        )
+        .is_ok());
+        assert_eq!(searchparams.max_matches, 3);
+        assert_eq!(searchparams.freq_weight, 0.5);
+        assert_eq!(
+            searchparams.oov_policy,
+            OovPolicy::Placeholder("?".to_string())
+        );
+        //an empty query leaves everything as is
+        assert!(apply_query_parameters(&mut searchparams, "").is_ok());
+        assert_eq!(searchparams.max_matches, 3);
+    }
+
+    #[test]
+    fn test_apply_query_parameters_invalid() {
+        let mut searchparams = get_test_searchparams();
+        assert_eq!(
+            apply_query_parameters(&mut searchparams, "max_matches=3&foo=1"),
+            Err("Unknown parameter: foo".to_string())
+        );
+        assert_eq!(
+            apply_query_parameters(&mut searchparams, "max_matches=three"),
+            Err("Invalid value for max_matches: three".to_string())
+        );
+        assert!(apply_query_parameters(&mut searchparams, "max_matches=%zz").is_err());
+    }
+
+    /// Reads a request from a string, returns the message of the error if any
+    fn read_test_request(request: &str) -> Result<(String, String, Vec<u8>), String> {
+        read_http_request(&mut BufReader::new(request.as_bytes())).map_err(|e| {
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            e.to_string()
+        })
+    }
+
+    #[test]
+    fn test_read_http_request() {
+        let (method, target, body) = read_test_request(
+            "POST /?max_matches=2 HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello",
+        )
+        .expect("valid request");
+        assert_eq!(method, "POST");
+        assert_eq!(target, "/?max_matches=2");
+        assert_eq!(body, b"hello");
+    }
+
+    #[test]
+    fn test_read_http_request_malformed() {
+        assert_eq!(
+            read_test_request("POST\r\n\r\n"),
+            Err("Malformed HTTP request line".to_string())
+        );
+        assert_eq!(
+            read_test_request("\r\n\r\n"),
+            Err("Malformed HTTP request line".to_string())
+        );
+        assert_eq!(
+            read_test_request("POST / HTTP/1.1\r\nContent-Length: five\r\n\r\n"),
+            Err("Invalid Content-Length".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_http_request_too_many_headers() {
+        let headers = "X-Header: value\r\n".repeat(MAX_HEADERS);
+        assert!(read_test_request(&format!("POST / HTTP/1.1\r\n{}\r\n", headers)).is_ok());
+        let headers = "X-Header: value\r\n".repeat(MAX_HEADERS + 1);
+        assert_eq!(
+            read_test_request(&format!("POST / HTTP/1.1\r\n{}\r\n", headers)),
+            Err("Too many request headers".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_http_request_line_too_long() {
+        let target = format!("/?{}", "a".repeat(MAX_HEADER_LINE));
+        assert_eq!(
+            read_test_request(&format!("POST {} HTTP/1.1\r\n\r\n", target)),
+            Err("Request header line too long".to_string())
+        );
+        let header = format!("X-Header: {}\r\n", "a".repeat(MAX_HEADER_LINE));
+        assert_eq!(
+            read_test_request(&format!("POST / HTTP/1.1\r\n{}\r\n", header)),
+            Err("Request header line too long".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_http_request_body_too_large() {
+        assert_eq!(
+            read_test_request(&format!(
+                "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                MAX_REQUEST_SIZE + 1
+            )),
+            Err("Request body too large".to_string())
+        );
+    }
+
+    #[test]
+    fn test_deadline_reader() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("binding");
+        let address = listener.local_addr().expect("local address");
+        //a client that sends a byte every 50ms, so no single read times out
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).expect("connecting");
+            for _ in 0..40 {
+                if stream.write_all(b"a").is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let (stream, _) = listener.accept().expect("accepting");
+        let begin = Instant::now();
+        let mut reader = BufReader::new(DeadlineReader {
+            stream: &stream,
+            deadline: begin + Duration::from_millis(300),
+        });
+        let e = read_header_line(&mut reader).expect_err("deadline must be exceeded");
+        //either the deadline passed between reads, or it passed during a read
+        assert!(matches!(
+            e.kind(),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        ));
+        assert!(begin.elapsed() < Duration::from_millis(1000));
+        drop(reader);
+        drop(stream);
+        client.join().expect("client thread");
+    }
+
+    #[test]
+    fn test_result_to_json_output_freq() {
+        let model = get_test_model();
+        let variants = model.find_variants("salamandre", &get_test_searchparams());
+        let json = serde_json::to_value(result_to_json(
+            &model,
+            &variants[0],
+            None,
+            &get_test_output(),
+        ))
+        .expect("serialising to json");
+        assert!(json.get("frequency").is_none());
+        assert!(json.get("tokencount").is_none());
+        let output = OutputOptions {
+            freq: true,
+            ..get_test_output()
+        };
+        let json = serde_json::to_value(result_to_json(&model, &variants[0], None, &output))
+            .expect("serialising to json");
+        assert_eq!(json["text"], "salamander");
+        assert_eq!(json["frequency"], 2);
+        assert_eq!(json["tokencount"], 1);
+    }
 }
@@ -1,4 +1,6 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::types::*;
 
@@ -26,3 +28,128 @@ impl Cache {
         }
     }
 }
+
+/// Identifies an anagram neighbourhood: the focus anagram value, the maximum anagram distance, the
+/// stop criterion and the maximum number of candidates it was computed with
+type NeighbourhoodKey = (AnaValue, u8, StopCriterion, usize);
+
+/// A least-recently-used cache of anagram neighbourhoods, i.e. the anagram values in the index
+/// that are found near a focus anagram value. Used by [`crate::VariantModel::find_variants()`]
+/// if [`SearchParameters::neighbourhood_cache_size`] is non-zero.
+#[derive(Default, Debug)]
+pub struct NeighbourhoodCache {
+    /// Maps keys to their neighbourhood and the time they were last used
+    entries: HashMap<NeighbourhoodKey, (Vec<Arc<AnaValue>>, u64)>,
+    /// Maps the time an entry was last used to its key, so the least recently used one comes first
+    recency: BTreeMap<u64, NeighbourhoodKey>,
+    clock: u64,
+    hits: usize,
+    misses: usize,
+}
+
+impl NeighbourhoodCache {
+    /// Returns the cached neighbourhood (if any), marking it as recently used
+    pub fn get(
+        &mut self,
+        focus: &AnaValue,
+        max_distance: u8,
+        stop_criterion: StopCriterion,
+        max_candidates: usize,
+    ) -> Option<Vec<Arc<AnaValue>>> {
+        let key = (focus.clone(), max_distance, stop_criterion, max_candidates);
+        if let Some((neighbourhood, lastused)) = self.entries.get_mut(&key) {
+            self.clock += 1;
+            let key = self.recency.remove(lastused).expect("recency entry");
+            self.recency.insert(self.clock, key);
+            *lastused = self.clock;
+            self.hits += 1;
+            Some(neighbourhood.clone())
+        } else {
+            self.misses += 1;
+            None
+        }
+    }
+
+    /// Tests if the neighbourhood is cached, without marking it as used or counting it as a lookup
+    pub fn contains(
+        &self,
+        focus: &AnaValue,
+        max_distance: u8,
+        stop_criterion: StopCriterion,
+        max_candidates: usize,
+    ) -> bool {
+        self.entries
+            .contains_key(&(focus.clone(), max_distance, stop_criterion, max_candidates))
+    }
+
+    /// Adds a neighbourhood to the cache, evicting the least recently used entries if the cache
+    /// would grow beyond the specified capacity
+    pub fn insert(
+        &mut self,
+        focus: AnaValue,
+        max_distance: u8,
+        stop_criterion: StopCriterion,
+        max_candidates: usize,
+        neighbourhood: Vec<Arc<AnaValue>>,
+        capacity: usize,
+    ) {
+        let key = (focus, max_distance, stop_criterion, max_candidates);
+        if let Some((_, lastused)) = self.entries.remove(&key) {
+            self.recency.remove(&lastused);
+        }
+        while self.entries.len() >= capacity {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.entries.remove(&oldest);
+            } else {
+                break;
+            }
+        }
+        if capacity > 0 {
+            self.clock += 1;
+            self.recency.insert(self.clock, key.clone());
+            self.entries.insert(key, (neighbourhood, self.clock));
+        }
+    }
+
+    /// The number of cached neighbourhoods
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of lookups that were served from the cache
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// The number of lookups that were not in the cache
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Removes all cached neighbourhoods, this is needed whenever the index changes
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+}
+
+/// Statistics on precomputing the neighbourhoods of a set of queries, as returned by
+/// [`crate::VariantModel::precompute()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PrecomputeStats {
+    /// The number of queries that were passed
+    pub queries: usize,
+    /// The number of (distinct) neighbourhoods that were computed and added to the cache
+    pub computed: usize,
+    /// The number of queries whose neighbourhood was already cached, or that share it with another query
+    pub cached: usize,
+    /// The number of queries that need no neighbourhood (e.g. empty inputs, or long inputs for
+    /// which the n-gram index is used)
+    pub skipped: usize,
+    /// The time it took to compute the neighbourhoods
+    pub duration: Duration,
+}
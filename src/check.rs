@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+
+use crate::compression::*;
+use crate::confusables::*;
+use crate::types::*;
+use crate::vocab::*;
+
+/// A problem found while checking an input file, see [`check_alphabet()`],
+/// [`check_vocabulary()`], [`check_variants()`] and [`check_confusablelist()`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileProblem {
+    pub filename: String,
+    /// Line number (1-indexed)
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FileProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.filename, self.line, self.message)
+    }
+}
+
+/// Collects the problems found in a single file
+struct ProblemCollector<'a> {
+    filename: &'a str,
+    problems: Vec<FileProblem>,
+}
+
+impl<'a> ProblemCollector<'a> {
+    fn new(filename: &'a str) -> Self {
+        Self {
+            filename,
+            problems: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, line: usize, message: String) {
+        self.problems.push(FileProblem {
+            filename: self.filename.to_owned(),
+            line,
+            message,
+        });
+    }
+}
+
+/// Checks an alphabet file (as read by [`crate::VariantModel::read_alphabet()`]) for problems,
+/// such as lines without entries, entries that are defined more than once, or an alphabet that is
+/// too large. Only I/O errors are returned as errors, all other problems are collected.
+pub fn check_alphabet(filename: &str) -> Result<Vec<FileProblem>, std::io::Error> {
+    let mut collector = ProblemCollector::new(filename);
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut size = 0;
+    for (linenr, line) in open_file(filename, Compression::Auto)?.lines().enumerate() {
+        let linenr = linenr + 1;
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = parse_alphabet_line(&line);
+        match fields.first().map(|x| x.as_str()) {
+            Some("\\ignore") | Some("\\unk") => {
+                fields.remove(0);
+                if fields.is_empty() {
+                    collector.report(linenr, "No entries after the directive".to_owned());
+                }
+            }
+            _ => {
+                if fields.is_empty() {
+                    collector.report(linenr, "Line holds no alphabet entries".to_owned());
+                }
+                size += 1;
+                if size + 1 == PRIMES.len() {
+                    collector.report(
+                        linenr,
+                        format!(
+                            "Alphabet too large, it may have at most {} entries",
+                            PRIMES.len() - 1
+                        ),
+                    );
+                }
+            }
+        }
+        for field in fields {
+            if let Some(firstlinenr) = seen.get(&field) {
+                collector.report(
+                    linenr,
+                    format!(
+                        "Duplicate entry {:?}, already defined on line {}",
+                        field, firstlinenr
+                    ),
+                );
+            } else {
+                seen.insert(field, linenr);
+            }
+        }
+    }
+    Ok(collector.problems)
+}
+
+/// Checks a vocabulary file (as read by [`crate::VariantModel::read_vocabulary()`]) for problems,
+/// such as missing text columns and frequencies that are not integers. Only I/O errors are
+/// returned as errors, all other problems are collected.
+pub fn check_vocabulary(
+    filename: &str,
+    params: &VocabParams,
+) -> Result<Vec<FileProblem>, std::io::Error> {
+    let mut collector = ProblemCollector::new(filename);
+    for (linenr, line) in open_file(filename, params.compression)?.lines().enumerate() {
+        let linenr = linenr + 1;
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let fields = params.split_fields(&line);
+        match fields.get(params.text_column as usize) {
+            None => collector.report(
+                linenr,
+                format!("Text column {} not found", params.text_column + 1),
+            ),
+            Some(text) if text.is_empty() => collector.report(linenr, "Empty text".to_owned()),
+            _ => {}
+        }
+        if let Some(freq_column) = params.freq_column {
+            if let Some(freq) = fields.get(freq_column as usize) {
+                if freq.parse::<u32>().is_err() {
+                    collector.report(
+                        linenr,
+                        format!(
+                            "Frequency must be an integer (column {}), got {:?}",
+                            freq_column + 1,
+                            freq
+                        ),
+                    );
+                }
+            }
+        }
+    }
+    Ok(collector.problems)
+}
+
+/// Checks a weighted variant list (as read by [`crate::VariantModel::read_variants()`]) for
+/// problems, such as scores that are not floats, frequencies that are not integers, and column
+/// counts that are inconsistent with the layout of the file (with or without frequency
+/// information, which is detected on the first line). Only I/O errors are returned as errors, all
+/// other problems are collected.
+pub fn check_variants(
+    filename: &str,
+    params: &VocabParams,
+) -> Result<Vec<FileProblem>, std::io::Error> {
+    let mut collector = ProblemCollector::new(filename);
+    let mut has_freq = None;
+    for (linenr, line) in open_file(filename, params.compression)?.lines().enumerate() {
+        let linenr = linenr + 1;
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let fields = params.split_fields(&line);
+        if has_freq.is_none() {
+            //autodetect whether we have frequency information or not, as read_variants() does
+            if fields.len() < 2 {
+                collector.report(
+                    linenr,
+                    "Expected a reference and at least one variant with its score".to_owned(),
+                );
+                continue;
+            } else if (fields.len() - 2).is_multiple_of(3) && fields[1].parse::<u32>().is_ok() {
+                has_freq = Some(true);
+            } else if !(fields.len() - 2).is_multiple_of(3) {
+                has_freq = Some(false);
+            }
+        }
+        let (offset, groupsize) = if has_freq == Some(true) {
+            if let Some(freq) = fields.get(1) {
+                if freq.parse::<u32>().is_err() {
+                    collector.report(
+                        linenr,
+                        format!("Frequency must be an integer (column 2), got {:?}", freq),
+                    );
+                }
+            }
+            (2, 3)
+        } else {
+            (1, 2)
+        };
+        if fields.len() < offset || !(fields.len() - offset).is_multiple_of(groupsize) {
+            collector.report(
+                linenr,
+                format!(
+                    "Inconsistent number of columns ({}), expected {} columns followed by groups of {} (variant, score{})",
+                    fields.len(),
+                    offset,
+                    groupsize,
+                    if groupsize == 3 { ", frequency" } else { "" }
+                ),
+            );
+        }
+        let variants = fields.get(offset..).unwrap_or_default();
+        for (i, group) in variants.chunks_exact(groupsize).enumerate() {
+            let column = offset + i * groupsize + 2;
+            if group[1].parse::<f64>().is_err() {
+                collector.report(
+                    linenr,
+                    format!(
+                        "Variant score must be a floating point value (column {}), got {:?}",
+                        column, group[1]
+                    ),
+                );
+            }
+            if let Some(freq) = group.get(2) {
+                if freq.parse::<u32>().is_err() {
+                    collector.report(
+                        linenr,
+                        format!(
+                            "Variant frequency must be an integer (column {}), got {:?}",
+                            column + 1,
+                            freq
+                        ),
+                    );
+                }
+            }
+        }
+    }
+    Ok(collector.problems)
+}
+
+/// Checks a confusable list (as read by [`crate::VariantModel::read_confusablelist()`]) for
+/// problems, such as invalid edit scripts, weights that are not floats and unknown flags. Only
+/// I/O errors are returned as errors, all other problems are collected.
+pub fn check_confusablelist(filename: &str) -> Result<Vec<FileProblem>, std::io::Error> {
+    let mut collector = ProblemCollector::new(filename);
+    for (linenr, line) in open_file(filename, Compression::Auto)?.lines().enumerate() {
+        let linenr = linenr + 1;
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let weight = match fields.get(1) {
+            Some(weight) => weight.parse::<f64>().unwrap_or_else(|_| {
+                collector.report(
+                    linenr,
+                    format!("Weight must be a floating point value, got {:?}", weight),
+                );
+                1.0
+            }),
+            None => 1.0,
+        };
+        let casefold = match fields.get(2).map(|s| s.trim()) {
+            None | Some("") => false,
+            Some("casefold") | Some("i") => true,
+            Some(flag) => {
+                collector.report(
+                    linenr,
+                    format!("Unknown flag {:?} (expected 'casefold')", flag),
+                );
+                false
+            }
+        };
+        if fields[0].is_empty() {
+            collector.report(linenr, "Empty edit script".to_owned());
+        } else if let Err(e) = Confusable::new_with_casefold(fields[0], weight, casefold) {
+            collector.report(linenr, format!("Invalid edit script: {}", e));
+        }
+    }
+    Ok(collector.problems)
+}
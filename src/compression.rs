@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+
+/// Compression of input files. Decompression is delegated to the standard system tools
+/// (`gzip`, `xz`, `bzip2`), which must be available in the `PATH` when reading compressed files,
+/// so the result depends on the versions installed. Opening a compressed file fails with a
+/// [`ErrorKind::NotFound`] error if the required tool is missing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Detect the compression from the file extension (`.gz`, `.xz`, `.bz2`)
+    #[default]
+    Auto,
+    /// Plain uncompressed text, regardless of the extension
+    None,
+    Gzip,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    /// Determine the compression from the extension of the filename
+    pub fn from_filename(filename: &str) -> Self {
+        if filename.ends_with(".gz") {
+            Self::Gzip
+        } else if filename.ends_with(".xz") {
+            Self::Xz
+        } else if filename.ends_with(".bz2") {
+            Self::Bzip2
+        } else {
+            Self::None
+        }
+    }
+
+    /// Returns the program used to decompress
+    fn program(&self) -> Option<&'static str> {
+        match self {
+            Self::Gzip => Some("gzip"),
+            Self::Xz => Some("xz"),
+            Self::Bzip2 => Some("bzip2"),
+            Self::Auto | Self::None => None,
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "none" => Ok(Self::None),
+            "gzip" | "gz" => Ok(Self::Gzip),
+            "xz" => Ok(Self::Xz),
+            "bzip2" | "bz2" => Ok(Self::Bzip2),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Compression must be one of: auto, none, gzip, xz, bzip2",
+            )),
+        }
+    }
+}
+
+/// Reads the standard output of a decompression process, and checks whether the process
+/// succeeded once all output has been consumed. A failure is reported once, after which
+/// the reader is at end of file.
+struct DecompressReader {
+    child: Child,
+    stdout: ChildStdout,
+    program: &'static str,
+    finished: bool,
+}
+
+impl Read for DecompressReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.finished {
+            return Ok(0);
+        }
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.finished = true;
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Decompression with {} failed ({})", self.program, status),
+                ));
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Open a file for reading, transparently decompressing it if needed
+pub fn open_file(filename: &str, compression: Compression) -> Result<Box<dyn BufRead>, Error> {
+    let compression = if compression == Compression::Auto {
+        Compression::from_filename(filename)
+    } else {
+        compression
+    };
+    if let Some(program) = compression.program() {
+        //check the file exists first so we get a proper error rather than one from the subprocess
+        File::open(filename)?;
+        let mut child = Command::new(program)
+            .arg("-dc")
+            .arg(filename)
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                if e.kind() == ErrorKind::NotFound {
+                    Error::new(
+                        e.kind(),
+                        format!(
+                            "Unable to decompress {}: {} was not found, it must be installed and in the PATH to read compressed files",
+                            filename, program
+                        ),
+                    )
+                } else {
+                    Error::new(
+                        e.kind(),
+                        format!(
+                            "Unable to run {} to decompress {}: {}",
+                            program, filename, e
+                        ),
+                    )
+                }
+            })?;
+        let stdout = child.stdout.take().expect("stdout must be piped");
+        Ok(Box::new(BufReader::new(DecompressReader {
+            child,
+            stdout,
+            program,
+            finished: false,
+        })))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(filename)?)))
+    }
+}
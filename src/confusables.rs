@@ -1,129 +1,320 @@
 use sesdiff::{EditInstruction, EditScript};
+use std::fmt;
 use std::io::{Error, ErrorKind};
 use std::str::FromStr;
 
+/// The weight assigned to the most frequent confusable by [`crate::VariantModel::derive_confusables()`]
+pub const DERIVED_CONFUSABLE_MAX_WEIGHT: f64 = 1.1;
+
 #[derive(Debug)]
 pub struct Confusable {
     pub editscript: EditScript<String>,
     pub weight: f64,
     pub strictbegin: bool,
     pub strictend: bool,
+    /// Characters (or strings) of which one must immediately precede the edit in the input, if not empty
+    pub leftcontext: Vec<String>,
+    /// Characters (or strings) of which one must immediately follow the edit in the input, if not empty
+    pub rightcontext: Vec<String>,
+    /// Match case-insensitively, the edit script is lowercased and is tested against the edit script
+    /// between the lowercased input and candidate
+    pub casefold: bool,
 }
 
 impl Confusable {
     pub fn new(editscript: &str, weight: f64) -> Result<Confusable, std::io::Error> {
+        Self::new_with_casefold(editscript, weight, false)
+    }
+
+    /// Instantiates a confusable that optionally matches case-insensitively
+    pub fn new_with_casefold(
+        editscript: &str,
+        weight: f64,
+        casefold: bool,
+    ) -> Result<Confusable, std::io::Error> {
+        let lowercased;
+        let editscript = if casefold {
+            lowercased = editscript.to_lowercase();
+            lowercased.as_str()
+        } else {
+            editscript
+        };
         let strictbegin = editscript.get(0..1).expect("Checking first character") == "^";
         let l = editscript.len();
         let strictend = editscript.get((l - 1)..).expect("Checking last character") == "$";
+        let mut script = if strictbegin && strictend {
+            &editscript[1..l - 1]
+        } else if strictbegin {
+            &editscript[1..]
+        } else if strictend {
+            &editscript[..l - 1]
+        } else {
+            editscript
+        };
+        //context in parentheses, the edit script itself never starts or ends with a parenthesis
+        let mut leftcontext = Vec::new();
+        if script.starts_with('(') {
+            if let Some(pos) = script.find(')') {
+                leftcontext = parse_context(&script[1..pos]);
+                script = &script[pos + 1..];
+            }
+        }
+        let mut rightcontext = Vec::new();
+        if script.ends_with(')') {
+            if let Some(pos) = script.rfind('(') {
+                rightcontext = parse_context(&script[pos + 1..script.len() - 1]);
+                script = &script[..pos];
+            }
+        }
         Ok(Confusable {
-            editscript: if strictbegin && strictend {
-                match EditScript::from_str(&editscript[1..l - 1]) {
-                    Ok(editscript) => editscript,
-                    Err(err) => return Err(Error::new(ErrorKind::Other, format!("{:?}", err))),
-                }
-            } else if strictbegin {
-                match EditScript::from_str(&editscript[1..]) {
-                    Ok(editscript) => editscript,
-                    Err(err) => return Err(Error::new(ErrorKind::Other, format!("{:?}", err))),
-                }
-            } else if strictend {
-                match EditScript::from_str(&editscript[..l - 1]) {
-                    Ok(editscript) => editscript,
-                    Err(err) => return Err(Error::new(ErrorKind::Other, format!("{:?}", err))),
-                }
-            } else {
-                match EditScript::from_str(editscript) {
-                    Ok(editscript) => editscript,
-                    Err(err) => return Err(Error::new(ErrorKind::Other, format!("{:?}", err))),
-                }
+            editscript: match EditScript::from_str(script) {
+                Ok(editscript) => editscript,
+                Err(err) => return Err(Error::new(ErrorKind::Other, format!("{:?}", err))),
             },
             weight: weight,
             strictbegin: strictbegin,
             strictend: strictend,
+            leftcontext,
+            rightcontext,
+            casefold,
         })
     }
 
     ///See if the confusable is found in a larger edit script
     pub fn found_in(&self, refscript: &EditScript<&str>) -> bool {
         let l = self.editscript.instructions.len();
-        let mut matches = 0; //number of matching instructions
-        for (i, refinstruction) in refscript.instructions.iter().enumerate() {
-            if let Some(instruction) = self.editscript.instructions.get(matches) {
-                let foundinstruction = match (instruction, refinstruction) {
-                    (EditInstruction::Insertion(s), EditInstruction::Insertion(sref))
-                    | (EditInstruction::Deletion(s), EditInstruction::Deletion(sref)) => {
-                        sref.ends_with(s)
-                    }
-                    (EditInstruction::Identity(s), EditInstruction::Identity(sref)) => {
-                        if matches == 0 && matches == l - 1 {
-                            s == sref
-                        } else if matches == 0 {
-                            sref.ends_with(s)
-                        } else if matches == l - 1 {
-                            sref.starts_with(s)
-                        } else {
-                            s == sref
-                        }
-                    }
-                    (EditInstruction::InsertionOptions(v), EditInstruction::Insertion(sref))
-                    | (EditInstruction::DeletionOptions(v), EditInstruction::Deletion(sref)) => {
-                        let mut foundoption = false;
-                        for s in v.iter() {
-                            if sref.ends_with(s) {
-                                foundoption = true;
-                                break;
-                            }
+        let n = refscript.instructions.len();
+        if l == 0 || l > n {
+            return false;
+        }
+        for begin in 0..=(n - l) {
+            if self.strictbegin && begin > 0 {
+                return false;
+            }
+            let end = begin + l - 1;
+            if self.strictend && end != n - 1 {
+                continue;
+            }
+            //length of the matched part of the first and last instructions
+            let mut firstlen = 0;
+            let mut lastlen = 0;
+            let mut found = true;
+            for (j, instruction) in self.editscript.instructions.iter().enumerate() {
+                match match_instruction(
+                    instruction,
+                    &refscript.instructions[begin + j],
+                    j == 0,
+                    j == l - 1,
+                ) {
+                    Some(len) => {
+                        if j == 0 {
+                            firstlen = len;
                         }
-                        foundoption
-                    }
-                    (EditInstruction::IdentityOptions(v), EditInstruction::Identity(sref)) => {
-                        let mut foundoption = false;
-                        for s in v.iter() {
-                            if matches == 0 && matches == l - 1 {
-                                if s == sref {
-                                    foundoption = true;
-                                    break;
-                                }
-                            } else if matches == 0 {
-                                if sref.ends_with(s) {
-                                    foundoption = true;
-                                    break;
-                                }
-                            } else if matches == l - 1 {
-                                if sref.starts_with(s) {
-                                    foundoption = true;
-                                    break;
-                                }
-                            } else {
-                                if s == sref {
-                                    foundoption = true;
-                                    break;
-                                }
-                            }
+                        if j == l - 1 {
+                            lastlen = len;
                         }
-                        foundoption
                     }
-                    _ => false,
-                };
-                if !foundinstruction {
-                    matches = 0;
-                    if self.strictbegin {
-                        return false;
-                    } else {
-                        continue; //try again with new reference offset
+                    None => {
+                        found = false;
+                        break;
                     }
-                } else {
-                    matches += 1;
-                    if matches == l {
-                        if self.strictend {
-                            return i == refscript.instructions.len() - 1;
-                        } else {
-                            return true;
+                }
+            }
+            if found && self.context_found_in(refscript, begin, firstlen, end, lastlen) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Checks the left and right context of a match between the instructions at index `begin` and `end` in the reference edit script.
+    /// The context is tested against the input side (i.e. identities and deletions) of the reference edit script.
+    fn context_found_in(
+        &self,
+        refscript: &EditScript<&str>,
+        begin: usize,
+        firstlen: usize,
+        end: usize,
+        lastlen: usize,
+    ) -> bool {
+        if !self.leftcontext.is_empty() {
+            let mut preceding = String::new();
+            for refinstruction in refscript.instructions[..begin].iter() {
+                preceding += input_side(refinstruction);
+            }
+            //the first instruction may match only the end of the reference instruction
+            let first = input_side(&refscript.instructions[begin]);
+            preceding += &first[..first.len() - firstlen.min(first.len())];
+            if !self
+                .leftcontext
+                .iter()
+                .any(|context| preceding.ends_with(context.as_str()))
+            {
+                return false;
+            }
+        }
+        if !self.rightcontext.is_empty() {
+            let mut following = String::new();
+            //the last instruction may match only the beginning of the reference instruction (identities only)
+            if let EditInstruction::Identity(s) = &refscript.instructions[end] {
+                following += &s[lastlen.min(s.len())..];
+            }
+            for refinstruction in refscript.instructions[end + 1..].iter() {
+                following += input_side(refinstruction);
+            }
+            if !self
+                .rightcontext
+                .iter()
+                .any(|context| following.starts_with(context.as_str()))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl fmt::Display for Confusable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.strictbegin {
+            write!(f, "^")?;
+        }
+        if !self.leftcontext.is_empty() {
+            write!(f, "({})", self.leftcontext.join("|"))?;
+        }
+        write!(f, "{}", self.editscript)?;
+        if !self.rightcontext.is_empty() {
+            write!(f, "({})", self.rightcontext.join("|"))?;
+        }
+        if self.strictend {
+            write!(f, "$")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the options of a context expression (separated by |)
+fn parse_context(s: &str) -> Vec<String> {
+    s.split('|')
+        .filter(|option| !option.is_empty())
+        .map(|option| option.to_string())
+        .collect()
+}
+
+/// Returns the text of the input side of a reference instruction: the text of identities and deletions
+fn input_side<'a>(refinstruction: &EditInstruction<&'a str>) -> &'a str {
+    match refinstruction {
+        EditInstruction::Identity(s) | EditInstruction::Deletion(s) => s,
+        _ => "",
+    }
+}
+
+/// Tests a single instruction of a confusable against an instruction of the reference edit script,
+/// returns the length of the matched text if it matches. Insertions and deletions must match the end of the
+/// reference instruction, identities at the beginning (end) of the confusable match the end (beginning)
+/// of the reference instruction (as these are context), other identities must match exactly.
+fn match_instruction(
+    instruction: &EditInstruction<String>,
+    refinstruction: &EditInstruction<&str>,
+    first: bool,
+    last: bool,
+) -> Option<usize> {
+    let test_identity = |s: &str, sref: &str| {
+        if first && last {
+            s == sref
+        } else if first {
+            sref.ends_with(s)
+        } else if last {
+            sref.starts_with(s)
+        } else {
+            s == sref
+        }
+    };
+    match (instruction, refinstruction) {
+        (EditInstruction::Insertion(s), EditInstruction::Insertion(sref))
+        | (EditInstruction::Deletion(s), EditInstruction::Deletion(sref)) => {
+            sref.ends_with(s.as_str()).then_some(s.len())
+        }
+        (EditInstruction::Identity(s), EditInstruction::Identity(sref)) => {
+            test_identity(s, sref).then_some(s.len())
+        }
+        (EditInstruction::InsertionOptions(v), EditInstruction::Insertion(sref))
+        | (EditInstruction::DeletionOptions(v), EditInstruction::Deletion(sref)) => v
+            .iter()
+            .find(|s| sref.ends_with(s.as_str()))
+            .map(|s| s.len()),
+        (EditInstruction::IdentityOptions(v), EditInstruction::Identity(sref)) => {
+            v.iter().find(|s| test_identity(s, sref)).map(|s| s.len())
+        }
+        _ => None,
+    }
+}
+
+/// Keyboard layouts from which confusables for physically adjacent keys can be generated, see
+/// `VariantModel::add_keyboard_confusables()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyboardLayout {
+    Qwerty,
+    Azerty,
+    /// A custom layout, expressed as a grid of rows (top to bottom). Like on a physical keyboard,
+    /// each row is assumed to be shifted half a key to the right relative to the row above it.
+    Custom(Vec<String>),
+}
+
+impl KeyboardLayout {
+    /// Returns the rows of (lowercase) letter keys, top to bottom
+    pub fn rows(&self) -> Vec<Vec<char>> {
+        match self {
+            Self::Qwerty => vec!["qwertyuiop", "asdfghjkl", "zxcvbnm"]
+                .into_iter()
+                .map(|row| row.chars().collect())
+                .collect(),
+            Self::Azerty => vec!["azertyuiop", "qsdfghjklm", "wxcvbn"]
+                .into_iter()
+                .map(|row| row.chars().collect())
+                .collect(),
+            Self::Custom(rows) => rows.iter().map(|row| row.chars().collect()).collect(),
+        }
+    }
+
+    /// Returns all pairs of adjacent keys, each pair is returned only once
+    pub fn adjacent_pairs(&self) -> Vec<(char, char)> {
+        let rows = self.rows();
+        let mut pairs = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            for (j, key) in row.iter().enumerate() {
+                //neighbour on the same row
+                if let Some(next) = row.get(j + 1) {
+                    pairs.push((*key, *next));
+                }
+                //neighbours on the row below, which is shifted half a key to the right
+                if let Some(below) = rows.get(i + 1) {
+                    if j > 0 {
+                        if let Some(left) = below.get(j - 1) {
+                            pairs.push((*key, *left));
                         }
                     }
+                    if let Some(right) = below.get(j) {
+                        pairs.push((*key, *right));
+                    }
                 }
             }
         }
-        false
+        pairs
+    }
+}
+
+impl FromStr for KeyboardLayout {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_lowercase().as_str() {
+            "qwerty" => Ok(Self::Qwerty),
+            "azerty" => Ok(Self::Azerty),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Keyboard layout must be either 'qwerty' or 'azerty'",
+            )),
+        }
     }
 }
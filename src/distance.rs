@@ -1,5 +1,5 @@
 use crate::types::*;
-use std::cmp::min;
+use std::cmp::{max, min};
 use std::collections::HashMap;
 
 ///Compute levenshtein distance between two normalised strings
@@ -178,6 +178,84 @@ pub fn damerau_levenshtein(
     }
 }
 
+/// Substitution costs for [`weighted_damerau_levenshtein()`], keyed on pairs of normalized
+/// character indices (source, target). Pairs that are absent have the unit cost.
+pub type SubstitutionCosts = HashMap<(CharIndexType, CharIndexType), f64>;
+
+///Compute the weighted Damerau-Levenshtein distance between two normalised strings, where
+///substitutions take the cost specified in `costs` (unit cost if absent). Insertions, deletions
+///and transpositions have unit cost.
+///Returns None if the maximum distance is exceeded
+pub fn weighted_damerau_levenshtein(
+    s: &[CharIndexType],
+    t: &[CharIndexType],
+    max_distance: f64,
+    costs: &SubstitutionCosts,
+) -> Option<f64> {
+    let len_s = s.len();
+    let len_t = t.len();
+
+    if len_s == 0 || len_t == 0 {
+        let result = max(len_s, len_t) as f64;
+        if result > max_distance {
+            return None;
+        } else {
+            return Some(result);
+        }
+    } else if max(len_s, len_t) - min(len_s, len_t) > max_distance as usize {
+        return None;
+    }
+
+    let distance_upper_bound = (len_t + len_s) as f64;
+
+    // initialize the matrix
+    let mut mat: Vec<Vec<f64>> = vec![vec![0.0; len_t + 2]; len_s + 2];
+    mat[0][0] = distance_upper_bound;
+    for i in 0..(len_s + 1) {
+        mat[i + 1][0] = distance_upper_bound;
+        mat[i + 1][1] = i as f64;
+    }
+    for i in 0..(len_t + 1) {
+        mat[0][i + 1] = distance_upper_bound;
+        mat[1][i + 1] = i as f64;
+    }
+
+    let mut char_map: HashMap<CharIndexType, usize> = HashMap::new();
+    // apply edit operations
+    for (i, s_char) in s.iter().enumerate() {
+        let mut db = 0;
+        let i = i + 1;
+
+        for (j, t_char) in t.iter().enumerate() {
+            let j = j + 1;
+            let last: usize = *char_map.get(t_char).unwrap_or(&0);
+
+            let cost = if s_char == t_char {
+                0.0
+            } else {
+                *costs.get(&(*s_char, *t_char)).unwrap_or(&1.0)
+            };
+            mat[i + 1][j + 1] = (mat[i + 1][j] + 1.0) // deletion
+                .min(mat[i][j + 1] + 1.0) // insertion
+                .min(mat[i][j] + cost) // substitution
+                .min(mat[last][db] + ((i - last - 1) + 1 + (j - db - 1)) as f64); // transposition
+
+            if s_char == t_char {
+                db = j;
+            }
+        }
+
+        char_map.insert(*s_char, i);
+    }
+
+    let result = mat[len_s + 1][len_t + 1];
+    if result > max_distance {
+        None
+    } else {
+        Some(result)
+    }
+}
+
 pub fn longest_common_substring_length(s1: &[CharIndexType], s2: &[CharIndexType]) -> u16 {
     let mut lcs = 0;
 
@@ -230,6 +308,116 @@ pub fn common_suffix_length(s1: &[CharIndexType], s2: &[CharIndexType]) -> u16 {
     suffixlen
 }
 
+///Number of occurrences of each symbol in a normalised string, see [`symbol_counts()`]
+pub type SymbolCounts = [u32; CharIndexType::MAX as usize + 1];
+
+///Counts how often each symbol occurs in a normalised string
+pub fn symbol_counts(s: &[CharIndexType]) -> SymbolCounts {
+    let mut counts = [0; CharIndexType::MAX as usize + 1];
+    for c in s {
+        counts[*c as usize] += 1;
+    }
+    counts
+}
+
+///Computes how many characters the first normalised string has that the second one lacks, and vice
+///versa (i.e. the sizes of both multiset differences), given the precomputed symbol counts of the
+///first string. The larger of the two is a lower bound on the (Damerau-)Levenshtein distance: a
+///transposition leaves the character counts unchanged and any other edit operation reduces each of
+///the differences by at most one. This takes a single pass over the second string.
+pub fn multiset_difference(
+    s1: &[CharIndexType],
+    s1_counts: &SymbolCounts,
+    s2: &[CharIndexType],
+) -> (usize, usize) {
+    let mut counts = *s1_counts;
+    let mut extra = 0;
+    for c in s2 {
+        let count = &mut counts[*c as usize];
+        if *count > 0 {
+            *count -= 1;
+        } else {
+            extra += 1;
+        }
+    }
+    (s1.len() - (s2.len() - extra), extra)
+}
+
+///Computes the Jaro similarity between two normalised strings, in range 0-1
+pub fn jaro(s1: &[CharIndexType], s2: &[CharIndexType]) -> f64 {
+    if s1.is_empty() && s2.is_empty() {
+        return 1.0;
+    } else if s1.is_empty() || s2.is_empty() {
+        return 0.0;
+    }
+    //characters only match if they are not further apart than this window
+    let window = (max(s1.len(), s2.len()) / 2).saturating_sub(1);
+    let mut s1_matched = vec![false; s1.len()];
+    let mut s2_matched = vec![false; s2.len()];
+    let mut matches = 0;
+    for (i, c) in s1.iter().enumerate() {
+        let begin = i.saturating_sub(window);
+        let end = min(i + window + 1, s2.len());
+        for j in begin..end {
+            if !s2_matched[j] && s2[j] == *c {
+                s1_matched[i] = true;
+                s2_matched[j] = true;
+                matches += 1;
+                break;
+            }
+        }
+    }
+    if matches == 0 {
+        return 0.0;
+    }
+    //count matching characters that are out of order
+    let mut transpositions = 0;
+    let mut j = 0;
+    for (i, c) in s1.iter().enumerate() {
+        if s1_matched[i] {
+            while !s2_matched[j] {
+                j += 1;
+            }
+            if *c != s2[j] {
+                transpositions += 1;
+            }
+            j += 1;
+        }
+    }
+    let matches = matches as f64;
+    (matches / s1.len() as f64
+        + matches / s2.len() as f64
+        + (matches - (transpositions / 2) as f64) / matches)
+        / 3.0
+}
+
+///Computes the Jaro-Winkler similarity between two normalised strings, in range 0-1.
+///This is the Jaro similarity with a bonus for a common prefix (up to 4 characters).
+pub fn jaro_winkler(s1: &[CharIndexType], s2: &[CharIndexType]) -> f64 {
+    let jaro = jaro(s1, s2);
+    let prefixlen = min(common_prefix_length(s1, s2), 4) as f64;
+    jaro + prefixlen * 0.1 * (1.0 - jaro)
+}
+
+///Computes the case similarity between two (unnormalised) strings, in range 0-1: the fraction of
+///characters, aligned by position up to the length of the shortest string, that are either both
+///uppercase or both not uppercase.
+pub fn case_similarity(s1: &str, s2: &str) -> f64 {
+    let mut aligned = 0;
+    let mut matches = 0;
+    for (c1, c2) in s1.chars().zip(s2.chars()) {
+        aligned += 1;
+        if c1.is_uppercase() == c2.is_uppercase() {
+            matches += 1;
+        }
+    }
+    if aligned == 0 {
+        1.0
+    } else {
+        matches as f64 / aligned as f64
+    }
+}
+
 #[inline(always)]
 pub fn min4(a: usize, b: usize, c: usize, d: usize) -> usize {
     return min(min(min(a, b), c), d);
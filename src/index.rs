@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
 use crate::types::*;
 
-pub type AnaIndex = HashMap<AnaValue, AnaIndexNode>;
+/// The main anagram index. Keys are reference counted so the secondary sorted index can share them
+/// rather than holding copies.
+pub type AnaIndex = HashMap<Arc<AnaValue>, AnaIndexNode>;
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct AnaIndexNode {
     ///Maps an anagram value to all existing instances that instantiate it
     pub instances: Vec<VocabId>,
@@ -19,5 +23,6 @@ pub enum Variant {
     Unknown(String),
 }
 
-///Links items in the lexicon to variants offered at test time, with a float score
-pub type ReverseIndex = HashMap<VocabId, Vec<(Variant, f64)>>;
+///Links items in the lexicon to variants offered at test time, with a float score. Ordered by
+///vocabulary id, so iterating over it is deterministic.
+pub type ReverseIndex = BTreeMap<VocabId, Vec<(Variant, f64)>>;
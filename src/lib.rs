@@ -7,36 +7,56 @@ extern crate simple_error;
 
 use rayon::prelude::*;
 use rustfst::prelude::*;
-use sesdiff::shortest_edit_script;
-use std::cmp::min;
+use sesdiff::{shortest_edit_script, EditInstruction};
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::mem::size_of;
 use std::str::FromStr;
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Instant, SystemTime};
 
+use crate::logging::{debug, error, info, trace, warning};
+
+pub mod affixes;
 pub mod anahash;
 pub mod cache;
+pub mod check;
+pub mod compression;
 pub mod confusables;
 pub mod distance;
 pub mod index;
 pub mod iterators;
+pub mod logging;
+pub mod ngramindex;
+pub mod persist;
+pub mod phonetic;
 pub mod search;
 pub mod test;
 pub mod types;
+pub mod unicode;
 pub mod vocab;
 
+pub use crate::affixes::*;
 pub use crate::anahash::*;
 pub use crate::cache::*;
+pub use crate::check::*;
+pub use crate::compression::*;
 pub use crate::confusables::*;
 pub use crate::distance::*;
 pub use crate::index::*;
 pub use crate::iterators::*;
+pub use crate::logging::{set_logger, unset_logger, LogLevel, Logger, StderrLogger};
+pub use crate::ngramindex::*;
+pub use crate::persist::*;
+pub use crate::phonetic::*;
 pub use crate::search::*;
 pub use crate::types::*;
+pub use crate::unicode::*;
 pub use crate::vocab::*;
 
 /// An absolute maximum on the anagram distance, even for long inputs
@@ -45,6 +65,10 @@ const MAX_ANAGRAM_DISTANCE: u8 = 12;
 /// An absolute maximum on the edit distance, even for long inputs
 const MAX_EDIT_DISTANCE: u8 = 12;
 
+/// Number of lexicon entries that are hashed in parallel between two progress reports when
+/// building the model
+const BUILD_CHUNK_SIZE: usize = 10000;
+
 /// The VariantModel is the most high-level model of analiticcl, it holds
 /// all data required for variant matching.
 pub struct VariantModel {
@@ -54,16 +78,42 @@ pub struct VariantModel {
     /// Map strings to vocabulary IDs
     pub encoder: VocabEncoder,
 
-    /// Defines the alphabet used for the variant model
+    /// Defines the alphabet used for the variant model. Use [`Self::add_alphabet_entries()`] rather than
+    /// modifying it directly, so the [`AlphabetMatcher`] that is used for encoding is kept in sync.
     pub alphabet: Alphabet,
 
+    /// Prebuilt matcher for the alphabet, used to encode text (see [`Self::alphabet_matcher()`])
+    alphabet_matcher: AlphabetMatcher,
+
+    /// Strings (e.g. soft hyphens or zero-width joiners) that are stripped from lexicon entries and
+    /// query input prior to encoding them with the alphabet, so they do not count at all
+    pub alphabet_ignore: Vec<String>,
+
+    /// Characters that are explicitly encoded as the unknown character, they are not reported as
+    /// missing from the alphabet (see [`Self::alphabet_coverage_report()`])
+    pub alphabet_unk: Vec<String>,
+
     ///The main index, mapping anagrams to instances
     pub index: AnaIndex,
 
     ///A secondary sorted index
     ///indices of the outer vector correspond to the length of an anagram (in chars)  - 1
-    ///Inner vector is always sorted
-    pub sortedindex: BTreeMap<u16, Vec<AnaValue>>,
+    ///Inner vector is always sorted, it shares its anagram values with the keys of the main index
+    pub sortedindex: BTreeMap<u16, Vec<Arc<AnaValue>>>,
+
+    ///Secondary phonetic index, mapping Double Metaphone codes to instances.
+    ///Only built if the phonetic weight is non-zero.
+    pub phonetic_index: HashMap<String, Vec<VocabId>>,
+
+    ///Inverted index of character n-grams, an alternative candidate generator for long inputs.
+    ///Only built if enabled, see [`Self::set_ngram_index()`].
+    pub ngram_index: NGramIndex,
+
+    /// Build the n-gram index, see [`Self::set_ngram_index()`]
+    ngram_index_enabled: bool,
+
+    /// Build the secondary sorted index and search for insertions, see [`Self::set_insertions()`]
+    insertions: bool,
 
     /// Ngrams for simple context-sensitive language modelling
     /// when finding the most probable sequence of variants
@@ -72,6 +122,15 @@ pub struct VariantModel {
     ///Total frequency, index corresponds to n-1 size, so this holds the total count for unigrams, bigrams, etc.
     pub freq_sum: Vec<usize>,
 
+    /// Smoothing method for the language model
+    pub lm_smoothing: LmSmoothing,
+
+    /// Continuation counts for each token in the bigrams of the language model, maintained by [`Self::add_ngram()`]
+    pub continuation_counts: HashMap<VocabId, ContinuationCounts>,
+
+    /// Number of distinct bigrams in the language model
+    pub bigram_types: u32,
+
     /// Do we have frequency information for variant matching?
     pub have_freq: bool,
 
@@ -90,12 +149,52 @@ pub struct VariantModel {
     /// items for provenance reasons
     pub lexicons: Vec<String>,
 
+    /// The weight of each loaded lexicon (corresponding by index), see [`VocabParams::weight`]
+    pub lexicon_weights: Vec<f64>,
+
+    /// Bitmask of lexicons that are disabled at query time (see [`Self::disable_lexicon()`])
+    disabled_lexicons: u32,
+
     /// Holds weighted confusable recipes that can be used in scoring and ranking
     pub confusables: Vec<Confusable>,
 
     ///Process confusables before pruning by max_matches
     pub confusables_before_pruning: bool,
 
+    /// Holds weighted affix rules, used to also match inputs against their stems, see [`Self::add_to_affixes()`]
+    pub affixes: Vec<AffixRule>,
+
+    /// Check how well the alphabet covers the vocabulary when building, see [`Self::set_check_alphabet_coverage()`]
+    check_alphabet_coverage: bool,
+
+    /// Unicode normalization applied to lexicon entries and query input, prior to encoding them with the alphabet
+    pub unicode_normalization: UnicodeNormalization,
+
+    /// Lowercase lexicon entries and query input prior to encoding them with the alphabet, so matching is
+    /// case-insensitive. The lexicon entries themselves retain their original casing.
+    pub casefold: bool,
+
+    /// Only compare the casing of the first character for the case component of the score,
+    /// see [`Self::set_case_first_char()`]
+    pub case_first_char: bool,
+
+    /// Encode each distinct character that is not covered by the alphabet with its own symbol,
+    /// see [`Self::set_distinct_unknowns()`]
+    pub distinct_unknowns: bool,
+
+    /// Custom substitution costs for the edit distance computation, if empty all substitutions have unit cost
+    pub substitution_costs: SubstitutionCosts,
+
+    /// Dedicated thread pool for parallel processing (see [`Self::set_num_threads()`]), the global
+    /// pool is used if not set
+    thread_pool: Option<rayon::ThreadPool>,
+
+    /// Cache of anagram neighbourhoods (see [`SearchParameters::neighbourhood_cache_size`]), shared by all threads
+    neighbourhood_cache: Mutex<NeighbourhoodCache>,
+
+    /// Called with the complete ranked list of candidates for each input, see [`Self::set_eval_hook()`]
+    eval_hook: Option<EvalHook>,
+
     pub debug: u8,
 }
 
@@ -104,18 +203,40 @@ impl VariantModel {
     pub fn new(alphabet_file: &str, weights: Weights, debug: u8) -> VariantModel {
         let mut model = VariantModel {
             alphabet: Vec::new(),
+            alphabet_ignore: Vec::new(),
+            alphabet_unk: Vec::new(),
+            alphabet_matcher: AlphabetMatcher::default(),
             encoder: HashMap::new(),
             decoder: Vec::new(),
             index: HashMap::new(),
             sortedindex: BTreeMap::new(),
+            phonetic_index: HashMap::new(),
+            ngram_index: NGramIndex::default(),
+            ngram_index_enabled: false,
+            insertions: true,
             ngrams: HashMap::new(),
             freq_sum: vec![0],
+            lm_smoothing: LmSmoothing::Fixed,
+            continuation_counts: HashMap::new(),
+            bigram_types: 0,
             have_freq: false,
             have_lm: false,
             weights,
             lexicons: Vec::new(),
+            lexicon_weights: Vec::new(),
+            disabled_lexicons: 0,
             confusables: Vec::new(),
             confusables_before_pruning: false,
+            affixes: Vec::new(),
+            check_alphabet_coverage: false,
+            unicode_normalization: UnicodeNormalization::None,
+            casefold: false,
+            case_first_char: false,
+            distinct_unknowns: false,
+            substitution_costs: HashMap::new(),
+            thread_pool: None,
+            neighbourhood_cache: Mutex::new(NeighbourhoodCache::default()),
+            eval_hook: None,
             context_rules: Vec::new(),
             tags: Vec::new(),
             debug,
@@ -132,32 +253,403 @@ impl VariantModel {
     pub fn new_with_alphabet(alphabet: Alphabet, weights: Weights, debug: u8) -> VariantModel {
         let mut model = VariantModel {
             alphabet: alphabet,
+            alphabet_ignore: Vec::new(),
+            alphabet_unk: Vec::new(),
+            alphabet_matcher: AlphabetMatcher::default(),
             decoder: Vec::new(),
             encoder: HashMap::new(),
             index: HashMap::new(),
             sortedindex: BTreeMap::new(),
+            phonetic_index: HashMap::new(),
+            ngram_index: NGramIndex::default(),
+            ngram_index_enabled: false,
+            insertions: true,
             ngrams: HashMap::new(),
             freq_sum: vec![0],
+            lm_smoothing: LmSmoothing::Fixed,
+            continuation_counts: HashMap::new(),
+            bigram_types: 0,
             have_freq: false,
             have_lm: false,
             weights,
             lexicons: Vec::new(),
+            lexicon_weights: Vec::new(),
+            disabled_lexicons: 0,
             confusables: Vec::new(),
             confusables_before_pruning: false,
+            affixes: Vec::new(),
+            check_alphabet_coverage: false,
+            unicode_normalization: UnicodeNormalization::None,
+            casefold: false,
+            case_first_char: false,
+            distinct_unknowns: false,
+            substitution_costs: HashMap::new(),
+            thread_pool: None,
+            neighbourhood_cache: Mutex::new(NeighbourhoodCache::default()),
+            eval_hook: None,
             context_rules: Vec::new(),
             tags: Vec::new(),
             debug,
         };
+        model.alphabet_matcher = AlphabetMatcher::new(&model.alphabet);
         init_vocab(&mut model.decoder, &mut model.encoder);
         model
     }
 
+    /// Load a previously built and saved model (see [`Self::save()`]) from file. The alphabet file is
+    /// loaded as usual and must match the alphabet the model was built with.
+    /// The model is ready for use immediately, there is no need to call `build()`.
+    pub fn load(
+        filename: &str,
+        alphabet_file: &str,
+        debug: u8,
+    ) -> Result<VariantModel, std::io::Error> {
+        let mut model = VariantModel::new(alphabet_file, Weights::default(), debug);
+        model.read_model(filename)?;
+        Ok(model)
+    }
+
+    /// Load a previously built and saved model (see [`Self::save()`]) from file, explicitly
+    /// passing the alphabet, which must match the alphabet the model was built with.
+    pub fn load_with_alphabet(
+        filename: &str,
+        alphabet: Alphabet,
+        debug: u8,
+    ) -> Result<VariantModel, std::io::Error> {
+        let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), debug);
+        model.read_model(filename)?;
+        Ok(model)
+    }
+
+    /// Save the model to a single binary file, so it can be loaded again later using [`Self::load()`]
+    /// without needing to rebuild it. You will want to call this after `build()`.
+    /// Saving the same model always yields a byte-identical file.
+    pub fn save(&self, filename: &str) -> Result<(), std::io::Error> {
+        if self.debug >= 1 {
+            info!("Saving model to {}...", filename);
+        }
+        let f = File::create(filename)?;
+        let mut writer = BufWriter::new(f);
+        write_model_header(&mut writer)?;
+        let mut substitution_costs: Vec<(CharIndexType, CharIndexType, f64)> = self
+            .substitution_costs
+            .iter()
+            .map(|((a, b), cost)| (*a, *b, *cost))
+            .collect();
+        substitution_costs.sort_by_key(|(a, b, _)| (*a, *b));
+        let data = ModelDataRef {
+            alphabet: &self.alphabet,
+            alphabet_ignore: &self.alphabet_ignore,
+            alphabet_unk: &self.alphabet_unk,
+            decoder: &self.decoder,
+            encoder: &self.encoder,
+            index: &self.index,
+            ngrams: &self.ngrams,
+            freq_sum: &self.freq_sum,
+            have_freq: self.have_freq,
+            have_lm: self.have_lm,
+            lm_smoothing: self.lm_smoothing,
+            context_rules: &self.context_rules,
+            tags: &self.tags,
+            weights: &self.weights,
+            lexicons: &self.lexicons,
+            lexicon_weights: &self.lexicon_weights,
+            confusables: self
+                .confusables
+                .iter()
+                .map(|confusable| (confusable.to_string(), confusable.weight))
+                .collect(),
+            confusables_casefold: self
+                .confusables
+                .iter()
+                .map(|confusable| confusable.casefold)
+                .collect(),
+            confusables_before_pruning: self.confusables_before_pruning,
+            affixes: self
+                .affixes
+                .iter()
+                .map(|rule| (rule.to_string(), rule.weight))
+                .collect(),
+            unicode_normalization: self.unicode_normalization,
+            casefold: self.casefold,
+            case_first_char: self.case_first_char,
+            distinct_unknowns: self.distinct_unknowns,
+            substitution_costs,
+            ngram_index: self.ngram_index_enabled,
+        };
+        serde_cbor::to_writer(&mut writer, &data).map_err(std::io::Error::other)?;
+        writer.flush()
+    }
+
+    /// Reads a saved model from file into this model, replacing all existing data but the alphabet, which
+    /// must match the alphabet stored in the file.
+    fn read_model(&mut self, filename: &str) -> Result<(), std::io::Error> {
+        if self.debug >= 1 {
+            info!("Loading model from {}...", filename);
+        }
+        let f = File::open(filename)?;
+        let mut reader = BufReader::new(f);
+        read_model_header(&mut reader)?;
+        let data: ModelData = serde_cbor::from_reader(reader)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        //the saved alphabet was normalized along with everything else, so the provided one must be too
+        self.set_unicode_normalization(data.unicode_normalization);
+        if data.alphabet != self.alphabet {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "The alphabet of the saved model does not match the alphabet that was provided",
+            ));
+        }
+        //the lexicon was encoded with these, so they take precedence over what was provided
+        self.alphabet_ignore = data.alphabet_ignore;
+        self.alphabet_unk = data.alphabet_unk;
+        self.decoder = data.decoder;
+        self.encoder = data.encoder;
+        self.index = data.index;
+        self.clear_neighbourhood_cache();
+        if self.insertions {
+            self.build_sorted_index(&mut |_stage, _current, _total| {});
+        }
+        self.ngrams = data.ngrams;
+        self.freq_sum = data.freq_sum;
+        self.have_freq = data.have_freq;
+        self.have_lm = data.have_lm;
+        self.lm_smoothing = data.lm_smoothing;
+        self.continuation_counts.clear();
+        self.bigram_types = 0;
+        let ngrams: Vec<NGram> = self.ngrams.keys().cloned().collect();
+        for ngram in ngrams.iter() {
+            self.count_continuation(ngram);
+        }
+        self.context_rules = data.context_rules;
+        self.tags = data.tags;
+        self.weights = data.weights;
+        self.lexicons = data.lexicons;
+        self.lexicon_weights = data.lexicon_weights;
+        //models saved by earlier versions have no lexicon weights
+        self.lexicon_weights.resize(self.lexicons.len(), 1.0);
+        self.confusables.clear();
+        for (i, (editscript, weight)) in data.confusables.into_iter().enumerate() {
+            if data.confusables_casefold.get(i) == Some(&true) {
+                self.add_to_confusables_casefold(&editscript, weight)?;
+            } else {
+                self.add_to_confusables(&editscript, weight)?;
+            }
+        }
+        self.confusables_before_pruning = data.confusables_before_pruning;
+        self.affixes.clear();
+        for (affix, weight) in data.affixes {
+            self.add_to_affixes(&affix, weight)?;
+        }
+        self.unicode_normalization = data.unicode_normalization;
+        self.casefold = data.casefold;
+        self.case_first_char = data.case_first_char;
+        self.distinct_unknowns = data.distinct_unknowns;
+        self.substitution_costs = data
+            .substitution_costs
+            .into_iter()
+            .map(|(a, b, cost)| ((a, b), cost))
+            .collect();
+        self.phonetic_index.clear();
+        if self.weights.phonetic > 0.0 {
+            self.build_phonetic_index();
+        }
+        self.ngram_index_enabled = data.ngram_index;
+        self.ngram_index.clear();
+        if self.ngram_index_enabled {
+            self.build_ngram_index();
+        }
+        Ok(())
+    }
+
     /// Configure the model to match against known confusables prior to pruning on maximum weight.
     /// This may lead to better results but may have a significant performance impact.
     pub fn set_confusables_before_pruning(&mut self) {
         self.confusables_before_pruning = true;
     }
 
+    /// Configure the Unicode normalization form (NFC or NFD) to apply to lexicon entries and query
+    /// input. This should be set before loading any lexicons. The alphabet is normalized as well,
+    /// so that matching against the alphabet happens after normalization.
+    pub fn set_unicode_normalization(&mut self, normalization: UnicodeNormalization) {
+        self.unicode_normalization = normalization;
+        for chars in self
+            .alphabet
+            .iter_mut()
+            .chain([&mut self.alphabet_ignore, &mut self.alphabet_unk])
+        {
+            for c in chars.iter_mut() {
+                if let Cow::Owned(normalized) = normalization.normalize(c) {
+                    *c = normalized;
+                }
+            }
+        }
+        self.alphabet_matcher = AlphabetMatcher::new(&self.alphabet);
+    }
+
+    /// Use a dedicated thread pool with the specified number of threads for all parallel processing
+    /// in [`Self::find_variants()`], [`Self::find_all_matches()`] and [`Self::learn_variants()`],
+    /// rather than the global one. This allows controlling parallelism per model without having to
+    /// set `RAYON_NUM_THREADS` for the whole process. A value of 0 lets rayon decide.
+    pub fn set_num_threads(&mut self, num_threads: usize) -> Result<(), std::io::Error> {
+        self.thread_pool = Some(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .map_err(std::io::Error::other)?,
+        );
+        Ok(())
+    }
+
+    /// Returns the dedicated thread pool, unless we are already running inside it
+    fn outside_thread_pool(&self) -> Option<&rayon::ThreadPool> {
+        self.thread_pool
+            .as_ref()
+            .filter(|pool| pool.current_thread_index().is_none())
+    }
+
+    /// Gives access to the cache of anagram neighbourhoods, e.g. to inspect its statistics. The
+    /// cache is only used if [`SearchParameters::neighbourhood_cache_size`] is non-zero.
+    pub fn neighbourhood_cache(&self) -> MutexGuard<'_, NeighbourhoodCache> {
+        self.neighbourhood_cache
+            .lock()
+            .expect("neighbourhood cache lock")
+    }
+
+    /// Removes all cached anagram neighbourhoods, needed whenever the index changes
+    fn clear_neighbourhood_cache(&mut self) {
+        self.neighbourhood_cache
+            .get_mut()
+            .expect("neighbourhood cache lock")
+            .clear();
+    }
+
+    /// Check how well the alphabet covers the vocabulary when building the model, and warn about
+    /// characters that are not in the alphabet and about entries that consist mostly of such
+    /// characters (see [`Self::alphabet_coverage_report()`]). This requires an extra pass over the
+    /// vocabulary.
+    pub fn set_check_alphabet_coverage(&mut self, check: bool) {
+        self.check_alphabet_coverage = check;
+    }
+
+    /// Enable or disable the character n-gram index, an alternative candidate generator for long
+    /// inputs (see [`NGramIndex`] and [`SearchParameters::ngram_min_length`]). The index is built
+    /// by `build()`, or immediately if the model was already built (e.g. after loading it).
+    pub fn set_ngram_index(&mut self, enabled: bool) {
+        self.ngram_index_enabled = enabled;
+        if !enabled {
+            self.ngram_index.clear();
+        } else if !self.index.is_empty() {
+            self.build_ngram_index();
+        }
+    }
+
+    /// Enable or disable the secondary sorted index (enabled by default). Without it, the model
+    /// uses less memory and queries are faster, but it restricts the edit operations that can be
+    /// reached: candidates that are longer than the input (i.e. that need insertions) are never
+    /// found. Deletions are still found, and substitutions are found by probing the main index
+    /// directly, which is only feasible for small anagram distances. The index is built by
+    /// `build()`, or immediately if the model was already built (e.g. after loading it).
+    pub fn set_insertions(&mut self, enabled: bool) {
+        self.insertions = enabled;
+        self.clear_neighbourhood_cache();
+        if !enabled {
+            self.sortedindex.clear();
+        } else if !self.index.is_empty() {
+            self.build_sorted_index(&mut |_stage, _current, _total| {});
+        }
+    }
+
+    /// Configure the smoothing method for the language model, see [`LmSmoothing`].
+    pub fn set_lm_smoothing(&mut self, smoothing: LmSmoothing) {
+        self.lm_smoothing = smoothing;
+    }
+
+    /// Enable or disable case folding: lexicon entries and query input are lowercased prior to
+    /// encoding them with the alphabet, so matching is case-insensitive, whereas returned variants
+    /// keep the casing of the lexicon. This should be set before loading any lexicons.
+    pub fn set_casefold(&mut self, casefold: bool) {
+        self.casefold = casefold;
+    }
+
+    /// Only compare the casing of the first character of the input and a variant for the case
+    /// component of the score, rather than that of all aligned characters. This is cheaper and
+    /// reproduces the rankings of earlier versions, but is too coarse for abbreviations and
+    /// all-caps input.
+    pub fn set_case_first_char(&mut self, case_first_char: bool) {
+        self.case_first_char = case_first_char;
+    }
+
+    /// Set a hook that is called with the complete ranked list of candidates for each input,
+    /// including all score components, before the list is truncated to the maximum number of
+    /// matches, the hard maximum and the cutoff threshold. This is intended for evaluating the
+    /// model against a gold standard (e.g. to compute recall@k), with weights that are being tuned.
+    /// Late confusable weights are already applied. The hook may be called from multiple threads
+    /// concurrently. Pass `None` to remove it.
+    pub fn set_eval_hook(&mut self, hook: Option<EvalHook>) {
+        self.eval_hook = hook;
+    }
+
+    /// Distinguish characters that are not covered by the alphabet from one another: by default they
+    /// are all encoded as the same unknown character, so two words that only differ in unknown
+    /// characters are considered identical by the edit distance. If enabled, each distinct unknown
+    /// character is encoded with its own symbol, so differing unknown characters count as a
+    /// substitution. They still all share the same character in the anagram hash. This trades
+    /// recall for precision on noisy (multilingual) input. The vocabulary that was already added
+    /// is normalized again.
+    ///
+    /// Symbols are single bytes, so only the range above the alphabet is available for unknown
+    /// characters: about 200 symbols for a typical alphabet. Beyond that, distinct unknown
+    /// characters share a symbol (see [`unknown_symbol()`]) and are indistinguishable again.
+    pub fn set_distinct_unknowns(&mut self, distinct_unknowns: bool) {
+        if self.distinct_unknowns == distinct_unknowns {
+            return;
+        }
+        self.distinct_unknowns = distinct_unknowns;
+        for i in 0..self.decoder.len() {
+            self.decoder[i].norm = self.normalize(&self.decoder[i].text);
+        }
+        if !self.ngram_index.is_empty() {
+            self.build_ngram_index();
+        }
+    }
+
+    /// Normalizes the text to the alphabet (see [`Self::encodable_text()`]), taking into account
+    /// whether unknown characters are distinguished
+    fn normalize(&self, text: &str) -> NormString {
+        self.encodable_text(text)
+            .normalize_to_alphabet_with(&self.alphabet_matcher, self.distinct_unknowns)
+    }
+
+    /// Lowercases the text if case folding is enabled, and strips all strings that the alphabet
+    /// marks as ignored. This prepares the text for encoding with the alphabet.
+    fn encodable_text<'b>(&self, text: &'b str) -> Cow<'b, str> {
+        let text = if self.casefold {
+            Cow::Owned(text.to_lowercase())
+        } else {
+            Cow::Borrowed(text)
+        };
+        if self
+            .alphabet_ignore
+            .iter()
+            .any(|ignore| text.contains(ignore.as_str()))
+        {
+            let mut text = text.into_owned();
+            for ignore in self.alphabet_ignore.iter() {
+                text = text.replace(ignore.as_str(), "");
+            }
+            Cow::Owned(text)
+        } else {
+            text
+        }
+    }
+
+    /// Returns the prebuilt matcher for the alphabet, pass this rather than [`Self::alphabet`] to
+    /// the methods of [`Anahashable`] to encode text the same way the model does.
+    pub fn alphabet_matcher(&self) -> &AlphabetMatcher {
+        &self.alphabet_matcher
+    }
+
     /// Returns the size of the alphabet, this is typically +1 longer than the actual alphabet file
     /// as it includes the UNKNOWN symbol.
     pub fn alphabet_size(&self) -> CharIndexType {
@@ -174,15 +666,16 @@ impl VariantModel {
                 .get_mut(anahash)
                 .expect("get_mut on node after check")
         } else {
+            self.clear_neighbourhood_cache();
             self.index.insert(
-                anahash.clone(),
+                Arc::new(anahash.clone()),
                 AnaIndexNode {
                     instances: Vec::new(),
                     charcount: anahash.char_count(self.alphabet_size()),
                 },
             );
             self.index
-                .get_mut(&anahash)
+                .get_mut(anahash)
                 .expect("get_mut on node after insert")
         }
     }
@@ -190,66 +683,102 @@ impl VariantModel {
     /// Build the anagram index (and secondary index) so the model
     /// is ready for variant matching
     pub fn build(&mut self) {
-        eprintln!("Computing anagram values for all items in the lexicon...");
+        self.build_with_progress(|_stage, _current, _total| {});
+    }
+
+    /// Build the model like [`build()`], reporting progress to the callback. The callback receives
+    /// the stage of the build, the number of items processed so far in that stage, and the
+    /// total number of items for that stage.
+    ///
+    /// Computing the anagram values is done in parallel (using the thread pool set with
+    /// [`Self::set_num_threads()`], if any), as this dominates the build time for large lexicons.
+    /// The resulting index is identical regardless of the number of threads.
+    pub fn build_with_progress(&mut self, mut progress: impl FnMut(BuildStage, usize, usize)) {
+        if self.check_alphabet_coverage {
+            info!("Checking alphabet coverage...");
+            self.warn_alphabet_coverage();
+        }
+
+        info!("Computing anagram values for all items in the lexicon...");
 
-        // Hash all strings in the lexicon
-        // and add them to the index
+        // Hash all strings in the lexicon (in parallel, chunk by chunk so we can report progress)
+        // and add them to the index (sequentially and in order, so the index is deterministic)
         let mut tmp_hashes: Vec<(AnaValue, VocabId)> = Vec::with_capacity(self.decoder.len());
-        for (id, value) in self.decoder.iter().enumerate() {
-            if value.vocabtype.check(VocabType::INDEXED) {
-                //get the anahash
-                let anahash = value.text.anahash(&self.alphabet);
-                if self.debug >= 2 {
-                    eprintln!(
-                        "   -- Anavalue={} VocabId={} Text={}",
-                        &anahash, id, value.text
-                    );
-                }
-                tmp_hashes.push((anahash, id as VocabId));
+        let total = self.decoder.len();
+        for (chunk_index, chunk) in self.decoder.chunks(BUILD_CHUNK_SIZE).enumerate() {
+            let offset = chunk_index * BUILD_CHUNK_SIZE;
+            let hash_chunk = || {
+                chunk
+                    .par_iter()
+                    .enumerate()
+                    .filter(|(_, value)| value.vocabtype.check(VocabType::INDEXED))
+                    .map(|(i, value)| {
+                        let id = offset + i;
+                        //get the anahash
+                        let anahash = self
+                            .encodable_text(&value.text)
+                            .anahash(&self.alphabet_matcher);
+                        if self.debug >= 2 {
+                            debug!(
+                                "   -- Anavalue={} VocabId={} Text={}",
+                                &anahash, id, value.text
+                            );
+                        }
+                        (anahash, id as VocabId)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            if let Some(pool) = self.outside_thread_pool() {
+                tmp_hashes.extend(pool.install(hash_chunk));
+            } else {
+                tmp_hashes.extend(hash_chunk());
             }
+            progress(BuildStage::Hashing, offset + chunk.len(), total);
         }
-        eprintln!(" - Found {} instances", tmp_hashes.len());
+        info!(" - Found {} instances", tmp_hashes.len());
 
-        eprintln!("Adding all instances to the index...");
+        info!("Adding all instances to the index...");
         self.index.clear();
-        for (anahash, id) in tmp_hashes {
+        self.clear_neighbourhood_cache();
+        let total = tmp_hashes.len();
+        for (i, (anahash, id)) in tmp_hashes.into_iter().enumerate() {
+            progress(BuildStage::Indexing, i + 1, total);
             //add it to the index
             let node = self.get_or_create_index(&anahash);
             node.instances.push(id);
         }
-        eprintln!(" - Found {} anagrams", self.index.len());
+        info!(" - Found {} anagrams", self.index.len());
 
-        eprintln!("Creating sorted secondary index...");
-        self.sortedindex.clear();
-        for (anahash, node) in self.index.iter() {
-            if !self.sortedindex.contains_key(&node.charcount) {
-                self.sortedindex.insert(node.charcount, Vec::new());
-            }
-            let keys = self
-                .sortedindex
-                .get_mut(&node.charcount)
-                .expect("getting sorted index (1)");
-            keys.push(anahash.clone()); //TODO: see if we can make this a reference later
+        if self.insertions {
+            self.build_sorted_index(&mut progress);
+        } else {
+            self.sortedindex.clear();
+        }
+
+        if self.weights.phonetic > 0.0 {
+            info!("Creating phonetic index...");
+            progress(BuildStage::PhoneticIndexing, 0, 1);
+            self.build_phonetic_index();
+            progress(BuildStage::PhoneticIndexing, 1, 1);
+            info!(" - Found {} phonetic codes", self.phonetic_index.len());
         }
 
-        eprintln!("Sorting secondary index...");
-        let mut sizes: Vec<u16> = self.sortedindex.keys().map(|x| *x).collect();
-        sizes.sort();
-        for size in sizes {
-            let keys = self
-                .sortedindex
-                .get_mut(&size)
-                .expect("getting sorted index (2)");
-            keys.sort();
-            eprintln!(" - Found {} anagrams of length {}", keys.len(), size);
+        if self.ngram_index_enabled {
+            info!("Creating n-gram index...");
+            progress(BuildStage::NGramIndexing, 0, 1);
+            self.build_ngram_index();
+            progress(BuildStage::NGramIndexing, 1, 1);
+            info!(" - Found {} n-grams", self.ngram_index.len());
         }
 
-        eprintln!("Constructing Language Model...");
+        info!("Constructing Language Model...");
 
         //extra unigrams extracted from n-grams that need to be added to the vocabulary decoder
         let mut unseen_parts: Option<VocabEncoder> = Some(VocabEncoder::new());
 
-        for id in 0..self.decoder.len() {
+        let total = self.decoder.len();
+        for id in 0..total {
+            progress(BuildStage::LanguageModel, id + 1, total);
             if self
                 .decoder
                 .get(id)
@@ -285,15 +814,161 @@ impl VariantModel {
             }
         }
         if self.ngrams.is_empty() {
-            eprintln!(" - No language model provided");
+            info!(" - No language model provided");
             self.have_lm = false;
         } else {
-            eprintln!(
+            info!(
                 " - Found {} n-grams for language modelling",
                 self.ngrams.len()
             );
             self.have_lm = true;
         }
+        if self.debug >= 1 {
+            let usage = self.memory_usage();
+            info!(
+                "Estimated memory usage: {} bytes (vocabulary: {}, index: {}, secondary index: {}, n-grams: {})",
+                usage.total(),
+                usage.vocabulary,
+                usage.index,
+                usage.sortedindex,
+                usage.ngrams
+            );
+        }
+    }
+
+    /// Creates the secondary index from the keys of the main index, sorted per character count
+    fn build_sorted_index(&mut self, progress: &mut impl FnMut(BuildStage, usize, usize)) {
+        info!("Creating sorted secondary index...");
+        self.sortedindex.clear();
+        for (anahash, node) in self.index.iter() {
+            self.sortedindex
+                .entry(node.charcount)
+                .or_default()
+                .push(Arc::clone(anahash));
+        }
+
+        info!("Sorting secondary index...");
+        let total = self.sortedindex.len();
+        for (i, (size, keys)) in self.sortedindex.iter_mut().enumerate() {
+            progress(BuildStage::Sorting, i + 1, total);
+            if let Some(pool) = self.thread_pool.as_ref() {
+                pool.install(|| keys.par_sort_unstable());
+            } else {
+                keys.par_sort_unstable();
+            }
+            info!(" - Found {} anagrams of length {}", keys.len(), size);
+        }
+    }
+
+    /// Collects statistics on the characters in the (indexed) vocabulary that are not covered by the
+    /// alphabet, see [`AlphabetCoverage`]. All such characters are encoded as the same unknown
+    /// character, so entries that consist mostly of them may collide at the anagram level with
+    /// very different entries.
+    pub fn alphabet_coverage_report(&self) -> AlphabetCoverage {
+        let mut report = AlphabetCoverage::default();
+        for (vocab_id, value) in self.decoder.iter().enumerate() {
+            if !value.vocabtype.check(VocabType::INDEXED) {
+                continue;
+            }
+            let text = self.encodable_text(&value.text);
+            let mut unknown = text.unknown_chars(&self.alphabet_matcher);
+            unknown.retain(|c| !self.alphabet_unk.iter().any(|unk| unk.contains(*c)));
+            if unknown.is_empty() {
+                continue;
+            }
+            report.affected_entries += 1;
+            if unknown.len() * 2 > text.chars().count() {
+                report.dominated_entries.push(vocab_id as VocabId);
+            }
+            for c in unknown {
+                *report.unknown_chars.entry(c).or_insert(0) += 1;
+            }
+        }
+        report
+    }
+
+    /// Logs warnings for the alphabet coverage report, called when building the model
+    fn warn_alphabet_coverage(&self) {
+        let report = self.alphabet_coverage_report();
+        if report.unknown_chars.is_empty() {
+            return;
+        }
+        warning!(
+            "WARNING: {} entries contain characters that are not in the alphabet: {}",
+            report.affected_entries,
+            report
+                .unknown_chars
+                .iter()
+                .map(|(c, count)| format!("{:?} ({})", c, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if !report.dominated_entries.is_empty() {
+            warning!(
+                "WARNING: {} entries consist mostly of characters that are not in the alphabet (wrong or incomplete alphabet?), e.g.: {}",
+                report.dominated_entries.len(),
+                report
+                    .dominated_entries
+                    .iter()
+                    .take(10)
+                    .map(|vocab_id| self.decoder[*vocab_id as usize].text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    /// Returns an estimate of the memory used by the main data structures of the model, see
+    /// [`MemoryUsage`]
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let vocabulary = self
+            .decoder
+            .iter()
+            .map(|value| {
+                size_of::<VocabValue>()
+                    + value.text.capacity()
+                    + value.norm.capacity() * size_of::<CharIndexType>()
+                    + value
+                        .metadata
+                        .as_ref()
+                        .map_or(0, |metadata| metadata.capacity())
+                    + value.lemma.as_ref().map_or(0, |lemma| lemma.capacity())
+                    + value.variants.as_ref().map_or(0, |variants| {
+                        variants.capacity() * size_of::<VariantReference>()
+                    })
+            })
+            .sum::<usize>()
+            + self
+                .encoder
+                .keys()
+                .map(|key| size_of::<(String, VocabId)>() + key.capacity())
+                .sum::<usize>();
+        let index = self
+            .index
+            .iter()
+            .map(|(anahash, node)| {
+                size_of::<(Arc<AnaValue>, AnaIndexNode)>()
+                    //the reference counted allocation holds two counters and the value itself
+                    + 2 * size_of::<usize>()
+                    + size_of::<AnaValue>()
+                    + anahash.heap_size()
+                    + node.instances.capacity() * size_of::<VocabId>()
+            })
+            .sum();
+        let sortedindex = self
+            .sortedindex
+            .values()
+            .map(|keys| {
+                size_of::<(u16, Vec<Arc<AnaValue>>)>()
+                    + keys.capacity() * size_of::<Arc<AnaValue>>()
+            })
+            .sum();
+        MemoryUsage {
+            vocabulary,
+            index,
+            sortedindex,
+            ngrams: self.ngrams.len() * size_of::<(NGram, u32)>(),
+        }
     }
 
     /// Tests if the anagram value exists in the index
@@ -303,7 +978,8 @@ impl VariantModel {
 
     ///Get all anagram instances for a specific entry
     pub fn get_anagram_instances(&self, text: &str) -> Vec<&VocabValue> {
-        let anavalue = text.anahash(&self.alphabet);
+        let text = self.unicode_normalization.normalize(text);
+        let anavalue = self.encodable_text(&text).anahash(&self.alphabet_matcher);
         let mut instances: Vec<&VocabValue> = Vec::new();
         if let Some(node) = self.index.get(&anavalue) {
             for vocab_id in node.instances.iter() {
@@ -319,7 +995,8 @@ impl VariantModel {
 
     ///Get an exact item in the lexicon (if it exists)
     pub fn get(&self, text: &str) -> Option<&VocabValue> {
-        for instance in self.get_anagram_instances(text) {
+        let text = self.unicode_normalization.normalize(text);
+        for instance in self.get_anagram_instances(&text) {
             if instance.text == text {
                 return Some(instance);
             }
@@ -329,7 +1006,8 @@ impl VariantModel {
 
     ///Tests if the lexicon has a specific entry, by text
     pub fn has(&self, text: &str) -> bool {
-        for instance in self.get_anagram_instances(text) {
+        let text = self.unicode_normalization.normalize(text);
+        for instance in self.get_anagram_instances(&text) {
             if instance.text == text {
                 return true;
             }
@@ -342,6 +1020,45 @@ impl VariantModel {
         self.decoder.get(vocab_id as usize)
     }
 
+    /// Gives the names of the lexicons the variant in this result was found in
+    pub fn result_lexicons(&self, result: &VariantResult) -> Vec<&str> {
+        if let Some(vocabvalue) = self.get_vocab(result.vocab_id) {
+            self.vocab_lexicons(vocabvalue)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Gives the names of the lexicons this vocabulary item occurs in
+    pub fn vocab_lexicons(&self, vocabvalue: &VocabValue) -> Vec<&str> {
+        self.lexicons
+            .iter()
+            .enumerate()
+            .filter_map(|(i, name)| {
+                if vocabvalue.in_lexicon(i as u8) {
+                    Some(name.as_str())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Decodes a normalized string back into the alphabet characters that make it up (using the first
+    /// representation of each alphabet character). Unknown characters are decoded as `<UNK>`.
+    /// Mostly intended for debugging purposes.
+    pub fn decompose_normstring(&self, norm: &[CharIndexType]) -> Vec<&str> {
+        norm.iter()
+            .map(|c| {
+                self.alphabet
+                    .get(*c as usize)
+                    .and_then(|chars| chars.first())
+                    .map(|s| s.as_str())
+                    .unwrap_or("<UNK>")
+            })
+            .collect()
+    }
+
     /// Decomposes and decodes and anagram value into the characters that make it up.
     /// Mostly intended for debugging purposes.
     pub fn decompose_anavalue(&self, av: &AnaValue) -> Vec<&str> {
@@ -366,46 +1083,117 @@ impl VariantModel {
     ///The alphabet is not limited to single characters but may consist
     ///of longer string, a greedy matching approach will be used so order
     ///matters (but only for this)
+    ///A line starting with `\ignore` lists strings that are stripped prior to encoding (see
+    ///[`Self::alphabet_ignore`]), a line starting with `\unk` lists characters that are explicitly
+    ///encoded as unknown (see [`Self::alphabet_unk`]). Codepoints may be written as `\uXXXX`.
     pub fn read_alphabet(&mut self, filename: &str) -> Result<(), std::io::Error> {
         if self.debug >= 1 {
-            eprintln!("Reading alphabet from {}...", filename);
+            info!("Reading alphabet from {}...", filename);
         }
-        let f = File::open(filename)?;
-        let f_buffer = BufReader::new(f);
+        let f_buffer = open_file(filename, Compression::Auto)?;
         for line in f_buffer.lines() {
-            if let Ok(line) = line {
-                if !line.is_empty() {
-                    let fields = line
-                        .split("\t")
-                        .filter_map(|x| match x {
-                            "\\s" => Some(" ".to_owned()),
-                            "\\t" => Some("\t".to_owned()),
-                            "\\n" => Some("\n".to_owned()),
-                            _ => {
-                                if x.trim().is_empty() {
-                                    None
-                                } else {
-                                    Some(x.trim().to_owned())
-                                }
-                            }
-                        })
-                        .collect();
-                    self.alphabet.push(fields);
+            let line = line?;
+            if !line.is_empty() {
+                let mut fields = parse_alphabet_line(&line);
+                match fields.first().map(|x| x.as_str()) {
+                    Some("\\ignore") => {
+                        fields.remove(0);
+                        self.alphabet_ignore.extend(fields);
+                    }
+                    Some("\\unk") => {
+                        fields.remove(0);
+                        self.alphabet_unk.extend(fields);
+                    }
+                    _ => self.alphabet.push(fields),
                 }
             }
         }
+        self.alphabet_matcher = AlphabetMatcher::new(&self.alphabet);
         if self.debug >= 2 {
-            eprintln!(" -- Read alphabet of size {}", self.alphabet.len());
+            debug!(" -- Read alphabet of size {}", self.alphabet.len());
             for (i, items) in self.alphabet.iter().enumerate() {
                 let av = AnaValue::character(i as CharIndexType);
-                eprintln!(" -- #{} -> {} - {:?}", i, av, items);
+                debug!(" -- #{} -> {} - {:?}", i, av, items);
             }
         } else if self.debug >= 1 {
-            eprintln!(" -- Read alphabet of size {}", self.alphabet.len());
+            info!(" -- Read alphabet of size {}", self.alphabet.len());
+        }
+        Ok(())
+    }
+
+    /// Appends entries to the alphabet, each entry is a list of strings that are treated as
+    /// identical (like a line in an alphabet file). This must be done before the model is built,
+    /// vocabulary that was already added is normalized again according to the extended alphabet.
+    /// Returns an error if the model was already built (its anagram values would no longer be
+    /// valid), or if an entry is empty or already covered by the alphabet. Entries are subject to
+    /// the Unicode normalization of the model (see [`Self::set_unicode_normalization()`]).
+    pub fn add_alphabet_entries(&mut self, mut entries: Alphabet) -> Result<(), std::io::Error> {
+        if !self.index.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Alphabet entries can only be added before the model is built",
+            ));
+        }
+        if self.alphabet.len() + entries.len() + 1 > PRIMES.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Alphabet too large, it may have at most {} entries",
+                    PRIMES.len() - 1
+                ),
+            ));
+        }
+        for element in entries.iter_mut().flatten() {
+            if let Cow::Owned(normalized) = self.unicode_normalization.normalize(element) {
+                *element = normalized;
+            }
+        }
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.is_empty() || entry.iter().any(|element| element.is_empty()) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Alphabet entries may not be empty",
+                ));
+            }
+            for element in entry.iter() {
+                if self
+                    .alphabet
+                    .iter()
+                    .chain(entries[..i].iter())
+                    .any(|existing| existing.contains(element))
+                {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("Alphabet entry is already in the alphabet: {}", element),
+                    ));
+                }
+            }
+        }
+        self.alphabet.extend(entries);
+        self.alphabet_matcher = AlphabetMatcher::new(&self.alphabet);
+        //the unknown character is the last one in the alphabet so all vocabulary must be normalized again
+        for i in 0..self.decoder.len() {
+            self.decoder[i].norm = self.normalize(&self.decoder[i].text);
+        }
+        if self.debug >= 1 {
+            info!(
+                " -- Extended alphabet to size {}, normalized {} vocabulary items again",
+                self.alphabet.len(),
+                self.decoder.len()
+            );
         }
         Ok(())
     }
 
+    /// Like [`Self::add_alphabet_entries()`], but consumes and returns the model so it can be chained after construction
+    pub fn with_additional_alphabet_entries(
+        mut self,
+        entries: Alphabet,
+    ) -> Result<Self, std::io::Error> {
+        self.add_alphabet_entries(entries)?;
+        Ok(self)
+    }
+
     ///Read a confusiblelist from a TSV file
     ///Contains edit scripts in the first columned (formatted in sesdiff style)
     ///and optionally a weight in the second column.
@@ -413,29 +1201,45 @@ impl VariantModel {
     ///Weight values should be relatively close to 1.0 as they are applied to the entire score
     pub fn read_confusablelist(&mut self, filename: &str) -> Result<(), std::io::Error> {
         if self.debug >= 1 {
-            eprintln!("Reading confusables from {}...", filename);
+            info!("Reading confusables from {}...", filename);
         }
-        let f = File::open(filename)?;
-        let f_buffer = BufReader::new(f);
+        let f_buffer = open_file(filename, Compression::Auto)?;
         for line in f_buffer.lines() {
-            if let Ok(line) = line {
-                if !line.is_empty() {
-                    let fields: Vec<&str> = line.split("\t").collect();
-                    let weight = if fields.len() >= 2 {
-                        fields
-                            .get(1)
-                            .unwrap()
-                            .parse::<f64>()
-                            .expect("score should be a float")
-                    } else {
-                        1.0
-                    };
-                    self.add_to_confusables(fields.get(0).unwrap(), weight)?;
+            let line = line?;
+            if !line.is_empty() {
+                let fields: Vec<&str> = line.split("\t").collect();
+                let weight = if fields.len() >= 2 {
+                    fields
+                        .get(1)
+                        .unwrap()
+                        .parse::<f64>()
+                        .expect("score should be a float")
+                } else {
+                    1.0
+                };
+                let casefold = match fields.get(2).map(|s| s.trim()) {
+                    None | Some("") => false,
+                    Some("casefold") | Some("i") => true,
+                    Some(flag) => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!(
+                                "Unknown flag for confusable {}: {} (expected 'casefold')",
+                                fields.first().unwrap(),
+                                flag
+                            ),
+                        ))
+                    }
+                };
+                if casefold {
+                    self.add_to_confusables_casefold(fields.first().unwrap(), weight)?;
+                } else {
+                    self.add_to_confusables(fields.first().unwrap(), weight)?;
                 }
             }
         }
         if self.debug >= 1 {
-            eprintln!(" -- Read {} confusables", self.confusables.len());
+            info!(" -- Read {} confusables", self.confusables.len());
         }
         Ok(())
     }
@@ -451,36 +1255,226 @@ impl VariantModel {
         Ok(())
     }
 
-    /// Add a (weighted) variant to the model, referring to a reference that already exists in
-    /// the model.
-    /// Variants will be added
-    /// to the lexicon automatically when necessary. Set VocabType::TRANSPARENT
-    /// if you want variants to only be used as an intermediate towards items that
-    /// have already been added previously through a more authoritative lexicon.
-    pub fn add_variant(
+    /// Add a confusable that matches case-insensitively: both the confusable and the edit script
+    /// between the input and a candidate are lowercased before testing for a match.
+    pub fn add_to_confusables_casefold(
         &mut self,
-        ref_id: VocabId,
-        variant: &str,
-        score: f64,
-        freq: Option<u32>,
-        params: &VocabParams,
-    ) -> bool {
-        let variantid = self.add_to_vocabulary(variant, freq, &params);
-        self.add_variant_by_id(ref_id, variantid, score)
+        editscript: &str,
+        weight: f64,
+    ) -> Result<(), std::io::Error> {
+        let confusable = Confusable::new_with_casefold(editscript, weight, true)?;
+        self.confusables.push(confusable);
+        Ok(())
     }
 
-    /// Add a (weighted) variant to the model, referring to a reference that already exists in
-    /// the model.
-    /// Variants will be added
-    /// to the lexicon automatically when necessary. Set VocabType::TRANSPARENT
-    /// if you want variants to only be used as an intermediate towards items that
-    /// have already been added previously through a more authoritative lexicon.
-    pub fn add_variant_by_id(&mut self, ref_id: VocabId, variantid: VocabId, score: f64) -> bool {
-        if variantid != ref_id {
-            //link reference to variant
-            if let Some(vocabvalue) = self.decoder.get_mut(ref_id as usize) {
-                let variantref = VariantReference::ReferenceFor((variantid, score));
-                if vocabvalue.variants.is_none() {
+    ///Read an affix list from a TSV file
+    ///Contains affixes in the first column, in which a hyphen marks where the stem attaches
+    ///(`-jes` for a suffix, `ge-` for a prefix), and optionally a weight in the second column (default 1.0).
+    pub fn read_affixlist(&mut self, filename: &str) -> Result<(), std::io::Error> {
+        if self.debug >= 1 {
+            info!("Reading affixes from {}...", filename);
+        }
+        let f_buffer = open_file(filename, Compression::Auto)?;
+        for (linenr, line) in f_buffer.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let weight = match fields.get(1) {
+                Some(weight) => weight.parse::<f64>().map_err(|_| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "Invalid weight for affix on line {}: {}",
+                            linenr + 1,
+                            weight
+                        ),
+                    )
+                })?,
+                None => 1.0,
+            };
+            self.add_to_affixes(fields[0], weight)?;
+        }
+        if self.debug >= 1 {
+            info!(" -- Read {} affixes", self.affixes.len());
+        }
+        Ok(())
+    }
+
+    /// Add an affix rule: a suffix (e.g. `-jes`) or prefix (e.g. `ge-`) that may be stripped from the
+    /// input, so that the stem that remains is matched against the lexicon as well. The scores of
+    /// variants found for the stem are multiplied by the weight, which must be in the range `(0.0, 1.0]`.
+    /// Unlike confusables, affix rules only apply at the beginning or end of the input.
+    pub fn add_to_affixes(&mut self, affix: &str, weight: f64) -> Result<(), std::io::Error> {
+        self.affixes.push(AffixRule::new(affix, weight)?);
+        Ok(())
+    }
+
+    /// Derives confusables from (correct, variant) pairs, such as OCR errors aligned with their
+    /// corrections. Each run of edits in the edit script from the variant to the correct form (e.g.
+    /// `-[y]+[i]`) is counted, the edits that occur at least `min_count` times are returned as confusables
+    /// with a weight proportional to their frequency, up to [`DERIVED_CONFUSABLE_MAX_WEIGHT`] for
+    /// the most frequent one. The confusables are sorted by decreasing frequency, they can be added
+    /// to a model with [`Self::add_to_confusables()`].
+    pub fn derive_confusables<'b>(
+        pairs: impl Iterator<Item = (&'b str, &'b str)>,
+        min_count: usize,
+    ) -> Vec<Confusable> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (correct, variant) in pairs {
+            let editscript = shortest_edit_script(variant, correct, false, false, false);
+            let mut edit = String::new();
+            for instruction in editscript.instructions.iter() {
+                if let EditInstruction::Identity(_) = instruction {
+                    if !edit.is_empty() {
+                        *counts.entry(std::mem::take(&mut edit)).or_insert(0) += 1;
+                    }
+                } else {
+                    edit += &instruction.to_string();
+                }
+            }
+            if !edit.is_empty() {
+                *counts.entry(edit).or_insert(0) += 1;
+            }
+        }
+        let mut counts: Vec<(String, usize)> = counts
+            .into_iter()
+            .filter(|(_, count)| *count >= min_count.max(1))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let maxcount = counts.first().map(|(_, count)| *count).unwrap_or(1) as f64;
+        counts
+            .into_iter()
+            .filter_map(|(edit, count)| {
+                let weight = 1.0 + (DERIVED_CONFUSABLE_MAX_WEIGHT - 1.0) * count as f64 / maxcount;
+                //edits of texts that contain characters with a special meaning in the edit script language
+                //would not be parsed back the same way, these are skipped
+                Confusable::new(&edit, weight)
+                    .ok()
+                    .filter(|confusable| confusable.editscript.to_string() == edit)
+            })
+            .collect()
+    }
+
+    /// Adds confusables for substitutions between physically adjacent keys on the specified
+    /// keyboard layout (in both directions), all with the same weight. Only covers
+    /// lowercase letters.
+    pub fn add_keyboard_confusables(
+        &mut self,
+        layout: KeyboardLayout,
+        weight: f64,
+    ) -> Result<(), std::io::Error> {
+        for (a, b) in layout.adjacent_pairs() {
+            self.add_to_confusables(&format!("-[{}]+[{}]", a, b), weight)?;
+            self.add_to_confusables(&format!("-[{}]+[{}]", b, a), weight)?;
+        }
+        if self.debug >= 1 {
+            info!(
+                " -- Added keyboard confusables, {} confusables in total",
+                self.confusables.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Load substitution costs from a TSV file with two characters (as defined in the alphabet) in
+    /// the first two columns and the cost of substituting one for the other in the third column
+    pub fn read_substitution_costs(&mut self, filename: &str) -> Result<(), std::io::Error> {
+        if self.debug >= 1 {
+            info!("Reading substitution costs from {}...", filename);
+        }
+        let f_buffer = open_file(filename, Compression::Auto)?;
+        for line in f_buffer.lines() {
+            let line = line?;
+            if !line.is_empty() {
+                let fields: Vec<&str> = line.split('\t').collect();
+                if fields.len() != 3 {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Expected three columns in substitution costs: {}", line),
+                    ));
+                }
+                let cost = fields[2].parse::<f64>().map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid substitution cost ({}): {}", fields[2], e),
+                    )
+                })?;
+                self.add_substitution_cost(fields[0], fields[1], cost)?;
+            }
+        }
+        if self.debug >= 1 {
+            info!(
+                " -- Read {} substitution costs",
+                self.substitution_costs.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Set the cost of substituting one character for another (in both directions) in the edit
+    /// distance computation, where a cost below 1.0 makes the substitution cheaper than an arbitrary
+    /// one. Both characters must correspond to a single character in the alphabet (which may consist
+    /// of multiple unicode points, like `ij`).
+    pub fn add_substitution_cost(
+        &mut self,
+        a: &str,
+        b: &str,
+        cost: f64,
+    ) -> Result<(), std::io::Error> {
+        let a = self.alphabet_index(a)?;
+        let b = self.alphabet_index(b)?;
+        self.substitution_costs.insert((a, b), cost);
+        self.substitution_costs.insert((b, a), cost);
+        Ok(())
+    }
+
+    /// Returns the index of a single character in the alphabet
+    fn alphabet_index(&self, c: &str) -> Result<CharIndexType, std::io::Error> {
+        let c = self.unicode_normalization.normalize(c);
+        let normstring = self
+            .encodable_text(&c)
+            .normalize_to_alphabet(&self.alphabet_matcher);
+        match normstring.as_slice() {
+            [index] if (*index as usize) < self.alphabet.len() => Ok(*index),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Not a single character of the alphabet: {}", c),
+            )),
+        }
+    }
+
+    /// Add a (weighted) variant to the model, referring to a reference that already exists in
+    /// the model.
+    /// Variants will be added
+    /// to the lexicon automatically when necessary. Set VocabType::TRANSPARENT
+    /// if you want variants to only be used as an intermediate towards items that
+    /// have already been added previously through a more authoritative lexicon.
+    pub fn add_variant(
+        &mut self,
+        ref_id: VocabId,
+        variant: &str,
+        score: f64,
+        freq: Option<u32>,
+        params: &VocabParams,
+    ) -> bool {
+        let variantid = self.add_to_vocabulary(variant, freq, &params);
+        self.add_variant_by_id(ref_id, variantid, score)
+    }
+
+    /// Add a (weighted) variant to the model, referring to a reference that already exists in
+    /// the model.
+    /// Variants will be added
+    /// to the lexicon automatically when necessary. Set VocabType::TRANSPARENT
+    /// if you want variants to only be used as an intermediate towards items that
+    /// have already been added previously through a more authoritative lexicon.
+    pub fn add_variant_by_id(&mut self, ref_id: VocabId, variantid: VocabId, score: f64) -> bool {
+        if variantid != ref_id {
+            //link reference to variant
+            if let Some(vocabvalue) = self.decoder.get_mut(ref_id as usize) {
+                let variantref = VariantReference::ReferenceFor((variantid, score));
+                if vocabvalue.variants.is_none() {
                     vocabvalue.variants = Some(vec![variantref]);
                 } else if let Some(variantrefs) = vocabvalue.variants.as_mut() {
                     //only add if it doesn't already exists (only first mention counts, regardless of score)
@@ -520,136 +1514,534 @@ impl VariantModel {
         &mut self,
         filename: &str,
         params: &VocabParams,
+    ) -> Result<(), std::io::Error> {
+        let reader = open_file(filename, params.compression)?;
+        self.read_vocabulary_from_reader(filename, reader, params)
+    }
+
+    ///Load vocabulary like [`read_vocabulary()`], but read the TSV data from a reader (e.g. an
+    ///in-memory buffer) rather than from file. The name is registered as the name of the lexicon.
+    ///No decompression takes place.
+    pub fn read_vocabulary_from_reader(
+        &mut self,
+        name: &str,
+        reader: impl BufRead,
+        params: &VocabParams,
     ) -> Result<(), std::io::Error> {
         if self.debug >= 1 {
-            eprintln!(
+            info!(
                 "Reading vocabulary #{} from {} ({:?})...",
                 self.lexicons.len() + 1,
-                filename,
+                name,
                 params.vocab_type
             );
         }
-        let beginlen = self.decoder.len();
-        let f = File::open(filename)?;
-        let f_buffer = BufReader::new(f);
         let mut params = params.clone();
         params.index = self.lexicons.len() as u8;
-        for line in f_buffer.lines() {
-            if let Ok(line) = line {
-                if !line.is_empty() {
-                    let fields: Vec<&str> = line.split("\t").collect();
-                    let text = fields
-                        .get(params.text_column as usize)
-                        .expect("Expected text column not found");
-                    let frequency = if let Some(freq_column) = params.freq_column {
-                        if params.vocab_type.check(VocabType::INDEXED) {
-                            self.have_freq = true;
+        self.read_vocabulary_entries(reader, &params)?;
+        self.lexicons.push(name.to_string());
+        self.lexicon_weights.push(params.weight);
+        Ok(())
+    }
+
+    ///Adds a vocabulary (a lexicon or corpus-derived lexicon) from entries and their frequencies
+    ///held in memory, rather than reading it from a TSV file. Behaves like [`read_vocabulary()`],
+    ///the name is registered as the name of the lexicon.
+    pub fn add_vocabulary_from_iter<S: AsRef<str>>(
+        &mut self,
+        name: &str,
+        entries: impl IntoIterator<Item = (S, u32)>,
+        params: &VocabParams,
+    ) {
+        if self.debug >= 1 {
+            info!(
+                "Adding vocabulary #{} ({}, {:?})...",
+                self.lexicons.len() + 1,
+                name,
+                params.vocab_type
+            );
+        }
+        let mut params = params.clone();
+        params.index = self.lexicons.len() as u8;
+        if params.freq_column.is_some() && params.vocab_type.check(VocabType::INDEXED) {
+            self.have_freq = true;
+        }
+        for (text, frequency) in entries {
+            self.add_to_vocabulary(text.as_ref(), Some(frequency), &params);
+        }
+        self.lexicons.push(name.to_string());
+        self.lexicon_weights.push(params.weight);
+    }
+
+    ///Reads all entries from a vocabulary file, the lexicon index must already be set in the
+    ///parameters. Auxiliary function used by [`read_vocabulary()`] and [`reload_lexicon()`].
+    fn read_vocabulary_entries(
+        &mut self,
+        reader: impl BufRead,
+        params: &VocabParams,
+    ) -> Result<(), std::io::Error> {
+        let beginlen = self.decoder.len();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.is_empty() {
+                let fields = params.split_fields(&line);
+                let text = fields
+                    .get(params.text_column as usize)
+                    .expect("Expected text column not found");
+                let frequency = if let Some(freq_column) = params.freq_column {
+                    if params.vocab_type.check(VocabType::INDEXED) {
+                        self.have_freq = true;
+                    }
+                    fields
+                        .get(freq_column as usize)
+                        .map_or("1", |freq| freq.as_ref())
+                        .parse::<u32>()
+                        .expect("frequency should be a valid integer")
+                } else {
+                    1
+                };
+                let vocab_id = self.add_to_vocabulary(text, Some(frequency), params);
+                if let Some(metadata_column) = params.metadata_column {
+                    if let Some(metadata) = fields.get(metadata_column as usize) {
+                        if !metadata.is_empty() {
+                            //a later lexicon providing metadata for the same entry takes precedence
+                            let item = self
+                                .decoder
+                                .get_mut(vocab_id as usize)
+                                .expect("vocab id must exist");
+                            item.metadata = Some(metadata.to_string());
                         }
-                        fields
-                            .get(freq_column as usize)
-                            .unwrap_or(&"1")
-                            .parse::<u32>()
-                            .expect("frequency should be a valid integer")
-                    } else {
-                        1
-                    };
-                    self.add_to_vocabulary(text, Some(frequency), &params);
+                    }
+                }
+                if let Some(lemma_column) = params.lemma_column {
+                    if let Some(lemma) = fields.get(lemma_column as usize) {
+                        if !lemma.is_empty() {
+                            //a later lexicon providing a lemma for the same entry takes precedence
+                            let item = self
+                                .decoder
+                                .get_mut(vocab_id as usize)
+                                .expect("vocab id must exist");
+                            item.lemma = Some(lemma.to_string());
+                        }
+                    }
                 }
             }
         }
         if self.debug >= 1 {
-            eprintln!(
+            info!(
                 " - Read vocabulary of size {}",
                 self.decoder.len() - beginlen
             );
         }
-        self.lexicons.push(filename.to_string());
         Ok(())
     }
 
+    /// Reloads a lexicon that was previously loaded with [`read_vocabulary()`] at the specified lexicon index.
+    /// All entries that were (only) provided by the old lexicon are removed and the file is read afresh into the
+    /// same index slot. If the model was already built, only the affected anagram buckets in the
+    /// index are updated, so there is no need to call [`build()`] again. Note that this
+    /// incremental update does not cover the language model, call [`build()`] if the lexicon
+    /// contributes to it.
+    ///
+    /// Entries that occur in multiple lexicons retain their (possibly aggregated) frequency.
+    pub fn reload_lexicon(
+        &mut self,
+        index: u8,
+        filename: &str,
+        params: &VocabParams,
+    ) -> Result<(), std::io::Error> {
+        self.check_lexicon_index(index)?;
+        if self.debug >= 1 {
+            info!(
+                "Reloading vocabulary #{} from {} ({:?})...",
+                index + 1,
+                filename,
+                params.vocab_type
+            );
+        }
+        let built = !self.index.is_empty();
+        let removed = self.purge_lexicon(index);
+        let beginlen = self.decoder.len();
+        let mut params = params.clone();
+        params.index = index;
+        self.read_vocabulary_entries(open_file(filename, params.compression)?, &params)?;
+        self.lexicons[index as usize] = filename.to_string();
+        if let Some(weight) = self.lexicon_weights.get_mut(index as usize) {
+            *weight = params.weight;
+        }
+
+        if built {
+            //incrementally update the affected anagram buckets
+            for vocab_id in removed {
+                if !self.decoder[vocab_id as usize]
+                    .vocabtype
+                    .check(VocabType::INDEXED)
+                {
+                    //not (or no longer) indexed, remove it from the index
+                    self.remove_from_index(vocab_id);
+                }
+            }
+            for vocab_id in beginlen..self.decoder.len() {
+                if self.decoder[vocab_id].vocabtype.check(VocabType::INDEXED) {
+                    self.add_to_index(vocab_id as VocabId);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds an item to the vocabulary of a model that has already been built, updating the index
+    /// incrementally rather than requiring a full rebuild. The anagram index and the sorted
+    /// secondary index are updated in place. Like [`Self::add_to_vocabulary()`], this returns the
+    /// vocabulary ID of the item. Note that the language model is not updated, so call `build()`
+    /// instead if the item should contribute to it. If the model has not been built yet, this is
+    /// equivalent to [`Self::add_to_vocabulary()`].
+    pub fn insert_built(
+        &mut self,
+        text: &str,
+        frequency: Option<u32>,
+        params: &VocabParams,
+    ) -> VocabId {
+        let built = !self.index.is_empty();
+        let vocab_id = self.add_to_vocabulary(text, frequency, params);
+        if built
+            && self
+                .decoder
+                .get(vocab_id as usize)
+                .expect("vocabitem must exist")
+                .vocabtype
+                .check(VocabType::INDEXED)
+        {
+            self.add_to_index(vocab_id);
+        }
+        vocab_id
+    }
+
+    /// Disables the specified lexicon: from now on, variants that occur *only* in disabled lexicons are
+    /// no longer returned. The model itself is left untouched, so this is cheap and can be undone
+    /// using [`Self::enable_lexicon()`], but the disabled entries are still gathered and filtered
+    /// out for every query. Use [`Self::remove_lexicon()`] to drop a lexicon permanently.
+    /// The disabled state is not saved along with the model.
+    pub fn disable_lexicon(&mut self, index: u8) -> Result<(), std::io::Error> {
+        self.check_lexicon_index(index)?;
+        self.disabled_lexicons |= 1 << index;
+        Ok(())
+    }
+
+    /// Enables a lexicon that was previously disabled using [`Self::disable_lexicon()`]
+    pub fn enable_lexicon(&mut self, index: u8) -> Result<(), std::io::Error> {
+        self.check_lexicon_index(index)?;
+        self.disabled_lexicons &= !(1 << index);
+        Ok(())
+    }
+
+    /// Returns whether the specified lexicon is enabled (i.e. not disabled using [`Self::disable_lexicon()`])
+    pub fn lexicon_enabled(&self, index: u8) -> bool {
+        self.disabled_lexicons & (1 << index) == 0
+    }
+
+    /// Returns the index of the lexicon with the specified name, the name may either be the full
+    /// name (e.g. the path) it was loaded with, or just its file name
+    pub fn lexicon_index(&self, name: &str) -> Option<u8> {
+        self.lexicons
+            .iter()
+            .position(|lexicon| {
+                lexicon == name
+                    || std::path::Path::new(lexicon).file_name() == Some(std::ffi::OsStr::new(name))
+            })
+            .map(|index| index as u8)
+    }
+
+    /// Returns the weight of the lexicons a vocabulary item occurs in (see [`VocabParams::weight`]),
+    /// this is the maximum weight over all enabled lexicons that hold it, or 1.0 if it occurs in none
+    pub fn lexicon_weight(&self, vocabitem: &VocabValue) -> f64 {
+        let lexindex = vocabitem.lexindex & !self.disabled_lexicons;
+        self.lexicon_weights
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| lexindex & (1 << index) != 0)
+            .map(|(_, weight)| *weight)
+            .reduce(f64::max)
+            .unwrap_or(1.0)
+    }
+
+    /// Returns whether a vocabulary item occurs only in disabled lexicons and should not be returned as a variant
+    fn in_disabled_lexicons_only(&self, vocabitem: &VocabValue) -> bool {
+        self.disabled_lexicons != 0
+            && vocabitem.lexindex != 0
+            && vocabitem.lexindex & !self.disabled_lexicons == 0
+    }
+
+    /// Removes all entries of the specified lexicon from the model. Entries that occur in no other
+    /// lexicon are removed from the index, which is updated incrementally (only the affected anagram
+    /// buckets are touched) if the model was already built. This requires a pass over the entire
+    /// vocabulary and clears the neighbourhood cache, so it is more expensive than
+    /// [`Self::disable_lexicon()`], but queries no longer pay for the removed entries. As with
+    /// [`Self::reload_lexicon()`], the language model is not updated and entries that occur in
+    /// multiple lexicons retain their (possibly aggregated) frequency.
+    ///
+    /// Lexicon indices are fixed, so the removed lexicon retains its slot (and name) in `lexicons`
+    /// and the indices of other lexicons remain valid. The slot can be refilled using `reload_lexicon()`.
+    pub fn remove_lexicon(&mut self, index: u8) -> Result<(), std::io::Error> {
+        self.check_lexicon_index(index)?;
+        if self.debug >= 1 {
+            info!("Removing vocabulary #{}...", index + 1);
+        }
+        let built = !self.index.is_empty();
+        let removed = self.purge_lexicon(index);
+        if built {
+            for vocab_id in removed {
+                self.remove_from_index(vocab_id);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_lexicon_index(&self, index: u8) -> Result<(), std::io::Error> {
+        if index as usize >= self.lexicons.len() {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Lexicon index {} does not exist", index),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Unsets the lexicon bit for all entries of the specified lexicon. Entries that are no longer
+    /// part of any lexicon as a result are orphaned (their vocabulary type is cleared), their
+    /// vocabulary IDs are returned. This does not update the index.
+    fn purge_lexicon(&mut self, index: u8) -> Vec<VocabId> {
+        let mut orphans = Vec::new();
+        for (vocab_id, vocabitem) in self.decoder.iter_mut().enumerate() {
+            if vocabitem.in_lexicon(index) {
+                vocabitem.lexindex ^= 1 << index;
+                if vocabitem.lexindex == 0 {
+                    vocabitem.vocabtype = VocabType::NONE;
+                    vocabitem.metadata = None;
+                    vocabitem.lemma = None;
+                    orphans.push(vocab_id as VocabId);
+                }
+            }
+        }
+        if self.debug >= 2 {
+            debug!(
+                " -- Purged lexicon #{}, {} entries orphaned",
+                index + 1,
+                orphans.len()
+            );
+        }
+        orphans
+    }
+
+    /// (Re)builds the phonetic index for all indexed vocabulary items
+    fn build_phonetic_index(&mut self) {
+        self.phonetic_index.clear();
+        for vocab_id in 0..self.decoder.len() as VocabId {
+            if self.decoder[vocab_id as usize]
+                .vocabtype
+                .check(VocabType::INDEXED)
+            {
+                self.add_to_phonetic_index(vocab_id);
+            }
+        }
+    }
+
+    /// (Re)builds the n-gram index for all indexed vocabulary items
+    fn build_ngram_index(&mut self) {
+        self.ngram_index.clear();
+        for (vocab_id, value) in self.decoder.iter().enumerate() {
+            if value.vocabtype.check(VocabType::INDEXED) {
+                self.ngram_index.insert(vocab_id as VocabId, &value.norm);
+            }
+        }
+    }
+
+    /// Adds a single vocabulary item to the phonetic index
+    fn add_to_phonetic_index(&mut self, vocab_id: VocabId) {
+        for code in phonetic_codes(&self.decoder[vocab_id as usize].text) {
+            let instances = self.phonetic_index.entry(code).or_default();
+            if !instances.contains(&vocab_id) {
+                instances.push(vocab_id);
+            }
+        }
+    }
+
+    /// Returns all vocabulary items that share a phonetic code with the input.
+    /// Always empty if no phonetic index was built.
+    pub fn find_phonetic_matches(&self, input: &str) -> HashSet<VocabId> {
+        let mut matches = HashSet::new();
+        if !self.phonetic_index.is_empty() {
+            for code in phonetic_codes(input) {
+                if let Some(instances) = self.phonetic_index.get(&code) {
+                    matches.extend(instances.iter().copied());
+                }
+            }
+        }
+        matches
+    }
+
+    /// Adds a single vocabulary item to an already built index,
+    /// the secondary index is kept sorted.
+    pub(crate) fn add_to_index(&mut self, vocab_id: VocabId) {
+        let anahash = self
+            .encodable_text(
+                &self
+                    .decoder
+                    .get(vocab_id as usize)
+                    .expect("vocabitem must exist")
+                    .text,
+            )
+            .anahash(&self.alphabet_matcher);
+        let isnew = !self.contains_key(&anahash) && self.insertions;
+        let node = self.get_or_create_index(&anahash);
+        if !node.instances.contains(&vocab_id) {
+            node.instances.push(vocab_id);
+        }
+        if isnew {
+            let charcount = node.charcount;
+            let (key, _) = self
+                .index
+                .get_key_value(&anahash)
+                .expect("key after insert");
+            let key = Arc::clone(key);
+            let keys = self.sortedindex.entry(charcount).or_default();
+            if let Err(pos) = keys.binary_search(&key) {
+                keys.insert(pos, key);
+            }
+        }
+        if self.weights.phonetic > 0.0 {
+            self.add_to_phonetic_index(vocab_id);
+        }
+        if self.ngram_index_enabled {
+            self.ngram_index
+                .insert(vocab_id, &self.decoder[vocab_id as usize].norm);
+        }
+    }
+
+    /// Removes a single vocabulary item from an already built index.
+    /// Anagram buckets that become empty are removed from the secondary index as well.
+    pub(crate) fn remove_from_index(&mut self, vocab_id: VocabId) {
+        if !self.ngram_index.is_empty() {
+            self.ngram_index
+                .remove(vocab_id, &self.decoder[vocab_id as usize].norm);
+        }
+        if !self.phonetic_index.is_empty() {
+            let text = &self.decoder[vocab_id as usize].text;
+            for code in phonetic_codes(text) {
+                if let Some(instances) = self.phonetic_index.get_mut(&code) {
+                    instances.retain(|x| *x != vocab_id);
+                    if instances.is_empty() {
+                        self.phonetic_index.remove(&code);
+                    }
+                }
+            }
+        }
+        let anahash = self
+            .encodable_text(
+                &self
+                    .decoder
+                    .get(vocab_id as usize)
+                    .expect("vocabitem must exist")
+                    .text,
+            )
+            .anahash(&self.alphabet_matcher);
+        if let Some(node) = self.index.get_mut(&anahash) {
+            node.instances.retain(|x| *x != vocab_id);
+            if node.instances.is_empty() {
+                let charcount = node.charcount;
+                self.index.remove(&anahash);
+                self.clear_neighbourhood_cache();
+                if let Some(keys) = self.sortedindex.get_mut(&charcount) {
+                    if let Ok(pos) = keys.binary_search_by(|key| key.as_ref().cmp(&anahash)) {
+                        keys.remove(pos);
+                    }
+                }
+            }
+        }
+    }
+
     pub fn read_contextrules(&mut self, filename: &str) -> Result<(), std::io::Error> {
         if self.debug >= 1 {
-            eprintln!("Reading context rules {}...", filename);
+            info!("Reading context rules {}...", filename);
         }
-        let f = File::open(filename)?;
-        let f_buffer = BufReader::new(f);
+        let f_buffer = open_file(filename, Compression::Auto)?;
         let mut linenr = 0;
         for line in f_buffer.lines() {
-            if let Ok(line) = line {
-                linenr += 1;
-                if !line.is_empty() && !line.starts_with('#') {
-                    let fields: Vec<&str> = line.split("\t").collect();
-                    if fields.len() < 2 {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!(
-                                "Expected at least two columns in context rules file {}, line {}",
-                                filename, linenr
-                            ),
-                        ));
-                    }
+            let line = line?;
+            linenr += 1;
+            if !line.is_empty() && !line.starts_with('#') {
+                let fields: Vec<&str> = line.split("\t").collect();
+                if fields.len() < 2 {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!(
+                            "Expected at least two columns in context rules file {}, line {}",
+                            filename, linenr
+                        ),
+                    ));
+                }
 
-                    let pattern: &str = fields.get(0).unwrap();
-                    if pattern.is_empty() {
-                        continue;
-                    }
+                let pattern: &str = fields.get(0).unwrap();
+                if pattern.is_empty() {
+                    continue;
+                }
 
-                    let score = fields.get(1).unwrap().parse::<f32>();
-                    if let Err(_) = score {
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("context rule score should be a floating point value above or below 1.0, got {} ({}, line {})", fields.get(1).unwrap(), filename,linenr)));
-                    }
-                    let score = score.unwrap();
-
-                    let tag: Vec<&str> = match fields.get(2) {
-                        Some(s) => s
-                            .split(";")
-                            .filter_map(|w| {
-                                let w = w.trim();
-                                if w.is_empty() {
-                                    None
-                                } else {
-                                    Some(w)
-                                }
-                            })
-                            .collect(),
-                        None => Vec::new(),
-                    };
+                let score = fields.get(1).unwrap().parse::<f32>();
+                if let Err(_) = score {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("context rule score should be a floating point value above or below 1.0, got {} ({}, line {})", fields.get(1).unwrap(), filename,linenr)));
+                }
+                let score = score.unwrap();
+
+                let tag: Vec<&str> = match fields.get(2) {
+                    Some(s) => s
+                        .split(";")
+                        .filter_map(|w| {
+                            let w = w.trim();
+                            if w.is_empty() {
+                                None
+                            } else {
+                                Some(w)
+                            }
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                };
 
-                    let mut tagoffset: Vec<&str> = match fields.get(3) {
-                        Some(s) => s
-                            .split(";")
-                            .filter_map(|w| {
-                                let w = w.trim();
-                                if w.is_empty() {
-                                    None
-                                } else {
-                                    Some(w)
-                                }
-                            })
-                            .collect(),
-                        None => Vec::new(),
-                    };
+                let mut tagoffset: Vec<&str> = match fields.get(3) {
+                    Some(s) => s
+                        .split(";")
+                        .filter_map(|w| {
+                            let w = w.trim();
+                            if w.is_empty() {
+                                None
+                            } else {
+                                Some(w)
+                            }
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                };
 
-                    if tag.len() == 1 && tagoffset.len() == 0 {
-                        tagoffset.push("0:");
-                    } else if tag.len() != tagoffset.len() {
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Multiple tags are specified for a context rule, expected the same number of tag offsets! (semicolon separated) ({}, line {})", filename, linenr)));
-                    }
+                if tag.len() == 1 && tagoffset.len() == 0 {
+                    tagoffset.push("0:");
+                } else if tag.len() != tagoffset.len() {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Multiple tags are specified for a context rule, expected the same number of tag offsets! (semicolon separated) ({}, line {})", filename, linenr)));
+                }
 
-                    if let Err(error) = self.add_contextrule(pattern, score, tag, tagoffset) {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!(
-                                "Error adding context rule: {} ({}, line {})",
-                                error, filename, linenr
-                            ),
-                        ));
-                    }
+                if let Err(error) = self.add_contextrule(pattern, score, tag, tagoffset) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!(
+                            "Error adding context rule: {} ({}, line {})",
+                            error, filename, linenr
+                        ),
+                    ));
                 }
             }
         }
         if self.debug >= 1 {
-            eprintln!(" -- Read {} context rules", self.context_rules.len());
+            info!(" -- Read {} context rules", self.context_rules.len());
         }
 
         Ok(())
@@ -763,17 +2155,65 @@ impl VariantModel {
         Ok(())
     }
 
+    ///Write all weighted variants held by the model (e.g. after [`Self::learn_variants()`]) to a TSV file.
+    ///Each line contains a canonical/reference form in the first column, and variants with score
+    ///(two columns) in the following columns. This is the format [`Self::read_variants()`] reads.
+    pub fn save_variants(&self, filename: &str) -> Result<(), std::io::Error> {
+        let f = File::create(filename)?;
+        let mut writer = BufWriter::new(f);
+        for vocabitem in self.decoder.iter() {
+            if let Some(variants) = &vocabitem.variants {
+                let mut first = true;
+                for variant in variants {
+                    if let VariantReference::ReferenceFor((vocab_id, score)) = variant {
+                        if first {
+                            write!(writer, "{}", vocabitem.text)?;
+                            first = false;
+                        }
+                        let variantitem = self
+                            .decoder
+                            .get(*vocab_id as usize)
+                            .expect("vocab id must exist");
+                        write!(writer, "\t{}\t{}", variantitem.text, score)?;
+                    }
+                }
+                if !first {
+                    writeln!(writer)?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
     ///Read a weighted variant list from a TSV file. Contains a canonical/reference form in the
     ///first column, and variants with score (two columns) in the following columns. May also
     ///contain frequency information (auto detected), in which case the first column has the
     ///canonical/reference form, the second column the frequency, and all further columns hold
     ///variants, their score and their frequency (three columns).
+    ///Each row may end with an extra column holding the name of a previously loaded lexicon prefixed
+    ///by `@` (e.g. `@lexicon.tsv`), the reference and variants of that row are then attributed to
+    ///that lexicon rather than to the variant list itself (see [`Self::lexicon_index()`]).
     ///Consumes much more memory than equally weighted variants.
     pub fn read_variants(
         &mut self,
         filename: &str,
         params: Option<&VocabParams>,
         transparent: bool,
+    ) -> Result<(), std::io::Error> {
+        let compression = params.map(|params| params.compression).unwrap_or_default();
+        let reader = open_file(filename, compression)?;
+        self.read_variants_from_reader(filename, reader, params, transparent)
+    }
+
+    ///Load a weighted variant list like [`read_variants()`], but read the TSV data from a reader
+    ///(e.g. an in-memory buffer) rather than from file. The name is registered as the name of the
+    ///lexicon and is used in error messages. No decompression takes place.
+    pub fn read_variants_from_reader(
+        &mut self,
+        name: &str,
+        reader: impl BufRead,
+        params: Option<&VocabParams>,
+        transparent: bool,
     ) -> Result<(), std::io::Error> {
         let params = if let Some(params) = params {
             let mut p = params.clone();
@@ -794,105 +2234,127 @@ impl VariantModel {
         };
 
         if self.debug >= 1 {
-            eprintln!("Reading variants from {}...", filename);
+            info!("Reading variants from {}...", name);
         }
         let mut count = 0;
         let mut has_freq = None;
-        let f = File::open(filename)?;
-        let f_buffer = BufReader::new(f);
-        for (linenr, line) in f_buffer.lines().enumerate() {
+        for (linenr, line) in reader.lines().enumerate() {
             let linenr = linenr + 1;
-            if let Ok(line) = line {
-                if !line.is_empty() {
-                    let fields: Vec<&str> = line.split("\t").collect();
-                    let reference = fields.get(0).expect(
-                        format!(
-                            "reference item (line {}, column 1, of {})",
-                            linenr, filename
+            let line = line?;
+            if !line.is_empty() {
+                let mut fields = params.split_fields(&line);
+                //an optional last column assigns the row to a specific lexicon
+                let row_params = if fields.len() > 1
+                    && fields.last().is_some_and(|field| field.starts_with('@'))
+                {
+                    let lexicon = fields.pop().expect("lexicon column");
+                    let index = self.lexicon_index(&lexicon[1..]).ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!(
+                                "Lexicon {} does not exist (line {}, last column, of {})",
+                                &lexicon[1..],
+                                linenr,
+                                name
+                            ),
                         )
-                        .as_str(),
-                    );
-                    let freq = if has_freq.is_none() {
-                        //autodetect whether we have frequency information or not
-                        if (fields.len() - 2) % 3 == 0 {
-                            let freq = fields.get(1).expect("second field");
-                            match freq.parse::<u32>() {
-                                Ok(freq) => {
-                                    has_freq = Some(true);
-                                    Some(freq)
-                                }
-                                _ => None,
+                    })?;
+                    let mut p = params.clone();
+                    p.index = index;
+                    let mut tp = transparent_params.clone();
+                    tp.index = index;
+                    Some((p, tp))
+                } else {
+                    None
+                };
+                let (params, transparent_params) = match row_params.as_ref() {
+                    Some((p, tp)) => (p, tp),
+                    None => (&params, &transparent_params),
+                };
+                let reference = fields.get(0).expect(
+                    format!("reference item (line {}, column 1, of {})", linenr, name).as_str(),
+                );
+                let freq = if has_freq.is_none() {
+                    //autodetect whether we have frequency information or not
+                    if (fields.len() - 2) % 3 == 0 {
+                        let freq = fields.get(1).expect("second field");
+                        match freq.parse::<u32>() {
+                            Ok(freq) => {
+                                has_freq = Some(true);
+                                Some(freq)
                             }
-                        } else {
-                            //number of columns not consistent with holding frequency information
-                            has_freq = Some(false);
-                            None
+                            _ => None,
                         }
-                    } else if has_freq == Some(true) {
-                        let freq = fields.get(1).expect("score of reference item");
-                        Some(
-                            freq.parse::<u32>().expect(
-                                format!(
-                                    "Frequency must be an integer (line {}, column 2, of {})",
-                                    linenr, filename
-                                )
-                                .as_str(),
-                            ),
-                        )
                     } else {
+                        //number of columns not consistent with holding frequency information
+                        has_freq = Some(false);
                         None
-                    };
-                    let ref_id = self.add_to_vocabulary(reference, freq, &params);
-                    let mut iter = fields.iter();
-
-                    if has_freq == Some(true) {
-                        iter.next();
-                        iter.next();
-                        while let (Some(variant), Some(score), Some(freq)) =
-                            (iter.next(), iter.next(), iter.next())
-                        {
-                            let score = score.parse::<f64>().expect(format!("Variant scores must be a floating point value (line {} of {}, got {} instead), also parsing frequency", linenr, filename, score).as_str());
-                            let freq = freq.parse::<u32>().expect(format!("Variant frequency must be an integer (line {} of {}), got {} instead", linenr, filename, freq).as_str());
-                            if self.add_variant(
-                                ref_id,
-                                variant,
-                                score,
-                                Some(freq),
-                                if transparent {
-                                    &transparent_params
-                                } else {
-                                    &params
-                                },
-                            ) {
-                                count += 1;
-                            }
+                    }
+                } else if has_freq == Some(true) {
+                    let freq = fields.get(1).expect("score of reference item");
+                    Some(
+                        freq.parse::<u32>().expect(
+                            format!(
+                                "Frequency must be an integer (line {}, column 2, of {})",
+                                linenr, name
+                            )
+                            .as_str(),
+                        ),
+                    )
+                } else {
+                    None
+                };
+                let ref_id = self.add_to_vocabulary(reference, freq, params);
+                let mut iter = fields.iter();
+
+                if has_freq == Some(true) {
+                    iter.next();
+                    iter.next();
+                    while let (Some(variant), Some(score), Some(freq)) =
+                        (iter.next(), iter.next(), iter.next())
+                    {
+                        let score = score.parse::<f64>().expect(format!("Variant scores must be a floating point value (line {} of {}, got {} instead), also parsing frequency", linenr, name, score).as_str());
+                        let freq = freq.parse::<u32>().expect(format!("Variant frequency must be an integer (line {} of {}), got {} instead", linenr, name, freq).as_str());
+                        if self.add_variant(
+                            ref_id,
+                            variant,
+                            score,
+                            Some(freq),
+                            if transparent {
+                                transparent_params
+                            } else {
+                                params
+                            },
+                        ) {
+                            count += 1;
                         }
-                    } else {
-                        iter.next();
-                        while let (Some(variant), Some(score)) = (iter.next(), iter.next()) {
-                            let score = score.parse::<f64>().expect(format!("Variant scores must be a floating point value (line {} of {}, got {}), no frequency information", linenr, filename, score).as_str());
-                            if self.add_variant(
-                                ref_id,
-                                variant,
-                                score,
-                                None,
-                                if transparent {
-                                    &transparent_params
-                                } else {
-                                    &params
-                                },
-                            ) {
-                                count += 1;
-                            }
+                    }
+                } else {
+                    iter.next();
+                    while let (Some(variant), Some(score)) = (iter.next(), iter.next()) {
+                        let score = score.parse::<f64>().expect(format!("Variant scores must be a floating point value (line {} of {}, got {}), no frequency information", linenr, name, score).as_str());
+                        if self.add_variant(
+                            ref_id,
+                            variant,
+                            score,
+                            None,
+                            if transparent {
+                                transparent_params
+                            } else {
+                                params
+                            },
+                        ) {
+                            count += 1;
                         }
                     }
                 }
             }
         }
         if self.debug >= 1 {
-            eprintln!(" - Read weighted variants list, added {} references", count);
+            info!(" - Read weighted variants list, added {} references", count);
         }
-        self.lexicons.push(filename.to_string());
+        self.lexicons.push(name.to_string());
+        self.lexicon_weights.push(params.weight);
         Ok(())
     }
 
@@ -903,9 +2365,11 @@ impl VariantModel {
         frequency: Option<u32>,
         params: &VocabParams,
     ) -> VocabId {
+        let text = self.unicode_normalization.normalize(text);
+        let text: &str = &text;
         let frequency = frequency.unwrap_or(1);
         if self.debug >= 2 {
-            eprintln!(" -- Adding to vocabulary: {}  ({})", text, frequency);
+            debug!(" -- Adding to vocabulary: {}  ({})", text, frequency);
         }
         if let Some(vocab_id) = self.encoder.get(text) {
             let item = self.decoder.get_mut(*vocab_id as usize).expect(&format!(
@@ -932,35 +2396,66 @@ impl VariantModel {
             }
             if vocab_id == &BOS || vocab_id == &EOS || vocab_id == &UNK {
                 item.vocabtype = VocabType::LM; //by definition
+            } else if item.lexindex == 0 && item.vocabtype == VocabType::NONE {
+                //item was purged from all lexicons earlier, revive it
+                item.vocabtype = params.vocab_type;
+                item.frequency = frequency;
             } else if item.vocabtype.check(VocabType::TRANSPARENT)
                 && !params.vocab_type.check(VocabType::TRANSPARENT)
             {
                 //we can lose the transparency flag if a later lexicon doesn't provide it
                 item.vocabtype ^= VocabType::TRANSPARENT;
             }
+            if *vocab_id > UNK {
+                //an entry may occur both in a lexicon and in a language model (or a corpus), it is then used for both
+                if params.vocab_type.check(VocabType::INDEXED)
+                    && !item.vocabtype.check(VocabType::INDEXED)
+                {
+                    item.vocabtype |=
+                        params.vocab_type & (VocabType::INDEXED | VocabType::TRANSPARENT);
+                }
+                if params.vocab_type.check(VocabType::LM) {
+                    item.vocabtype |= VocabType::LM;
+                }
+            }
+            if params.vocab_type.check(VocabType::PHRASE)
+                && !item.vocabtype.check(VocabType::PHRASE)
+            {
+                //a later lexicon may mark an existing entry as an atomic phrase
+                item.vocabtype |= VocabType::PHRASE;
+                item.tokencount = 1;
+            }
             item.lexindex |= 1 << params.index;
             if self.debug >= 3 {
-                eprintln!(
+                trace!(
                     "    (updated) freq={}, lexindex+={}",
-                    item.frequency, params.index
+                    item.frequency,
+                    params.index
                 );
             }
             *vocab_id
         } else {
             //item is new
+            let norm = self.normalize(text);
             self.encoder
                 .insert(text.to_string(), self.decoder.len() as u64);
             self.decoder.push(VocabValue {
                 text: text.to_string(),
-                norm: text.normalize_to_alphabet(&self.alphabet),
+                norm,
                 frequency: frequency,
-                tokencount: text.chars().filter(|c| *c == ' ').count() as u8 + 1,
+                tokencount: if params.vocab_type.check(VocabType::PHRASE) {
+                    1
+                } else {
+                    text.chars().filter(|c| *c == ' ').count() as u8 + 1
+                },
                 lexindex: 1 << params.index,
                 variants: None,
                 vocabtype: params.vocab_type,
+                metadata: None,
+                lemma: None,
             });
             if self.debug >= 3 {
-                eprintln!("    (new) lexindex={}", params.index);
+                trace!("    (new) lexindex={}", params.index);
             }
             self.decoder.len() as VocabId - 1
         }
@@ -969,63 +2464,416 @@ impl VariantModel {
     /// Find variants in the vocabulary for a given string (in its totality), returns a vector of vocabulary ID and score pairs
     /// Returns a vector of three-tuples (VocabId, distance_score, freq_score)
     /// The resulting vocabulary Ids can be resolved through `get_vocab()`
+    /// Inputs that are empty or only whitespace (after normalization) yield no variants.
+    /// If `params.transfer_case` is set, the casing of the input is applied to the variants, see [`Self::variant_text()`].
     pub fn find_variants(&self, input: &str, params: &SearchParameters) -> Vec<VariantResult> {
+        let prepared = self.prepare_query(input);
+        let mut results = self.find_variants_prepared(&prepared, params);
+        if params.return_edits && prepared.input != input {
+            //the offsets of the edits should refer to the original input rather than the normalized one
+            for result in results.iter_mut() {
+                result.edits = Some(self.compute_edits(input, result.vocab_id));
+            }
+        }
+        if params.transfer_case {
+            self.transfer_case(input, &mut results);
+        }
+        results
+    }
+
+    /// Applies the casing pattern of the input to the text of the results (see [`CasePattern`]),
+    /// the cased text is stored in [`VariantResult::cased`] if it differs from the text in the lexicon.
+    pub fn transfer_case(&self, input: &str, results: &mut [VariantResult]) {
+        let pattern = CasePattern::of(input);
+        for result in results.iter_mut() {
+            if let Some(vocabvalue) = self.decoder.get(result.vocab_id as usize) {
+                if let Cow::Owned(cased) = pattern.apply(&vocabvalue.text) {
+                    result.cased = Some(cased);
+                }
+            }
+        }
+    }
+
+    /// Returns the text of a variant: the text from the lexicon, or the cased text if the casing of
+    /// the input was transferred to it (see [`SearchParameters::transfer_case`])
+    pub fn variant_text<'a>(&'a self, result: &'a VariantResult) -> &'a str {
+        if let Some(cased) = &result.cased {
+            cased.as_str()
+        } else {
+            self.decoder
+                .get(result.vocab_id as usize)
+                .map(|vocabvalue| vocabvalue.text.as_str())
+                .expect("getting vocab by id")
+        }
+    }
+
+    /// Returns the best (top-ranked) variant for the input, or `None` if there is no variant that
+    /// meets the thresholds in the parameters (notably `score_threshold`). This is a convenience
+    /// method for autocorrect-style usage.
+    pub fn best_variant(&self, input: &str, params: &SearchParameters) -> Option<&VocabValue> {
+        self.find_variants(input, params)
+            .first()
+            .and_then(|result| self.get_vocab(result.vocab_id))
+    }
+
+    /// Find variants for multiple inputs at once, like [`Self::find_variants()`]. The inputs are processed
+    /// in parallel (unless `params.single_thread` is set) and the results are returned in the same order
+    /// as the inputs, each paired with the input it pertains to.
+    pub fn find_variants_batch<'a>(
+        &self,
+        inputs: &[&'a str],
+        params: &SearchParameters,
+    ) -> Vec<(&'a str, Vec<VariantResult>)> {
+        if let Some(pool) = self.outside_thread_pool() {
+            return pool.install(|| self.find_variants_batch(inputs, params));
+        }
+        if params.single_thread {
+            inputs
+                .iter()
+                .map(|input| (*input, self.find_variants(input, params)))
+                .collect()
+        } else {
+            inputs
+                .par_iter()
+                .map(|input| (*input, self.find_variants(input, params)))
+                .collect()
+        }
+    }
+
+    /// Preprocesses an input for lookup: applies unicode normalization and computes the normalized
+    /// string and anagram hash. Carriage returns are normalized to newlines. The result can be passed
+    /// (repeatedly) to [`Self::find_variants_prepared()`].
+    pub fn prepare_query(&self, input: &str) -> PreparedQuery {
+        let mut input = self.unicode_normalization.normalize(input).into_owned();
+        if input.contains('\r') {
+            //normalize windows (and old mac) linebreaks to unix linebreaks
+            input = input.replace("\r\n", "\n").replace('\r', "\n");
+        }
+        let folded = self.encodable_text(&input);
+        let normstring =
+            folded.normalize_to_alphabet_with(&self.alphabet_matcher, self.distinct_unknowns);
+        let anahash = folded.anahash(&self.alphabet_matcher);
+        PreparedQuery {
+            input,
+            normstring,
+            anahash,
+        }
+    }
+
+    /// Find variants for an input that was already preprocessed using [`Self::prepare_query()`].
+    /// This is otherwise identical to [`Self::find_variants()`].
+    pub fn find_variants_prepared(
+        &self,
+        prepared: &PreparedQuery,
+        params: &SearchParameters,
+    ) -> Vec<VariantResult> {
+        if let Some(pool) = self.outside_thread_pool() {
+            return pool.install(|| self.find_variants_prepared(prepared, params));
+        }
+
         if self.index.is_empty() {
-            eprintln!("ERROR: Model has not been built yet! Call build() before find_variants()");
+            error!("ERROR: Model has not been built yet! Call build() before find_variants()");
             return vec![];
         }
 
-        //Compute the anahash
-        let normstring = input.normalize_to_alphabet(&self.alphabet);
-        let anahash = input.anahash(&self.alphabet);
+        let input: &str = &prepared.input;
 
-        let max_anagram_distance: u8 = match params.max_anagram_distance {
-            DistanceThreshold::Ratio(x) => min(
-                (normstring.len() as f32 * x).floor() as u8,
-                MAX_ANAGRAM_DISTANCE, //absolute maximum as a safeguard
-            ),
-            DistanceThreshold::RatioWithLimit(x, limit) => {
-                min((normstring.len() as f32 * x).floor() as u8, limit)
+        if prepared.normstring.is_empty() || input.trim().is_empty() {
+            //nothing to match, e.g. empty input, whitespace or only characters the alphabet ignores
+            if self.debug >= 2 {
+                debug!("   (input is empty after normalization, skipping)");
             }
-            DistanceThreshold::Absolute(x) => min(
-                x,
-                (normstring.len() as f64 / 2.0).floor() as u8, //we still override the absolute threshold when dealing with very small inputs
-            ),
-        };
+            return vec![];
+        }
 
-        //Compute neighbouring anahashes and find the nearest anahashes in the model
-        let anahashes =
-            self.find_nearest_anahashes(&anahash, max_anagram_distance, params.stop_criterion);
+        if preserved_input(input, params) {
+            if self.debug >= 2 {
+                debug!("   (input is shorter than the minimum length or numeric, skipping)");
+            }
+            return vec![];
+        }
 
-        let max_edit_distance: u8 = match params.max_edit_distance {
-            DistanceThreshold::Ratio(x) => min(
-                (normstring.len() as f32 * x).floor() as u8,
-                MAX_EDIT_DISTANCE, //absolute maximum as a safeguard
-            ),
-            DistanceThreshold::RatioWithLimit(x, limit) => {
-                min((normstring.len() as f32 * x).floor() as u8, limit)
+        if params.skip_exact {
+            if let Some(result) = self.exact_match(prepared, params) {
+                if self.debug >= 2 {
+                    debug!("   (input is an exact match, skipping the neighbourhood search)");
+                }
+                return vec![result];
             }
-            DistanceThreshold::Absolute(x) => min(
-                x,
-                (normstring.len() as f64 / 2.0).floor() as u8, //we still override the absolute threshold when dealing with very small inputs
-            ),
-        };
+        }
+
+        let mut results = self.score_and_rank(
+            self.gather_candidates(prepared, params),
+            input,
+            prepared.normstring.len(),
+            params,
+        );
+
+        if !self.affixes.is_empty() {
+            self.add_stripped_variants(prepared, params, &mut results);
+        }
+
+        if params.collapse_lemmas {
+            self.collapse_lemmas(&mut results);
+        }
+
+        if params.return_edits {
+            for result in results.iter_mut() {
+                result.edits = Some(self.compute_edits(input, result.vocab_id));
+            }
+        }
+        results
+    }
+
+    /// Retains only the best ranking variant of each lemma in the (ranked) results, see
+    /// [`SearchParameters::collapse_lemmas`]. Variants without a lemma are all retained.
+    pub fn collapse_lemmas(&self, results: &mut Vec<VariantResult>) {
+        let mut seen: HashSet<&str> = HashSet::new();
+        results.retain(|result| match self.variant_lemma(result) {
+            Some(lemma) => seen.insert(lemma),
+            None => true,
+        });
+    }
+
+    /// Returns the lemma of the variant, if the lexicon provides one (see [`VocabParams::lemma_column`])
+    pub fn variant_lemma(&self, result: &VariantResult) -> Option<&str> {
+        self.decoder
+            .get(result.vocab_id as usize)
+            .and_then(|vocabvalue| vocabvalue.lemma.as_deref())
+    }
+
+    /// Gathers the candidate instances for the input along with their distances, by shared
+    /// character n-grams for long inputs (see [`SearchParameters::ngram_min_length`]) and by
+    /// anagram distance otherwise
+    fn gather_candidates(
+        &self,
+        prepared: &PreparedQuery,
+        params: &SearchParameters,
+    ) -> Vec<(VocabId, Distance)> {
+        if params.ngram_min_length > 0
+            && prepared.normstring.len() >= params.ngram_min_length
+            && !self.ngram_index.is_empty()
+        {
+            //long inputs: find candidates by shared character n-grams rather than anagram distance
+            self.gather_ngram_instances(
+                &prepared.normstring,
+                &prepared.input,
+                params.ngram_min_overlap,
+            )
+        } else {
+            self.gather_anagram_instances(prepared, params)
+        }
+    }
+
+    /// Finds variants for the stems that remain after stripping each applicable affix rule from
+    /// the input (see [`Self::add_to_affixes()`]) and merges them into the results, their scores
+    /// multiplied by the weight of the rule. Variants that are found multiple times retain their best score.
+    fn add_stripped_variants(
+        &self,
+        prepared: &PreparedQuery,
+        params: &SearchParameters,
+        results: &mut Vec<VariantResult>,
+    ) {
+        let input = self.encodable_text(&prepared.input);
+        let mut found = false;
+        for rule in self.affixes.iter() {
+            let stem = match rule.strip(&input) {
+                Some(stem) => stem,
+                None => continue,
+            };
+            let stemprepared = self.prepare_query(stem);
+            if stemprepared.normstring.is_empty() {
+                continue;
+            }
+            if self.debug >= 2 {
+                debug!("   (stripped affix {}, matching stem {})", rule, stem);
+            }
+            let stemresults = self.score_and_rank(
+                self.gather_candidates(&stemprepared, params),
+                stem,
+                stemprepared.normstring.len(),
+                params,
+            );
+            for mut result in stemresults {
+                result.dist_score *= rule.weight;
+                if result.dist_score < params.score_threshold {
+                    continue;
+                }
+                found = true;
+                if let Some(existing) = results.iter_mut().find(|x| x.vocab_id == result.vocab_id) {
+                    if result.dist_score > existing.dist_score {
+                        *existing = result;
+                    }
+                } else {
+                    results.push(result);
+                }
+            }
+        }
+        if found {
+            self.rank_results(results, params.freq_weight);
+            if params.max_matches > 0 {
+                results.truncate(params.max_matches);
+            }
+            if params.hard_max > 0 {
+                results.truncate(params.hard_max);
+            }
+        }
+    }
+
+    /// Gathers the instances in the anagram neighbourhood of the input (and phonetically matching
+    /// instances if enabled), within the maximum edit distance, along with their distances
+    fn gather_anagram_instances(
+        &self,
+        prepared: &PreparedQuery,
+        params: &SearchParameters,
+    ) -> Vec<(VocabId, Distance)> {
+        let input: &str = &prepared.input;
+        let normstring = &prepared.normstring;
+        let anahash = &prepared.anahash;
+
+        let (max_edit_distance, max_anagram_distance) =
+            self.effective_distances(normstring.len(), params);
+
+        //Compute neighbouring anahashes and find the nearest anahashes in the model
+        let mut anahashes =
+            self.find_nearest_anahashes_cached(anahash, max_anagram_distance, params);
+
+        //Find phonetically matching instances (if a phonetic index is available), and add their
+        //anahashes to the candidates if phonetic matching is enabled
+        let phonetic_matches = self.find_phonetic_matches(input);
+        if params.phonetic {
+            for vocab_id in phonetic_matches.iter() {
+                let phonetic_anahash = self
+                    .encodable_text(&self.decoder[*vocab_id as usize].text)
+                    .anahash(&self.alphabet_matcher);
+                if let Some((phonetic_anahash, _)) = self.index.get_key_value(&phonetic_anahash) {
+                    anahashes.insert(phonetic_anahash);
+                }
+            }
+        }
 
         //Get the instances pertaining to the collected hashes, within a certain maximum distance
         //and compute distances
-        let variants = self.gather_instances(&anahashes, &normstring, input, max_edit_distance);
-
-        self.score_and_rank(
-            variants,
+        self.gather_instances(
+            &anahashes,
+            normstring,
             input,
-            normstring.len(),
-            params.max_matches,
-            params.score_threshold,
-            params.cutoff_threshold,
-            params.freq_weight,
+            max_edit_distance,
+            &phonetic_matches,
         )
     }
 
+    /// Returns the maximum edit distance and maximum anagram distance that apply to an input of the
+    /// specified length (in characters)
+    fn effective_distances(&self, length: usize, params: &SearchParameters) -> (u8, u8) {
+        let max_edit_distance: u8 = params
+            .max_edit_distance
+            .effective(length, MAX_EDIT_DISTANCE);
+
+        let mut max_anagram_distance: u8 = match params.max_anagram_distance {
+            DistanceThreshold::DerivedFromEdit(delta) => min(
+                max_edit_distance.saturating_add(delta),
+                MAX_ANAGRAM_DISTANCE,
+            ),
+            threshold => threshold.effective(length, MAX_ANAGRAM_DISTANCE),
+        };
+        if length <= 2 && max_edit_distance >= 1 {
+            //for very small inputs, substitutions (anagram distance 2) must remain reachable
+            //so distance-1 matches can be found
+            max_anagram_distance = max(max_anagram_distance, 2);
+        }
+        (max_edit_distance, max_anagram_distance)
+    }
+
+    /// Gathers the instances that share enough character n-grams with the input (see
+    /// [`NGramIndex::find()`]) along with their distances. No maximum edit distance applies.
+    fn gather_ngram_instances(
+        &self,
+        querystring: &[CharIndexType],
+        query: &str,
+        min_overlap: f64,
+    ) -> Vec<(VocabId, Distance)> {
+        let candidates = self.ngram_index.find(querystring, min_overlap);
+        if self.debug >= 2 {
+            debug!(
+                "(found {} candidates sharing n-grams in the n-gram index)",
+                candidates.len()
+            );
+        }
+        candidates
+            .into_iter()
+            .filter_map(|(vocab_id, _)| {
+                let vocabitem = &self.decoder[vocab_id as usize];
+                if self.in_disabled_lexicons_only(vocabitem) {
+                    return None;
+                }
+                let max_distance = querystring
+                    .len()
+                    .max(vocabitem.norm.len())
+                    .min(u8::MAX as usize) as u8;
+                self.compute_distance(
+                    querystring,
+                    query,
+                    &vocabitem.norm,
+                    &vocabitem.text,
+                    max_distance,
+                    false,
+                )
+                .map(|distance| (vocab_id, distance))
+            })
+            .collect()
+    }
+
+    /// Returns the input itself as a variant with a perfect score, if it occurs verbatim in the (enabled) lexicons
+    fn exact_match(
+        &self,
+        prepared: &PreparedQuery,
+        params: &SearchParameters,
+    ) -> Option<VariantResult> {
+        let vocab_id = *self.encoder.get(prepared.input.as_str())?;
+        let vocabitem = self.decoder.get(vocab_id as usize)?;
+        if !vocabitem.vocabtype.check(VocabType::INDEXED)
+            || self.in_disabled_lexicons_only(vocabitem)
+        {
+            return None;
+        }
+        let length = prepared.normstring.len() as u16;
+        Some(VariantResult {
+            vocab_id,
+            dist_score: 1.0,
+            freq_score: 1.0,
+            via: None,
+            edits: if params.return_edits {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            cased: None,
+            explanation: if params.explain {
+                Some(ScoreExplanation {
+                    distance: Distance {
+                        ld: 0,
+                        weighted_ld: None,
+                        lcs: length,
+                        prefixlen: length,
+                        suffixlen: length,
+                        samecase: true,
+                        casesimilarity: 1.0,
+                        jarowinkler: 1.0,
+                        phonetic: false,
+                    },
+                    combined_score: 1.0,
+                    confusable_weight: 1.0,
+                    lexicon_weight: 1.0,
+                    lm_perplexity: None,
+                    context_score: None,
+                })
+            } else {
+                None
+            },
+        })
+    }
+
     ///Auxiliary function used by [`learn_variants()`], abstracts over strict mode
     fn find_variants_for_learning<'a>(
         &self,
@@ -1067,10 +2915,10 @@ impl VariantModel {
         auto_build: bool,
     ) -> usize
     where
-        I: IntoParallelIterator<Item = &'a String> + IntoIterator<Item = &'a String>,
+        I: IntoParallelIterator<Item = &'a String> + IntoIterator<Item = &'a String> + Send,
     {
         if self.debug >= 1 {
-            eprintln!("(Learning variants)");
+            info!("(Learning variants)");
         }
 
         let vocabparams = VocabParams::default()
@@ -1083,13 +2931,20 @@ impl VariantModel {
                 self.find_variants_for_learning(inputstr.as_str(), params, strict)
             }));
         } else {
-            all_variants.par_extend(input.into_par_iter().map(|inputstr| {
-                self.find_variants_for_learning(inputstr.as_str(), params, strict)
-            }));
+            let learn = || {
+                all_variants.par_extend(input.into_par_iter().map(|inputstr| {
+                    self.find_variants_for_learning(inputstr.as_str(), params, strict)
+                }))
+            };
+            if let Some(pool) = self.outside_thread_pool() {
+                pool.install(learn);
+            } else {
+                learn();
+            }
         }
 
         if self.debug >= 1 {
-            eprintln!(
+            info!(
                 "(adding variants over {} input items to the model)",
                 all_variants.len()
             );
@@ -1126,30 +2981,187 @@ impl VariantModel {
         }
 
         if self.debug >= 1 {
-            eprintln!("(added {} variants)", count);
+            info!("(added {} variants)", count);
         }
 
         if auto_build {
             if self.debug >= 1 {
-                eprintln!("((re)building the model)");
+                info!("((re)building the model)");
             }
             self.build();
         }
         count
     }
 
+    /// Computes the anagram neighbourhoods of a known set of queries up front and adds them to the
+    /// neighbourhood cache, so subsequent calls to [`Self::find_variants()`] with the same
+    /// parameters only need to look them up. This is intended for batch jobs where the queries are
+    /// known in advance (e.g. all distinct tokens of a corpus). The neighbourhoods are computed in
+    /// parallel (unless `params.single_thread` is set). The cache must be enabled in the parameters
+    /// (see [`SearchParameters::neighbourhood_cache_size`]) and should be large enough to hold all
+    /// neighbourhoods, or the least recently computed ones are evicted again.
+    pub fn precompute(&self, queries: &[&str], params: &SearchParameters) -> PrecomputeStats {
+        if let Some(pool) = self.outside_thread_pool() {
+            return pool.install(|| self.precompute(queries, params));
+        }
+        let begintime = Instant::now();
+        let mut stats = PrecomputeStats {
+            queries: queries.len(),
+            ..Default::default()
+        };
+        if params.neighbourhood_cache_size == 0 {
+            warning!("Neighbourhood cache is disabled, nothing to precompute");
+            stats.skipped = queries.len();
+            return stats;
+        }
+
+        //determine the distinct neighbourhoods we need
+        let mut pending: Vec<(AnaValue, u8)> = Vec::new();
+        let mut seen: HashSet<(AnaValue, u8)> = HashSet::new();
+        {
+            let cache = self.neighbourhood_cache();
+            for query in queries {
+                let prepared = self.prepare_query(query);
+                if prepared.normstring.is_empty()
+                    || prepared.input.trim().is_empty()
+                    || preserved_input(&prepared.input, params)
+                    || (params.ngram_min_length > 0
+                        && prepared.normstring.len() >= params.ngram_min_length
+                        && !self.ngram_index.is_empty())
+                {
+                    stats.skipped += 1;
+                    continue;
+                }
+                let (_, max_anagram_distance) =
+                    self.effective_distances(prepared.normstring.len(), params);
+                let key = (prepared.anahash, max_anagram_distance);
+                if seen.contains(&key)
+                    || cache.contains(&key.0, key.1, params.stop_criterion, params.max_candidates)
+                {
+                    stats.cached += 1;
+                } else {
+                    seen.insert(key.clone());
+                    pending.push(key);
+                }
+            }
+        }
+
+        let compute = |(focus, max_distance): &(AnaValue, u8)| {
+            self.find_nearest_anahashes(
+                focus,
+                *max_distance,
+                params.stop_criterion,
+                params.max_candidates,
+            )
+            .iter()
+            .filter_map(|anahash| self.index.get_key_value(*anahash))
+            .map(|(anahash, _)| Arc::clone(anahash))
+            .collect::<Vec<_>>()
+        };
+        let neighbourhoods: Vec<Vec<Arc<AnaValue>>> = if params.single_thread {
+            pending.iter().map(compute).collect()
+        } else {
+            pending.par_iter().map(compute).collect()
+        };
+
+        let mut cache = self.neighbourhood_cache();
+        for ((focus, max_distance), neighbourhood) in pending.into_iter().zip(neighbourhoods) {
+            cache.insert(
+                focus,
+                max_distance,
+                params.stop_criterion,
+                params.max_candidates,
+                neighbourhood,
+                params.neighbourhood_cache_size,
+            );
+            stats.computed += 1;
+        }
+        stats.duration = begintime.elapsed();
+        if self.debug >= 1 {
+            info!(
+                "Precomputed {} neighbourhoods for {} queries in {} ms ({} already cached, {} skipped)",
+                stats.computed,
+                stats.queries,
+                stats.duration.as_millis(),
+                stats.cached,
+                stats.skipped
+            );
+        }
+        stats
+    }
+
+    /// Find the nearest anahashes like [`Self::find_nearest_anahashes()`], but reuse a
+    /// previously computed neighbourhood from the cache if the cache is enabled in the parameters.
+    fn find_nearest_anahashes_cached<'a>(
+        &'a self,
+        focus: &AnaValue,
+        max_distance: u8,
+        params: &SearchParameters,
+    ) -> BTreeSet<&'a AnaValue> {
+        if params.neighbourhood_cache_size == 0 {
+            return self.find_nearest_anahashes(
+                focus,
+                max_distance,
+                params.stop_criterion,
+                params.max_candidates,
+            );
+        }
+        let cached = self.neighbourhood_cache().get(
+            focus,
+            max_distance,
+            params.stop_criterion,
+            params.max_candidates,
+        );
+        if let Some(neighbourhood) = cached {
+            if self.debug >= 2 {
+                debug!(
+                    "(found {} nearest anagram matches in cache)",
+                    neighbourhood.len()
+                );
+            }
+            return neighbourhood
+                .iter()
+                .filter_map(|anahash| self.index.get_key_value(anahash.as_ref()))
+                .map(|(anahash, _)| anahash.as_ref())
+                .collect();
+        }
+        //computed without holding the lock, so other threads can proceed in the meantime
+        let nearest = self.find_nearest_anahashes(
+            focus,
+            max_distance,
+            params.stop_criterion,
+            params.max_candidates,
+        );
+        let neighbourhood = nearest
+            .iter()
+            .filter_map(|anahash| self.index.get_key_value(*anahash))
+            .map(|(anahash, _)| Arc::clone(anahash))
+            .collect();
+        self.neighbourhood_cache().insert(
+            focus.clone(),
+            max_distance,
+            params.stop_criterion,
+            params.max_candidates,
+            neighbourhood,
+            params.neighbourhood_cache_size,
+        );
+        nearest
+    }
+
     /// Find the nearest anahashes that exists in the model (computing anahashes in the
-    /// neigbhourhood if needed).
+    /// neigbhourhood if needed). If `max_candidates` is non-zero, no more anahashes are gathered
+    /// once this many are found; the ones with the smallest anagram distance are kept.
     pub(crate) fn find_nearest_anahashes<'a>(
         &'a self,
         focus: &AnaValue,
         max_distance: u8,
         stop_criterion: StopCriterion,
+        max_candidates: usize,
     ) -> BTreeSet<&'a AnaValue> {
         let mut nearest: BTreeSet<&AnaValue> = BTreeSet::new();
 
         let begintime = if self.debug >= 2 {
-            eprintln!("(finding nearest anagram matches for focus anavalue {}, max_distance={}, stop_criterion={:?})", focus, max_distance, stop_criterion);
+            debug!("(finding nearest anagram matches for focus anavalue {}, max_distance={}, stop_criterion={:?})", focus, max_distance, stop_criterion);
             Some(SystemTime::now())
         } else {
             None
@@ -1158,14 +3170,14 @@ impl VariantModel {
         if let Some((matched_anahash, node)) = self.index.get_key_value(focus) {
             //the easiest case, this anahash exists in the model!
             if self.debug >= 2 {
-                eprintln!(" (found exact match)");
+                debug!(" (found exact match)");
             }
             nearest.insert(matched_anahash);
             if StopCriterion::StopAtExactMatch == stop_criterion {
                 for vocab_id in node.instances.iter() {
                     if let Some(_) = self.decoder.get(*vocab_id as usize) {
                         if self.debug >= 2 {
-                            eprintln!(" (stopping early)");
+                            debug!(" (stopping early)");
                         }
                         return nearest;
                     }
@@ -1177,24 +3189,24 @@ impl VariantModel {
         let focus_alphabet_size = focus_upper_bound + 1;
 
         // Gather lookups to match against the secondary index
-        // keys correspond to the number of characters
-        // We first gather all lookups rather than doing them immediately,
-        // so we need to iterate over the secondary index only once, which
-        // has a slight performance benefit
-        let mut lookups: HashMap<u8, Vec<AnaValue>> = HashMap::new();
+        // keys correspond to the total anagram distance, values hold the anagram values
+        // to look up along with the character count to search for and the number of insertions
+        let mut lookups: HashMap<u8, Vec<(AnaValue, u16, u16)>> = HashMap::new();
 
         //Find anagrams reachable through insertions within the the maximum distance
-        for distance in 1..=max_distance {
+        //(only if we have a secondary index, otherwise we can not reach longer anagrams)
+        for distance in (1..=max_distance).filter(|_| self.insertions) {
             let search_charcount = focus_charcount + distance as u16;
-            if let Some(lookups) = lookups.get_mut(&(search_charcount as u8)) {
-                lookups.push(focus.clone());
-            } else {
-                lookups.insert(search_charcount as u8, vec![focus.clone()]);
-            }
+            lookups.entry(distance).or_default().push((
+                focus.clone(),
+                search_charcount,
+                distance as u16,
+            ));
             if self.debug >= 3 {
-                eprintln!(
+                trace!(
                     " (scheduling finding insertion at distance {}, charcount {})",
-                    distance, search_charcount
+                    distance,
+                    search_charcount
                 );
             }
         }
@@ -1210,77 +3222,122 @@ impl VariantModel {
         /*let iterator = if let Some(cache) = cache {
             focus.iter_recursive_external_cache(focus_alphabet_size+1, &searchparams, cache)
         } else {*/
-        let iterator = focus.iter_recursive(focus_alphabet_size + 1, &searchparams);
+        let mut iterator = focus
+            .iter_recursive(focus_alphabet_size + 1, &searchparams)
+            .peekable();
         /*};*/
 
-        // Do a breadth first search for deletions
-        for (deletion, distance) in iterator {
-            if self.debug >= 3 {
-                eprintln!(
-                    " (testing deletion at distance {}, charcount {}: anavalue {})",
-                    distance,
-                    focus_charcount as u32 - distance,
-                    deletion.value
-                );
-                if self.debug >= 4 {
-                    let decomposed: String = self.decompose_anavalue(&deletion.value).join("");
-                    eprintln!("  (anavalue decomposition: {})", decomposed);
-                }
+        //The deletion iterator does not yield the empty anagram, for a single character focus we
+        //still need it to find substitutions
+        if focus_charcount == 1 {
+            let max_search_distance = if self.insertions {
+                max_distance as u16
+            } else {
+                2
+            };
+            for search_distance in 1..max_search_distance.min(max_distance as u16) {
+                lookups.entry(1 + search_distance as u8).or_default().push((
+                    AnaValue::empty(),
+                    search_distance,
+                    search_distance,
+                ));
             }
+        }
+
+        // Candidates are gathered per anagram distance, in increasing order, so that if the number
+        // of candidates is capped we deterministically keep the nearest ones
+        let mut count = 0;
+        let beginlength = nearest.len();
+        for level in 1..=max_distance {
+            let mut found: BTreeSet<&AnaValue> = BTreeSet::new();
 
-            if let Some((matched_anahash, _node)) = self.index.get_key_value(&deletion) {
+            // Do a breadth first search for deletions (at this distance)
+            while let Some((deletion, distance)) =
+                iterator.next_if(|(_, distance)| *distance <= level as u32)
+            {
                 if self.debug >= 3 {
-                    eprintln!("  (deletion matches; anagram exists in index)");
+                    trace!(
+                        " (testing deletion at distance {}, charcount {}: anavalue {})",
+                        distance,
+                        focus_charcount as u32 - distance,
+                        deletion.value
+                    );
+                    if self.debug >= 4 {
+                        let decomposed: String = self.decompose_anavalue(&deletion.value).join("");
+                        trace!("  (anavalue decomposition: {})", decomposed);
+                    }
                 }
-                //This deletion exists in the model
-                nearest.insert(matched_anahash);
-            }
 
-            let deletion_charcount = focus_charcount - distance as u16;
-            if self.debug >= 3 {
-                eprintln!(
-                    "  (scheduling search for insertions from deletion result anavalue {})",
-                    deletion.value
-                );
-            }
-            //Find possible insertions starting from this deletion
-            for search_distance in 1..=(max_distance as u16 - distance as u16) {
-                let search_charcount = deletion_charcount + search_distance;
+                if let Some((matched_anahash, _node)) = self.index.get_key_value(&deletion.value) {
+                    if self.debug >= 3 {
+                        trace!("  (deletion matches; anagram exists in index)");
+                    }
+                    //This deletion exists in the model
+                    found.insert(matched_anahash);
+                }
+
+                let deletion_charcount = focus_charcount - distance as u16;
                 if self.debug >= 3 {
-                    eprintln!(
-                        "   (search_distance={}, search_charcount={})",
-                        search_distance, search_charcount
+                    trace!(
+                        "  (scheduling search for insertions from deletion result anavalue {})",
+                        deletion.value
                     );
                 }
-                if let Some(lookups) = lookups.get_mut(&(search_charcount as u8)) {
-                    lookups.push(deletion.value.clone());
+                //Find possible insertions starting from this deletion, without a secondary index
+                //we only consider insertions that do not exceed the length of the focus
+                //(i.e. substitutions)
+                let max_search_distance = if self.insertions {
+                    max_distance as u16 - distance as u16
                 } else {
-                    lookups.insert(search_charcount as u8, vec![deletion.value.clone()]);
+                    (max_distance as u16 - distance as u16).min(distance as u16)
+                };
+                for search_distance in 1..=max_search_distance {
+                    let search_charcount = deletion_charcount + search_distance;
+                    if self.debug >= 3 {
+                        trace!(
+                            "   (search_distance={}, search_charcount={})",
+                            search_distance,
+                            search_charcount
+                        );
+                    }
+                    lookups
+                        .entry(distance as u8 + search_distance as u8)
+                        .or_default()
+                        .push((deletion.value.clone(), search_charcount, search_distance));
                 }
             }
-        }
 
-        if self.debug >= 2 {
-            eprintln!("(finding all insertions)");
-        }
-        let mut count = 0;
-        let beginlength = nearest.len();
-        for (search_charcount, anavalues) in lookups.iter() {
-            if let Some(sortedindex) = self.sortedindex.get(&(*search_charcount as u16)) {
-                for candidate in sortedindex.iter() {
-                    for av in anavalues {
-                        if candidate.contains(&av) {
-                            //this is where the magic happens
-                            count += 1;
-                            nearest.insert(candidate);
-                            break;
-                        }
+            if self.debug >= 2 {
+                debug!("(finding all insertions at distance {})", level);
+            }
+            for (av, search_charcount, insertions) in lookups.remove(&level).unwrap_or_default() {
+                //this is where the magic happens
+                let candidates = if self.insertions {
+                    self.find_insertions(&av, search_charcount, insertions)
+                } else {
+                    self.probe_insertions(&av, insertions)
+                };
+                for candidate in candidates {
+                    count += 1;
+                    found.insert(candidate);
+                }
+            }
+
+            for candidate in found {
+                if max_candidates > 0 && nearest.len() >= max_candidates {
+                    if self.debug >= 1 {
+                        info!(
+                            "(number of candidates for focus anavalue {} reached the maximum of {} at anagram distance {}, skipping the remainder)",
+                            focus, max_candidates, level
+                        );
                     }
+                    return nearest;
                 }
+                nearest.insert(candidate);
             }
         }
         if self.debug >= 2 {
-            eprintln!(
+            debug!(
                 " (added {} out of {} candidates, preventing duplicates)",
                 nearest.len() - beginlength,
                 count
@@ -1293,30 +3350,100 @@ impl VariantModel {
                 .duration_since(begintime.expect("begintime"))
                 .expect("clock can't go backwards")
                 .as_micros();
-            eprint!(
-                "(found {} anagram matches in total (in {} μs) for focus anavalue {}: ",
+            let anavalues: Vec<String> = nearest.iter().map(|av| av.to_string()).collect();
+            debug!(
+                "(found {} anagram matches in total (in {} μs) for focus anavalue {}:  {})",
                 nearest.len(),
                 duration,
-                focus
+                focus,
+                anavalues.join(" ")
             );
-            for av in nearest.iter() {
-                eprint!(" {}", av);
+        }
+        nearest
+    }
+
+    /// Returns all anagram values in the secondary index with the given character count that contain
+    /// the specified anagram value, i.e. that can be reached from it by the specified number of
+    /// insertions. Rather than testing all anagram values of that length, this only considers the
+    /// range of the (sorted) secondary index in which such values can occur: between the anagram
+    /// value multiplied by the lowest character value and by the highest character value as
+    /// often as there are insertions.
+    pub fn find_insertions(
+        &self,
+        anavalue: &AnaValue,
+        charcount: u16,
+        insertions: u16,
+    ) -> Vec<&AnaValue> {
+        let keys = if let Some(keys) = self.sortedindex.get(&charcount) {
+            keys
+        } else {
+            return Vec::new();
+        };
+        let lowest = AnaValue::character(0);
+        let highest = AnaValue::character(self.alphabet_size() - 1);
+        let mut lower_bound = anavalue.clone();
+        let mut upper_bound = anavalue.clone();
+        for _ in 0..insertions {
+            lower_bound = lower_bound * lowest.clone();
+            upper_bound = upper_bound * highest.clone();
+        }
+        let begin = keys.partition_point(|key| key.as_ref() < &lower_bound);
+        let end = keys.partition_point(|key| key.as_ref() <= &upper_bound);
+        keys[begin..end]
+            .iter()
+            .filter(|key| key.contains(anavalue))
+            .map(|key| key.as_ref())
+            .collect()
+    }
+
+    /// Returns all anagram values in the main index that can be reached from the specified anagram
+    /// value by the specified number of insertions, by trying all combinations of characters to
+    /// insert. This is the alternative to [`Self::find_insertions()`] if the secondary index is
+    /// disabled (see [`Self::set_insertions()`]), it is only feasible for few insertions.
+    pub fn probe_insertions(&self, anavalue: &AnaValue, insertions: u16) -> Vec<&AnaValue> {
+        let mut found = Vec::new();
+        self.probe_insertions_from(anavalue, insertions, 0, &mut found);
+        found
+    }
+
+    fn probe_insertions_from<'a>(
+        &'a self,
+        anavalue: &AnaValue,
+        insertions: u16,
+        first: CharIndexType,
+        found: &mut Vec<&'a AnaValue>,
+    ) {
+        //characters are inserted in non-decreasing order so each combination is tried only once
+        for seqnr in first..self.alphabet_size() {
+            let candidate = anavalue.insert(&AnaValue::character(seqnr));
+            if insertions > 1 {
+                self.probe_insertions_from(&candidate, insertions - 1, seqnr, found);
+            } else if let Some((key, _)) = self.index.get_key_value(&candidate) {
+                found.push(key);
             }
-            eprintln!(")");
         }
-        nearest
     }
 
-    /// Gather instances with their edit distances and frequency, given a search string (normalised to the alphabet) and anagram hashes
+    /// Gather instances with their edit distances and frequency, given a search string (normalised to the alphabet) and anagram hashes.
+    /// Instances whose characters differ too much from the query's (see [`multiset_difference()`])
+    /// are pruned without computing the edit distance.
     pub(crate) fn gather_instances(
         &self,
         nearest_anagrams: &BTreeSet<&AnaValue>,
         querystring: &[u8],
         query: &str,
         max_edit_distance: u8,
+        phonetic_matches: &HashSet<VocabId>,
     ) -> Vec<(VocabId, Distance)> {
         let mut found_instances = Vec::new();
         let mut pruned_instances = 0;
+        let mut skipped_instances = 0;
+        //the character counts only bound the unweighted edit distance, custom substitution costs may be lower
+        let query_counts = if self.substitution_costs.is_empty() {
+            Some(symbol_counts(querystring))
+        } else {
+            None
+        };
 
         let begintime = if self.debug >= 2 {
             Some(SystemTime::now())
@@ -1327,63 +3454,64 @@ impl VariantModel {
         for anahash in nearest_anagrams {
             let node = self
                 .index
-                .get(anahash)
+                .get(*anahash)
                 .expect("all anahashes from nearest_anagrams must occur in the index");
             for vocab_id in node.instances.iter() {
                 let vocabitem = self
                     .decoder
                     .get(*vocab_id as usize)
                     .expect("vocabulary id must exist in the decoder");
+                if self.in_disabled_lexicons_only(vocabitem) {
+                    continue;
+                }
                 if self.debug >= 4 {
-                    eprintln!(
+                    trace!(
                         "  (comparing query {} with instance {})",
-                        query, vocabitem.text
+                        query,
+                        vocabitem.text
                     )
                 }
-                if let Some(ld) =
-                    damerau_levenshtein(querystring, &vocabitem.norm, max_edit_distance)
-                {
+                //phonetic matches are not subject to the maximum edit distance
+                let phonetic = phonetic_matches.contains(vocab_id);
+                let max_distance = if phonetic {
+                    MAX_EDIT_DISTANCE
+                } else {
+                    max_edit_distance
+                };
+                //a cheap lower bound on the edit distance, from the character counts alone
+                let distance_lower_bound = if let Some(query_counts) = query_counts.as_ref() {
+                    let (missing, extra) =
+                        multiset_difference(querystring, query_counts, &vocabitem.norm);
+                    max(missing, extra)
+                } else {
+                    0
+                };
+                if distance_lower_bound > max_distance as usize {
                     if self.debug >= 4 {
-                        eprintln!("   (ld={})", ld);
+                        trace!(
+                            "   (character counts exceed max_edit_distance {})",
+                            max_distance
+                        );
+                    }
+                    skipped_instances += 1;
+                    continue;
+                }
+                if let Some(distance) = self.compute_distance(
+                    querystring,
+                    query,
+                    &vocabitem.norm,
+                    &vocabitem.text,
+                    max_distance,
+                    phonetic,
+                ) {
+                    if self.debug >= 4 {
+                        trace!("   (ld={})", distance.ld);
                     }
                     //we only get here if we make the max_edit_distance cut-off
-                    let distance = Distance {
-                        ld: ld,
-                        lcs: if self.weights.lcs > 0.0 {
-                            longest_common_substring_length(querystring, &vocabitem.norm)
-                        } else {
-                            0
-                        },
-                        prefixlen: if self.weights.prefix > 0.0 {
-                            common_prefix_length(querystring, &vocabitem.norm)
-                        } else {
-                            0
-                        },
-                        suffixlen: if self.weights.suffix > 0.0 {
-                            common_suffix_length(querystring, &vocabitem.norm)
-                        } else {
-                            0
-                        },
-                        samecase: if self.weights.case > 0.0 {
-                            vocabitem
-                                .text
-                                .chars()
-                                .next()
-                                .expect("first char")
-                                .is_lowercase()
-                                == query.chars().next().expect("first char").is_lowercase()
-                        } else {
-                            true
-                        },
-                    };
-                    //match will be added to found_instances at the end of the block (we
-                    //need to borrow the distance for a bit still)
-
-                    //add the original match
                     found_instances.push((*vocab_id, distance));
                 } else {
                     if self.debug >= 4 {
-                        eprintln!("   (exceeds max_edit_distance {})", max_edit_distance);
+                        trace!("   (exceeds max_edit_distance {})", max_edit_distance);
                     }
                     pruned_instances += 1;
                 }
@@ -1396,31 +3524,174 @@ impl VariantModel {
                 .duration_since(begintime.expect("begintime"))
                 .expect("clock can't go backwards")
                 .as_micros();
-            eprintln!("(found {} instances (pruned {} above max_edit_distance {}) over {} anagrams in {} μs)", found_instances.len(), pruned_instances, max_edit_distance,  nearest_anagrams.len(), duration);
+            debug!("(found {} instances (pruned {} above max_edit_distance {}, of which {} without computing the distance) over {} anagrams in {} μs)", found_instances.len(), pruned_instances + skipped_instances, max_edit_distance, skipped_instances, nearest_anagrams.len(), duration);
         }
         found_instances
     }
 
+    /// Computes the distance between the query and a candidate, both as text and normalized to the
+    /// alphabet. Returns `None` if the candidate exceeds the maximum edit distance. The more
+    /// expensive distance metrics are only computed if they carry weight.
+    fn compute_distance(
+        &self,
+        querystring: &[CharIndexType],
+        query: &str,
+        candidatestring: &[CharIndexType],
+        candidate: &str,
+        max_distance: u8,
+        phonetic: bool,
+    ) -> Option<Distance> {
+        //the weighted distance is only computed if there are custom substitution costs
+        let (ld, weighted_ld) = if self.substitution_costs.is_empty() {
+            (
+                damerau_levenshtein(querystring, candidatestring, max_distance)?,
+                None,
+            )
+        } else {
+            let weighted_ld = weighted_damerau_levenshtein(
+                querystring,
+                candidatestring,
+                max_distance as f64,
+                &self.substitution_costs,
+            )?;
+            (weighted_ld.ceil() as CharIndexType, Some(weighted_ld))
+        };
+        let samecase = self.weights.case <= 0.0
+            || candidate.chars().next().expect("first char").is_lowercase()
+                == query.chars().next().expect("first char").is_lowercase();
+        Some(Distance {
+            ld,
+            weighted_ld,
+            lcs: if self.weights.lcs > 0.0 {
+                longest_common_substring_length(querystring, candidatestring)
+            } else {
+                0
+            },
+            prefixlen: if self.weights.prefix > 0.0 {
+                common_prefix_length(querystring, candidatestring)
+            } else {
+                0
+            },
+            suffixlen: if self.weights.suffix > 0.0 {
+                common_suffix_length(querystring, candidatestring)
+            } else {
+                0
+            },
+            samecase,
+            casesimilarity: if self.weights.case > 0.0 && !self.case_first_char {
+                case_similarity(query, candidate)
+            } else if samecase {
+                1.0
+            } else {
+                0.0
+            },
+            jarowinkler: if self.weights.jarowinkler > 0.0 {
+                jaro_winkler(querystring, candidatestring)
+            } else {
+                0.0
+            },
+            phonetic,
+        })
+    }
+
+    /// Combines the distance metrics into a single similarity score, expressed in relation to the
+    /// length of the input (in characters normalized to the alphabet). An exact match by default is 1.0.
+    fn distance_score(
+        &self,
+        distance: &Distance,
+        input_length: usize,
+        score_combination: ScoreCombination,
+    ) -> f64 {
+        let ld = distance.weighted_ld.unwrap_or(distance.ld as f64);
+        let distance_score: f64 = if ld > input_length as f64 {
+            0.0
+        } else {
+            1.0 - (ld / input_length as f64)
+        };
+        let lcs_score: f64 = distance.lcs as f64 / input_length as f64;
+        let prefix_score: f64 = distance.prefixlen as f64 / input_length as f64;
+        let suffix_score: f64 = distance.suffixlen as f64 / input_length as f64;
+        //weighted combination (arithmetic mean by default) over all normalized distance factors
+        score_combination.combine(
+            &[
+                (self.weights.ld, distance_score),
+                (self.weights.lcs, lcs_score),
+                (self.weights.prefix, prefix_score),
+                (self.weights.suffix, suffix_score),
+                (self.weights.case, distance.casesimilarity),
+                (self.weights.jarowinkler, distance.jarowinkler),
+                (
+                    self.weights.phonetic,
+                    if distance.phonetic { 1.0 } else { 0.0 },
+                ),
+            ],
+            self.weights.sum(),
+        )
+    }
+
+    /// Computes the similarity score between two arbitrary strings, using the weights of the
+    /// model, without consulting the index. The score is the same as [`Self::find_variants()`]
+    /// would assign to the candidate as a variant of the input (prior to any frequency or context
+    /// weighting), including the weights of confusables, using the default score combination
+    /// ([`ScoreCombination::ArithmeticMean`]). Unlike in [`Self::find_variants()`], the candidate need not be
+    /// in the lexicon and no maximum edit distance applies. Returns 0.0 if either string is empty.
+    pub fn score_pair(&self, input: &str, candidate: &str) -> f64 {
+        let input = self.prepare_query(input);
+        let candidate = self.prepare_query(candidate);
+        if input.normstring.is_empty() || candidate.normstring.is_empty() {
+            return 0.0;
+        }
+        let phonetic = self.weights.phonetic > 0.0 && {
+            let codes = phonetic_codes(&input.input);
+            phonetic_codes(&candidate.input)
+                .iter()
+                .any(|code| codes.contains(code))
+        };
+        let max_distance = input
+            .normstring
+            .len()
+            .max(candidate.normstring.len())
+            .min(u8::MAX as usize) as u8;
+        let score = match self.compute_distance(
+            &input.normstring,
+            &input.input,
+            &candidate.normstring,
+            &candidate.input,
+            max_distance,
+            phonetic,
+        ) {
+            Some(distance) => self.distance_score(
+                &distance,
+                input.normstring.len(),
+                ScoreCombination::default(),
+            ),
+            None => 0.0,
+        };
+        if self.confusables.is_empty() {
+            score
+        } else {
+            score * self.confusable_weight(&input.input, &candidate.input)
+        }
+    }
+
     /// Rank and score all variants, returns a vector of three-tuples: (VocabId, distance score, frequency score)
     pub(crate) fn score_and_rank(
         &self,
         instances: Vec<(VocabId, Distance)>,
         input: &str,
         input_length: usize,
-        max_matches: usize,
-        score_threshold: f64,
-        cutoff_threshold: f64,
-        freq_weight: f32,
+        params: &SearchParameters,
     ) -> Vec<VariantResult> {
         let mut results: Vec<VariantResult> = Vec::new();
-        let mut max_freq = 0.0;
         let mut has_expandable_variants = false;
-        let weights_sum = self.weights.sum();
 
         assert!(input_length > 0);
 
+        //the evaluation hook needs all score components
+        let explain_all = params.explain || self.eval_hook.is_some();
+
         let begintime = if self.debug >= 2 {
-            eprintln!("(scoring and ranking {} instances)", instances.len());
+            debug!("(scoring and ranking {} instances)", instances.len());
             Some(SystemTime::now())
         } else {
             None
@@ -1429,27 +3700,12 @@ impl VariantModel {
         //Compute scores
         for (vocab_id, distance) in instances.iter() {
             if let Some(vocabitem) = self.decoder.get(*vocab_id as usize) {
-                //all scores are expressed in relation to the input length
-                let distance_score: f64 = if distance.ld as usize > input_length {
-                    0.0
-                } else {
-                    1.0 - (distance.ld as f64 / input_length as f64)
-                };
-                let lcs_score: f64 = distance.lcs as f64 / input_length as f64;
-                let prefix_score: f64 = distance.prefixlen as f64 / input_length as f64;
-                let suffix_score: f64 = distance.suffixlen as f64 / input_length as f64;
-                //simple weighted linear combination (arithmetic mean to normalize it again) over all normalized distance factors
+                //all scores are expressed in relation to the input length, the combined score
                 //expresses a similarity score, sensitive to the length of the input string, and where an exact match by default is 1.0
-                let score = (self.weights.ld * distance_score
-                    + self.weights.lcs * lcs_score
-                    + self.weights.prefix * prefix_score
-                    + self.weights.suffix * suffix_score
-                    + if distance.samecase {
-                        self.weights.case
-                    } else {
-                        0.0
-                    })
-                    / weights_sum;
+                let combined_score =
+                    self.distance_score(distance, input_length, params.score_combination);
+                let lexicon_weight = self.lexicon_weight(vocabitem);
+                let score = combined_score * lexicon_weight;
 
                 let freq_score: f64 = if self.have_freq {
                     //absolute frequency, normalisation in later pass
@@ -1457,9 +3713,6 @@ impl VariantModel {
                 } else {
                     1.0
                 };
-                if freq_score > max_freq {
-                    max_freq = freq_score;
-                }
 
                 if !has_expandable_variants && vocabitem.variants.is_some() {
                     has_expandable_variants = true;
@@ -1472,15 +3725,29 @@ impl VariantModel {
                         vocabitem.text, distance, score
                     );
                 }
-                if score >= score_threshold {
+                if score >= params.score_threshold {
                     results.push(VariantResult {
                         vocab_id: *vocab_id,
                         dist_score: score,
                         freq_score,
                         via: None,
+                        edits: None,
+                        cased: None,
+                        explanation: if explain_all {
+                            Some(ScoreExplanation {
+                                distance: distance.clone(),
+                                combined_score,
+                                confusable_weight: 1.0,
+                                lexicon_weight,
+                                lm_perplexity: None,
+                                context_score: None,
+                            })
+                        } else {
+                            None
+                        },
                     });
                     if self.debug >= 3 {
-                        eprintln!(
+                        trace!(
                             "   (variant={}, distance={:?}, score={}, transparent={})",
                             vocabitem.text,
                             distance,
@@ -1490,7 +3757,7 @@ impl VariantModel {
                     }
                 } else {
                     if self.debug >= 3 {
-                        eprintln!(
+                        trace!(
                             "   (PRUNED variant={}, distance={:?}, score={}, transparent={})",
                             vocabitem.text,
                             distance,
@@ -1508,50 +3775,72 @@ impl VariantModel {
         }
 
         if has_expandable_variants {
-            results = self.expand_variants(results);
-            //Collect maximum frequency after expansion
-            for result in results.iter() {
-                if result.freq_score > max_freq {
-                    max_freq = result.freq_score;
-                }
-            }
+            results = self.expand_variants_with(results, params.expansion_frequency);
         }
 
-        //normalize frequency score
-        if max_freq > 0.0 {
-            for result in results.iter_mut() {
-                result.freq_score = result.freq_score / max_freq;
-            }
+        //clip at the frequency cap (if any), then transform and normalize frequency score (after expansion)
+        let mut freqs: Vec<f64> = results
+            .iter()
+            .map(|result| {
+                if params.freq_cap > 0 {
+                    result.freq_score.min(params.freq_cap as f64)
+                } else {
+                    result.freq_score
+                }
+            })
+            .collect();
+        params.freq_transform.normalize(&mut freqs);
+        for (result, freq_score) in results.iter_mut().zip(freqs) {
+            result.freq_score = freq_score;
         }
 
         //Sort the results by distance score, descending order
-        self.rank_results(&mut results, freq_weight);
+        self.rank_results(&mut results, params.freq_weight);
 
         if has_expandable_variants {
             //remove duplicates (can only occur when variant expansion was performed)
             results.dedup_by_key(|x| x.vocab_id);
         }
 
+        if let Some(eval_hook) = self.eval_hook.as_ref() {
+            let mut candidates = results.clone();
+            if !self.confusables.is_empty() && !self.confusables_before_pruning {
+                self.apply_confusables(input, &mut candidates);
+                self.rank_results(&mut candidates, params.freq_weight);
+            }
+            eval_hook(&EvalCandidates {
+                model: self,
+                input,
+                candidates: &candidates,
+                freq_weight: params.freq_weight,
+            });
+            if !params.explain {
+                for result in results.iter_mut() {
+                    result.explanation = None;
+                }
+            }
+        }
+
         //Crop the results at max_matches or cut off at the cutoff threshold
-        if max_matches > 0 && results.len() > max_matches {
+        if params.max_matches > 0 && results.len() > params.max_matches {
             let last_score = results
-                .get(max_matches - 1)
+                .get(params.max_matches - 1)
                 .expect("get last score")
-                .score(freq_weight);
+                .score(params.freq_weight);
             let cropped_score = results
-                .get(max_matches)
+                .get(params.max_matches)
                 .expect("get cropped score")
-                .score(freq_weight);
+                .score(params.freq_weight);
             if cropped_score < last_score {
                 if self.debug >= 2 {
-                    eprintln!(
+                    debug!(
                         "   (truncating {} matches to {})",
                         results.len(),
-                        max_matches
+                        params.max_matches
                     );
                 }
                 //simplest case, crop at the max_matches
-                results.truncate(max_matches);
+                results.truncate(params.max_matches);
             } else {
                 //cropping at max_matches comes at arbitrary point of equal scoring items,
                 //we crop earlier instead:
@@ -1568,7 +3857,7 @@ impl VariantModel {
                 }
                 if early_cutoff > 0 {
                     if self.debug >= 2 {
-                        eprintln!(
+                        debug!(
                             "   (truncating {} matches (early) to {})",
                             results.len(),
                             early_cutoff + 1
@@ -1577,7 +3866,7 @@ impl VariantModel {
                     results.truncate(early_cutoff + 1);
                 } else if late_cutoff > 0 {
                     if self.debug >= 2 {
-                        eprintln!(
+                        debug!(
                             "   (truncating {} matches (late) to {})",
                             results.len(),
                             late_cutoff + 1
@@ -1588,24 +3877,35 @@ impl VariantModel {
             }
         }
 
+        //Enforce the absolute ceiling, even if the number of matches is unlimited
+        if params.hard_max > 0 && results.len() > params.hard_max {
+            warning!(
+                "WARNING: Truncating {} variants for input '{}' to the hard maximum of {}",
+                results.len(),
+                input,
+                params.hard_max
+            );
+            results.truncate(params.hard_max);
+        }
+
         //rescore with confusable weights (LATE, default)
         if !self.confusables.is_empty() && !self.confusables_before_pruning {
             self.rescore_confusables(&mut results, input);
-            self.rank_results(&mut results, freq_weight);
+            self.rank_results(&mut results, params.freq_weight);
         }
 
         // apply the cutoff threshold
         let mut cutoff = 0;
         let mut bestscore = None;
-        if cutoff_threshold >= 1.0 {
+        if params.cutoff_threshold >= 1.0 {
             for (i, result) in results.iter().enumerate() {
                 if let Some(bestscore) = bestscore {
-                    if result.score(freq_weight) <= bestscore / cutoff_threshold {
+                    if result.score(params.freq_weight) <= bestscore / params.cutoff_threshold {
                         cutoff = i;
                         break;
                     }
                 } else {
-                    bestscore = Some(result.score(freq_weight));
+                    bestscore = Some(result.score(params.freq_weight));
                 }
             }
         }
@@ -1613,7 +3913,7 @@ impl VariantModel {
             let l = results.len();
             results.truncate(cutoff);
             if self.debug >= 2 {
-                eprintln!(
+                debug!(
                     "   (truncating {} matches to {} due to cutoff value)",
                     l,
                     results.len()
@@ -1624,11 +3924,11 @@ impl VariantModel {
         if self.debug >= 2 {
             for (i, result) in results.iter().enumerate() {
                 if let Some(vocabitem) = self.decoder.get(result.vocab_id as usize) {
-                    eprintln!(
+                    debug!(
                         "   (ranked #{}, variant={}, score={}, distance_score={}, freq_score={})",
                         i + 1,
                         vocabitem.text,
-                        result.score(freq_weight),
+                        result.score(params.freq_weight),
                         result.dist_score,
                         result.freq_score
                     );
@@ -1642,7 +3942,7 @@ impl VariantModel {
                 .duration_since(begintime.expect("begintime"))
                 .expect("clock can't go backwards")
                 .as_micros();
-            eprintln!(
+            debug!(
                 " (scored and ranked {} results in {} μs)",
                 results.len(),
                 duration
@@ -1654,18 +3954,49 @@ impl VariantModel {
 
     /// Rescore results according to confusables
     pub fn rescore_confusables(&self, results: &mut Vec<VariantResult>, input: &str) {
+        self.apply_confusables(input, results);
+    }
+
+    /// Applies the confusable weights to the distance scores of the given candidates for the
+    /// input (see [`Self::compute_confusable_weight()`]). This is the reweighting
+    /// [`Self::find_variants()`] does, but it can also be used independently for candidates
+    /// obtained elsewhere. The candidates are not reranked, use [`Self::rank_results()`] for that.
+    pub fn apply_confusables(&self, input: &str, candidates: &mut [VariantResult]) {
         if self.debug >= 2 {
-            eprintln!("   (rescoring with confusable weights)");
+            debug!("   (rescoring with confusable weights)");
         }
-        for result in results.iter_mut() {
-            result.dist_score *= self.compute_confusable_weight(input, result.vocab_id);
+        for result in candidates.iter_mut() {
+            let weight = self.compute_confusable_weight(input, result.vocab_id);
+            result.dist_score *= weight;
+            if let Some(explanation) = result.explanation.as_mut() {
+                explanation.confusable_weight *= weight;
+            }
         }
     }
 
     /// Sorts a result vector of (VocabId, distance_score, freq_score)
-    /// in decreasing order (best result first)
+    /// in decreasing order (best result first). Ties are broken deterministically, see [`Self::rank_cmp()`].
     pub fn rank_results(&self, results: &mut Vec<VariantResult>, freq_weight: f32) {
-        results.sort_by(|a, b| a.rank_cmp(&b, freq_weight).expect("ordering"));
+        results.sort_by(|a, b| self.rank_cmp(a, b, freq_weight));
+    }
+
+    /// Compares two results for ranking like [`VariantResult::rank_cmp()`], but breaks ties so the
+    /// order is the same across runs and platforms: results from the lexicon that was loaded first
+    /// come first, then results with a higher absolute frequency, then results in lexicographical order of their text.
+    pub fn rank_cmp(&self, a: &VariantResult, b: &VariantResult, freq_weight: f32) -> Ordering {
+        a.rank_cmp(b, freq_weight)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                let a_item = &self.decoder[a.vocab_id as usize];
+                let b_item = &self.decoder[b.vocab_id as usize];
+                a_item
+                    .lexindex
+                    .trailing_zeros()
+                    .cmp(&b_item.lexindex.trailing_zeros())
+                    .then_with(|| b_item.frequency.cmp(&a_item.frequency))
+                    .then_with(|| a_item.text.cmp(&b_item.text))
+                    .then_with(|| a.vocab_id.cmp(&b.vocab_id))
+            })
     }
 
     /// Expand variants, adding all references for variants
@@ -1674,50 +4005,47 @@ impl VariantModel {
     /// The results list does not need to be sorted yet. This function may yield
     /// duplicates. For performance, call this only when you know there are variants that
     /// may be expanded.
-    pub fn expand_variants(&self, mut results: Vec<VariantResult>) -> Vec<VariantResult> {
+    pub fn expand_variants(&self, results: Vec<VariantResult>) -> Vec<VariantResult> {
+        self.expand_variants_with(results, ExpansionFrequency::default())
+    }
+
+    /// Like [`Self::expand_variants()`], but with the specified policy to determine the
+    /// frequency of the expanded results. References that are themselves transparent are expanded
+    /// further, so chains of transparent variants are resolved to the first non-transparent item.
+    /// The `via` property of expanded results always holds the originally matched variant.
+    pub fn expand_variants_with(
+        &self,
+        mut results: Vec<VariantResult>,
+        expansion_frequency: ExpansionFrequency,
+    ) -> Vec<VariantResult> {
         if self.debug >= 3 {
-            eprintln!("   (expanding variants, resolving transparency)");
+            trace!("   (expanding variants, resolving transparency)");
         }
         let mut new_results = Vec::with_capacity(results.len());
         let mut count = 0;
+        let mut chain: Vec<VocabId> = Vec::new();
         for result in results.drain(..) {
             count += 1;
             let vocabitem = self
                 .decoder
                 .get(result.vocab_id as usize)
                 .expect("vocabitem must exist");
-            if let Some(variantrefs) = &vocabitem.variants {
-                for variantref in variantrefs.iter() {
-                    if let VariantReference::VariantOf((target_id, variant_dist_score)) = variantref
-                    {
-                        new_results.push(VariantResult {
-                            vocab_id: *target_id,
-                            dist_score: result.dist_score * variant_dist_score,
-                            freq_score: {
-                                //take the minimum frequency of the item we refer to and the one of this variant
-                                //note: frequency score is still absolute (not-normalised) at this point
-                                let targetitem = self
-                                    .decoder
-                                    .get(*target_id as usize)
-                                    .expect("vocabitem must exist");
-                                if (targetitem.frequency as f64) < result.freq_score {
-                                    targetitem.frequency as f64
-                                } else {
-                                    result.freq_score
-                                }
-                            },
-                            via: Some(result.vocab_id),
-                        });
-                    }
-                }
-            }
+            chain.clear();
+            chain.push(result.vocab_id);
+            self.expand_variant(
+                &result,
+                result.vocab_id,
+                expansion_frequency,
+                &mut chain,
+                &mut new_results,
+            );
             if !vocabitem.vocabtype.check(VocabType::TRANSPARENT) {
                 //add the original item
                 new_results.push(result);
             }
         }
         if self.debug >= 3 {
-            eprintln!(
+            trace!(
                 "    (expanded {} instances to {})",
                 count,
                 new_results.len()
@@ -1726,35 +4054,135 @@ impl VariantModel {
         new_results
     }
 
+    /// Adds the items the variant in the result refers to, recursing into references that are
+    /// transparent themselves. The chain holds the items expanded so far, to prevent cycles.
+    fn expand_variant(
+        &self,
+        result: &VariantResult,
+        via: VocabId,
+        expansion_frequency: ExpansionFrequency,
+        chain: &mut Vec<VocabId>,
+        new_results: &mut Vec<VariantResult>,
+    ) {
+        let vocabitem = self
+            .decoder
+            .get(result.vocab_id as usize)
+            .expect("vocabitem must exist");
+        if let Some(variantrefs) = &vocabitem.variants {
+            for variantref in variantrefs.iter() {
+                if let VariantReference::VariantOf((target_id, variant_dist_score)) = variantref {
+                    if chain.contains(target_id) {
+                        continue;
+                    }
+                    let targetitem = self
+                        .decoder
+                        .get(*target_id as usize)
+                        .expect("vocabitem must exist");
+                    //note: frequency score is still absolute (not-normalised) at this point
+                    let freq_score = match expansion_frequency {
+                        ExpansionFrequency::Minimum => {
+                            (targetitem.frequency as f64).min(result.freq_score)
+                        }
+                        ExpansionFrequency::Product => {
+                            targetitem.frequency as f64 * result.freq_score
+                        }
+                        ExpansionFrequency::Target => targetitem.frequency as f64,
+                    };
+                    let expanded = VariantResult {
+                        vocab_id: *target_id,
+                        dist_score: result.dist_score * variant_dist_score,
+                        freq_score,
+                        via: Some(via),
+                        edits: None,
+                        cased: None,
+                        explanation: result.explanation.clone(),
+                    };
+                    if targetitem.vocabtype.check(VocabType::TRANSPARENT) {
+                        //the reference is a transparent variant itself, resolve it further
+                        chain.push(*target_id);
+                        self.expand_variant(
+                            &expanded,
+                            via,
+                            expansion_frequency,
+                            chain,
+                            new_results,
+                        );
+                        chain.pop();
+                    } else {
+                        new_results.push(expanded);
+                    }
+                }
+            }
+        }
+    }
+
     /// compute weight over known confusables
     /// Should return 1.0 when there are no known confusables
     /// < 1.0 when there are unfavourable confusables
     /// > 1.0 when there are favourable confusables
     pub fn compute_confusable_weight(&self, input: &str, candidate: VocabId) -> f64 {
-        let mut weight = 1.0;
         if let Some(candidate) = self.decoder.get(candidate as usize) {
-            let editscript = shortest_edit_script(input, &candidate.text, false, false, false);
-            if self.debug >= 3 {
-                eprintln!(
-                    "   (editscript {} -> {}: {:?})",
-                    input, candidate.text, editscript
-                );
-            }
-            for confusable in self.confusables.iter() {
-                if confusable.found_in(&editscript) {
-                    if self.debug >= 3 {
-                        eprintln!(
-                            "   (input {} with candidate {} instantiates {:?})",
-                            input, &candidate.text, confusable
-                        );
-                    }
-                    weight *= confusable.weight;
+            self.confusable_weight(input, &candidate.text)
+        } else {
+            1.0
+        }
+    }
+
+    /// Like [`Self::compute_confusable_weight()`], but for an arbitrary candidate text
+    fn confusable_weight(&self, input: &str, candidate: &str) -> f64 {
+        let mut weight = 1.0;
+        let editscript = shortest_edit_script(input, candidate, false, false, false);
+        if self.debug >= 3 {
+            trace!(
+                "   (editscript {} -> {}: {:?})",
+                input,
+                candidate,
+                editscript
+            );
+        }
+        //case-insensitive confusables are tested against the edit script between the lowercased texts
+        let lowercased = if self
+            .confusables
+            .iter()
+            .any(|confusable| confusable.casefold)
+        {
+            Some((input.to_lowercase(), candidate.to_lowercase()))
+        } else {
+            None
+        };
+        let editscript_casefolded = lowercased
+            .as_ref()
+            .map(|(input, text)| shortest_edit_script(input, text, false, false, false));
+        for confusable in self.confusables.iter() {
+            let editscript = match &editscript_casefolded {
+                Some(editscript_casefolded) if confusable.casefold => editscript_casefolded,
+                _ => &editscript,
+            };
+            if confusable.found_in(editscript) {
+                if self.debug >= 3 {
+                    trace!(
+                        "   (input {} with candidate {} instantiates {:?})",
+                        input,
+                        candidate,
+                        confusable
+                    );
                 }
+                weight *= confusable.weight;
             }
         }
         weight
     }
 
+    /// Computes the edits that transform the input into the candidate
+    pub fn compute_edits(&self, input: &str, candidate: VocabId) -> Vec<EditOp> {
+        if let Some(candidate) = self.decoder.get(candidate as usize) {
+            let editscript = shortest_edit_script(input, &candidate.text, false, false, false);
+            EditOp::from_editscript(&editscript)
+        } else {
+            Vec::new()
+        }
+    }
+
     ///Adds the input item to the reverse index, as instantiation of the given vocabulary id
     pub fn add_to_reverse_index(
         &self,
@@ -1774,7 +4202,7 @@ impl VariantModel {
             _ => Variant::Unknown(input.to_string()),
         };
         if self.debug >= 2 {
-            eprintln!(
+            debug!(
                 "   (adding variant {:?} to reverse index for match {})",
                 variant, matched_vocab_id
             );
@@ -1786,8 +4214,14 @@ impl VariantModel {
         }
     }
 
-    ///Searches a text and returns all highest-ranking variants found in the text
+    ///Searches a text and returns all highest-ranking variants found in the text.
+    ///If [`SearchParameters::consolidate_matches`] is disabled, all n-gram matches (including
+    ///overlapping ones) are returned with their ranked variants, rather than a single most likely sequence.
     pub fn find_all_matches<'a>(&self, text: &'a str, params: &SearchParameters) -> Vec<Match<'a>> {
+        if let Some(pool) = self.outside_thread_pool() {
+            return pool.install(|| self.find_all_matches(text, params));
+        }
+
         let mut matches = Vec::new();
 
         if text.is_empty() {
@@ -1795,27 +4229,25 @@ impl VariantModel {
         }
 
         if self.debug >= 1 {
-            eprintln!("(finding all matches in text: {})", text);
+            info!("(finding all matches in text: {})", text);
         }
 
         if self.index.is_empty() {
-            eprintln!(
-                "ERROR: Model has not been built yet! Call build() before find_all_matches()"
-            );
+            error!("ERROR: Model has not been built yet! Call build() before find_all_matches()");
             return matches;
         }
 
         //Find the boundaries and classify their strength
-        let boundaries = find_boundaries(text);
-        let strengths = classify_boundaries(&boundaries);
+        let (boundaries, strengths) = self.find_classified_boundaries(text, params);
 
         if self.debug >= 2 {
-            eprintln!("  (boundaries: {:?})", boundaries);
-            eprintln!("  ( strenghts: {:?})", strengths);
+            debug!("  (boundaries: {:?})", boundaries);
+            debug!("  ( strenghts: {:?})", strengths);
         }
 
         let mut begin: usize = 0;
         let mut begin_index: usize = 0;
+        let mut sentence_index: usize = 0;
 
         //Compose the text into batches, each batch ends where a hard boundary is found
         for (i, (strength, boundary)) in strengths.iter().zip(boundaries.iter()).enumerate() {
@@ -1824,12 +4256,12 @@ impl VariantModel {
 
                 let boundaries = &boundaries[begin_index..i + 1];
                 if self.debug >= 2 {
-                    eprintln!(
+                    debug!(
                         "  (found hard boundary at {}:{}: {})",
                         boundary.offset.begin, boundary.offset.end, text_current
                     );
                     for boundary in boundaries.iter() {
-                        eprintln!(
+                        debug!(
                             "    (inner boundary {}:{})",
                             boundary.offset.begin, boundary.offset.end
                         );
@@ -1848,7 +4280,7 @@ impl VariantModel {
                         Some(boundary.offset.begin),
                     );
                     if self.debug >= 2 {
-                        eprintln!(
+                        debug!(
                             "  (processing {} {}-grams: {:?})",
                             currentorder_matches.len(),
                             order,
@@ -1864,18 +4296,18 @@ impl VariantModel {
                         currentorder_matches.iter_mut().for_each(|segment| {
                             if order == 1 || !redundant_match(segment, &batch_matches) {
                                 if self.debug >= 1 {
-                                    eprintln!(
+                                    info!(
                                         "   (----------- finding variants for: {} -----------)",
                                         segment.text
                                     );
                                 }
                                 let variants = self.find_variants(&segment.text, params);
                                 if self.debug >= 1 {
-                                    eprintln!("   (found {} variants)", variants.len());
+                                    info!("   (found {} variants)", variants.len());
                                 }
                                 segment.variants = Some(variants);
                             } else if self.debug >= 2 {
-                                eprintln!("   (skipping redundant match: {})", segment.text);
+                                debug!("   (skipping redundant match: {})", segment.text);
                             }
                         });
                     } else {
@@ -1883,18 +4315,18 @@ impl VariantModel {
                         currentorder_matches.par_iter_mut().for_each(|segment| {
                             if order == 1 || !redundant_match(segment, &batch_matches) {
                                 if self.debug >= 1 {
-                                    eprintln!(
+                                    info!(
                                         "   (----------- finding variants for: {} -----------)",
                                         segment.text
                                     );
                                 }
                                 let variants = self.find_variants(&segment.text, params);
                                 if self.debug >= 1 {
-                                    eprintln!("    (found {} variants)", variants.len());
+                                    info!("    (found {} variants)", variants.len());
                                 }
                                 segment.variants = Some(variants);
                             } else if self.debug >= 2 {
-                                eprintln!("   (skipping redundant match: {})", segment.text);
+                                debug!("   (skipping redundant match: {})", segment.text);
                             }
                         });
                     }
@@ -1907,9 +4339,30 @@ impl VariantModel {
                 }*/
 
                 let l = matches.len();
-                //consolidate the matches, finding a single segmentation that has the best (highest
-                //scoring) solution
-                if params.max_ngram > 1 || self.have_lm || !self.context_rules.is_empty() {
+                if !params.consolidate_matches {
+                    //return all matches, including overlapping ones, and leave the selection to the caller
+                    if self.debug >= 1 {
+                        info!("  (returning all matches, not consolidating)");
+                    }
+                    let mut batch_matches: Vec<Match<'a>> = batch_matches
+                        .into_iter()
+                        .filter(|m| m.variants.is_some()) //skip redundant matches
+                        .map(|mut m| {
+                            if !m.is_empty() {
+                                m.selected = Some(0); //select the first (highest ranking) option
+                            }
+                            m
+                        })
+                        .collect();
+                    batch_matches.sort_by_key(|m| (m.offset.begin, m.offset.end));
+                    matches.extend(batch_matches);
+                } else if params.max_ngram > 1
+                    || self.have_lm
+                    || !self.context_rules.is_empty()
+                    || params.nbest > 1
+                {
+                    //consolidate the matches, finding a single segmentation that has the best (highest
+                    //scoring) solution
                     //(debug will be handled in the called method)
                     matches.extend(
                         self.most_likely_sequence(
@@ -1924,36 +4377,184 @@ impl VariantModel {
                     );
                 } else {
                     if self.debug >= 1 {
-                        eprintln!("  (returning matches directly, no need to find most likely sequence for unigrams)");
+                        info!("  (returning matches directly, no need to find most likely sequence for unigrams)");
                     }
                     matches.extend(batch_matches.into_iter().map(|mut m| {
-                        m.selected = Some(0); //select the first (highest ranking) option
+                        if !m.is_empty() {
+                            m.selected = Some(0); //select the first (highest ranking) option
+                        }
                         m
                     }));
                 }
                 if self.debug >= 1 {
-                    eprintln!("  (added sequence of {} matches)", matches.len() - l);
+                    info!("  (added sequence of {} matches)", matches.len() - l);
+                }
+
+                if params.sentence_confidence && params.consolidate_matches && matches.len() > l {
+                    //sequences that were not decoded (e.g. unigrams only) have no confidence yet
+                    if matches[l].confidence.is_none() {
+                        let score = self.sentence_confidence_score(&matches[l..], params);
+                        matches[l].confidence = Some(SentenceConfidence {
+                            score,
+                            lm_perplexity: None,
+                            context_score: None,
+                        });
+                    }
+                }
+
+                //assign sentence and token indices to the matches of this batch
+                for m in matches[l..].iter_mut() {
+                    m.oov = m.selected.is_none() && !preserved_input(m.text, params);
+                    m.sentence_index = sentence_index;
+                    m.token_index = boundaries
+                        .iter()
+                        .filter(|boundary| boundary.offset.end <= m.offset.begin)
+                        .count();
+                    if params.debug_normalization {
+                        m.normalized = Some(self.normalize(m.text));
+                    }
                 }
 
+                sentence_index += 1;
                 begin = boundary.offset.end; //(the hard boundary itself is not included in any variant/sequence matching)
                 begin_index = i + 1
             }
         }
 
+        if params.global_context_rules && params.consolidate_matches {
+            if self.debug >= 1 {
+                info!("(applying context rules over all matches)");
+            }
+            self.apply_context_rules_global(&mut matches);
+        }
+
         if self.debug >= 1 {
-            eprintln!("(returning {} matches)", matches.len());
+            info!("(returning {} matches)", matches.len());
             if self.debug >= 2 {
-                eprintln!(" (MATCHES={:?})", matches);
+                debug!(" (MATCHES={:?})", matches);
             }
         }
-        if params.unicodeoffsets {
-            if self.debug >= 1 {
-                eprintln!("(remapping UTF-8 offsets to unicodepoints)");
+        if self.debug >= 1 && params.unicodeoffsets {
+            info!("(remapping UTF-8 offsets to unicodepoints)");
+        }
+        remap_offsets_to_unicodepoints(text, matches, params.unicodeoffsets)
+    }
+
+    /// Searches a text read from the reader, like [`Self::find_all_matches()`], but processes it
+    /// incrementally so neither the whole text nor all matches need to be held in memory. Text is
+    /// buffered until a hard boundary is found, all text up to and including that boundary is then
+    /// searched and the matches are passed to the callback (in order) before reading on.
+    /// Offsets and sentence indices are relative to the whole text.
+    pub fn find_matches_streaming<R: BufRead>(
+        &self,
+        mut reader: R,
+        params: &SearchParameters,
+        mut callback: impl FnMut(Match<'_>),
+    ) -> Result<(), std::io::Error> {
+        let mut buffer = String::new();
+        //global offsets of the start of the buffer
+        let mut byteoffset: usize = 0;
+        let mut charoffset: usize = 0;
+        let mut sentence_index: usize = 0;
+        loop {
+            let eof = reader.read_line(&mut buffer)? == 0;
+            let end = if eof || buffer.len() >= STREAMING_BUFFER_LIMIT {
+                buffer.len()
+            } else if let Some(end) = self.last_hard_boundary(&buffer, params) {
+                end
+            } else {
+                continue;
+            };
+            if end > 0 {
+                if self.debug >= 1 {
+                    info!("(streaming: processing {} bytes at {})", end, byteoffset);
+                }
+                let text = &buffer[..end];
+                let mut sentences = 0;
+                for mut m in self.find_all_matches(text, params) {
+                    sentences = std::cmp::max(sentences, m.sentence_index + 1);
+                    m.sentence_index += sentence_index;
+                    m.offset.shift(if params.unicodeoffsets {
+                        charoffset
+                    } else {
+                        byteoffset
+                    });
+                    if let Some(offset) = m.byteoffset.as_mut() {
+                        offset.shift(byteoffset);
+                    }
+                    if let Some(offset) = m.charoffset.as_mut() {
+                        offset.shift(charoffset);
+                    }
+                    callback(m);
+                }
+                sentence_index += sentences;
+                byteoffset += end;
+                charoffset += text.chars().count();
+                buffer.drain(..end);
+            }
+            if eof {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Finds the boundaries in the text and classifies their strength, as configured in the
+    /// search parameters. If [`SearchParameters::skip_unknown_scripts`] is set, runs of characters
+    /// not covered by the alphabet are protected and their boundaries are always hard.
+    fn find_classified_boundaries<'a>(
+        &self,
+        text: &'a str,
+        params: &SearchParameters,
+    ) -> (Vec<Match<'a>>, Vec<BoundaryStrength>) {
+        let mut spans: Vec<(usize, usize)> = params
+            .protected_patterns
+            .iter()
+            .flat_map(|pattern| pattern.find_iter(text).map(|m| (m.start(), m.end())))
+            .collect();
+        let runs = if params.skip_unknown_scripts {
+            let mut known: HashMap<char, bool> = HashMap::new();
+            for c in text.chars().filter(|c| c.is_alphabetic()) {
+                known.entry(c).or_insert_with(|| self.in_alphabet(c));
             }
-            remap_offsets_to_unicodepoints(text, matches)
+            find_unknown_runs(text, |c| known.get(&c).copied().unwrap_or(true))
         } else {
-            matches
+            Vec::new()
+        };
+        spans.extend(runs.iter().copied());
+        let boundaries = find_boundaries_with_spans(text, spans, &params.boundary_config);
+        let mut strengths = classify_boundaries(&boundaries, &params.boundary_config);
+        for (boundary, strength) in boundaries.iter().zip(strengths.iter_mut()) {
+            if runs
+                .iter()
+                .any(|(begin, end)| boundary.offset.begin < *end && *begin < boundary.offset.end)
+            {
+                *strength = BoundaryStrength::Hard;
+            }
         }
+        (boundaries, strengths)
+    }
+
+    /// Tests whether a character is covered by the alphabet, i.e. it is not encoded as unknown.
+    /// Characters the alphabet explicitly marks as unknown or ignored count as covered.
+    fn in_alphabet(&self, c: char) -> bool {
+        let text = c.to_string();
+        let text = self.encodable_text(&text);
+        text.unknown_chars(&self.alphabet_matcher)
+            .iter()
+            .all(|c| self.alphabet_unk.iter().any(|unk| unk.contains(*c)))
+    }
+
+    /// Returns the end offset of the last hard boundary in the text, not counting the final
+    /// boundary, which is always considered hard as the text may still continue.
+    fn last_hard_boundary(&self, text: &str, params: &SearchParameters) -> Option<usize> {
+        let (boundaries, strengths) = self.find_classified_boundaries(text, params);
+        boundaries
+            .iter()
+            .zip(strengths.iter())
+            .rev()
+            .skip(1)
+            .find(|(_, strength)| **strength == BoundaryStrength::Hard)
+            .map(|(boundary, _)| boundary.offset.end)
     }
 
     /*
@@ -2011,7 +4612,7 @@ impl VariantModel {
     /// input text rather than corrected output from other parts.
     fn rescore_input_context<'a>(&self, matches: &mut Vec<Match<'a>>, boundaries: &[Match<'a>], params: &SearchParameters) {
         if self.debug >= 2 {
-            eprintln!("   (rescoring variants according to input context)");
+            debug!("   (rescoring variants according to input context)");
         }
         self.set_match_boundaries(matches, boundaries);
         let matches_with_context = self.find_input_context(matches);
@@ -2045,7 +4646,7 @@ impl VariantModel {
                         }
                         tokens.push(right);
 
-                        let (_lm_logprob, perplexity) = self.lm_score_tokens(&tokens);
+                        let (_lm_logprob, perplexity) = self.lm_score_tokens(&tokens, params);
 
                         if perplexity < best_perplexity {
                             best_perplexity = perplexity;
@@ -2055,7 +4656,7 @@ impl VariantModel {
                 }
             }
             if self.debug >= 2 {
-                eprintln!("    (processing {} variants for match {}, best_perplexity={})", perplexities.len(), i+1, best_perplexity);
+                debug!("    (processing {} variants for match {}, best_perplexity={})", perplexities.len(), i+1, best_perplexity);
             }
 
             let m = matches.get_mut(*i).expect("match must exist");
@@ -2075,7 +4676,7 @@ impl VariantModel {
                 //                      fixed weight for variant model ------------------^
                 if self.debug >= 3 {
                     if let Some(vocabitem) = self.decoder.get(*vocab_id as usize) {
-                        eprintln!("      (leftcontext={:?}, variant={}, rightcontext={:?}, oldscore={}, score={}, norm_lm_score={}, perplexity={})", context.left, vocabitem.text, context.right, oldscore, score, lmscore, perplexity);
+                        trace!("      (leftcontext={:?}, variant={}, rightcontext={:?}, oldscore={}, score={}, norm_lm_score={}, perplexity={})", context.left, vocabitem.text, context.right, oldscore, score, lmscore, perplexity);
                     }
                 }
             }
@@ -2095,7 +4696,7 @@ impl VariantModel {
         input_text: &str,
     ) -> Vec<Match<'a>> {
         if self.debug >= 2 {
-            eprintln!(
+            debug!(
                 "(building FST for finding most likely sequence in range {}:{})",
                 begin_offset, end_offset
             );
@@ -2130,7 +4731,7 @@ impl VariantModel {
         }
 
         if self.debug >= 2 {
-            eprintln!(
+            debug!(
                 " (added {} states ({} boundaries), not including the start state)",
                 states.len(),
                 boundaries.len()
@@ -2200,7 +4801,7 @@ impl VariantModel {
                             .text
                             .as_str();
                         variant_text += format!(" ({})", output_symbol).as_str(); //we encode the output symbol in the text otherwise the symbol table returns the old match
-                        eprintln!(
+                        trace!(
                             "   (transition state {}->{}: {} ({}) -> {} and variant score {})",
                             prevstate,
                             nextstate,
@@ -2239,9 +4840,14 @@ impl VariantModel {
                 let cost: f32 = n as f32 + 1.0;
 
                 if self.debug >= 3 {
-                    eprintln!(
+                    trace!(
                         "   (transition state {}->{}: {} ({}) -> OOV ({}) and score {})",
-                        prevstate, nextstate, m.text, input_symbol, output_symbol, cost
+                        prevstate,
+                        nextstate,
+                        m.text,
+                        input_symbol,
+                        output_symbol,
+                        cost
                     );
                     let mut variant_text = String::from_str(m.text).expect("from str");
                     variant_text += format!(" ({})", output_symbol).as_str(); //we encode the output symbol in the text otherwise the symbol table returns the old match
@@ -2277,7 +4883,7 @@ impl VariantModel {
 
         if output_symbols.len() == 1 {
             if self.debug >= 2 {
-                eprintln!("   (no output symbols found, FST not needed, aborting)");
+                debug!("   (no output symbols found, FST not needed, aborting)");
             }
             //we have no output symbols, building an FST is not needed, just return the input
             return matches;
@@ -2287,10 +4893,10 @@ impl VariantModel {
         //language modelling (considering context) is applied in a separate step later
 
         if self.debug >= 3 {
-            eprintln!(" (computed FST: {:?})", fst);
-            eprintln!("   (symtab_in={:?})", symtab_in);
-            eprintln!("   (symtab_out={:?})", symtab_out);
-            eprintln!(" (finding shortest path)");
+            trace!(" (computed FST: {:?})", fst);
+            trace!("   (symtab_in={:?})", symtab_in);
+            trace!("   (symtab_out={:?})", symtab_out);
+            trace!(" (finding shortest path)");
             fst.set_input_symbols(Arc::new(symtab_in));
             fst.set_output_symbols(Arc::new(symtab_out));
             let input_text_filename = input_text
@@ -2310,41 +4916,17 @@ impl VariantModel {
                 panic!("FST draw error: {}", e);
             }
         }
-        let fst: VectorFst<TropicalWeight> = shortest_path_with_config(
-            &fst,
-            ShortestPathConfig::default().with_nshortest(params.max_seq),
-        )
-        .expect("computing shortest path fst");
-        let mut sequences: Vec<Sequence> = Vec::new();
-        let mut best_lm_perplexity: f64 = 999999.0; //to be minimised
-        let mut best_variant_cost: f32 = (boundaries.len() - 1) as f32 * 2.0; //worst score, to be improved (to be minimised)
-        let mut best_context_score: f64 = 0.0; //to be maximised
-
-        for (i, path) in fst.paths_iter().enumerate() {
-            //iterates over the n shortest path hypotheses (does not return them in weighted order)
-            let variant_cost: f32 = *path.weight.value();
-            let mut sequence = Sequence::new(variant_cost);
-            if self.debug >= 3 {
-                eprintln!("  (#{}, path: {:?})", i + 1, path);
-            }
-            for output_symbol in path.olabels.iter() {
-                let output_symbol = output_symbols
-                    .get(*output_symbol as usize)
-                    .expect("expected valid output symbol");
-                sequence.output_symbols.push(output_symbol.clone());
-            }
+        let mut sequences = self.nbest_sequences(&fst, &output_symbols, params);
+        for sequence in sequences.iter_mut() {
             if self.have_lm && params.lm_weight > 0.0 {
                 //Apply the language model, considers context
-                let (lm_logprob, perplexity) = self.lm_score(&sequence, &boundaries);
+                let (lm_logprob, perplexity) = self.lm_score(sequence, boundaries, params);
                 sequence.lm_logprob = lm_logprob;
                 sequence.perplexity = perplexity;
-                if sequence.perplexity < best_lm_perplexity {
-                    best_lm_perplexity = sequence.perplexity;
-                }
             }
             if !self.context_rules.is_empty() {
                 //Apply context rules and apply tags (if any), considers context
-                let (context_score, sequence_results) = self.test_context_rules(&sequence);
+                let (context_score, sequence_results) = self.test_context_rules(sequence, &matches);
                 sequence.context_score = context_score;
                 sequence.tags = sequence_results
                     .into_iter()
@@ -2362,78 +4944,19 @@ impl VariantModel {
                     })
                     .collect();
                 if self.debug >= 3 && sequence.context_score != 1.0 {
-                    eprintln!("   (context_score: {})", sequence.context_score);
+                    trace!("   (context_score: {})", sequence.context_score);
                 }
             }
-            if variant_cost < best_variant_cost {
-                best_variant_cost = variant_cost;
-            }
-            if sequence.context_score > best_context_score {
-                best_context_score = sequence.context_score;
-            }
-            sequences.push(sequence);
         }
 
-        let mut debug_ranked: Option<Vec<(Sequence, f64, f64, f64, f64)>> = if self.debug >= 1 {
-            Some(Vec::new())
-        } else {
-            None
-        };
-
-        //Compute the normalized scores
-        let mut best_score: f64 = -99999999.0; //to be maximised
-        let mut best_sequence: Option<Sequence> = None;
-        for sequence in sequences.into_iter() {
-            //we normalize both LM and variant model scores so the best score corresponds with 1.0 (in non-logarithmic terms, 0.0 in logarithmic space). We take the natural logarithm for more numerical stability and easier computation.
-            let norm_lm_score: f64 = if self.have_lm && params.lm_weight > 0.0 {
-                (best_lm_perplexity / sequence.perplexity).ln()
-            } else {
-                0.0
-            };
-            let norm_variant_score: f64 =
-                (best_variant_cost as f64 / sequence.variant_cost as f64).ln();
-            let norm_context_score: f64 = (sequence.context_score / best_context_score).ln();
-
-            //then we interpret the score as a kind of pseudo-probability and minimize the joint
-            //probability (the product; addition in log-space)
-            let score = if (!self.have_lm || params.lm_weight == 0.0)
-                && (self.context_rules.is_empty() || params.contextrules_weight == 0.0)
-            {
-                //no need for full computation, take a shortcut:
-                norm_variant_score
-            } else {
-                (params.lm_weight as f64 * norm_lm_score
-                    + params.variantmodel_weight as f64 * norm_variant_score
-                    + params.contextrules_weight as f64 * norm_context_score)
-                    / (params.lm_weight as f64
-                        + params.variantmodel_weight as f64
-                        + params.contextrules_weight as f64) //note: the denominator isn't really relevant for finding the best score but normalizes the output for easier interpretability (=geometric mean)
-            };
-            if self.debug >= 1 {
-                debug_ranked.as_mut().unwrap().push((
-                    sequence.clone(),
-                    norm_lm_score,
-                    norm_variant_score,
-                    norm_context_score,
-                    score,
-                ));
-            }
-            if score > best_score || best_sequence.is_none() {
-                best_score = score;
-                best_sequence = Some(sequence);
-            }
-        }
+        let ranked = self.rank_sequences(sequences, (boundaries.len() - 1) as f32 * 2.0, params);
 
         if self.debug >= 1 {
             //debug mode: output all candidate sequences and their scores in order
-            debug_ranked
-                .as_mut()
-                .unwrap()
-                .sort_by(|a, b| b.4.partial_cmp(&a.4).unwrap_or(Ordering::Equal)); //sort by score
             for (i, (sequence, norm_lm_score, norm_variant_score, norm_context_score, score)) in
-                debug_ranked.unwrap().into_iter().enumerate()
+                ranked.iter().enumerate()
             {
-                eprintln!("  (#{}, final_score={}, norm_lm_score={} (perplexity={}, logprob={}, weight={}), norm_variant_score={} (variant_cost={}, weight={}), norm_context_score={} (context_score={}, weight={})", i+1, score.exp(), norm_lm_score.exp(), sequence.perplexity, sequence.lm_logprob, params.lm_weight,  norm_variant_score.exp(), sequence.variant_cost, params.variantmodel_weight, norm_context_score.exp(), sequence.context_score, params.contextrules_weight);
+                info!("  (#{}, final_score={}, norm_lm_score={} (perplexity={}, logprob={}, weight={}), norm_variant_score={} (variant_cost={}, weight={}), norm_context_score={} (context_score={}, weight={})", i+1, score.exp(), norm_lm_score.exp(), sequence.perplexity, sequence.lm_logprob, params.lm_weight,  norm_variant_score.exp(), sequence.variant_cost, params.variantmodel_weight, norm_context_score.exp(), sequence.context_score, params.contextrules_weight);
                 let mut inputtext: String = String::new();
                 let mut text: String = String::new();
                 for (j, output_symbol) in sequence.output_symbols.iter().enumerate() {
@@ -2466,14 +4989,41 @@ impl VariantModel {
                     inputtext += " | ";
                     text += " | ";
                 }
-                eprintln!("    (text_out={})", text);
-                eprintln!("    (text_in={})", inputtext);
+                info!("    (text_out={})", text);
+                info!("    (text_in={})", inputtext);
             }
         }
 
+        let alternatives: Vec<SequenceAlternative> = if params.nbest > 1 {
+            ranked
+                .iter()
+                .take(params.nbest)
+                .map(|(sequence, .., score)| SequenceAlternative {
+                    text: self.sequence_text(sequence, &matches, boundaries),
+                    score: score.exp(),
+                    variant_cost: sequence.variant_cost,
+                    lm_perplexity: if self.have_lm && params.lm_weight > 0.0 {
+                        Some(sequence.perplexity)
+                    } else {
+                        None
+                    },
+                    context_score: if !self.context_rules.is_empty() {
+                        Some(sequence.context_score)
+                    } else {
+                        None
+                    },
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
         //return matches corresponding to best sequence
-        let best_sequence = best_sequence.expect("there must be a best sequence");
-        best_sequence
+        let (best_sequence, ..) = ranked
+            .into_iter()
+            .next()
+            .expect("there must be a best sequence");
+        let mut result: Vec<Match<'a>> = best_sequence
             .output_symbols
             .iter()
             .enumerate()
@@ -2483,6 +5033,18 @@ impl VariantModel {
                     .expect("match should be in bounds");
                 let mut m = m.clone();
                 m.selected = osym.variant_index;
+                if params.explain {
+                    if let (Some(variants), Some(selected)) = (m.variants.as_mut(), m.selected) {
+                        if let Some(explanation) = variants[selected].explanation.as_mut() {
+                            if self.have_lm && params.lm_weight > 0.0 {
+                                explanation.lm_perplexity = Some(best_sequence.perplexity);
+                            }
+                            if !self.context_rules.is_empty() {
+                                explanation.context_score = Some(best_sequence.context_score);
+                            }
+                        }
+                    }
+                }
                 if !best_sequence.tags.is_empty() {
                     if let Some(tags) = best_sequence.tags.get(i) {
                         m.tag = tags.iter().map(|x| x.0).collect();
@@ -2491,9 +5053,295 @@ impl VariantModel {
                 }
                 m
             })
+            .collect();
+        if params.sentence_confidence {
+            let score = self.sentence_confidence_score(&result, params);
+            if let Some(first) = result.first_mut() {
+                first.confidence = Some(SentenceConfidence {
+                    score,
+                    lm_perplexity: if self.have_lm && params.lm_weight > 0.0 {
+                        Some(best_sequence.perplexity)
+                    } else {
+                        None
+                    },
+                    context_score: if !self.context_rules.is_empty() {
+                        Some(best_sequence.context_score)
+                    } else {
+                        None
+                    },
+                });
+            }
+        }
+        if let Some(first) = result.first_mut() {
+            first.alternatives = alternatives;
+        }
+        result
+    }
+
+    /// Computes the mean score of the selected variants of the matches of a sentence, weighted by the
+    /// number of tokens each match covers, see [`SentenceConfidence::score`]
+    fn sentence_confidence_score(&self, matches: &[Match], params: &SearchParameters) -> f64 {
+        let mut total = 0.0;
+        let mut tokens = 0;
+        for m in matches.iter() {
+            let n = max(m.n, 1);
+            if let Some(solution) = m.solution() {
+                total += n as f64 * solution.score(params.freq_weight).min(1.0);
+            } else if preserved_input(m.text, params) {
+                continue;
+            }
+            tokens += n;
+        }
+        if tokens == 0 {
+            1.0
+        } else {
+            total / tokens as f64
+        }
+    }
+
+    /// Composes the text of a sequence as found by [`Self::most_likely_sequence()`], out of
+    /// vocabulary tokens are copied from the input and the boundaries between tokens are retained.
+    fn sequence_text(
+        &self,
+        sequence: &Sequence,
+        matches: &[Match],
+        boundaries: &[Match],
+    ) -> String {
+        let mut text = String::new();
+        for (i, output_symbol) in sequence.output_symbols.iter().enumerate() {
+            if output_symbol.vocab_id > 0 {
+                text += self
+                    .decoder
+                    .get(output_symbol.vocab_id as usize)
+                    .expect("vocab")
+                    .text
+                    .as_str();
+            } else if let Some(m) = matches.get(output_symbol.match_index) {
+                text += m.text;
+            }
+            if i + 1 < sequence.output_symbols.len() {
+                if let Some(boundary) = boundaries.get(output_symbol.boundary_index) {
+                    text += boundary.text;
+                }
+            }
+        }
+        text
+    }
+
+    /// Finds the n best paths (as configured by [`SearchParameters::max_seq`]) through a finite
+    /// state transducer as built by [`Self::most_likely_sequence()`] or [`Self::decode_sequence()`],
+    /// and returns them as sequences of output symbols with their variant cost. The paths are not
+    /// returned in weighted order.
+    fn nbest_sequences(
+        &self,
+        fst: &VectorFst<TropicalWeight>,
+        output_symbols: &[OutputSymbol],
+        params: &SearchParameters,
+    ) -> Vec<Sequence> {
+        let fst: VectorFst<TropicalWeight> = shortest_path_with_config(
+            fst,
+            ShortestPathConfig::default().with_nshortest(params.max_seq),
+        )
+        .expect("computing shortest path fst");
+        fst.paths_iter()
+            .enumerate()
+            .map(|(i, path)| {
+                if self.debug >= 3 {
+                    trace!("  (#{}, path: {:?})", i + 1, path);
+                }
+                let mut sequence = Sequence::new(*path.weight.value());
+                for output_symbol in path.olabels.iter() {
+                    let output_symbol = output_symbols
+                        .get(*output_symbol as usize)
+                        .expect("expected valid output symbol");
+                    sequence.output_symbols.push(output_symbol.clone());
+                }
+                sequence
+            })
             .collect()
     }
 
+    /// Computes the combined score for each of the candidate sequences, weighing the variant cost,
+    /// language model perplexity and context rule score as configured in the search parameters.
+    /// Each component is normalized so the best candidate gets 1.0 (0.0 in log space). Returns the
+    /// sequences along with their normalized LM, variant and context scores and the combined score
+    /// (all in log space), ranked by decreasing combined score.
+    fn rank_sequences(
+        &self,
+        sequences: Vec<Sequence>,
+        worst_variant_cost: f32,
+        params: &SearchParameters,
+    ) -> Vec<(Sequence, f64, f64, f64, f64)> {
+        let best_lm_perplexity: f64 = sequences
+            .iter()
+            .map(|sequence| sequence.perplexity)
+            .fold(999999.0, f64::min); //to be minimised
+        let best_variant_cost: f32 = sequences
+            .iter()
+            .map(|sequence| sequence.variant_cost)
+            .fold(worst_variant_cost, f32::min); //to be minimised
+        let best_context_score: f64 = sequences
+            .iter()
+            .map(|sequence| sequence.context_score)
+            .fold(0.0, f64::max); //to be maximised
+
+        let mut ranked: Vec<(Sequence, f64, f64, f64, f64)> = sequences
+            .into_iter()
+            .map(|sequence| {
+                //we normalize both LM and variant model scores so the best score corresponds with 1.0 (in non-logarithmic terms, 0.0 in logarithmic space). We take the natural logarithm for more numerical stability and easier computation.
+                let norm_lm_score: f64 = if self.have_lm && params.lm_weight > 0.0 {
+                    (best_lm_perplexity / sequence.perplexity).ln()
+                } else {
+                    0.0
+                };
+                let norm_variant_score: f64 =
+                    (best_variant_cost as f64 / sequence.variant_cost as f64).ln();
+                let norm_context_score: f64 = (sequence.context_score / best_context_score).ln();
+
+                //then we interpret the score as a kind of pseudo-probability and minimize the joint
+                //probability (the product; addition in log-space)
+                let score = if (!self.have_lm || params.lm_weight == 0.0)
+                    && (self.context_rules.is_empty() || params.contextrules_weight == 0.0)
+                {
+                    //no need for full computation, take a shortcut:
+                    norm_variant_score
+                } else {
+                    (params.lm_weight as f64 * norm_lm_score
+                        + params.variantmodel_weight as f64 * norm_variant_score
+                        + params.contextrules_weight as f64 * norm_context_score)
+                        / (params.lm_weight as f64
+                            + params.variantmodel_weight as f64
+                            + params.contextrules_weight as f64) //note: the denominator isn't really relevant for finding the best score but normalizes the output for easier interpretability (=geometric mean)
+                };
+                (
+                    sequence,
+                    norm_lm_score,
+                    norm_variant_score,
+                    norm_context_score,
+                    score,
+                )
+            })
+            .collect();
+        //stable sort, so the first of equally scoring sequences is retained as the best one
+        ranked.sort_by(|a, b| b.4.total_cmp(&a.4));
+        ranked
+    }
+
+    /// Decodes the most likely sequence through a lattice of candidates from an external source,
+    /// holding one [`TokenHypotheses`] per input token. This uses the same decoder as
+    /// [`Self::find_all_matches()`]: the [`SearchParameters::max_seq`] best paths according to the
+    /// candidate costs are rescored with the language model and the context rules (if any), weighted
+    /// by [`SearchParameters::variantmodel_weight`], [`SearchParameters::lm_weight`] and
+    /// [`SearchParameters::contextrules_weight`].
+    ///
+    /// Returns the index of the selected candidate for each position in the lattice. Positions
+    /// without any candidates are treated as out-of-vocabulary and get index 0.
+    pub fn decode_sequence(
+        &self,
+        lattice: &[TokenHypotheses],
+        params: &SearchParameters,
+    ) -> Vec<usize> {
+        if lattice.is_empty() {
+            return Vec::new();
+        }
+
+        //Build a finite state transducer, a simple chain with one state per position
+        let mut fst = VectorFst::<TropicalWeight>::new();
+        let start = fst.add_state();
+        fst.set_start(start).expect("set start state");
+
+        let mut output_symbols: Vec<OutputSymbol> = vec![
+            OutputSymbol {
+                vocab_id: 0,
+                symbol: 0,
+                match_index: 0,
+                variant_index: None,
+                boundary_index: 0,
+            }, //first entry is a dummy entry because the 0 symbol is reserved for epsilon
+        ];
+
+        let mut prevstate = start;
+        for (position, hypotheses) in lattice.iter().enumerate() {
+            let nextstate = fst.add_state();
+            let input_symbol = (position + 1) as u32;
+            if hypotheses.candidates.is_empty() {
+                //OOV emission, same cost as in most_likely_sequence()
+                let output_symbol = output_symbols.len() as u32;
+                output_symbols.push(OutputSymbol {
+                    vocab_id: 0,
+                    symbol: output_symbol,
+                    match_index: position,
+                    variant_index: None,
+                    boundary_index: position,
+                });
+                fst.add_tr(
+                    prevstate,
+                    Tr::new(input_symbol, output_symbol, 2.0, nextstate),
+                )
+                .expect("adding transition");
+            }
+            for (candidate_index, candidate) in hypotheses.candidates.iter().enumerate() {
+                let output_symbol = output_symbols.len() as u32;
+                output_symbols.push(OutputSymbol {
+                    vocab_id: candidate.vocab_id.unwrap_or(0),
+                    symbol: output_symbol,
+                    match_index: position,
+                    variant_index: Some(candidate_index),
+                    boundary_index: position,
+                });
+                //base cost of 1 for the single input token covered, as in most_likely_sequence()
+                fst.add_tr(
+                    prevstate,
+                    Tr::new(input_symbol, output_symbol, 1.0 + candidate.cost, nextstate),
+                )
+                .expect("adding transition");
+            }
+            prevstate = nextstate;
+        }
+        fst.set_final(prevstate, 0.0).expect("set end state");
+
+        let mut sequences = self.nbest_sequences(&fst, &output_symbols, params);
+        for sequence in sequences.iter_mut() {
+            if self.have_lm && params.lm_weight > 0.0 {
+                let mut tokens: Vec<Option<VocabId>> =
+                    Vec::with_capacity(sequence.output_symbols.len() + 2);
+                tokens.push(Some(BOS));
+                for output_symbol in sequence.output_symbols.iter() {
+                    self.push_lm_tokens(output_symbol.vocab_id, &mut tokens);
+                }
+                tokens.push(Some(EOS));
+                let (lm_logprob, perplexity) = self.lm_score_tokens(&tokens, params);
+                sequence.lm_logprob = lm_logprob;
+                sequence.perplexity = perplexity;
+            }
+            if !self.context_rules.is_empty() {
+                let tokens: Vec<(VocabId, u32, &str)> = sequence
+                    .output_symbols
+                    .iter()
+                    .map(|output_symbol| {
+                        self.context_rule_token(
+                            output_symbol.vocab_id,
+                            lattice[output_symbol.match_index].text.as_str(),
+                        )
+                    })
+                    .collect();
+                sequence.context_score = self.score_context_rules(&tokens).0;
+            }
+        }
+
+        self.rank_sequences(sequences, lattice.len() as f32 * 2.0, params)
+            .into_iter()
+            .next()
+            .map(|(sequence, ..)| {
+                sequence
+                    .output_symbols
+                    .iter()
+                    .map(|output_symbol| output_symbol.variant_index.unwrap_or(0))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     /// Favours or penalizes certain combinations of lexicon matches. matching words X and Y
     /// respectively with lexicons A and B might be favoured over other combinations.
     /// This returns either a bonus or penalty (number slightly above/below 1.0) score/
@@ -2501,23 +5349,75 @@ impl VariantModel {
     pub fn test_context_rules<'a>(
         &self,
         sequence: &Sequence,
+        matches: &[Match<'a>],
     ) -> (f64, Vec<Vec<PatternMatchResult>>) {
-        let sequence: Vec<(VocabId, u32)> = sequence
+        let sequence: Vec<(VocabId, u32, &str)> = sequence
             .output_symbols
             .iter()
             .map(|output_symbol| {
-                if output_symbol.vocab_id == 0 {
-                    (output_symbol.vocab_id, 0)
-                } else {
-                    if let Some(vocabvalue) = self.decoder.get(output_symbol.vocab_id as usize) {
-                        (output_symbol.vocab_id, vocabvalue.lexindex)
-                    } else {
-                        (output_symbol.vocab_id, 0)
-                    }
-                }
+                //out of vocabulary tokens are copied from the input
+                let input = matches
+                    .get(output_symbol.match_index)
+                    .map(|m| m.text)
+                    .unwrap_or("");
+                self.context_rule_token(output_symbol.vocab_id, input)
             })
             .collect();
+        self.score_context_rules(&sequence)
+    }
+
+    /// Returns the vocabulary ID, lexicon index and text of a token for context rule matching. Out
+    /// of vocabulary tokens (vocabulary ID 0) take the input text.
+    fn context_rule_token<'b>(
+        &'b self,
+        vocab_id: VocabId,
+        input: &'b str,
+    ) -> (VocabId, u32, &'b str) {
+        if vocab_id == 0 {
+            (vocab_id, 0, input)
+        } else if let Some(vocabvalue) = self.decoder.get(vocab_id as usize) {
+            (vocab_id, vocabvalue.lexindex, vocabvalue.text.as_str())
+        } else {
+            (vocab_id, 0, input)
+        }
+    }
+
+    /// Computes the context rule score for the sequence as a whole, and returns it along with the
+    /// context rule results for each item in the sequence (see [`Self::test_context_rules()`])
+    fn score_context_rules(
+        &self,
+        sequence: &[(VocabId, u32, &str)],
+    ) -> (f64, Vec<Vec<PatternMatchResult>>) {
+        let (found, sequence_results) = self.match_context_rules(sequence);
+
+        if !found {
+            (1.0, sequence_results) //just a shortcut to prevent unnecessary computation
+        } else {
+            (
+                //compute sum score
+                sequence_results
+                    .iter()
+                    .map(|x| {
+                        if !x.is_empty() {
+                            x[0].score //score is equal for all subelements (only tags differ), just grab first one
+                        } else {
+                            1.0
+                        }
+                    })
+                    .sum::<f32>() as f64
+                    / sequence.len() as f64,
+                sequence_results,
+            )
+        }
+    }
 
+    /// Matches all context rules against the sequence, consisting of the vocabulary ID (0 if out-of-vocabulary),
+    /// the lexicon index and the text of each token. Returns whether any rule matched, and for each item
+    /// in the sequence the context rule results that apply to it.
+    fn match_context_rules(
+        &self,
+        sequence: &[(VocabId, u32, &str)],
+    ) -> (bool, Vec<Vec<PatternMatchResult>>) {
         //The sequence will flag which items in the sequence have been covered by matches (non-empty vec)
         //and if so, what context rule scores and tags (inner vec) apply to that match. It's later used to compute
         //the final score
@@ -2526,12 +5426,12 @@ impl VariantModel {
         let mut found = false;
         for begin in 0..sequence.len() {
             for context_rule in self.context_rules.iter() {
-                if context_rule.matches(&sequence, begin, &mut sequence_results) {
+                if context_rule.matches(sequence, begin, &mut sequence_results) {
                     found = true;
                     if self.debug >= 2 {
                         let text: Vec<&str> = sequence
                             .iter()
-                            .map(|(vocab_id, _)| {
+                            .map(|(vocab_id, _, _)| {
                                 if *vocab_id == 0 {
                                     "<UNK>"
                                 } else {
@@ -2543,7 +5443,7 @@ impl VariantModel {
                                 }
                             })
                             .collect();
-                        eprintln!(
+                        debug!(
                             "    Context rule matches: {:?} <-- \"{}\" --> {:?}",
                             context_rule,
                             text.join(" | "),
@@ -2553,31 +5453,59 @@ impl VariantModel {
                 }
             }
         }
+        (found, sequence_results)
+    }
 
-        if !found {
-            (1.0, sequence_results) //just a shortcut to prevent unnecessary computation
-        } else {
-            (
-                //compute sum score
-                sequence_results
-                    .iter()
-                    .map(|x| {
-                        if !x.is_empty() {
-                            x[0].score //score is equal for all subelements (only tags differ), just grab first one
-                        } else {
-                            1.0
-                        }
-                    })
-                    .sum::<f32>() as f64
-                    / sequence.len() as f64,
-                sequence_results,
-            )
+    /// Re-runs context rule matching over the final sequence of matches as returned by
+    /// [`Self::find_all_matches()`], independent of the batches delimited by hard boundaries, so rules
+    /// can tag sequences that span a hard boundary (such as a name split by a line break). This only
+    /// (re)assigns tags, the selection of variants is not affected. The matches must not overlap (i.e.
+    /// they must be consolidated). This is done automatically by [`Self::find_all_matches()`] if
+    /// [`SearchParameters::global_context_rules`] is set.
+    pub fn apply_context_rules_global(&self, matches: &mut Vec<Match>) {
+        if self.context_rules.is_empty() {
+            return;
+        }
+        let sequence: Vec<(VocabId, u32, &str)> = matches
+            .iter()
+            .map(|m| {
+                if let Some(variant) = m.selected.and_then(|selected| {
+                    m.variants
+                        .as_ref()
+                        .and_then(|variants| variants.get(selected))
+                }) {
+                    let vocabvalue = self.decoder.get(variant.vocab_id as usize).expect("vocab");
+                    (
+                        variant.vocab_id,
+                        vocabvalue.lexindex,
+                        vocabvalue.text.as_str(),
+                    )
+                } else {
+                    (0, 0, m.text)
+                }
+            })
+            .collect();
+        let (_found, sequence_results) = self.match_context_rules(&sequence);
+        for (m, results) in matches.iter_mut().zip(sequence_results) {
+            m.tag.clear();
+            m.seqnr.clear();
+            for result in results {
+                if let Some(tag) = result.tag {
+                    m.tag.push(tag);
+                    m.seqnr.push(result.seqnr);
+                }
+            }
         }
     }
 
     /// Computes the logprob and perplexity for a given sequence as produced in
     /// most_likely_sequence()
-    pub fn lm_score<'a>(&self, sequence: &Sequence, boundaries: &[Match<'a>]) -> (f32, f64) {
+    pub fn lm_score<'a>(
+        &self,
+        sequence: &Sequence,
+        boundaries: &[Match<'a>],
+        params: &SearchParameters,
+    ) -> (f32, f64) {
         //step 1: collect all tokens in the sequence
 
         let mut tokens: Vec<Option<VocabId>> =
@@ -2589,31 +5517,12 @@ impl VariantModel {
                 .get(output_symbol.boundary_index)
                 .expect("boundary should be in bounds");
 
-            if output_symbol.vocab_id == 0 {
-                //out of vocabulary (copied from input)
-                tokens.push(None);
-            } else {
-                if let Ok(mut ngram) = self.into_ngram(output_symbol.vocab_id, &mut None) {
-                    loop {
-                        match ngram.pop_first() {
-                            NGram::Empty => break,
-                            unigram => tokens.push(unigram.first()),
-                        }
-                    }
-                }
-            }
+            self.push_lm_tokens(output_symbol.vocab_id, &mut tokens);
 
             //add boundary as a token too
             if !next_boundary.text.trim().is_empty() {
                 if let Some(vocab_id) = self.encoder.get(next_boundary.text.trim()) {
-                    if let Ok(mut ngram) = self.into_ngram(*vocab_id, &mut None) {
-                        loop {
-                            match ngram.pop_first() {
-                                NGram::Empty => break,
-                                unigram => tokens.push(unigram.first()),
-                            }
-                        }
-                    }
+                    self.push_lm_tokens(*vocab_id, &mut tokens);
                 } else {
                     //out of vocabulary boundary tokens (copied from input)
                     tokens.push(None);
@@ -2624,12 +5533,43 @@ impl VariantModel {
         tokens.push(Some(EOS));
 
         //Compute the score over the tokens
-        self.lm_score_tokens(&tokens)
+        self.lm_score_tokens(&tokens, params)
+    }
+
+    /// Adds the tokens for the language model corresponding to a vocabulary item (multiple if it
+    /// is an n-gram). Vocabulary ID 0 denotes an out of vocabulary token (copied from input).
+    fn push_lm_tokens(&self, vocab_id: VocabId, tokens: &mut Vec<Option<VocabId>>) {
+        if vocab_id == 0 {
+            tokens.push(None);
+        } else if let Ok(mut ngram) = self.into_ngram(vocab_id, &mut None) {
+            loop {
+                match ngram.pop_first() {
+                    NGram::Empty => break,
+                    unigram => tokens.push(unigram.first()),
+                }
+            }
+        }
     }
 
     /// Computes the logprob and perplexity for a given sequence of tokens.
     /// The tokens are either in the vocabulary or are None if out-of-vocabulary.
-    pub fn lm_score_tokens<'a>(&self, tokens: &Vec<Option<VocabId>>) -> (f32, f64) {
+    /// Transitions are smoothed according to [`Self::lm_smoothing`], for [`LmSmoothing::Fixed`] unseen
+    /// transitions are smoothed as configured in the search parameters (`lm_smoothing_logprob` or `lm_add_k`).
+    pub fn lm_score_tokens<'a>(
+        &self,
+        tokens: &Vec<Option<VocabId>>,
+        params: &SearchParameters,
+    ) -> (f32, f64) {
+        if self.lm_smoothing != LmSmoothing::Fixed {
+            let mut logprob = 0.0;
+            for i in 1..=tokens.len() - 1 {
+                logprob += self.lm_interpolated_prob(tokens[i - 1], tokens[i]).ln();
+            }
+            let perplexity = -1.0 / ((tokens.len() - 1) as f64) * logprob as f64;
+            return (logprob, perplexity);
+        }
+        //vocabulary size, used in the denominator for add-k smoothing
+        let vocabsize = self.decoder.len() as f32;
         //move a sliding window over the tokens
         let mut logprob = 0.0;
         let mut n = 0;
@@ -2637,32 +5577,48 @@ impl VariantModel {
             if let Ok(bigram) = NGram::from_option_list(&tokens[i - 1..i + 1]) {
                 let prior = NGram::from_option_list(&tokens[i - 1..i]).expect("extracting prior");
 
-                let priorcount = if let Some(priorcount) = self.ngrams.get(&prior) {
-                    *priorcount
+                if params.lm_add_k > 0.0 {
+                    //add-k smoothing
+                    let priorcount = self.ngrams.get(&prior).copied().unwrap_or(0) as f32;
+                    let jointcount = self.ngrams.get(&bigram).copied().unwrap_or(0) as f32;
+                    logprob += ((jointcount + params.lm_add_k)
+                        / (priorcount + params.lm_add_k * vocabsize))
+                        .ln();
                 } else {
-                    1
-                };
+                    let priorcount = if let Some(priorcount) = self.ngrams.get(&prior) {
+                        *priorcount
+                    } else {
+                        1
+                    };
 
-                //Do we have a joint probability for the bigram that forms the transition?
-                if let Some(jointcount) = self.ngrams.get(&bigram) {
-                    if priorcount < *jointcount {
-                        //sanity check, shouldn't be the case, correct:
-                        logprob += (*jointcount as f32).ln()
+                    //Do we have a joint probability for the bigram that forms the transition?
+                    if let Some(jointcount) = self.ngrams.get(&bigram) {
+                        if priorcount < *jointcount {
+                            //sanity check, shouldn't be the case, correct:
+                            logprob += (*jointcount as f32).ln()
+                        } else {
+                            logprob += (*jointcount as f32 / priorcount as f32).ln()
+                        }
                     } else {
-                        logprob += (*jointcount as f32 / priorcount as f32).ln()
+                        logprob += params.lm_smoothing_logprob
                     }
-                } else {
-                    logprob += TRANSITION_SMOOTHING_LOGPROB
                 }
 
                 n += 1;
             } else {
-                //if we have an out of vocabulary bigram or prior we fall back to add-one smoothing
-                //simply setting the count of that ngram/prior to 1
-                //for the perplexity computation this means the score doesn't change, but n does
-                //increase, so we end up with a lower perplexity
+                //if we have an out of vocabulary bigram or prior we fall back to the smoothing
+                //value, for add-k smoothing the joint count is 0 (and so is the prior count if the
+                //prior is out of vocabulary)
                 n += 1;
-                logprob += TRANSITION_SMOOTHING_LOGPROB
+                if params.lm_add_k > 0.0 {
+                    let priorcount = match NGram::from_option_list(&tokens[i - 1..i]) {
+                        Ok(prior) => self.ngrams.get(&prior).copied().unwrap_or(0) as f32,
+                        Err(_) => 0.0,
+                    };
+                    logprob += (params.lm_add_k / (priorcount + params.lm_add_k * vocabsize)).ln();
+                } else {
+                    logprob += params.lm_smoothing_logprob
+                }
             }
         }
 
@@ -2673,6 +5629,52 @@ impl VariantModel {
         (logprob, perplexity)
     }
 
+    /// Computes the transition probability P(next|prev) using Witten-Bell or Kneser-Ney
+    /// smoothing, interpolating the bigram estimate with a lower-order distribution. Out-of-vocabulary
+    /// tokens are None.
+    fn lm_interpolated_prob(&self, prev: Option<VocabId>, next: Option<VocabId>) -> f32 {
+        let vocabsize = self.decoder.len() as f32;
+        let counts = |token: Option<VocabId>| {
+            token
+                .and_then(|token| self.continuation_counts.get(&token).copied())
+                .unwrap_or_default()
+        };
+
+        //lower-order distribution, with add-one smoothing so out-of-vocabulary tokens still get some probability mass
+        let lower = if self.lm_smoothing == LmSmoothing::KneserNey {
+            (counts(next).predecessors as f32 + 1.0) / (self.bigram_types as f32 + vocabsize)
+        } else {
+            let unigramcount = next
+                .and_then(|next| self.ngrams.get(&NGram::UniGram(next)).copied())
+                .unwrap_or(0);
+            (unigramcount as f32 + 1.0) / (self.freq_sum[0] as f32 + vocabsize)
+        };
+
+        let prev = match prev {
+            Some(prev) => prev,
+            None => return lower,
+        };
+        let jointcount = next
+            .and_then(|next| self.ngrams.get(&NGram::BiGram(prev, next)).copied())
+            .unwrap_or(0) as f32;
+        //the prior count can't be lower than the joint count (these may come from different entries in the n-gram list)
+        let priorcount =
+            (self.ngrams.get(&NGram::UniGram(prev)).copied().unwrap_or(0) as f32).max(jointcount);
+        let followers = counts(Some(prev)).followers as f32;
+        if priorcount == 0.0 || followers == 0.0 {
+            //no bigrams for the prior, back off completely
+            return lower;
+        }
+
+        match self.lm_smoothing {
+            LmSmoothing::KneserNey => {
+                (jointcount - KNESER_NEY_DISCOUNT).max(0.0) / priorcount
+                    + KNESER_NEY_DISCOUNT * followers / priorcount * lower
+            }
+            _ => (jointcount + followers * lower) / (priorcount + followers),
+        }
+    }
+
     /// Add an ngram for language modelling
     pub fn add_ngram(&mut self, ngram: NGram, frequency: u32) {
         if let Some(ngram) = self.ngrams.get_mut(&ngram) {
@@ -2680,10 +5682,26 @@ impl VariantModel {
             *ngram += frequency;
         } else {
             //add the new ngram
+            self.count_continuation(&ngram);
             self.ngrams.insert(ngram, frequency);
         }
     }
 
+    /// Updates the continuation counts for a newly added ngram
+    fn count_continuation(&mut self, ngram: &NGram) {
+        if let NGram::BiGram(first, second) = ngram {
+            self.continuation_counts
+                .entry(*first)
+                .or_default()
+                .followers += 1;
+            self.continuation_counts
+                .entry(*second)
+                .or_default()
+                .predecessors += 1;
+            self.bigram_types += 1;
+        }
+    }
+
     /// Decompose a known vocabulary Id into an Ngram
     fn into_ngram(
         &self,
@@ -2697,8 +5715,9 @@ impl VariantModel {
         let mut iter = word_dec.text.split(" ");
         match word_dec.tokencount {
             0 => Ok(NGram::Empty),
+            //(the text of a single token may still contain spaces if it is an atomic phrase)
             1 => Ok(NGram::UniGram(self.encode_token(
-                iter.next().expect("ngram part"),
+                word_dec.text.as_str(),
                 true,
                 unseen_parts,
             ))),
@@ -2763,6 +5782,63 @@ impl VariantModel {
         }
     }
 
+    /// Gives the text to output for this match: the solution (if any), otherwise the input text,
+    /// unless the match is out-of-vocabulary, in which case [`SearchParameters::oov_policy`]
+    /// determines the output. Returns `None` if nothing should be output.
+    pub fn match_output<'a>(
+        &'a self,
+        m: &'a Match<'a>,
+        params: &'a SearchParameters,
+    ) -> Option<&'a str> {
+        if let Some(result) = m.solution() {
+            Some(self.variant_text(result))
+        } else if m.oov {
+            params.oov_policy.apply(m.text)
+        } else {
+            Some(m.text)
+        }
+    }
+
+    /// Corrects a text: searches it like [`Self::find_all_matches()`] and returns the text with
+    /// every match replaced by its output (see [`Self::match_output()`]). All text in between the
+    /// matches, such as whitespace, punctuation and protected markup, is retained verbatim.
+    pub fn correct_text(&self, text: &str, params: &SearchParameters) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut cursor = 0;
+        for m in self.find_all_matches(text, params) {
+            let offset = m.byteoffset.as_ref().unwrap_or(&m.offset);
+            if offset.begin < cursor {
+                //overlapping match, only possible if matches are not consolidated
+                continue;
+            }
+            output += &text[cursor..offset.begin];
+            if let Some(matchoutput) = self.match_output(&m, params) {
+                output += matchoutput;
+            }
+            cursor = offset.end;
+        }
+        output += &text[cursor..];
+        output
+    }
+
+    /// Gives the tags (by name) that the context rules assigned to this match, along with the
+    /// sequence number of the match in the tagged sequence
+    pub fn match_tags<'a>(&'a self, m: &Match<'_>) -> Vec<(&'a str, u8)> {
+        m.tag
+            .iter()
+            .zip(m.seqnr.iter())
+            .map(|(tagindex, seqnr)| {
+                (
+                    self.tags
+                        .get(*tagindex as usize)
+                        .expect("tag must exist in model")
+                        .as_str(),
+                    *seqnr,
+                )
+            })
+            .collect()
+    }
+
     /// Gives the vocabitem for this match, always uses the solution (if any) and falls
     /// back to the input text only when no solution was found.
     pub fn match_to_vocabvalue<'a>(&'a self, m: &Match<'a>) -> Option<&'a VocabValue> {
@@ -0,0 +1,85 @@
+//! Routes all progress and debug messages through a logger that is set by the application. No
+//! messages are written unless a logger is set, the command line tool sets [`StderrLogger`].
+//! The verbosity is still determined by the `debug` level of the model; the log level of each
+//! message only allows the logger to filter or route messages further.
+
+use std::fmt;
+use std::sync::RwLock;
+
+/// Severity of a log message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    /// Progress information, and messages at debug level 1
+    Info,
+    /// Messages at debug level 2
+    Debug,
+    /// Messages at debug level 3 and higher
+    Trace,
+}
+
+/// Receives all log messages from analiticcl
+pub trait Logger: Send + Sync {
+    fn log(&self, level: LogLevel, message: fmt::Arguments);
+}
+
+/// A logger that writes all messages to standard error
+pub struct StderrLogger;
+
+impl Logger for StderrLogger {
+    fn log(&self, _level: LogLevel, message: fmt::Arguments) {
+        eprintln!("{}", message);
+    }
+}
+
+static LOGGER: RwLock<Option<Box<dyn Logger>>> = RwLock::new(None);
+
+/// Sets the logger that receives all log messages (replacing any previous one)
+pub fn set_logger(logger: Box<dyn Logger>) {
+    *LOGGER.write().expect("logger lock") = Some(logger);
+}
+
+/// Removes the logger, discarding all further log messages
+pub fn unset_logger() {
+    *LOGGER.write().expect("logger lock") = None;
+}
+
+/// Passes a message to the logger, if any
+pub fn log(level: LogLevel, message: fmt::Arguments) {
+    if let Some(logger) = LOGGER.read().expect("logger lock").as_ref() {
+        logger.log(level, message);
+    }
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Error, format_args!($($arg)*))
+    };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Warn, format_args!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Info, format_args!($($arg)*))
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Debug, format_args!($($arg)*))
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Trace, format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {debug, error, info, trace, warning};
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::types::*;
+
+/// The length of the character n-grams in the [`NGramIndex`]
+pub const NGRAM_INDEX_ORDER: usize = 3;
+
+/// A character n-gram (over a string normalized to the alphabet)
+pub type CharNGram = [CharIndexType; NGRAM_INDEX_ORDER];
+
+/// An inverted index from character n-grams (trigrams) to the vocabulary items that contain them.
+/// This is an alternative candidate generator for long inputs (such as multi-word phrases), for
+/// which the anagram neighbourhood search is expensive. Candidates are found by the number of
+/// n-grams they share with the input, expressed as a Dice coefficient.
+/// Only built if enabled with [`crate::VariantModel::set_ngram_index()`] and used for inputs of at
+/// least [`SearchParameters::ngram_min_length`] characters.
+#[derive(Default, Debug, Clone)]
+pub struct NGramIndex {
+    /// Maps each n-gram to all vocabulary items that contain it
+    index: HashMap<CharNGram, Vec<VocabId>>,
+    /// The number of distinct n-grams for each indexed vocabulary item
+    sizes: HashMap<VocabId, usize>,
+}
+
+impl NGramIndex {
+    /// Returns the distinct character n-grams of a string normalized to the alphabet (in sorted
+    /// order). Strings shorter than [`NGRAM_INDEX_ORDER`] have none.
+    pub fn ngrams(norm: &[CharIndexType]) -> Vec<CharNGram> {
+        let mut ngrams: Vec<CharNGram> = norm
+            .windows(NGRAM_INDEX_ORDER)
+            .map(|window| window.try_into().expect("window has the n-gram length"))
+            .collect();
+        ngrams.sort_unstable();
+        ngrams.dedup();
+        ngrams
+    }
+
+    /// Adds a vocabulary item (normalized to the alphabet) to the index
+    pub fn insert(&mut self, vocab_id: VocabId, norm: &[CharIndexType]) {
+        let ngrams = Self::ngrams(norm);
+        if ngrams.is_empty() {
+            return;
+        }
+        self.sizes.insert(vocab_id, ngrams.len());
+        for ngram in ngrams {
+            let instances = self.index.entry(ngram).or_default();
+            if !instances.contains(&vocab_id) {
+                instances.push(vocab_id);
+            }
+        }
+    }
+
+    /// Removes a vocabulary item (normalized to the alphabet) from the index
+    pub fn remove(&mut self, vocab_id: VocabId, norm: &[CharIndexType]) {
+        if self.sizes.remove(&vocab_id).is_none() {
+            return;
+        }
+        for ngram in Self::ngrams(norm) {
+            if let Some(instances) = self.index.get_mut(&ngram) {
+                instances.retain(|x| *x != vocab_id);
+                if instances.is_empty() {
+                    self.index.remove(&ngram);
+                }
+            }
+        }
+    }
+
+    /// Finds all vocabulary items that share n-grams with the input (normalized to the alphabet),
+    /// along with their Dice coefficient (twice the number of shared n-grams divided by the total
+    /// number of n-grams of both), which must be at least `min_overlap`.
+    pub fn find(&self, norm: &[CharIndexType], min_overlap: f64) -> Vec<(VocabId, f64)> {
+        let ngrams = Self::ngrams(norm);
+        let mut shared: HashMap<VocabId, usize> = HashMap::new();
+        for ngram in ngrams.iter() {
+            if let Some(instances) = self.index.get(ngram) {
+                for vocab_id in instances.iter() {
+                    *shared.entry(*vocab_id).or_default() += 1;
+                }
+            }
+        }
+        let mut candidates: Vec<(VocabId, f64)> = shared
+            .into_iter()
+            .filter_map(|(vocab_id, count)| {
+                let size = self.sizes.get(&vocab_id).copied().unwrap_or(0);
+                let dice = 2.0 * count as f64 / (ngrams.len() + size) as f64;
+                if dice >= min_overlap {
+                    Some((vocab_id, dice))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        candidates.sort_unstable_by_key(|(vocab_id, _)| *vocab_id);
+        candidates
+    }
+
+    /// The number of distinct n-grams in the index
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.sizes.clear();
+    }
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::index::*;
+use crate::search::*;
+use crate::types::*;
+use crate::unicode::*;
+use crate::vocab::*;
+
+/// Magic bytes at the start of every serialized model file
+pub const MODEL_MAGIC: &[u8; 10] = b"ANALITICCL";
+
+/// Version of the serialized model format, increment this whenever the format changes so old
+/// files are rejected rather than silently misinterpreted.
+pub const MODEL_FORMAT_VERSION: u32 = 2;
+
+/// Borrowed view on a model, used for serialisation. Hash maps are serialised with their entries
+/// sorted by key, so saving the same model always yields byte-identical files.
+#[derive(Serialize)]
+pub(crate) struct ModelDataRef<'a> {
+    pub alphabet: &'a Alphabet,
+    pub alphabet_ignore: &'a Vec<String>,
+    pub alphabet_unk: &'a Vec<String>,
+    pub decoder: &'a VocabDecoder,
+    #[serde(serialize_with = "serialize_sorted")]
+    pub encoder: &'a VocabEncoder,
+    #[serde(serialize_with = "serialize_sorted")]
+    pub index: &'a AnaIndex,
+    #[serde(serialize_with = "serialize_sorted")]
+    pub ngrams: &'a HashMap<NGram, u32>,
+    pub freq_sum: &'a Vec<usize>,
+    pub have_freq: bool,
+    pub have_lm: bool,
+    pub lm_smoothing: LmSmoothing,
+    pub context_rules: &'a Vec<ContextRule>,
+    pub tags: &'a Vec<String>,
+    pub weights: &'a Weights,
+    pub lexicons: &'a Vec<String>,
+    pub lexicon_weights: &'a Vec<f64>,
+    /// Confusables are stored in their edit script notation along with their weight
+    pub confusables: Vec<(String, f64)>,
+    /// Whether each confusable (corresponding by index) matches case-insensitively
+    pub confusables_casefold: Vec<bool>,
+    pub confusables_before_pruning: bool,
+    /// Affix rules are stored in their notation (e.g. `-jes`) along with their weight
+    pub affixes: Vec<(String, f64)>,
+    pub unicode_normalization: UnicodeNormalization,
+    pub casefold: bool,
+    pub case_first_char: bool,
+    pub distinct_unknowns: bool,
+    /// Substitution costs are stored as (source, target, cost) triples
+    pub substitution_costs: Vec<(CharIndexType, CharIndexType, f64)>,
+    /// Whether the n-gram index is enabled, the index itself is rebuilt on load
+    pub ngram_index: bool,
+}
+
+/// Owned model data, used for deserialisation
+#[derive(Deserialize)]
+pub(crate) struct ModelData {
+    pub alphabet: Alphabet,
+    #[serde(default)]
+    pub alphabet_ignore: Vec<String>,
+    #[serde(default)]
+    pub alphabet_unk: Vec<String>,
+    pub decoder: VocabDecoder,
+    pub encoder: VocabEncoder,
+    pub index: AnaIndex,
+    pub ngrams: HashMap<NGram, u32>,
+    pub freq_sum: Vec<usize>,
+    pub have_freq: bool,
+    pub have_lm: bool,
+    #[serde(default)]
+    pub lm_smoothing: LmSmoothing,
+    pub context_rules: Vec<ContextRule>,
+    pub tags: Vec<String>,
+    pub weights: Weights,
+    pub lexicons: Vec<String>,
+    #[serde(default)]
+    pub lexicon_weights: Vec<f64>,
+    pub confusables: Vec<(String, f64)>,
+    #[serde(default)]
+    pub confusables_casefold: Vec<bool>,
+    pub confusables_before_pruning: bool,
+    #[serde(default)]
+    pub affixes: Vec<(String, f64)>,
+    pub unicode_normalization: UnicodeNormalization,
+    #[serde(default)]
+    pub casefold: bool,
+    #[serde(default)]
+    pub case_first_char: bool,
+    #[serde(default)]
+    pub distinct_unknowns: bool,
+    #[serde(default)]
+    pub substitution_costs: Vec<(CharIndexType, CharIndexType, f64)>,
+    #[serde(default)]
+    pub ngram_index: bool,
+}
+
+/// Serialises a hash map as a map with its entries sorted by key, rather than in the (arbitrary)
+/// iteration order of the hash map
+fn serialize_sorted<S, K, V>(map: &&HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Ord + Serialize,
+    V: Serialize,
+{
+    let sorted: BTreeMap<&K, &V> = map.iter().collect();
+    sorted.serialize(serializer)
+}
+
+/// Writes the header (magic bytes and format version) of a model file
+pub(crate) fn write_model_header(writer: &mut impl Write) -> Result<(), Error> {
+    writer.write_all(MODEL_MAGIC)?;
+    writer.write_all(&MODEL_FORMAT_VERSION.to_le_bytes())
+}
+
+/// Reads and verifies the header (magic bytes and format version) of a model file
+pub(crate) fn read_model_header(reader: &mut impl Read) -> Result<(), Error> {
+    let mut magic = [0u8; 10];
+    reader.read_exact(&mut magic)?;
+    if &magic != MODEL_MAGIC {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Not a valid analiticcl model file",
+        ));
+    }
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != MODEL_FORMAT_VERSION {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Unsupported model format version {} (expected {}), please rebuild the model",
+                version, MODEL_FORMAT_VERSION
+            ),
+        ));
+    }
+    Ok(())
+}
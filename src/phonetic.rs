@@ -0,0 +1,633 @@
+/// Maximum length of a phonetic code
+const MAX_CODE_LENGTH: usize = 4;
+
+const L_R_N_M_B_H_F_V_W_SPACE: &[&str] = &["L", "R", "N", "M", "B", "H", "F", "V", "W", " "];
+const ES_EP_EB_EL_EY_IB_IL_IN_IE_EI_ER: &[&str] = &[
+    "ES", "EP", "EB", "EL", "EY", "IB", "IL", "IN", "IE", "EI", "ER",
+];
+const L_T_K_S_N_M_B_Z: &[&str] = &["L", "T", "K", "S", "N", "M", "B", "Z"];
+
+/// Holds the primary and alternate phonetic codes as they are being computed
+struct PhoneticCode {
+    primary: String,
+    alternate: String,
+}
+
+impl PhoneticCode {
+    fn append(&mut self, primary: &str, alternate: &str) {
+        self.append_primary(primary);
+        self.append_alternate(alternate);
+    }
+
+    fn append_both(&mut self, value: &str) {
+        self.append(value, value);
+    }
+
+    fn append_primary(&mut self, value: &str) {
+        for c in value.chars() {
+            if self.primary.len() < MAX_CODE_LENGTH {
+                self.primary.push(c);
+            }
+        }
+    }
+
+    fn append_alternate(&mut self, value: &str) {
+        for c in value.chars() {
+            if self.alternate.len() < MAX_CODE_LENGTH {
+                self.alternate.push(c);
+            }
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.primary.len() >= MAX_CODE_LENGTH && self.alternate.len() >= MAX_CODE_LENGTH
+    }
+}
+
+/// The (uppercased) word being encoded, along with some properties computed up front
+struct Word {
+    chars: Vec<char>,
+    slavo_germanic: bool,
+}
+
+impl Word {
+    fn new(word: &str) -> Self {
+        let chars: Vec<char> = word.trim().to_uppercase().chars().collect();
+        let text: String = chars.iter().collect();
+        let slavo_germanic = text.contains('W')
+            || text.contains('K')
+            || text.contains("CZ")
+            || text.contains("WITZ");
+        Self {
+            chars,
+            slavo_germanic,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// Returns the character at the specified index, or a null character if out of bounds
+    fn at(&self, index: isize) -> char {
+        if index >= 0 && (index as usize) < self.chars.len() {
+            self.chars[index as usize]
+        } else {
+            '\0'
+        }
+    }
+
+    fn is_vowel(&self, index: isize) -> bool {
+        matches!(self.at(index), 'A' | 'E' | 'I' | 'O' | 'U' | 'Y')
+    }
+
+    /// Tests whether the substring of the specified length at the specified index equals any of the candidates
+    fn contains(&self, start: isize, length: usize, candidates: &[&str]) -> bool {
+        if start < 0 || start as usize + length > self.chars.len() {
+            return false;
+        }
+        let start = start as usize;
+        candidates.iter().any(|candidate| {
+            candidate.chars().count() == length
+                && candidate
+                    .chars()
+                    .zip(self.chars[start..start + length].iter())
+                    .all(|(a, b)| a == *b)
+        })
+    }
+
+    fn is_last(&self, index: isize) -> bool {
+        index == self.len() as isize - 1
+    }
+
+    fn is_germanic(&self) -> bool {
+        self.contains(0, 4, &["VAN ", "VON "]) || self.contains(0, 3, &["SCH"])
+    }
+}
+
+/// Computes the primary and alternate Double Metaphone codes (Lawrence Philips, 2000) for a word. The alternate code
+/// equals the primary code for words without an alternative pronunciation.
+pub fn double_metaphone(word: &str) -> (String, String) {
+    let word = Word::new(word);
+    let mut code = PhoneticCode {
+        primary: String::with_capacity(MAX_CODE_LENGTH),
+        alternate: String::with_capacity(MAX_CODE_LENGTH),
+    };
+    //skip silent letters at the start
+    let mut index: isize = if word.contains(0, 2, &["GN", "KN", "PN", "WR", "PS"]) {
+        1
+    } else {
+        0
+    };
+    while !code.is_complete() && index < word.len() as isize {
+        index = match word.at(index) {
+            'A' | 'E' | 'I' | 'O' | 'U' | 'Y' => {
+                if index == 0 {
+                    code.append_both("A");
+                }
+                index + 1
+            }
+            'B' => {
+                code.append_both("P");
+                skip_double(&word, index, 'B')
+            }
+            'Ç' => {
+                code.append_both("S");
+                index + 1
+            }
+            'C' => handle_c(&word, &mut code, index),
+            'D' => handle_d(&word, &mut code, index),
+            'F' => {
+                code.append_both("F");
+                skip_double(&word, index, 'F')
+            }
+            'G' => handle_g(&word, &mut code, index),
+            'H' => handle_h(&word, &mut code, index),
+            'J' => handle_j(&word, &mut code, index),
+            'K' => {
+                code.append_both("K");
+                skip_double(&word, index, 'K')
+            }
+            'L' => handle_l(&word, &mut code, index),
+            'M' => {
+                code.append_both("M");
+                if word.at(index + 1) == 'M'
+                    || (word.contains(index - 1, 3, &["UMB"])
+                        && (word.is_last(index + 1) || word.contains(index + 2, 2, &["ER"])))
+                {
+                    index + 2
+                } else {
+                    index + 1
+                }
+            }
+            'N' => {
+                code.append_both("N");
+                skip_double(&word, index, 'N')
+            }
+            'Ñ' => {
+                code.append_both("N");
+                index + 1
+            }
+            'P' => handle_p(&word, &mut code, index),
+            'Q' => {
+                code.append_both("K");
+                skip_double(&word, index, 'Q')
+            }
+            'R' => handle_r(&word, &mut code, index),
+            'S' => handle_s(&word, &mut code, index),
+            'T' => handle_t(&word, &mut code, index),
+            'V' => {
+                code.append_both("F");
+                skip_double(&word, index, 'V')
+            }
+            'W' => handle_w(&word, &mut code, index),
+            'X' => handle_x(&word, &mut code, index),
+            'Z' => handle_z(&word, &mut code, index),
+            _ => index + 1,
+        };
+    }
+    (code.primary, code.alternate)
+}
+
+/// Returns the primary and (if different) the alternate Double Metaphone code for a word, empty
+/// codes (for words without any letters) are omitted.
+pub fn phonetic_codes(word: &str) -> Vec<String> {
+    let (primary, alternate) = double_metaphone(word);
+    let mut codes = Vec::with_capacity(2);
+    if !primary.is_empty() {
+        codes.push(primary);
+    }
+    if !alternate.is_empty() && !codes.contains(&alternate) {
+        codes.push(alternate);
+    }
+    codes
+}
+
+fn skip_double(word: &Word, index: isize, c: char) -> isize {
+    if word.at(index + 1) == c {
+        index + 2
+    } else {
+        index + 1
+    }
+}
+
+fn handle_c(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if condition_c0(word, index) {
+        code.append_both("K");
+        index + 2
+    } else if index == 0 && word.contains(index, 6, &["CAESAR"]) {
+        code.append_both("S");
+        index + 2
+    } else if word.contains(index, 2, &["CH"]) {
+        handle_ch(word, code, index)
+    } else if word.contains(index, 2, &["CZ"]) && !word.contains(index - 2, 4, &["WICZ"]) {
+        code.append("S", "X");
+        index + 2
+    } else if word.contains(index + 1, 3, &["CIA"]) {
+        code.append_both("X");
+        index + 3
+    } else if word.contains(index, 2, &["CC"]) && !(index == 1 && word.at(0) == 'M') {
+        handle_cc(word, code, index)
+    } else if word.contains(index, 2, &["CK", "CG", "CQ"]) {
+        code.append_both("K");
+        index + 2
+    } else if word.contains(index, 2, &["CI", "CE", "CY"]) {
+        if word.contains(index, 3, &["CIO", "CIE", "CIA"]) {
+            code.append("S", "X");
+        } else {
+            code.append_both("S");
+        }
+        index + 2
+    } else {
+        code.append_both("K");
+        if word.contains(index + 1, 2, &[" C", " Q", " G"]) {
+            index + 3
+        } else if word.contains(index + 1, 1, &["C", "K", "Q"])
+            && !word.contains(index + 1, 2, &["CE", "CI"])
+        {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+}
+
+fn condition_c0(word: &Word, index: isize) -> bool {
+    if word.contains(index, 4, &["CHIA"]) {
+        true
+    } else if index <= 1 || word.is_vowel(index - 2) || !word.contains(index - 1, 3, &["ACH"]) {
+        false
+    } else {
+        let c = word.at(index + 2);
+        (c != 'I' && c != 'E') || word.contains(index - 2, 6, &["BACHER", "MACHER"])
+    }
+}
+
+fn handle_cc(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.contains(index + 2, 1, &["I", "E", "H"]) && !word.contains(index + 2, 2, &["HU"]) {
+        if (index == 1 && word.at(index - 1) == 'A')
+            || word.contains(index - 1, 5, &["UCCEE", "UCCES"])
+        {
+            code.append_both("KS");
+        } else {
+            code.append_both("X");
+        }
+        index + 3
+    } else {
+        code.append_both("K");
+        index + 2
+    }
+}
+
+fn handle_ch(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if index > 0 && word.contains(index, 4, &["CHAE"]) {
+        code.append("K", "X");
+    } else if condition_ch0(word, index) || condition_ch1(word, index) {
+        code.append_both("K");
+    } else if index > 0 {
+        if word.contains(0, 2, &["MC"]) {
+            code.append_both("K");
+        } else {
+            code.append("X", "K");
+        }
+    } else {
+        code.append_both("X");
+    }
+    index + 2
+}
+
+fn condition_ch0(word: &Word, index: isize) -> bool {
+    index == 0
+        && (word.contains(index + 1, 5, &["HARAC", "HARIS"])
+            || word.contains(index + 1, 3, &["HOR", "HYM", "HIA", "HEM"]))
+        && !word.contains(0, 5, &["CHORE"])
+}
+
+fn condition_ch1(word: &Word, index: isize) -> bool {
+    word.is_germanic()
+        || word.contains(index - 2, 6, &["ORCHES", "ARCHIT", "ORCHID"])
+        || word.contains(index + 2, 1, &["T", "S"])
+        || ((word.contains(index - 1, 1, &["A", "O", "U", "E"]) || index == 0)
+            && (word.contains(index + 2, 1, L_R_N_M_B_H_F_V_W_SPACE) || word.is_last(index + 1)))
+}
+
+fn handle_d(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.contains(index, 2, &["DG"]) {
+        if word.contains(index + 2, 1, &["I", "E", "Y"]) {
+            code.append_both("J");
+            index + 3
+        } else {
+            code.append_both("TK");
+            index + 2
+        }
+    } else if word.contains(index, 2, &["DT", "DD"]) {
+        code.append_both("T");
+        index + 2
+    } else {
+        code.append_both("T");
+        index + 1
+    }
+}
+
+fn handle_g(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.at(index + 1) == 'H' {
+        handle_gh(word, code, index)
+    } else if word.at(index + 1) == 'N' {
+        if index == 1 && word.is_vowel(0) && !word.slavo_germanic {
+            code.append("KN", "N");
+        } else if !word.contains(index + 2, 2, &["EY"])
+            && word.at(index + 1) != 'Y'
+            && !word.slavo_germanic
+        {
+            code.append("N", "KN");
+        } else {
+            code.append_both("KN");
+        }
+        index + 2
+    } else if word.contains(index + 1, 2, &["LI"]) && !word.slavo_germanic {
+        code.append("KL", "L");
+        index + 2
+    } else if (index == 0
+        && (word.at(index + 1) == 'Y'
+            || word.contains(index + 1, 2, ES_EP_EB_EL_EY_IB_IL_IN_IE_EI_ER)))
+        || ((word.contains(index + 1, 2, &["ER"]) || word.at(index + 1) == 'Y')
+            && !word.contains(0, 6, &["DANGER", "RANGER", "MANGER"])
+            && !word.contains(index - 1, 1, &["E", "I"])
+            && !word.contains(index - 1, 3, &["RGY", "OGY"]))
+    {
+        code.append("K", "J");
+        index + 2
+    } else if word.contains(index + 1, 1, &["E", "I", "Y"])
+        || word.contains(index - 1, 4, &["AGGI", "OGGI"])
+    {
+        if word.is_germanic() || word.contains(index + 1, 2, &["ET"]) {
+            code.append_both("K");
+        } else if word.contains(index + 1, 3, &["IER"]) {
+            code.append_both("J");
+        } else {
+            code.append("J", "K");
+        }
+        index + 2
+    } else {
+        code.append_both("K");
+        skip_double(word, index, 'G')
+    }
+}
+
+fn handle_gh(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if index > 0 && !word.is_vowel(index - 1) {
+        code.append_both("K");
+    } else if index == 0 {
+        if word.at(index + 2) == 'I' {
+            code.append_both("J");
+        } else {
+            code.append_both("K");
+        }
+    } else if (index > 1 && word.contains(index - 2, 1, &["B", "H", "D"]))
+        || (index > 2 && word.contains(index - 3, 1, &["B", "H", "D"]))
+        || (index > 3 && word.contains(index - 4, 1, &["B", "H"]))
+    {
+        //silent
+    } else if index > 2
+        && word.at(index - 1) == 'U'
+        && word.contains(index - 3, 1, &["C", "G", "L", "R", "T"])
+    {
+        code.append_both("F");
+    } else if index > 0 && word.at(index - 1) != 'I' {
+        code.append_both("K");
+    }
+    index + 2
+}
+
+fn handle_h(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if (index == 0 || word.is_vowel(index - 1)) && word.is_vowel(index + 1) {
+        code.append_both("H");
+        index + 2
+    } else {
+        index + 1
+    }
+}
+
+fn handle_j(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.contains(index, 4, &["JOSE"]) || word.contains(0, 4, &["SAN "]) {
+        if (index == 0 && word.at(index + 4) == ' ')
+            || word.len() == 4
+            || word.contains(0, 4, &["SAN "])
+        {
+            code.append_both("H");
+        } else {
+            code.append("J", "H");
+        }
+        index + 1
+    } else {
+        if index == 0 {
+            code.append("J", "A");
+        } else if word.is_vowel(index - 1)
+            && !word.slavo_germanic
+            && (word.at(index + 1) == 'A' || word.at(index + 1) == 'O')
+        {
+            code.append("J", "H");
+        } else if word.is_last(index) {
+            code.append("J", "");
+        } else if !word.contains(index + 1, 1, L_T_K_S_N_M_B_Z)
+            && !word.contains(index - 1, 1, &["S", "K", "L"])
+        {
+            code.append_both("J");
+        }
+        skip_double(word, index, 'J')
+    }
+}
+
+fn handle_l(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.at(index + 1) == 'L' {
+        let last = word.len() as isize - 1;
+        if (index == last - 2 && word.contains(index - 1, 4, &["ILLO", "ILLA", "ALLE"]))
+            || ((word.contains(last - 1, 2, &["AS", "OS"]) || word.contains(last, 1, &["A", "O"]))
+                && word.contains(index - 1, 4, &["ALLE"]))
+        {
+            code.append_primary("L");
+        } else {
+            code.append_both("L");
+        }
+        index + 2
+    } else {
+        code.append_both("L");
+        index + 1
+    }
+}
+
+fn handle_p(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.at(index + 1) == 'H' {
+        code.append_both("F");
+        index + 2
+    } else {
+        code.append_both("P");
+        if word.contains(index + 1, 1, &["P", "B"]) {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+}
+
+fn handle_r(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.is_last(index)
+        && !word.slavo_germanic
+        && word.contains(index - 2, 2, &["IE"])
+        && !word.contains(index - 4, 2, &["ME", "MA"])
+    {
+        code.append_alternate("R");
+    } else {
+        code.append_both("R");
+    }
+    skip_double(word, index, 'R')
+}
+
+fn handle_s(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.contains(index - 1, 3, &["ISL", "YSL"]) {
+        //silent
+        index + 1
+    } else if index == 0 && word.contains(index, 5, &["SUGAR"]) {
+        code.append("X", "S");
+        index + 1
+    } else if word.contains(index, 2, &["SH"]) {
+        if word.contains(index + 1, 4, &["HEIM", "HOEK", "HOLM", "HOLZ"]) {
+            code.append_both("S");
+        } else {
+            code.append_both("X");
+        }
+        index + 2
+    } else if word.contains(index, 3, &["SIO", "SIA"]) || word.contains(index, 4, &["SIAN"]) {
+        if word.slavo_germanic {
+            code.append_both("S");
+        } else {
+            code.append("S", "X");
+        }
+        index + 3
+    } else if (index == 0 && word.contains(index + 1, 1, &["M", "N", "L", "W"]))
+        || word.contains(index + 1, 1, &["Z"])
+    {
+        code.append("S", "X");
+        if word.contains(index + 1, 1, &["Z"]) {
+            index + 2
+        } else {
+            index + 1
+        }
+    } else if word.contains(index, 2, &["SC"]) {
+        handle_sc(word, code, index)
+    } else {
+        if word.is_last(index) && word.contains(index - 2, 2, &["AI", "OI"]) {
+            code.append_alternate("S");
+        } else {
+            code.append_both("S");
+        }
+        if word.contains(index + 1, 1, &["S", "Z"]) {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+}
+
+fn handle_sc(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.at(index + 2) == 'H' {
+        if word.contains(index + 3, 2, &["OO", "ER", "EN", "UY", "ED", "EM"]) {
+            if word.contains(index + 3, 2, &["ER", "EN"]) {
+                code.append("X", "SK");
+            } else {
+                code.append_both("SK");
+            }
+        } else if index == 0 && !word.is_vowel(3) && word.at(3) != 'W' {
+            code.append("X", "S");
+        } else {
+            code.append_both("X");
+        }
+    } else if word.contains(index + 2, 1, &["I", "E", "Y"]) {
+        code.append_both("S");
+    } else {
+        code.append_both("SK");
+    }
+    index + 3
+}
+
+fn handle_t(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.contains(index, 4, &["TION"]) || word.contains(index, 3, &["TIA", "TCH"]) {
+        code.append_both("X");
+        index + 3
+    } else if word.contains(index, 2, &["TH"]) || word.contains(index, 3, &["TTH"]) {
+        if word.contains(index + 2, 2, &["OM", "AM"]) || word.is_germanic() {
+            code.append_both("T");
+        } else {
+            code.append("0", "T");
+        }
+        index + 2
+    } else {
+        code.append_both("T");
+        if word.contains(index + 1, 1, &["T", "D"]) {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+}
+
+fn handle_w(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.contains(index, 2, &["WR"]) {
+        code.append_both("R");
+        index + 2
+    } else if index == 0 && (word.is_vowel(index + 1) || word.contains(index, 2, &["WH"])) {
+        if word.is_vowel(index + 1) {
+            code.append("A", "F");
+        } else {
+            code.append_both("A");
+        }
+        index + 1
+    } else if (word.is_last(index) && word.is_vowel(index - 1))
+        || word.contains(index - 1, 5, &["EWSKI", "EWSKY", "OWSKI", "OWSKY"])
+        || word.contains(0, 3, &["SCH"])
+    {
+        code.append_alternate("F");
+        index + 1
+    } else if word.contains(index, 4, &["WICZ", "WITZ"]) {
+        code.append("TS", "FX");
+        index + 4
+    } else {
+        index + 1
+    }
+}
+
+fn handle_x(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if index == 0 {
+        code.append_both("S");
+        index + 1
+    } else {
+        //final X is silent in French words
+        if !(word.is_last(index)
+            && (word.contains(index - 3, 3, &["IAU", "EAU"])
+                || word.contains(index - 2, 2, &["AU", "OU"])))
+        {
+            code.append_both("KS");
+        }
+        if word.contains(index + 1, 1, &["C", "X"]) {
+            index + 2
+        } else {
+            index + 1
+        }
+    }
+}
+
+fn handle_z(word: &Word, code: &mut PhoneticCode, index: isize) -> isize {
+    if word.at(index + 1) == 'H' {
+        code.append_both("J");
+        index + 2
+    } else {
+        if word.contains(index + 1, 2, &["ZO", "ZI", "ZA"])
+            || (word.slavo_germanic && index > 0 && word.at(index - 1) != 'T')
+        {
+            code.append("S", "TS");
+        } else {
+            code.append_both("S");
+        }
+        skip_double(word, index, 'Z')
+    }
+}
@@ -1,8 +1,17 @@
 use crate::types::*;
 use crate::vocab::*;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::sync::OnceLock;
 
 pub const TRANSITION_SMOOTHING_LOGPROB: f32 = -13.815510557964274;
 
+/// Maximum size (in bytes) of the text buffered by [`crate::VariantModel::find_matches_streaming()`]
+/// while waiting for a hard boundary, the buffer is processed as-is when it grows beyond this size.
+pub const STREAMING_BUFFER_LIMIT: usize = 1024 * 1024;
+
 /// Byte Offset
 #[derive(PartialEq, Clone, Debug)]
 pub struct Offset {
@@ -13,6 +22,12 @@ pub struct Offset {
 }
 
 impl Offset {
+    /// Shift the offset by the specified amount
+    pub fn shift(&mut self, delta: usize) {
+        self.begin += delta;
+        self.end += delta;
+    }
+
     pub fn convert(&mut self, map: &Vec<Option<usize>>) {
         self.begin = map
             .get(self.begin)
@@ -43,9 +58,18 @@ pub struct Match<'a> {
     ///The text of this match, corresponding to the input text.
     pub text: &'a str,
 
-    /// The byte offset where this match was found in the larger text
+    /// The offset where this match was found in the larger text, in UTF-8 bytes or in unicode
+    /// points if [`SearchParameters::unicodeoffsets`] is set.
     pub offset: Offset,
 
+    /// The UTF-8 byte offset where this match was found in the larger text, set for all matches returned by
+    /// [`crate::VariantModel::find_all_matches()`] regardless of [`SearchParameters::unicodeoffsets`]
+    pub byteoffset: Option<Offset>,
+
+    /// The unicode point offset where this match was found in the larger text, set for all matches returned by
+    /// [`crate::VariantModel::find_all_matches()`] regardless of [`SearchParameters::unicodeoffsets`]
+    pub charoffset: Option<Offset>,
+
     /// The variants for this match (sorted by decreasing distance score (first score), second score is frequency score)
     pub variants: Option<Vec<VariantResult>>,
 
@@ -65,6 +89,31 @@ pub struct Match<'a> {
 
     /// The number of tokens (boundaries spanned)
     pub n: usize,
+
+    /// The index of the sentence in which this match occurs (0-indexed). Sentences here correspond to the
+    /// batches delimited by hard boundaries in [`crate::VariantModel::find_all_matches()`].
+    pub sentence_index: usize,
+
+    /// The index of the first token of this match in the sentence (0-indexed)
+    pub token_index: usize,
+
+    /// The normalized representation of the text of this match, as encoded by the alphabet. Only
+    /// set if [`SearchParameters::debug_normalization`] is enabled.
+    pub normalized: Option<NormString>,
+
+    /// Set if no variant met the thresholds for this match, so it is out-of-vocabulary. What
+    /// to output for it is governed by [`SearchParameters::oov_policy`], see
+    /// [`crate::VariantModel::match_output()`].
+    pub oov: bool,
+
+    /// Alternative sequences for the sentence this match is the first match of, ranked by
+    /// decreasing score, the selected sequence comes first. Only set if [`SearchParameters::nbest`]
+    /// is larger than 1.
+    pub alternatives: Vec<SequenceAlternative>,
+
+    /// Confidence measures for the sentence this match is the first match of. Only set if
+    /// [`SearchParameters::sentence_confidence`] is enabled.
+    pub confidence: Option<SentenceConfidence>,
 }
 
 impl<'a> Match<'a> {
@@ -72,6 +121,8 @@ impl<'a> Match<'a> {
         Match {
             text,
             offset,
+            byteoffset: None,
+            charoffset: None,
             variants: None,
             selected: None,
             prevboundary: None,
@@ -79,6 +130,12 @@ impl<'a> Match<'a> {
             tag: vec![],
             seqnr: vec![],
             n: 0,
+            sentence_index: 0,
+            token_index: 0,
+            normalized: None,
+            oov: false,
+            alternatives: Vec::new(),
+            confidence: None,
         }
     }
 
@@ -173,6 +230,74 @@ impl Sequence {
     }
 }
 
+/// A single candidate for a position in a lattice passed to [`crate::VariantModel::decode_sequence()`]
+#[derive(PartialEq, Clone, Debug)]
+pub struct TokenHypothesis {
+    /// The vocabulary Id of the candidate, None if the candidate is out-of-vocabulary, in which case
+    /// the input text of the position is used (e.g. for language modelling and context rules)
+    pub vocab_id: Option<VocabId>,
+    /// The cost of the candidate, 0.0 is best and 1.0 is worst. This corresponds to the inverse of
+    /// the variant score (1.0 - score) in the built-in variant search.
+    pub cost: f32,
+}
+
+/// All candidates for a single position (token) in a lattice passed to [`crate::VariantModel::decode_sequence()`]
+#[derive(PartialEq, Clone, Debug)]
+pub struct TokenHypotheses {
+    /// The input text at this position
+    pub text: String,
+    pub candidates: Vec<TokenHypothesis>,
+}
+
+impl TokenHypotheses {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Adds a candidate with the specified cost, see [`TokenHypothesis`]
+    pub fn with_candidate(mut self, vocab_id: Option<VocabId>, cost: f32) -> Self {
+        self.candidates.push(TokenHypothesis { vocab_id, cost });
+        self
+    }
+}
+
+/// One of the n best sequences for a sentence, see [`SearchParameters::nbest`]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SequenceAlternative {
+    /// The text of the sentence with the variants of this sequence applied
+    pub text: String,
+    /// The combined score of the sequence (0.0 - 1.0), where each component is normalized
+    /// relative to the best candidate sequence for that component
+    pub score: f64,
+    /// The cost of the variants in the sequence, lower is better
+    pub variant_cost: f32,
+    /// The perplexity assigned by the language model, if used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lm_perplexity: Option<f64>,
+    /// The score assigned by the context rules, if loaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_score: Option<f64>,
+}
+
+/// Confidence measures for the sequence that was selected for a sentence, intended to flag
+/// low-quality sentences (e.g. badly recognised OCR) for review, see [`SearchParameters::sentence_confidence`]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SentenceConfidence {
+    /// The mean score of the selected variants (0.0 - 1.0), weighted by the number of tokens they
+    /// cover. Out-of-vocabulary tokens count as 0.0, tokens that are preserved as-is (e.g.
+    /// numbers) are not counted.
+    pub score: f64,
+    /// The perplexity the language model assigns to the sequence, if used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lm_perplexity: Option<f64>,
+    /// The score assigned by the context rules to the sequence, if loaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_score: Option<f64>,
+}
+
 #[derive(PartialEq, PartialOrd, Copy, Clone, Debug)]
 pub enum BoundaryStrength {
     None,
@@ -184,24 +309,189 @@ pub enum BoundaryStrength {
     Hard,
 }
 
+/// Determines which characters constitute token boundaries and how strong these boundaries are.
+/// The default corresponds to the built-in behaviour: all non-alphabetic characters are
+/// boundaries, and apostrophes, hyphens and underscores are weak boundaries.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoundaryConfig {
+    /// Non-alphabetic characters that are not boundaries but part of tokens (e.g. `-` and `/` for
+    /// chemical nomenclature or code identifiers)
+    pub nonboundary: Vec<char>,
+    /// Non-alphabetic characters that are part of a token if they occur between two alphabetic
+    /// characters, and boundaries otherwise (e.g. the apostrophe in `don't` and `O'Brien`, or the
+    /// period in abbreviations like `U.S.`, where only the final period remains a boundary)
+    pub intraword: Vec<char>,
+    /// Characters that form a weak (soft) boundary when they occur on their own
+    pub soft: Vec<char>,
+    /// Characters that always form a hard boundary
+    pub hard: Vec<char>,
+    /// The strength of a boundary consisting of a single whitespace character
+    pub whitespace: BoundaryStrength,
+}
+
+impl Default for BoundaryConfig {
+    fn default() -> Self {
+        Self {
+            nonboundary: Vec::new(),
+            intraword: Vec::new(),
+            soft: vec!['\'', '-', '_'],
+            hard: Vec::new(),
+            whitespace: BoundaryStrength::Normal,
+        }
+    }
+}
+
+impl BoundaryConfig {
+    /// Characters that are not boundaries but part of tokens
+    pub fn with_nonboundary(mut self, chars: &str) -> Self {
+        self.nonboundary = chars.chars().collect();
+        self
+    }
+
+    /// Characters that are part of a token if they occur between two alphabetic characters
+    pub fn with_intraword(mut self, chars: &str) -> Self {
+        self.intraword = chars.chars().collect();
+        self
+    }
+
+    /// Characters that form a weak boundary
+    pub fn with_soft(mut self, chars: &str) -> Self {
+        self.soft = chars.chars().collect();
+        self
+    }
+
+    /// Characters that always form a hard boundary
+    pub fn with_hard(mut self, chars: &str) -> Self {
+        self.hard = chars.chars().collect();
+        self
+    }
+
+    /// The strength of single whitespace boundaries
+    pub fn with_whitespace(mut self, strength: BoundaryStrength) -> Self {
+        self.whitespace = strength;
+        self
+    }
+
+    /// Is this character a boundary character?
+    pub fn is_boundary(&self, c: char) -> bool {
+        !c.is_alphabetic() && !self.nonboundary.contains(&c)
+    }
+
+    /// Is this character a boundary character, given the characters that precede and follow it?
+    /// This takes intra-word characters into account (see [`Self::intraword`]).
+    pub fn is_boundary_in_context(&self, c: char, prev: Option<char>, next: Option<char>) -> bool {
+        self.is_boundary(c)
+            && !(self.intraword.contains(&c)
+                && prev.is_some_and(|prev| prev.is_alphabetic())
+                && next.is_some_and(|next| next.is_alphabetic()))
+    }
+}
+
+impl FromStr for BoundaryStrength {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "none" => Ok(Self::None),
+            "weak" => Ok(Self::Weak),
+            "normal" => Ok(Self::Normal),
+            "hard" => Ok(Self::Hard),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Boundary strength must be one of: none, weak, normal, hard",
+            )),
+        }
+    }
+}
+
+/// Returns the proportion of (non-whitespace) characters in the text that are decimal digits, in
+/// any script (unicode general category Nd)
+pub fn numeric_ratio(text: &str) -> f32 {
+    static DIGIT: OnceLock<Regex> = OnceLock::new();
+    let digit = DIGIT.get_or_init(|| Regex::new(r"\p{Nd}").expect("valid regular expression"));
+    let length = text.chars().filter(|c| !c.is_whitespace()).count();
+    if length == 0 {
+        0.0
+    } else {
+        digit.find_iter(text).count() as f32 / length as f32
+    }
+}
+
+/// Returns true if the input is deliberately left uncorrected because it is shorter than
+/// [`SearchParameters::min_length`] or numeric (see [`SearchParameters::preserve_numeric`])
+pub(crate) fn preserved_input(input: &str, params: &SearchParameters) -> bool {
+    (params.min_length > 0 && input.chars().count() < params.min_length)
+        || (params.preserve_numeric > 0.0 && numeric_ratio(input) >= params.preserve_numeric)
+}
+
 /// Given a text string, identify at what points token boundaries
 /// occur, for instance between alphabetic characters and punctuation.
 /// The text string always ends with a boundary (but it may be a dummy one that covers no length).
 pub fn find_boundaries<'a>(text: &'a str) -> Vec<Match<'a>> {
+    find_boundaries_protected(text, &[])
+}
+
+/// Like [`find_boundaries()`], but any text spans matching one of the protected patterns (e.g.
+/// inline markup like `<lb/>` or `[unclear]`) are considered part of a boundary, so they are
+/// passed through verbatim and never subject to variant matching. Offsets still refer to the
+/// original text.
+pub fn find_boundaries_protected<'a>(text: &'a str, protected: &[Regex]) -> Vec<Match<'a>> {
+    find_boundaries_with_config(text, protected, &BoundaryConfig::default())
+}
+
+/// Like [`find_boundaries_protected()`], but uses the specified configuration to determine which
+/// characters are boundaries.
+pub fn find_boundaries_with_config<'a>(
+    text: &'a str,
+    protected: &[Regex],
+    config: &BoundaryConfig,
+) -> Vec<Match<'a>> {
+    let protected_spans: Vec<(usize, usize)> = protected
+        .iter()
+        .flat_map(|pattern| pattern.find_iter(text).map(|m| (m.start(), m.end())))
+        .collect();
+    find_boundaries_with_spans(text, protected_spans, config)
+}
+
+/// Like [`find_boundaries_with_config()`], but takes the protected spans directly as (begin, end)
+/// UTF-8 byte offsets.
+pub fn find_boundaries_with_spans<'a>(
+    text: &'a str,
+    mut protected_spans: Vec<(usize, usize)>,
+    config: &BoundaryConfig,
+) -> Vec<Match<'a>> {
     let mut boundaries = Vec::new();
 
+    //sort all protected spans by begin offset
+    protected_spans.sort_unstable();
+    let mut protected_spans = protected_spans.into_iter().peekable();
+    let mut protected_end: usize = 0;
+
     //boundary begin
     let mut begin: Option<usize> = None;
 
+    let mut prev: Option<char> = None;
     for (i, c) in text.char_indices() {
+        while let Some((span_begin, span_end)) = protected_spans.peek() {
+            if *span_begin > i {
+                break;
+            }
+            if *span_end > protected_end {
+                protected_end = *span_end;
+            }
+            protected_spans.next();
+        }
+        let next = text[i + c.len_utf8()..].chars().next();
+        let is_boundary = config.is_boundary_in_context(c, prev, next) || i < protected_end;
+        prev = Some(c);
         if let Some(b) = begin {
-            if c.is_alphabetic() {
+            if !is_boundary {
                 //boundary ends here
                 boundaries.push(Match::new_empty(&text[b..i], Offset { begin: b, end: i }));
                 begin = None;
             }
         } else {
-            if !c.is_alphabetic() {
+            if is_boundary {
                 //boundary starts here
                 begin = Some(i);
             }
@@ -232,23 +522,63 @@ pub fn find_boundaries<'a>(text: &'a str) -> Vec<Match<'a>> {
     boundaries
 }
 
+/// Finds the maximal runs of alphabetic characters that are unknown according to the `known`
+/// predicate, such as text in a script the alphabet does not cover. Non-alphabetic characters
+/// neither start nor end a run, but are included if they occur between unknown characters. Returns
+/// (begin, end) UTF-8 byte offsets.
+pub fn find_unknown_runs(text: &str, known: impl Fn(char) -> bool) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut run: Option<(usize, usize)> = None;
+    for (i, c) in text.char_indices() {
+        if !c.is_alphabetic() {
+            continue;
+        }
+        if known(c) {
+            if let Some(run) = run.take() {
+                runs.push(run);
+            }
+        } else if let Some(run) = run.as_mut() {
+            run.1 = i + c.len_utf8();
+        } else {
+            run = Some((i, i + c.len_utf8()));
+        }
+    }
+    if let Some(run) = run {
+        runs.push(run);
+    }
+    runs
+}
+
 /// Classify the token boundaries as detected by `find_boundaries` as
 /// either weak, normal or hard boundaries. This information determines
 /// how eager the system is to split on certain boundaries.
-pub fn classify_boundaries(boundaries: &Vec<Match<'_>>) -> Vec<BoundaryStrength> {
+pub fn classify_boundaries(
+    boundaries: &Vec<Match<'_>>,
+    config: &BoundaryConfig,
+) -> Vec<BoundaryStrength> {
     let mut strengths = Vec::new();
 
     for (i, boundary) in boundaries.iter().enumerate() {
         let strength = if i == boundaries.len() - 1 {
             //last boundary is always a hard one
             BoundaryStrength::Hard
-        } else if boundary.text.len() > 1 {
-            //multichar boundaries are hard ones
+        } else if boundary.text.chars().any(|c| config.hard.contains(&c)) {
+            BoundaryStrength::Hard
+        } else if boundary.text.len() > 1 && boundary.text != "\r\n" {
+            //multichar boundaries are hard ones (a windows linebreak counts as a single character)
             BoundaryStrength::Hard
         } else {
-            match boundary.text {
-                "'" | "-" | "_" => BoundaryStrength::Weak,
-                _ => BoundaryStrength::Normal,
+            let c = boundary
+                .text
+                .chars()
+                .last()
+                .expect("boundary must have a character");
+            if c.is_whitespace() {
+                config.whitespace
+            } else if config.soft.contains(&c) {
+                BoundaryStrength::Weak
+            } else {
+                BoundaryStrength::Normal
             }
         };
         strengths.push(strength)
@@ -335,7 +665,7 @@ pub fn redundant_match<'a>(candidate: &Match<'a>, matches: &[Match<'a>]) -> bool
     true
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum PatternMatch {
     /// Exact match with specific vocabulary
     Vocab(VocabId),
@@ -349,9 +679,25 @@ pub enum PatternMatch {
     Not(Box<PatternMatch>),
     /// Disjunction (|)
     Disjunction(Box<Vec<PatternMatch>>),
+    /// Match if the regular expression matches the whole text of the token (regex:/.../)
+    Regex(#[serde(with = "serde_regex")] Regex),
 }
 
-#[derive(Clone, Debug)]
+/// (De)serializes a compiled regular expression as its pattern
+mod serde_regex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(regex.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ContextRule {
     /// Lexicon index
     pub pattern: Vec<PatternMatch>,
@@ -370,32 +716,39 @@ pub struct PatternMatchResult {
 }
 
 impl PatternMatch {
-    pub fn matches(&self, sequence: &[(VocabId, u32)], index: usize) -> bool {
+    /// Tests whether the item at the index in the sequence matches. The sequence consists of the
+    /// vocabulary ID (0 if out-of-vocabulary), the lexicon index and the text of each token.
+    pub fn matches(&self, sequence: &[(VocabId, u32, &str)], index: usize) -> bool {
         match self {
             PatternMatch::Any => {
                 return true;
             }
             PatternMatch::NoLexicon => {
-                if let Some((vocabid, lexindex)) = sequence.get(index) {
+                if let Some((vocabid, lexindex, _text)) = sequence.get(index) {
                     if *lexindex == 0 || *vocabid == 0 {
                         return true;
                     }
                 }
             }
             PatternMatch::Vocab(testvocabid) => {
-                if let Some((vocabid, _lexindex)) = sequence.get(index) {
+                if let Some((vocabid, _lexindex, _text)) = sequence.get(index) {
                     if testvocabid == vocabid {
                         return true;
                     }
                 }
             }
             PatternMatch::FromLexicon(lextest) => {
-                if let Some((_vocabid, lexindex)) = sequence.get(index) {
+                if let Some((_vocabid, lexindex, _text)) = sequence.get(index) {
                     if lexindex & (1 << lextest) == 1 << lextest {
                         return true;
                     }
                 }
             }
+            PatternMatch::Regex(regex) => {
+                if let Some((_vocabid, _lexindex, text)) = sequence.get(index) {
+                    return regex.is_match(text);
+                }
+            }
             PatternMatch::Not(pm) => {
                 return !pm.matches(sequence, index);
             }
@@ -418,6 +771,18 @@ impl PatternMatch {
         let s = s.trim();
         if s == "?" {
             Ok(Self::Any)
+        } else if let Some(pattern) = s.strip_prefix("regex:/").and_then(|s| s.strip_suffix('/')) {
+            //checked before disjunctions and negations, as the regular expression itself may contain | and !
+            match Regex::new(&format!("^(?:{})$", pattern)) {
+                Ok(regex) => Ok(Self::Regex(regex)),
+                Err(err) => Err(std::io::Error::other(format!(
+                    "Context rule has an invalid regular expression '{}': {}",
+                    pattern, err
+                ))),
+            }
+        } else if s.starts_with("!regex:/") && s.ends_with('/') {
+            let pm = Self::parse(&s[1..], lexicons, encoder)?;
+            Ok(Self::Not(Box::new(pm)))
         } else if s == "^" {
             Ok(Self::NoLexicon)
         } else if s.starts_with("!(") && s.ends_with(")") {
@@ -471,7 +836,7 @@ impl ContextRule {
     ///provided as parameter. Returns the matches in `sequence_result`
     pub fn matches(
         &self,
-        sequence: &[(VocabId, u32)],
+        sequence: &[(VocabId, u32, &str)],
         begin: usize,
         sequence_result: &mut Vec<Vec<PatternMatchResult>>,
     ) -> bool {
@@ -524,9 +889,12 @@ impl ContextRule {
 }
 
 /// Remap all UTF-8 offsets to unicode codepoint offsets
+/// Sets both the byte offsets and the unicode point offsets on all matches. If `unicodeoffsets` is
+/// set, the primary offset is remapped to unicode points as well.
 pub(crate) fn remap_offsets_to_unicodepoints<'a>(
     text: &'a str,
     mut matches: Vec<Match<'a>>,
+    unicodeoffsets: bool,
 ) -> Vec<Match<'a>> {
     let mut bytes2unicodepoints: Vec<Option<usize>> = Vec::new();
     let mut end = 0;
@@ -540,7 +908,13 @@ pub(crate) fn remap_offsets_to_unicodepoints<'a>(
     //add an end offset
     bytes2unicodepoints.push(Some(end));
     for m in matches.iter_mut() {
-        m.offset.convert(&bytes2unicodepoints);
+        let mut charoffset = m.offset.clone();
+        charoffset.convert(&bytes2unicodepoints);
+        m.byteoffset = Some(m.offset.clone());
+        if unicodeoffsets {
+            m.offset = charoffset.clone();
+        }
+        m.charoffset = Some(charoffset);
     }
     matches
 }
@@ -1,3 +1,4 @@
+use crate::search::{BoundaryConfig, TRANSITION_SMOOTHING_LOGPROB};
 use crate::types::*;
 
 pub const ALPHABET: &[&[&str]] = &[
@@ -61,8 +62,35 @@ pub fn get_test_searchparams() -> SearchParameters {
         lm_weight: 1.0,
         variantmodel_weight: 3.0,
         contextrules_weight: 1.0,
+        lm_smoothing_logprob: TRANSITION_SMOOTHING_LOGPROB,
+        lm_add_k: 0.0,
         max_seq: 250,
+        nbest: 1,
         consolidate_matches: true,
         unicodeoffsets: false,
+        score_combination: ScoreCombination::ArithmeticMean,
+        freq_transform: FrequencyTransform::Linear,
+        freq_cap: 0,
+        expansion_frequency: ExpansionFrequency::Minimum,
+        protected_patterns: Vec::new(),
+        skip_unknown_scripts: false,
+        debug_normalization: false,
+        phonetic: false,
+        return_edits: false,
+        transfer_case: false,
+        collapse_lemmas: false,
+        sentence_confidence: false,
+        boundary_config: BoundaryConfig::default(),
+        global_context_rules: false,
+        neighbourhood_cache_size: 0,
+        max_candidates: 0,
+        explain: false,
+        min_length: 0,
+        skip_exact: false,
+        preserve_numeric: 0.0,
+        oov_policy: OovPolicy::Keep,
+        hard_max: 0,
+        ngram_min_length: 0,
+        ngram_min_overlap: 0.5,
     }
 }
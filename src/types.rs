@@ -1,5 +1,11 @@
+use crate::search::{BoundaryConfig, TRANSITION_SMOOTHING_LOGPROB};
+use crate::VariantModel;
 use ibig::UBig;
-use std::cmp::Ordering;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sesdiff::{EditInstruction, EditScript};
+use std::cmp::{max, min, Ordering};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::io::Error;
 use std::io::ErrorKind;
@@ -28,13 +34,48 @@ pub const PRIMES: &[CharType] = &[
 ];
 
 ///The anagram hash: uses a bag-of-characters representation where each bit flags the presence/absence of a certain character (the order of the bits are defined by Alphabet)
-pub type AnaValue = UBig;
+///
+///Values that fit in a `u128` (the common case of short words) are kept in native integer
+///arithmetic, larger values are promoted to a big integer. A value is always held in the `Small`
+///representation when it fits, so equality, hashing and ordering are consistent with the
+///numeric value.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AnaValue {
+    Small(u128),
+    Big(UBig),
+}
 
 ///Defines the alphabet, index corresponds how things are encoded, multiple strings may be encoded
 ///in the same way
 pub type Alphabet = Vec<Vec<String>>;
 
-#[derive(Clone, PartialEq, Debug)]
+///Parses a line of an alphabet file into its (tab separated) entries, resolving the escapes `\s`,
+///`\t`, `\n` and `\uXXXX`. Empty fields are skipped.
+pub(crate) fn parse_alphabet_line(line: &str) -> Vec<String> {
+    line.split('\t')
+        .filter_map(|x| match x {
+            "\\s" => Some(" ".to_owned()),
+            "\\t" => Some("\t".to_owned()),
+            "\\n" => Some("\n".to_owned()),
+            _ if x.starts_with("\\u") && x.len() > 2 => Some(
+                u32::from_str_radix(&x[2..], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| x.trim().to_owned()),
+            ),
+            _ => {
+                if x.trim().is_empty() {
+                    None
+                } else {
+                    Some(x.trim().to_owned())
+                }
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Weights {
     ///Weight for the Levenshtein (or Damarau-Levenshtein) distance
     pub ld: f64,
@@ -50,6 +91,15 @@ pub struct Weights {
 
     ///Weight to assign to difference in case (lowercase/uppercase)
     pub case: f64,
+
+    ///Weight for the Jaro-Winkler similarity, disabled (0.0) by default
+    #[serde(default)]
+    pub jarowinkler: f64,
+
+    ///Weight for phonetic similarity (sharing a Double Metaphone code), disabled (0.0) by default.
+    ///The phonetic index is only built if this weight is non-zero.
+    #[serde(default)]
+    pub phonetic: f64,
 }
 
 impl Default for Weights {
@@ -60,13 +110,21 @@ impl Default for Weights {
             prefix: 0.125,
             suffix: 0.125,
             case: 0.125,
+            jarowinkler: 0.0,
+            phonetic: 0.0,
         }
     }
 }
 
 impl Weights {
     pub fn sum(&self) -> f64 {
-        self.ld + self.lcs + self.prefix + self.suffix + self.case
+        self.ld
+            + self.lcs
+            + self.prefix
+            + self.suffix
+            + self.case
+            + self.jarowinkler
+            + self.phonetic
     }
 }
 
@@ -78,6 +136,37 @@ pub enum DistanceThreshold {
     RatioWithLimit(f32, u8),
     ///Absolute distance threshold
     Absolute(u8),
+    ///Only for the anagram distance: the effective edit distance threshold for the input plus the
+    ///specified delta. Used as edit distance threshold, this is equivalent to `Absolute(delta)`.
+    DerivedFromEdit(u8),
+}
+
+impl DistanceThreshold {
+    /// Computes the effective absolute threshold for a text of the given length (in characters),
+    /// ratios are capped by the specified absolute maximum which acts as a safeguard.
+    ///
+    /// The threshold is adjusted for very small inputs: absolute thresholds never exceed half
+    /// the length of the input, but always allow at least distance 1. Ratios are raised to
+    /// distance 1 for inputs of one or two characters only, so exact and distance-1 matches can
+    /// still be found for those. An explicit threshold of zero is respected. Used as an absolute
+    /// threshold, `DerivedFromEdit(x)` is equivalent to `Absolute(x)`.
+    pub fn effective(&self, length: usize, maximum: u8) -> u8 {
+        let ratio = |x: f32, limit: u8| {
+            let distance = min((length as f32 * x).floor() as usize, limit as usize) as u8;
+            if x > 0.0 && length <= 2 {
+                distance.max(min(1, limit))
+            } else {
+                distance
+            }
+        };
+        match *self {
+            Self::Ratio(x) => ratio(x, maximum),
+            Self::RatioWithLimit(x, limit) => ratio(x, limit),
+            Self::Absolute(x) | Self::DerivedFromEdit(x) => {
+                min(x as usize, max(length / 2, 1)) as u8
+            }
+        }
+    }
 }
 
 impl FromStr for DistanceThreshold {
@@ -94,6 +183,10 @@ impl FromStr for DistanceThreshold {
                 }
             }
             return Err(Error::new(ErrorKind::InvalidInput, "Expected a combination of a ratio (float) and and absolute maximum (integer) separated by a semicolon"));
+        } else if let Some(delta) = s.strip_prefix("+") {
+            if let Ok(delta) = delta.parse::<u8>() {
+                return Ok(Self::DerivedFromEdit(delta));
+            }
         } else if let Ok(num) = s.parse::<u8>() {
             return Ok(Self::Absolute(num));
         } else if let Ok(num) = s.parse::<f32>() {
@@ -101,7 +194,217 @@ impl FromStr for DistanceThreshold {
                 return Ok(Self::Ratio(num));
             }
         }
-        Err(Error::new(ErrorKind::InvalidInput, "Input must be integer (absolute threshold) or float between 0.0 and 1.0 (ratio), or a combination of a ratio and and absolute maximum separated by a semicolon"))
+        Err(Error::new(ErrorKind::InvalidInput, "Input must be integer (absolute threshold) or float between 0.0 and 1.0 (ratio), or a combination of a ratio and and absolute maximum separated by a semicolon, or an integer prefixed by a plus sign (delta relative to the edit distance)"))
+    }
+}
+
+/// Determines how the individual (weighted) similarity components are combined into a single score
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ScoreCombination {
+    /// Weighted arithmetic mean over all similarity components (the default)
+    #[default]
+    ArithmeticMean,
+
+    /// Weighted geometric mean over all similarity components. This penalizes any single weak
+    /// component more harshly than the arithmetic mean. Components that are zero are clamped to a
+    /// small positive value ([`GEOMETRIC_MEAN_FLOOR`]) so a single zero does not nullify the whole score.
+    GeometricMean,
+}
+
+/// The minimum value a similarity component takes when computing a geometric mean
+pub const GEOMETRIC_MEAN_FLOOR: f64 = 0.01;
+
+impl ScoreCombination {
+    /// Combine a number of (weight, component score) pairs into a single score. The weights
+    /// are normalized by `weights_sum`.
+    pub fn combine(&self, components: &[(f64, f64)], weights_sum: f64) -> f64 {
+        match self {
+            Self::ArithmeticMean => {
+                components
+                    .iter()
+                    .map(|(weight, score)| weight * score)
+                    .sum::<f64>()
+                    / weights_sum
+            }
+            Self::GeometricMean => (components
+                .iter()
+                .filter(|(weight, _)| *weight > 0.0)
+                .map(|(weight, score)| weight * score.max(GEOMETRIC_MEAN_FLOOR).ln())
+                .sum::<f64>()
+                / weights_sum)
+                .exp(),
+        }
+    }
+}
+
+impl FromStr for ScoreCombination {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, std::io::Error> {
+        match s {
+            "arithmetic" | "arithmeticmean" => Ok(Self::ArithmeticMean),
+            "geometric" | "geometricmean" => Ok(Self::GeometricMean),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Score combination must be either 'arithmetic' or 'geometric'",
+            )),
+        }
+    }
+}
+
+/// Determines how the absolute frequencies of the variants are transformed into frequency scores
+/// (prior to normalizing them to a scale of 0.0 to 1.0 over all variants of an input)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FrequencyTransform {
+    /// Use the absolute frequency as is (the default). A single very frequent variant dwarfs all others.
+    #[default]
+    Linear,
+
+    /// Use the logarithm of the frequency, this dampens very skewed frequency distributions
+    Log,
+
+    /// Use the rank of the frequency amongst the variants. The most frequent variant scores 1.0 and each
+    /// next distinct frequency scores a fixed step lower. Variants with the same frequency share a rank.
+    Rank,
+}
+
+impl FrequencyTransform {
+    /// Transforms the absolute frequencies into frequency scores and normalizes them, in place.
+    pub fn normalize(&self, freqs: &mut [f64]) {
+        match self {
+            Self::Linear | Self::Log => {
+                if *self == Self::Log {
+                    for freq in freqs.iter_mut() {
+                        *freq = freq.ln_1p();
+                    }
+                }
+                let max_freq = freqs.iter().copied().fold(0.0, f64::max);
+                if max_freq > 0.0 {
+                    for freq in freqs.iter_mut() {
+                        *freq /= max_freq;
+                    }
+                }
+            }
+            Self::Rank => {
+                let mut distinct: Vec<f64> = freqs.to_vec();
+                distinct.sort_unstable_by(|a, b| b.total_cmp(a));
+                distinct.dedup();
+                let count = distinct.len() as f64;
+                for freq in freqs.iter_mut() {
+                    let rank = distinct.partition_point(|x| x > freq) as f64;
+                    *freq = (count - rank) / count;
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for FrequencyTransform {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, std::io::Error> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "log" | "logarithmic" => Ok(Self::Log),
+            "rank" => Ok(Self::Rank),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Frequency transform must be either 'linear', 'log' or 'rank'",
+            )),
+        }
+    }
+}
+
+/// Determines the (absolute) frequency a variant gets when it is expanded to the item it is a
+/// variant of, see [`crate::VariantModel::expand_variants_with()`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExpansionFrequency {
+    /// The minimum of the frequency of the matched variant and that of the item it refers to (the
+    /// default), so a frequent reference does not boost a rare erroneous variant
+    #[default]
+    Minimum,
+
+    /// The product of the frequency of the matched variant and that of the item it refers to
+    Product,
+
+    /// The frequency of the item referred to, the frequency of the matched variant is disregarded
+    Target,
+}
+
+impl FromStr for ExpansionFrequency {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, std::io::Error> {
+        match s {
+            "min" | "minimum" => Ok(Self::Minimum),
+            "product" => Ok(Self::Product),
+            "target" | "keep-target" => Ok(Self::Target),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Expansion frequency must be either 'min', 'product' or 'target'",
+            )),
+        }
+    }
+}
+
+/// The default placeholder text for [`OovPolicy::Placeholder`]
+pub const OOV_PLACEHOLDER: &str = "<unk>";
+
+/// Determines what to output for matches in [`crate::VariantModel::find_all_matches()`] for which
+/// no variant meets the thresholds (out-of-vocabulary matches)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum OovPolicy {
+    /// Keep the original input text (the default)
+    #[default]
+    Keep,
+
+    /// Output nothing for the match
+    Empty,
+
+    /// Output the given placeholder text, this allows downstream tools to explicitly mark tokens that could not be corrected
+    Placeholder(String),
+}
+
+impl OovPolicy {
+    /// Returns the text to output for an out-of-vocabulary match with the given input text, or `None` if nothing should be output
+    pub fn apply<'a>(&'a self, input: &'a str) -> Option<&'a str> {
+        match self {
+            Self::Keep => Some(input),
+            Self::Empty => None,
+            Self::Placeholder(placeholder) => Some(placeholder.as_str()),
+        }
+    }
+}
+
+impl FromStr for OovPolicy {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, std::io::Error> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "empty" => Ok(Self::Empty),
+            "placeholder" => Ok(Self::Placeholder(OOV_PLACEHOLDER.to_string())),
+            _ => {
+                if let Some(placeholder) = s.strip_prefix("placeholder:") {
+                    Ok(Self::Placeholder(placeholder.to_string()))
+                } else {
+                    Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "OOV policy must be either 'keep', 'empty', 'placeholder' or 'placeholder:TEXT'",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for OovPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Keep => write!(f, "keep"),
+            Self::Empty => write!(f, "empty"),
+            Self::Placeholder(placeholder) => write!(f, "placeholder:{}", placeholder),
+        }
     }
 }
 
@@ -135,6 +438,11 @@ pub struct SearchParameters {
     /// Maximum number of candidate sequences to take along to the language modelling stage
     pub max_seq: usize,
 
+    /// Number of alternative sequences to return for each sentence in
+    /// [`crate::VariantModel::find_all_matches()`], ranked by decreasing score (see [`crate::Match::alternatives`]).
+    /// Values of 1 or lower only return the single best sequence. Can not exceed [`Self::max_seq`].
+    pub nbest: usize,
+
     /// Use only a single-thread instead of leveraging multiple cores (lowers resource use and
     /// performance)
     pub single_thread: bool,
@@ -152,6 +460,14 @@ pub struct SearchParameters {
     /// Weight attributed to the context rules model in finding the most likely sequence
     pub contextrules_weight: f32,
 
+    /// Log-probability the language model assigns to transitions (bigrams) it has not seen, and to
+    /// transitions involving out-of-vocabulary tokens. Only used if `lm_add_k` is 0.
+    pub lm_smoothing_logprob: f32,
+
+    /// If non-zero, use add-k smoothing with this value for k when computing language model
+    /// transition probabilities, instead of the fixed `lm_smoothing_logprob`.
+    pub lm_add_k: f32,
+
     /// Weight attributed to the frequency information in frequency reranking, in relation to
     /// the similarity component. 0 = disabled)
     pub freq_weight: f32,
@@ -162,6 +478,108 @@ pub struct SearchParameters {
 
     /// Output text offsets in unicode points rather than UTF-8 byte offsets
     pub unicodeoffsets: bool,
+
+    /// How to combine the individual similarity components into a single distance score
+    pub score_combination: ScoreCombination,
+
+    /// How to transform the frequencies of the variants into frequency scores (see [`FrequencyTransform`])
+    pub freq_transform: FrequencyTransform,
+
+    /// Clip the absolute frequencies of the variants at this ceiling before they are transformed and
+    /// normalized into frequency scores, so extremely frequent entries (e.g. function words) do not
+    /// dominate on frequency alone. 0 = no cap.
+    pub freq_cap: u32,
+
+    /// The frequency that variants get when they are expanded to the items they are variants of
+    /// (e.g. for error lists), see [`ExpansionFrequency`]
+    pub expansion_frequency: ExpansionFrequency,
+
+    /// Patterns for inline markup (e.g. `<lb/>`, `[unclear]`) that should be protected from
+    /// matching; text matching these is passed through verbatim and treated as a hard boundary.
+    pub protected_patterns: Vec<Regex>,
+
+    /// Treat maximal runs of alphabetic characters that are not covered by the alphabet (e.g. text
+    /// in another script), including any non-alphabetic characters in between, as hard boundaries in
+    /// [`crate::VariantModel::find_all_matches()`]. They are passed through verbatim rather than
+    /// being subjected to variant search.
+    pub skip_unknown_scripts: bool,
+
+    /// Attach the normalized representation (as encoded by the alphabet) of the input and of each candidate
+    /// to the output, intended for diagnosing encoding and offset issues
+    pub debug_normalization: bool,
+
+    /// Add instances that share a phonetic code with the input to the candidates, regardless of
+    /// their anagram distance. Requires a phonetic index, which is only built if the phonetic weight is non-zero.
+    pub phonetic: bool,
+
+    /// Compute and return the edits that transform the input into each variant
+    pub return_edits: bool,
+
+    /// Transfer the casing pattern of the input (all-caps, title case or lowercase, see [`CasePattern`])
+    /// to the text of the variants, so e.g. `Huys` is corrected to `Huis` rather than to the lowercase
+    /// lexicon form `huis`. The cased text is stored in [`VariantResult::cased`].
+    pub transfer_case: bool,
+
+    /// Collapse variants that share the same lemma (see [`crate::VocabParams::lemma_column`]),
+    /// retaining only the best ranking inflection of each lemma
+    pub collapse_lemmas: bool,
+
+    /// Attach confidence measures for the selected sequence to the first match of each sentence in
+    /// [`crate::VariantModel::find_all_matches()`] (see [`crate::SentenceConfidence`]), so
+    /// low-confidence sentences can be flagged for review. Only applies if matches are consolidated.
+    pub sentence_confidence: bool,
+
+    /// Determines which characters are token boundaries and how strong these are
+    pub boundary_config: BoundaryConfig,
+
+    /// Re-apply the context rules over the final sequence of matches, across hard boundaries, so rules can tag
+    /// sequences that span them (see [`crate::VariantModel::apply_context_rules_global()`])
+    pub global_context_rules: bool,
+
+    /// Maximum number of anagram neighbourhoods to keep in a least-recently-used cache in the model,
+    /// so repeated or similar inputs need not recompute them. This trades memory for speed. 0 = disabled.
+    pub neighbourhood_cache_size: usize,
+
+    /// Maximum number of anagram values to gather as candidates for a single input; if this budget
+    /// is reached, the remaining (more distant) anagram values are not considered. This protects
+    /// against pathological inputs with huge neighbourhoods. 0 = unlimited.
+    pub max_candidates: usize,
+
+    /// Attach a breakdown of how each score was computed to each variant (see [`ScoreExplanation`])
+    pub explain: bool,
+
+    /// Inputs shorter than this (in unicode characters) are not matched at all, they yield no
+    /// variants and are thus left uncorrected in search mode. 0 = no minimum.
+    pub min_length: usize,
+
+    /// If the input occurs verbatim in the lexicon, return it as the sole variant with a perfect score
+    /// rather than searching its anagram neighbourhood. This speeds up cleaning mostly correct text.
+    pub skip_exact: bool,
+
+    /// Inputs of which at least this proportion of (non-whitespace) characters are decimal digits
+    /// (in any script) are not matched, so they are left uncorrected in search mode. This prevents
+    /// nonsense corrections of numbers such as years. 1.0 only affects inputs consisting entirely of
+    /// digits, 0.5 affects inputs that are at least half digits. 0.0 = disabled.
+    pub preserve_numeric: f32,
+
+    /// What to output for matches in [`crate::VariantModel::find_all_matches()`] for which no
+    /// variant meets the thresholds, see [`crate::VariantModel::match_output()`].
+    pub oov_policy: OovPolicy,
+
+    /// Absolute ceiling on the number of variants to return per input, which also applies if
+    /// `max_matches` is 0 (unlimited). Results beyond it are truncated after ranking and this is
+    /// logged. This protects against huge result sets with large lexicons and loose thresholds. 0 = no ceiling.
+    pub hard_max: usize,
+
+    /// Inputs of at least this many characters (normalized to the alphabet) find their candidates
+    /// by shared character n-grams (see [`crate::NGramIndex`]) rather than by anagram distance,
+    /// which is expensive for long inputs such as multi-word phrases. Requires an n-gram index, see
+    /// [`crate::VariantModel::set_ngram_index()`]. 0 = disabled.
+    pub ngram_min_length: usize,
+
+    /// The minimum overlap (Dice coefficient over the character n-grams) for candidates found
+    /// through the n-gram index (see [`Self::ngram_min_length`])
+    pub ngram_min_overlap: f64,
 }
 
 impl Default for SearchParameters {
@@ -177,13 +595,40 @@ impl Default for SearchParameters {
             lm_order: 3,
             single_thread: false,
             max_seq: 250,
+            nbest: 1,
             context_weight: 0.0,
             freq_weight: 0.0,
             variantmodel_weight: 3.0,
             lm_weight: 1.0,
             contextrules_weight: 1.0,
+            lm_smoothing_logprob: TRANSITION_SMOOTHING_LOGPROB,
+            lm_add_k: 0.0,
             consolidate_matches: true,
             unicodeoffsets: false,
+            score_combination: ScoreCombination::ArithmeticMean,
+            freq_transform: FrequencyTransform::Linear,
+            freq_cap: 0,
+            expansion_frequency: ExpansionFrequency::Minimum,
+            protected_patterns: Vec::new(),
+            skip_unknown_scripts: false,
+            debug_normalization: false,
+            phonetic: false,
+            return_edits: false,
+            transfer_case: false,
+            collapse_lemmas: false,
+            sentence_confidence: false,
+            boundary_config: BoundaryConfig::default(),
+            global_context_rules: false,
+            neighbourhood_cache_size: 0,
+            max_candidates: 0,
+            explain: false,
+            min_length: 0,
+            skip_exact: false,
+            preserve_numeric: 0.0,
+            oov_policy: OovPolicy::Keep,
+            hard_max: 0,
+            ngram_min_length: 0,
+            ngram_min_overlap: 0.5,
         }
     }
 }
@@ -199,12 +644,50 @@ impl fmt::Display for SearchParameters {
         writeln!(f, " lm_order={}", self.lm_order)?;
         writeln!(f, " single_thread={}", self.single_thread)?;
         writeln!(f, " max_seq={}", self.max_seq)?;
+        writeln!(f, " nbest={}", self.nbest)?;
         writeln!(f, " freq_weight={}", self.freq_weight)?;
         writeln!(f, " variantmodel_weight={}", self.variantmodel_weight)?;
         writeln!(f, " lm_weight={}", self.lm_weight)?;
         writeln!(f, " contextrules_weight={}", self.contextrules_weight)?;
+        writeln!(f, " lm_smoothing_logprob={}", self.lm_smoothing_logprob)?;
+        writeln!(f, " lm_add_k={}", self.lm_add_k)?;
         writeln!(f, " consolidate_matches={}", self.consolidate_matches)?;
-        writeln!(f, " unicodeoffsets={}", self.unicodeoffsets)
+        writeln!(f, " unicodeoffsets={}", self.unicodeoffsets)?;
+        writeln!(f, " score_combination={:?}", self.score_combination)?;
+        writeln!(f, " freq_transform={:?}", self.freq_transform)?;
+        writeln!(f, " freq_cap={}", self.freq_cap)?;
+        writeln!(f, " expansion_frequency={:?}", self.expansion_frequency)?;
+        writeln!(
+            f,
+            " protected_patterns={:?}",
+            self.protected_patterns
+                .iter()
+                .map(|pattern| pattern.as_str())
+                .collect::<Vec<_>>()
+        )?;
+        writeln!(f, " skip_unknown_scripts={}", self.skip_unknown_scripts)?;
+        writeln!(f, " debug_normalization={}", self.debug_normalization)?;
+        writeln!(f, " phonetic={}", self.phonetic)?;
+        writeln!(f, " return_edits={}", self.return_edits)?;
+        writeln!(f, " transfer_case={}", self.transfer_case)?;
+        writeln!(f, " collapse_lemmas={}", self.collapse_lemmas)?;
+        writeln!(f, " sentence_confidence={}", self.sentence_confidence)?;
+        writeln!(f, " boundary_config={:?}", self.boundary_config)?;
+        writeln!(f, " global_context_rules={}", self.global_context_rules)?;
+        writeln!(
+            f,
+            " neighbourhood_cache_size={}",
+            self.neighbourhood_cache_size
+        )?;
+        writeln!(f, " max_candidates={}", self.max_candidates)?;
+        writeln!(f, " explain={}", self.explain)?;
+        writeln!(f, " min_length={}", self.min_length)?;
+        writeln!(f, " skip_exact={}", self.skip_exact)?;
+        writeln!(f, " preserve_numeric={}", self.preserve_numeric)?;
+        writeln!(f, " oov_policy={}", self.oov_policy)?;
+        writeln!(f, " hard_max={}", self.hard_max)?;
+        writeln!(f, " ngram_min_length={}", self.ngram_min_length)?;
+        writeln!(f, " ngram_min_overlap={}", self.ngram_min_overlap)
     }
 }
 
@@ -241,6 +724,10 @@ impl SearchParameters {
         self.max_seq = max_seq;
         self
     }
+    pub fn with_nbest(mut self, nbest: usize) -> Self {
+        self.nbest = nbest;
+        self
+    }
     pub fn with_single_thread(mut self) -> Self {
         self.single_thread = true;
         self
@@ -277,17 +764,122 @@ impl SearchParameters {
         self.contextrules_weight = weight;
         self
     }
+    pub fn with_lm_smoothing_logprob(mut self, logprob: f32) -> Self {
+        self.lm_smoothing_logprob = logprob;
+        self
+    }
+    pub fn with_lm_add_k(mut self, k: f32) -> Self {
+        self.lm_add_k = k;
+        self
+    }
     pub fn with_consolidate_matches(mut self, value: bool) -> Self {
         self.consolidate_matches = value;
         self
     }
+    pub fn with_score_combination(mut self, combination: ScoreCombination) -> Self {
+        self.score_combination = combination;
+        self
+    }
+    pub fn with_freq_transform(mut self, transform: FrequencyTransform) -> Self {
+        self.freq_transform = transform;
+        self
+    }
+    pub fn with_freq_cap(mut self, freq_cap: u32) -> Self {
+        self.freq_cap = freq_cap;
+        self
+    }
+    pub fn with_expansion_frequency(mut self, expansion_frequency: ExpansionFrequency) -> Self {
+        self.expansion_frequency = expansion_frequency;
+        self
+    }
+    pub fn with_protected_pattern(mut self, pattern: Regex) -> Self {
+        self.protected_patterns.push(pattern);
+        self
+    }
+    pub fn with_skip_unknown_scripts(mut self, value: bool) -> Self {
+        self.skip_unknown_scripts = value;
+        self
+    }
+    pub fn with_debug_normalization(mut self, value: bool) -> Self {
+        self.debug_normalization = value;
+        self
+    }
+    pub fn with_phonetic(mut self, value: bool) -> Self {
+        self.phonetic = value;
+        self
+    }
+    pub fn with_return_edits(mut self, value: bool) -> Self {
+        self.return_edits = value;
+        self
+    }
+    pub fn with_transfer_case(mut self, value: bool) -> Self {
+        self.transfer_case = value;
+        self
+    }
+    pub fn with_collapse_lemmas(mut self, value: bool) -> Self {
+        self.collapse_lemmas = value;
+        self
+    }
+    pub fn with_sentence_confidence(mut self, value: bool) -> Self {
+        self.sentence_confidence = value;
+        self
+    }
+    pub fn with_boundary_config(mut self, config: BoundaryConfig) -> Self {
+        self.boundary_config = config;
+        self
+    }
+    pub fn with_global_context_rules(mut self, value: bool) -> Self {
+        self.global_context_rules = value;
+        self
+    }
+    pub fn with_neighbourhood_cache_size(mut self, size: usize) -> Self {
+        self.neighbourhood_cache_size = size;
+        self
+    }
+    pub fn with_max_candidates(mut self, max_candidates: usize) -> Self {
+        self.max_candidates = max_candidates;
+        self
+    }
+    pub fn with_explain(mut self, value: bool) -> Self {
+        self.explain = value;
+        self
+    }
+    pub fn with_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+    pub fn with_skip_exact(mut self, value: bool) -> Self {
+        self.skip_exact = value;
+        self
+    }
+    pub fn with_preserve_numeric(mut self, ratio: f32) -> Self {
+        self.preserve_numeric = ratio;
+        self
+    }
+    pub fn with_oov_policy(mut self, policy: OovPolicy) -> Self {
+        self.oov_policy = policy;
+        self
+    }
+    pub fn with_hard_max(mut self, hard_max: usize) -> Self {
+        self.hard_max = hard_max;
+        self
+    }
+    pub fn with_ngram_candidates(mut self, min_length: usize, min_overlap: f64) -> Self {
+        self.ngram_min_length = min_length;
+        self.ngram_min_overlap = min_overlap;
+        self
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Distance {
     ///Levenshtein (or Damarau-Levenshtein) distance
     pub ld: CharIndexType,
 
+    ///Weighted Damerau-Levenshtein distance, only computed if the model has substitution costs
+    ///(in which case `ld` holds this value rounded up)
+    pub weighted_ld: Option<f64>,
+
     ///Longest common substring length
     pub lcs: u16,
 
@@ -297,11 +889,74 @@ pub struct Distance {
     ///Common suffix length
     pub suffixlen: u16,
 
-    ///Is the casing different or not?
+    ///Is the casing of the first character the same or not?
     pub samecase: bool,
+
+    ///Case similarity (0-1): the fraction of aligned characters with the same casing, or only that
+    ///of the first character if the model is configured so (see
+    ///[`crate::VariantModel::set_case_first_char()`])
+    pub casesimilarity: f64,
+
+    ///Jaro-Winkler similarity (0-1)
+    pub jarowinkler: f64,
+
+    ///Do the input and the instance share a phonetic code?
+    pub phonetic: bool,
+}
+
+/// The stages of building a model, as reported by [`crate::VariantModel::build_with_progress()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildStage {
+    /// Computing anagram values for all items in the lexicon
+    Hashing,
+    /// Adding all instances to the anagram index
+    Indexing,
+    /// Creating and sorting the secondary index
+    Sorting,
+    /// Creating the phonetic index (only if the phonetic weight is non-zero)
+    PhoneticIndexing,
+    /// Creating the character n-gram index (only if enabled, see [`crate::VariantModel::set_ngram_index()`])
+    NGramIndexing,
+    /// Constructing the language model
+    LanguageModel,
+}
+
+/// Estimated memory usage (in bytes) of the main data structures of a model, as reported by
+/// [`crate::VariantModel::memory_usage()`]. These are estimates that include heap allocations
+/// but ignore allocator overhead and unused capacity of hash tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryUsage {
+    /// The vocabulary (decoder and encoder)
+    pub vocabulary: usize,
+    /// The main anagram index
+    pub index: usize,
+    /// The secondary sorted index
+    pub sortedindex: usize,
+    /// The n-grams of the language model
+    pub ngrams: usize,
+}
+
+impl MemoryUsage {
+    /// The total estimated memory usage
+    pub fn total(&self) -> usize {
+        self.vocabulary + self.index + self.sortedindex + self.ngrams
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Statistics on how well the alphabet covers the (indexed) vocabulary, as returned by
+/// [`crate::VariantModel::alphabet_coverage_report()`]. Characters that are not in the alphabet are all
+/// encoded as the same unknown character, so many of them are a sign of a wrong or incomplete alphabet.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AlphabetCoverage {
+    /// The characters that are not covered by the alphabet, along with the number of times they occur
+    pub unknown_chars: BTreeMap<char, usize>,
+    /// The number of entries with at least one unknown character
+    pub affected_entries: usize,
+    /// The entries of which the majority of characters are unknown
+    pub dominated_entries: Vec<VocabId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum StopCriterion {
     Exhaustive,
 
@@ -309,7 +964,7 @@ pub enum StopCriterion {
     StopAtExactMatch,
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum VariantReference {
     ///The current item is a reference for a variant. The score expressed similarity from the
     ///variant to the reference.
@@ -325,7 +980,156 @@ pub struct VariantResult {
     pub vocab_id: VocabId,
     pub dist_score: f64,
     pub freq_score: f64,
+    /// The variant that was originally matched, if this result was obtained by expanding it to
+    /// the item it is a variant of (possibly over multiple transparent variants)
     pub via: Option<VocabId>,
+    /// The edits that transform the input into this variant, only set if requested via `SearchParameters.return_edits`
+    pub edits: Option<Vec<EditOp>>,
+    /// The text of this variant with the casing pattern of the input applied to it, only set if requested
+    /// via `SearchParameters.transfer_case` and if it differs from the text in the lexicon. Use
+    /// [`crate::VariantModel::variant_text()`] to obtain the text to output.
+    pub cased: Option<String>,
+    /// How the score of this variant was computed, only set if requested via `SearchParameters.explain`
+    pub explanation: Option<ScoreExplanation>,
+}
+
+/// A breakdown of how the score of a variant was computed, for diagnosing surprising rankings
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoreExplanation {
+    /// The individual similarity components between the input and the variant
+    pub distance: Distance,
+    /// The combination of the weighted similarity components (see [`ScoreCombination`]), prior to applying confusables and lexicon weights
+    pub combined_score: f64,
+    /// The factor the score was multiplied with due to known confusables (1.0 if none apply)
+    pub confusable_weight: f64,
+    /// The factor the score was multiplied with due to the weight of the lexicon(s) the variant
+    /// occurs in (see [`crate::VocabParams::weight`])
+    pub lexicon_weight: f64,
+    /// The perplexity assigned by the language model to the most likely sequence this variant was selected in
+    /// (search mode only, if a language model is used)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lm_perplexity: Option<f64>,
+    /// The score assigned by the context rules to the most likely sequence this variant was selected in
+    /// (search mode only, if context rules are loaded)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_score: Option<f64>,
+}
+
+/// The complete ranked list of candidates for an input, as passed to the evaluation hook (see
+/// [`VariantModel::set_eval_hook()`])
+pub struct EvalCandidates<'a> {
+    /// The model the candidates were found with, to resolve their vocabulary IDs
+    pub model: &'a VariantModel,
+    /// The input (or the stem of the input, if it was matched through an affix rule)
+    pub input: &'a str,
+    /// All candidates, ranked (best first), prior to truncation to the maximum number of matches,
+    /// the hard maximum and the cutoff threshold. All carry a score explanation.
+    pub candidates: &'a [VariantResult],
+    /// The frequency weight the candidates were ranked with (see [`VariantResult::score()`])
+    pub freq_weight: f32,
+}
+
+/// A hook that is called with the complete ranked list of candidates for each input, see
+/// [`VariantModel::set_eval_hook()`]
+pub type EvalHook = Box<dyn Fn(&EvalCandidates) + Send + Sync>;
+
+/// An input that has been preprocessed for lookup, as returned by `VariantModel::prepare_query()`.
+/// Preparing a query once and passing it to `VariantModel::find_variants_prepared()` avoids
+/// recomputing the normalization and anagram hash for inputs that are looked up repeatedly.
+/// A prepared query is only valid for the model (or rather the alphabet) it was prepared with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedQuery {
+    /// The input after unicode normalization
+    pub input: String,
+    /// The casefolded input normalized to the alphabet
+    pub normstring: NormString,
+    /// The anagram hash of the casefolded input
+    pub anahash: AnaValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditOpType {
+    Insertion,
+    Deletion,
+    Substitution,
+}
+
+impl EditOpType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Insertion => "insertion",
+            Self::Deletion => "deletion",
+            Self::Substitution => "substitution",
+        }
+    }
+}
+
+/// A single edit operation in transforming an input string into a variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditOp {
+    pub optype: EditOpType,
+    /// Offset in the input (in unicode points) where this edit applies
+    pub offset: usize,
+    /// Offset in the input (in unicode points) where this edit ends (exclusive), so `offset..end`
+    /// is the span of the input that differs. Equal to `offset` for insertions.
+    pub end: usize,
+    /// The text in the input that is deleted or substituted (empty for insertions)
+    pub input: String,
+    /// The text that is inserted or substituted (empty for deletions)
+    pub output: String,
+}
+
+impl EditOp {
+    /// Convert an edit script into edit operations, a deletion and insertion at the same
+    /// position are merged into a substitution.
+    pub fn from_editscript(editscript: &EditScript<&str>) -> Vec<EditOp> {
+        let mut ops: Vec<EditOp> = Vec::new();
+        let mut offset = 0;
+        for instruction in editscript.instructions.iter() {
+            match instruction {
+                EditInstruction::Identity(s) => offset += s.chars().count(),
+                EditInstruction::GenericIdentity(n) => offset += *n as usize,
+                EditInstruction::Deletion(s) => {
+                    match ops.last_mut() {
+                        Some(last)
+                            if last.optype == EditOpType::Insertion && last.offset == offset =>
+                        {
+                            last.optype = EditOpType::Substitution;
+                            last.input = s.to_string();
+                            last.end = offset + s.chars().count();
+                        }
+                        _ => ops.push(EditOp {
+                            optype: EditOpType::Deletion,
+                            offset,
+                            end: offset + s.chars().count(),
+                            input: s.to_string(),
+                            output: String::new(),
+                        }),
+                    }
+                    offset += s.chars().count();
+                }
+                EditInstruction::Insertion(s) => match ops.last_mut() {
+                    Some(last)
+                        if last.optype == EditOpType::Deletion
+                            && last.offset + last.input.chars().count() == offset =>
+                    {
+                        last.optype = EditOpType::Substitution;
+                        last.output = s.to_string();
+                    }
+                    _ => ops.push(EditOp {
+                        optype: EditOpType::Insertion,
+                        offset,
+                        end: offset,
+                        input: String::new(),
+                        output: s.to_string(),
+                    }),
+                },
+                //disjunctions only occur in user-provided edit scripts
+                _ => {}
+            }
+        }
+        ops
+    }
 }
 
 impl VariantResult {
@@ -337,33 +1141,46 @@ impl VariantResult {
         }
     }
 
-    /// Custom comparison function for ranking, takes an extra freq_weight parameter
+    /// Custom comparison function for ranking, takes an extra freq_weight parameter. Scores are
+    /// compared as a total order (see [`f64::total_cmp()`]), so this always returns `Some`. Results with
+    /// identical scores compare as equal, use [`crate::VariantModel::rank_cmp()`] for a deterministic order
     pub fn rank_cmp(&self, other: &Self, freq_weight: f32) -> Option<Ordering> {
         if freq_weight > 0.0 {
-            other
-                .score(freq_weight)
-                .partial_cmp(&self.score(freq_weight)) //reverse parameters because we want decreasing order
+            //reverse parameters because we want decreasing order
+            Some(other.score(freq_weight).total_cmp(&self.score(freq_weight)))
         } else {
-            if self.dist_score > other.dist_score {
-                Some(Ordering::Less) //opposite because we want decreasing order
-            } else if self.dist_score < other.dist_score {
-                Some(Ordering::Greater)
-            } else {
-                //when tied, fall back to frequency score
-                if self.freq_score > other.freq_score {
-                    Some(Ordering::Less)
-                } else if self.freq_score < other.freq_score {
-                    Some(Ordering::Greater)
-                } else {
-                    Some(Ordering::Equal)
-                }
-            }
+            //when tied, fall back to frequency score
+            Some(
+                other
+                    .dist_score
+                    .total_cmp(&self.dist_score)
+                    .then_with(|| other.freq_score.total_cmp(&self.freq_score)),
+            )
+        }
+    }
+}
+
+/// Assigns tie groups to a ranked list of results. Consecutive results with an identical
+/// score (as computed by `score(freq_weight)`) share the same group number. Group numbers start at 1.
+/// The returned vector has the same length as the results; the ordering within a tie group is deterministic
+/// (see [`crate::VariantModel::rank_cmp()`]) but should not be interpreted as a ranking.
+pub fn tie_groups(results: &[VariantResult], freq_weight: f32) -> Vec<usize> {
+    let mut groups = Vec::with_capacity(results.len());
+    let mut group = 0;
+    let mut prevscore: Option<f64> = None;
+    for result in results.iter() {
+        let score = result.score(freq_weight);
+        if prevscore != Some(score) {
+            group += 1;
         }
+        groups.push(group);
+        prevscore = Some(score);
     }
+    groups
 }
 
 ///A simple lower-order n-gram type that does not require heap allocation
-#[derive(Clone, Hash, PartialEq, Eq, PartialOrd)]
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum NGram {
     Empty,
     UniGram(VocabId),
@@ -527,3 +1344,48 @@ impl NGram {
         }
     }
 }
+
+/// Determines how the language model estimates the probability of transitions (bigrams)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum LmSmoothing {
+    /// Maximum likelihood estimate, unseen transitions get a fixed log-probability or add-k
+    /// smoothing as configured in the search parameters (`lm_smoothing_logprob`, `lm_add_k`). This is the default.
+    #[default]
+    Fixed,
+
+    /// Witten-Bell smoothing: interpolates with the unigram distribution, weighted by the number of
+    /// distinct tokens seen following the preceding token
+    WittenBell,
+
+    /// Interpolated Kneser-Ney smoothing: absolute discounting ([`KNESER_NEY_DISCOUNT`]) that
+    /// interpolates with the continuation probability, i.e. in how many distinct contexts a token occurs
+    KneserNey,
+}
+
+/// The discount subtracted from each bigram count in Kneser-Ney smoothing
+pub const KNESER_NEY_DISCOUNT: f32 = 0.75;
+
+impl FromStr for LmSmoothing {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, std::io::Error> {
+        match s {
+            "fixed" => Ok(Self::Fixed),
+            "wittenbell" | "witten-bell" => Ok(Self::WittenBell),
+            "kneserney" | "kneser-ney" => Ok(Self::KneserNey),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "LM smoothing must be one of: fixed, witten-bell, kneser-ney",
+            )),
+        }
+    }
+}
+
+/// Continuation counts of a token in the language model, tracked for Witten-Bell and Kneser-Ney smoothing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContinuationCounts {
+    /// Number of distinct tokens that follow this token in a bigram
+    pub followers: u32,
+    /// Number of distinct tokens that precede this token in a bigram
+    pub predecessors: u32,
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+//normalization tables, generated from the Unicode Character Database by scripts/unicode_tables.py
+mod tables;
+use tables::*;
+
+/// Unicode normalization form to apply to lexicon entries and query input prior to
+/// encoding them with the alphabet
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnicodeNormalization {
+    /// No normalization, text is used as-is
+    #[default]
+    None,
+    /// Canonical composition (precomposed characters wherever possible)
+    NFC,
+    /// Canonical decomposition (base characters followed by combining characters)
+    NFD,
+}
+
+impl UnicodeNormalization {
+    /// Normalize the text according to this normalization form. Text is borrowed rather than
+    /// copied when no normalization is configured or the text is plain ASCII.
+    pub fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if *self == Self::None || text.is_ascii() {
+            Cow::Borrowed(text)
+        } else {
+            let decomposed = decompose(text);
+            match self {
+                Self::NFC => Cow::Owned(compose(&decomposed).into_iter().collect()),
+                _ => Cow::Owned(decomposed.into_iter().collect()),
+            }
+        }
+    }
+}
+
+impl FromStr for UnicodeNormalization {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "nfc" => Ok(Self::NFC),
+            "nfd" => Ok(Self::NFD),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Unicode normalization must be one of: none, nfc, nfd",
+            )),
+        }
+    }
+}
+
+/// Apply canonical decomposition (NFD) to a string
+pub fn nfd(text: &str) -> String {
+    UnicodeNormalization::NFD.normalize(text).into_owned()
+}
+
+/// Apply canonical composition (NFC) to a string
+pub fn nfc(text: &str) -> String {
+    UnicodeNormalization::NFC.normalize(text).into_owned()
+}
+
+/// The casing pattern of a text, used to transfer the casing of an input to a variant
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CasePattern {
+    /// All cased characters are lowercase
+    Lowercase,
+    /// All cased characters are uppercase (and there are at least two of them)
+    Uppercase,
+    /// The first cased character is uppercase, all others are lowercase
+    Titlecase,
+    /// Any other pattern, or no cased characters at all; such a pattern is not transferred
+    Mixed,
+}
+
+impl CasePattern {
+    /// Determine the casing pattern of a text
+    pub fn of(text: &str) -> Self {
+        let mut upper = 0;
+        let mut lower = 0;
+        let mut first_upper = None;
+        for c in text.chars() {
+            if c.is_uppercase() {
+                upper += 1;
+            } else if c.is_lowercase() {
+                lower += 1;
+            } else {
+                continue;
+            }
+            if first_upper.is_none() {
+                first_upper = Some(c.is_uppercase());
+            }
+        }
+        match (upper, lower, first_upper) {
+            (0, 0, _) => Self::Mixed,
+            (0, _, _) => Self::Lowercase,
+            (1, _, Some(true)) => Self::Titlecase,
+            (_, 0, _) => Self::Uppercase,
+            _ => Self::Mixed,
+        }
+    }
+
+    /// Apply this casing pattern to a text. Title case only uppercases the first cased character
+    /// and leaves the remainder as is. Text is borrowed rather than copied if it does not change.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let cased: String = match self {
+            Self::Mixed => return Cow::Borrowed(text),
+            Self::Lowercase => text.to_lowercase(),
+            Self::Uppercase => text.to_uppercase(),
+            Self::Titlecase => {
+                if let Some(pos) = text.find(|c: char| c.is_lowercase() || c.is_uppercase()) {
+                    let c = text[pos..].chars().next().expect("char");
+                    let mut cased = String::with_capacity(text.len());
+                    cased.push_str(&text[..pos]);
+                    cased.extend(c.to_uppercase());
+                    cased.push_str(&text[pos + c.len_utf8()..]);
+                    cased
+                } else {
+                    return Cow::Borrowed(text);
+                }
+            }
+        };
+        if cased == text {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(cased)
+        }
+    }
+}
+
+/// Transfer the casing pattern of the source text to the target text (see [`CasePattern`])
+pub fn transfer_case<'a>(source: &str, target: &'a str) -> Cow<'a, str> {
+    CasePattern::of(source).apply(target)
+}
+
+const HANGUL_SBASE: u32 = 0xAC00;
+const HANGUL_LBASE: u32 = 0x1100;
+const HANGUL_VBASE: u32 = 0x1161;
+const HANGUL_TBASE: u32 = 0x11A7;
+const HANGUL_LCOUNT: u32 = 19;
+const HANGUL_VCOUNT: u32 = 21;
+const HANGUL_TCOUNT: u32 = 28;
+const HANGUL_NCOUNT: u32 = HANGUL_VCOUNT * HANGUL_TCOUNT;
+const HANGUL_SCOUNT: u32 = HANGUL_LCOUNT * HANGUL_NCOUNT;
+
+/// Returns the canonical combining class of a character
+fn combining_class(c: char) -> u8 {
+    let c = c as u32;
+    match COMBINING_CLASSES.binary_search_by(|(first, last, _)| {
+        if c < *first {
+            std::cmp::Ordering::Greater
+        } else if c > *last {
+            std::cmp::Ordering::Less
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    }) {
+        Ok(i) => COMBINING_CLASSES[i].2,
+        Err(_) => 0,
+    }
+}
+
+/// Recursively decompose a single character, appending the result
+fn decompose_char(c: char, result: &mut Vec<char>) {
+    let cp = c as u32;
+    if (HANGUL_SBASE..HANGUL_SBASE + HANGUL_SCOUNT).contains(&cp) {
+        let sindex = cp - HANGUL_SBASE;
+        let l = HANGUL_LBASE + sindex / HANGUL_NCOUNT;
+        let v = HANGUL_VBASE + (sindex % HANGUL_NCOUNT) / HANGUL_TCOUNT;
+        let t = HANGUL_TBASE + sindex % HANGUL_TCOUNT;
+        result.push(char::from_u32(l).expect("valid hangul"));
+        result.push(char::from_u32(v).expect("valid hangul"));
+        if t != HANGUL_TBASE {
+            result.push(char::from_u32(t).expect("valid hangul"));
+        }
+    } else if let Ok(i) = CANONICAL_DECOMPOSITIONS.binary_search_by_key(&cp, |x| x.0) {
+        let (_, first, second) = CANONICAL_DECOMPOSITIONS[i];
+        decompose_char(char::from_u32(first).expect("valid char"), result);
+        if second != 0 {
+            decompose_char(char::from_u32(second).expect("valid char"), result);
+        }
+    } else {
+        result.push(c);
+    }
+}
+
+/// Canonical decomposition followed by canonical ordering of combining characters
+fn decompose(text: &str) -> Vec<char> {
+    let mut result = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        decompose_char(c, &mut result);
+    }
+    //canonical ordering: stable sort of each run of non-starters by combining class
+    let mut i = 0;
+    while i < result.len() {
+        if combining_class(result[i]) == 0 {
+            i += 1;
+            continue;
+        }
+        let begin = i;
+        while i < result.len() && combining_class(result[i]) != 0 {
+            i += 1;
+        }
+        result[begin..i].sort_by_key(|c| combining_class(*c));
+    }
+    result
+}
+
+/// Returns the primary composite for a pair of characters, if any
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (a, b) = (first as u32, second as u32);
+    if (HANGUL_LBASE..HANGUL_LBASE + HANGUL_LCOUNT).contains(&a)
+        && (HANGUL_VBASE..HANGUL_VBASE + HANGUL_VCOUNT).contains(&b)
+    {
+        let lindex = a - HANGUL_LBASE;
+        let vindex = b - HANGUL_VBASE;
+        return char::from_u32(HANGUL_SBASE + (lindex * HANGUL_VCOUNT + vindex) * HANGUL_TCOUNT);
+    }
+    if (HANGUL_SBASE..HANGUL_SBASE + HANGUL_SCOUNT).contains(&a)
+        && (a - HANGUL_SBASE).is_multiple_of(HANGUL_TCOUNT)
+        && (HANGUL_TBASE + 1..HANGUL_TBASE + HANGUL_TCOUNT).contains(&b)
+    {
+        return char::from_u32(a + b - HANGUL_TBASE);
+    }
+    CANONICAL_COMPOSITIONS
+        .binary_search_by_key(&(a, b), |x| (x.0, x.1))
+        .ok()
+        .and_then(|i| char::from_u32(CANONICAL_COMPOSITIONS[i].2))
+}
+
+/// Canonical composition of a decomposed and canonically ordered sequence
+fn compose(chars: &[char]) -> Vec<char> {
+    let mut result: Vec<char> = Vec::with_capacity(chars.len());
+    let mut starter: Option<usize> = None;
+    let mut last_class: Option<u8> = None;
+    for c in chars.iter() {
+        let class = combining_class(*c);
+        if let Some(starter_index) = starter {
+            //a character is blocked from the starter if there is an intervening character with class 0 or >= its own class
+            let blocked = match last_class {
+                Some(last_class) => last_class == 0 || last_class >= class,
+                None => false,
+            };
+            if !blocked {
+                if let Some(composite) = compose_pair(result[starter_index], *c) {
+                    result[starter_index] = composite;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(result.len());
+            last_class = None;
+        } else {
+            last_class = Some(class);
+        }
+        result.push(*c);
+    }
+    result
+}
@@ -1,9 +1,12 @@
 use bitflags::bitflags;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
 use std::collections::HashMap;
 
+use crate::compression::*;
 use crate::types::*;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VocabValue {
     pub text: String,
 
@@ -25,6 +28,16 @@ pub struct VocabValue {
     pub variants: Option<Vec<VariantReference>>,
 
     pub vocabtype: VocabType,
+
+    /// Arbitrary metadata associated with this entry (e.g. a lemma, a category or a source URL), read from the
+    /// column set in `VocabParams.metadata_column`
+    #[serde(default)]
+    pub metadata: Option<String>,
+
+    /// The lemma of this entry (if it is an inflected form), read from the column set in
+    /// `VocabParams.lemma_column`
+    #[serde(default)]
+    pub lemma: Option<String>,
 }
 
 bitflags! {
@@ -42,6 +55,14 @@ bitflags! {
         /// and will never be returned as a solution by itself. For example, all erroneous variants in
         /// an errorlist are marked as intermediate.
         const TRANSPARENT = 0b00000100;
+
+        /// Marks this entry as an atomic phrase; spaces in the text are not considered token separators,
+        /// so a multi-word entry like "van Aylva" counts as a single token (also for language modelling).
+        const PHRASE = 0b00001000;
+
+        /// Indexed for variant matching and also used for Language Modelling, for corpus-derived
+        /// frequency lists that should serve both purposes
+        const CORPUS = Self::INDEXED.bits | Self::LM.bits;
     }
 }
 
@@ -51,6 +72,19 @@ impl VocabType {
     }
 }
 
+impl Serialize for VocabType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for VocabType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = u8::deserialize(deserializer)?;
+        Ok(VocabType::from_bits_truncate(bits))
+    }
+}
+
 impl From<VocabType> for bool {
     fn from(v: VocabType) -> bool {
         v != VocabType::NONE
@@ -68,6 +102,8 @@ impl VocabValue {
             lexindex: 0,
             variants: None,
             vocabtype,
+            metadata: None,
+            lemma: None,
         }
     }
 
@@ -113,6 +149,21 @@ pub struct VocabParams {
     pub vocab_type: VocabType,
     /// Lexicon index
     pub index: u8,
+    /// Compression of the file, detected from the extension by default
+    pub compression: Compression,
+    ///Column containing metadata for the entry (if any, 0-indexed)
+    pub metadata_column: Option<u8>,
+    ///Column containing the lemma of the entry (if any, 0-indexed)
+    pub lemma_column: Option<u8>,
+    /// The character that separates the columns (a tab by default)
+    pub delimiter: char,
+    /// Handle RFC 4180-style quoting: fields enclosed in double quotes may contain the delimiter, and
+    /// double quotes inside them are escaped by doubling them. Quoted fields can not span multiple lines.
+    pub quoting: bool,
+    /// Weight of the lexicon: the distance score of variants from this lexicon is multiplied by it,
+    /// so variants from an authoritative lexicon (> 1.0) can be preferred over those from a noisy
+    /// one (< 1.0), all else being equal
+    pub weight: f64,
 }
 
 impl Default for VocabParams {
@@ -123,6 +174,12 @@ impl Default for VocabParams {
             freq_handling: FrequencyHandling::Max,
             vocab_type: VocabType::INDEXED,
             index: 0,
+            compression: Compression::Auto,
+            metadata_column: None,
+            lemma_column: None,
+            delimiter: '\t',
+            quoting: false,
+            weight: 1.0,
         }
     }
 }
@@ -137,6 +194,85 @@ impl VocabParams {
         self.freq_handling = freq_handling;
         self
     }
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+    /// Read metadata for each entry from the specified column (0-indexed)
+    pub fn with_metadata_column(mut self, column: u8) -> Self {
+        self.metadata_column = Some(column);
+        self
+    }
+    /// Read the lemma of each entry from the specified column (0-indexed)
+    pub fn with_lemma_column(mut self, column: u8) -> Self {
+        self.lemma_column = Some(column);
+        self
+    }
+    /// Set the character that separates the columns, e.g. `,` to read CSV files
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+    /// Enable RFC 4180-style quoting (see [`Self::quoting`])
+    pub fn with_quoting(mut self) -> Self {
+        self.quoting = true;
+        self
+    }
+    /// Set the weight of the lexicon (see [`Self::weight`])
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Splits a line into fields according to the delimiter and quoting configuration
+    pub fn split_fields<'a>(&self, line: &'a str) -> Vec<Cow<'a, str>> {
+        if !self.quoting {
+            return line.split(self.delimiter).map(Cow::Borrowed).collect();
+        }
+        let mut fields = Vec::new();
+        let mut chars = line.char_indices().peekable();
+        let mut begin = 0;
+        loop {
+            if let Some((_, '"')) = chars.peek() {
+                //quoted field
+                chars.next();
+                let mut field = String::new();
+                while let Some((_, c)) = chars.next() {
+                    if c == '"' {
+                        if chars.next_if(|(_, c)| *c == '"').is_some() {
+                            field.push('"');
+                        } else {
+                            break;
+                        }
+                    } else {
+                        field.push(c);
+                    }
+                }
+                //anything between the closing quote and the next delimiter is kept as-is
+                while let Some((_, c)) = chars.next_if(|(_, c)| *c != self.delimiter) {
+                    field.push(c);
+                }
+                fields.push(Cow::Owned(field));
+                match chars.next() {
+                    Some((i, _)) => begin = i + self.delimiter.len_utf8(),
+                    None => break,
+                }
+            } else {
+                //unquoted field
+                match chars.find(|(_, c)| *c == self.delimiter) {
+                    Some((i, _)) => {
+                        fields.push(Cow::Borrowed(&line[begin..i]));
+                        begin = i + self.delimiter.len_utf8();
+                    }
+                    None => {
+                        fields.push(Cow::Borrowed(&line[begin..]));
+                        break;
+                    }
+                }
+            }
+        }
+        fields
+    }
 }
 
 pub const BOS: VocabId = 0;
@@ -153,6 +289,8 @@ pub(crate) fn init_vocab(decoder: &mut VocabDecoder, encoder: &mut HashMap<Strin
         lexindex: 0,
         variants: None,
         vocabtype: VocabType::NONE,
+        metadata: None,
+        lemma: None,
     });
     decoder.push(VocabValue {
         text: "<eos>".to_string(),
@@ -162,6 +300,8 @@ pub(crate) fn init_vocab(decoder: &mut VocabDecoder, encoder: &mut HashMap<Strin
         lexindex: 0,
         variants: None,
         vocabtype: VocabType::NONE,
+        metadata: None,
+        lemma: None,
     });
     decoder.push(VocabValue {
         text: "<unk>".to_string(),
@@ -171,6 +311,8 @@ pub(crate) fn init_vocab(decoder: &mut VocabDecoder, encoder: &mut HashMap<Strin
         lexindex: 0,
         variants: None,
         vocabtype: VocabType::NONE,
+        metadata: None,
+        lemma: None,
     });
     encoder.insert("<bos>".to_string(), BOS);
     encoder.insert("<eos>".to_string(), EOS);
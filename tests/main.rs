@@ -5,6 +5,8 @@ extern crate sesdiff;
 
 use analiticcl::test::*;
 use analiticcl::*;
+use ibig::UBig;
+use std::sync::{Arc, Mutex};
 
 const LEXICON_AMPHIBIANS: &str = "bindings/python/tests/amphibians.tsv";
 const LEXICON_REPTILES: &str = "bindings/python/tests/reptiles.tsv";
@@ -26,6 +28,90 @@ fn test0002_primes() {
     }
 }
 
+#[test]
+fn test0003_unicode_normalization() {
+    assert_eq!(nfc("e\u{301}"), "\u{e9}");
+    assert_eq!(nfd("\u{e9}"), "e\u{301}");
+    assert_eq!(nfc("cafe\u{301} cre\u{300}me"), "caf\u{e9} cr\u{e8}me");
+    //combining marks are put in canonical order
+    assert_eq!(nfd("\u{1e0b}\u{323}"), "d\u{323}\u{307}");
+    assert_eq!(nfc("d\u{307}\u{323}"), "\u{1e0d}\u{307}");
+    //hangul syllables are decomposed and composed algorithmically
+    assert_eq!(nfd("\u{d4db}"), "\u{1111}\u{1171}\u{11b6}");
+    assert_eq!(nfc("\u{1111}\u{1171}\u{11b6}"), "\u{d4db}");
+    //composition exclusions are not recomposed
+    assert_eq!(nfc("\u{958}"), "\u{915}\u{93c}");
+    assert_eq!(nfc("plain ascii"), "plain ascii");
+    assert_eq!(
+        "nfd".parse::<UnicodeNormalization>().unwrap(),
+        UnicodeNormalization::NFD
+    );
+    assert!("nfkc".parse::<UnicodeNormalization>().is_err());
+}
+
+#[test]
+fn test0004_unicode_normalization_conformance() {
+    //samples from NormalizationTest.txt (Unicode 14.0.0): source; NFC; NFD (the NFKC and NFKD
+    //columns are omitted as compatibility normalization is not supported). The first lines are
+    //the specific cases of part 0, the remainder are characters from part 1.
+    let samples = [
+        "1E0A;1E0A;0044 0307;",
+        "1E0C;1E0C;0044 0323;",
+        "1E0A 0323;1E0C 0307;0044 0323 0307;",
+        "1E0C 0307;1E0C 0307;0044 0323 0307;",
+        "0044 0307 0323;1E0C 0307;0044 0323 0307;",
+        "0044 0323 0307;1E0C 0307;0044 0323 0307;",
+        "1E0A 031B;1E0A 031B;0044 031B 0307;",
+        "1E0C 031B;1E0C 031B;0044 031B 0323;",
+        "1E0A 031B 0323;1E0C 031B 0307;0044 031B 0323 0307;",
+        "1E0C 031B 0307;1E0C 031B 0307;0044 031B 0323 0307;",
+        "0044 031B 0307 0323;1E0C 031B 0307;0044 031B 0323 0307;",
+        "0044 031B 0323 0307;1E0C 031B 0307;0044 031B 0323 0307;",
+        "00C8;00C8;0045 0300;",
+        "0112;0112;0045 0304;",
+        "0045 0300;00C8;0045 0300;",
+        "0045 0304;0112;0045 0304;",
+        "1E14;1E14;0045 0304 0300;",
+        "0112 0300;1E14;0045 0304 0300;",
+        "1E14 0301;1E14 0301;0045 0304 0300 0301;",
+        "0045 0304 0300;1E14;0045 0304 0300;",
+        "0045 0300 0304;00C8 0304;0045 0300 0304;",
+        "05B8 05B9 05B1 0591 05C3 05B0 05AC 059F;05B1 05B8 05B9 0591 05C3 05B0 05AC 059F;05B1 05B8 05B9 0591 05C3 05B0 05AC 059F;",
+        "0592 05B7 05BC 05A5 05B0 05C0 05C4 05AD;05B0 05B7 05BC 05A5 0592 05C0 05AD 05C4;05B0 05B7 05BC 05A5 0592 05C0 05AD 05C4;",
+        "1100 AC00 11A8;1100 AC01;1100 1100 1161 11A8;",
+        "1100 AC00 11A8 11A8;1100 AC01 11A8;1100 1100 1161 11A8 11A8;",
+        "00C5;00C5;0041 030A;",
+        "0344;0308 0301;0308 0301;",
+        "0958;0915 093C;0915 093C;",
+        "0F73;0F71 0F72;0F71 0F72;",
+        "1E9B;1E9B;017F 0307;",
+        "1F80;1F80;03B1 0313 0345;",
+        "212B;00C5;0041 030A;",
+        "2126;03A9;03A9;",
+        "2ADC;2ADD 0338;2ADD 0338;",
+        "AC00;AC00;1100 1161;",
+        "D4DB;D4DB;1111 1171 11B6;",
+        "F900;8C48;8C48;",
+        "1D15E;1D157 1D165;1D157 1D165;",
+        "2F800;4E3D;4E3D;",
+    ];
+    let decode = |field: &str| -> String {
+        field
+            .split(' ')
+            .map(|cp| char::from_u32(u32::from_str_radix(cp, 16).expect("hex")).expect("char"))
+            .collect()
+    };
+    for sample in samples {
+        let columns: Vec<String> = sample.split(';').take(3).map(decode).collect();
+        let (expected_nfc, expected_nfd) = (&columns[1], &columns[2]);
+        //NFC(c1) == NFC(c2) == NFC(c3) == c2 and NFD(c1) == NFD(c2) == NFD(c3) == c3
+        for column in columns.iter() {
+            assert_eq!(&nfc(column), expected_nfc, "NFC of {}", sample);
+            assert_eq!(&nfd(column), expected_nfd, "NFD of {}", sample);
+        }
+    }
+}
+
 #[test]
 fn test0102_hash_hash() {
     let (_alphabet, _alphabet_size) = get_test_alphabet();
@@ -152,6 +238,191 @@ fn test0108_hash_upper_bound() {
     assert_eq!(x.alphabet_upper_bound(alphabet_size), (23, 1));
 }
 
+#[test]
+fn test0109_hash_small_big() {
+    let (alphabet, alphabet_size) = get_test_alphabet();
+
+    //reference implementation on big integers only
+    let bighash = |text: &str| -> UBig {
+        text.chars().fold(UBig::from(1u8), |hash, c| {
+            hash * UBig::from(PRIMES[(c as u8 - b'a') as usize])
+        })
+    };
+
+    let short = "cat".anahash(&alphabet);
+    assert!(matches!(short, AnaValue::Small(_)));
+    assert_eq!(short.to_ubig(), bighash("cat"));
+
+    let long = "xyzxyzxyzxyzxyzxyzxyzxyzxyz";
+    let longvalue = long.anahash(&alphabet);
+    assert!(matches!(longvalue, AnaValue::Big(_)));
+    assert_eq!(longvalue.to_ubig(), bighash(long));
+    assert!(longvalue > short);
+
+    //insertion promotes to the big representation once it no longer fits
+    let mut value = AnaValue::empty();
+    let mut reference = UBig::from(1u8);
+    for c in long.chars() {
+        let charvalue = AnaValue::character((c as u8 - b'a') as CharIndexType);
+        value = value.insert(&charvalue);
+        reference *= charvalue.to_ubig();
+        assert_eq!(value.to_ubig(), reference);
+    }
+    assert_eq!(value, longvalue);
+
+    //deletion demotes to the small representation again
+    let x = "x".anahash(&alphabet);
+    let mut value = longvalue.clone();
+    for _ in 0..9 {
+        assert!(value.contains(&x));
+        value = value.delete(&x).expect("deletion must succeed");
+    }
+    assert!(!value.contains(&x));
+    assert_eq!(value.delete(&x), None);
+    assert_eq!(value, "yzyzyzyzyzyzyzyzyz".anahash(&alphabet));
+    assert!(matches!(value, AnaValue::Small(_)));
+    assert!(!short.contains(&longvalue));
+    assert!(longvalue.contains(&"xyz".anahash(&alphabet)));
+
+    assert_eq!(longvalue.char_count(alphabet_size), 27);
+    assert_eq!(longvalue.count_matches(&x), 9);
+    assert_eq!(longvalue.alphabet_upper_bound(alphabet_size), (25, 27));
+    let parents: Vec<String> = longvalue
+        .iter_parents(alphabet_size)
+        .map(|deletion| deletion.value.to_string())
+        .collect();
+    assert_eq!(
+        parents,
+        vec![
+            (bighash(long) / bighash("z")).to_string(),
+            (bighash(long) / bighash("y")).to_string(),
+            (bighash(long) / bighash("x")).to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test0110_alphabet_matcher_equivalence() {
+    //a large alphabet with multi-character entries that overlap, share prefixes, occur in multiple
+    //entries, and that are listed both before and after their own prefixes
+    let mut alphabet: Alphabet = Vec::new();
+    for c in 'a'..='z' {
+        alphabet.push(vec![c.to_string(), c.to_uppercase().to_string()]);
+    }
+    alphabet.insert(0, vec!["sch".to_string(), "SCH".to_string()]);
+    alphabet.push(vec!["ch".to_string(), "ij".to_string()]);
+    alphabet.push(vec!["sch".to_string(), "schr".to_string()]);
+    alphabet.push(vec!["ä".to_string(), "ae".to_string(), "æ".to_string()]);
+    alphabet.push(vec!["ö".to_string(), "oe".to_string(), "œ".to_string()]);
+    alphabet.push(vec!["ß".to_string(), "ss".to_string()]);
+    alphabet.insert(5, vec!["eeuw".to_string(), "eeu".to_string()]);
+    for (a, b) in ('a'..='z').zip(('a'..='z').rev()) {
+        alphabet.push(vec![format!("{}{}", a, b), format!("{}{}{}", a, b, a)]);
+    }
+    for a in "aeiou".chars() {
+        for b in "aeiou".chars() {
+            alphabet.push(vec![format!("{}{}", a, b)]);
+        }
+    }
+    for (a, b) in ('a'..='y').zip('b'..='z') {
+        alphabet.push(vec![format!("{}{}", a, b)]);
+    }
+    for c in ['α', 'β', 'γ', 'δ', 'ε', 'ж', 'щ', '中', '文'] {
+        alphabet.push(vec![c.to_string()]);
+    }
+    assert!(alphabet.len() > 100 && alphabet.len() < PRIMES.len());
+    let matcher = AlphabetMatcher::new(&alphabet);
+    assert_eq!(matcher.alphabet_len(), alphabet.len());
+
+    //deterministic pseudo-random strings, including characters that are not in the alphabet
+    let pool: Vec<char> = "abcdehijorsuwzSCHAEOäöæœßαβγεж中文?!- 0é".chars().collect();
+    let mut state: u64 = 42;
+    for _ in 0..2000 {
+        let mut text = String::new();
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let length = (state >> 33) % 20;
+        for _ in 0..length {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            text.push(pool[((state >> 33) % pool.len() as u64) as usize]);
+        }
+        let text = text.as_str();
+        assert_eq!(text.anahash(&matcher), text.anahash(&alphabet), "{}", text);
+        assert_eq!(
+            text.normalize_to_alphabet(&matcher),
+            text.normalize_to_alphabet(&alphabet),
+            "{}",
+            text
+        );
+        assert_eq!(
+            text.normalize_to_alphabet_with(&matcher, true),
+            text.normalize_to_alphabet_with(&alphabet, true),
+            "{}",
+            text
+        );
+        assert_eq!(
+            text.unknown_chars(&matcher),
+            text.unknown_chars(&alphabet),
+            "{}",
+            text
+        );
+    }
+
+    //first match in alphabet order wins, not the longest match
+    assert_eq!("schr".normalize_to_alphabet(&matcher), vec![0, 19]);
+    assert_eq!("eeuw".normalize_to_alphabet(&matcher), vec![5]);
+    assert_eq!("eeu".normalize_to_alphabet(&matcher), vec![5]);
+}
+
+#[test]
+fn test0111_multiset_difference() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let difference = |a: &str, b: &str| {
+        let a = a.normalize_to_alphabet(&alphabet);
+        multiset_difference(&a, &symbol_counts(&a), &b.normalize_to_alphabet(&alphabet))
+    };
+    assert_eq!(difference("house", "ohuse"), (0, 0));
+    assert_eq!(difference("house", "hose"), (1, 0));
+    assert_eq!(difference("hose", "house"), (0, 1));
+    assert_eq!(difference("house", "blouse"), (1, 2));
+    assert_eq!(difference("house", ""), (5, 0));
+    assert_eq!(difference("", "house"), (0, 5));
+    assert_eq!(difference("aaab", "abbb"), (2, 2));
+
+    //the larger difference is a lower bound on the edit distance
+    let words = [
+        "house",
+        "ohuse",
+        "horse",
+        "mouse",
+        "blouse",
+        "xyzxyzxyzxyzxyzxyzxyzxyzxyz",
+        "xyzxyzxyzxyzxyzxyzxyzxyzxy",
+        "zyxzyxzyxzyxzyxzyxzyxzyxzyxa",
+        "",
+    ];
+    for a in words {
+        for b in words {
+            let (missing, extra) = difference(a, b);
+            let distance = damerau_levenshtein(
+                &a.normalize_to_alphabet(&alphabet),
+                &b.normalize_to_alphabet(&alphabet),
+                255,
+            )
+            .expect("distance");
+            assert!(
+                std::cmp::max(missing, extra) <= distance as usize,
+                "{} {}",
+                a,
+                b
+            );
+        }
+    }
+}
+
 #[test]
 fn test0201_iterator_parents() {
     let (alphabet, alphabet_size) = get_test_alphabet();
@@ -806,6 +1077,139 @@ fn test0304_suffixlen() {
     );
 }
 
+#[test]
+fn test0305_jaro_winkler() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let jw = |a: &str, b: &str| {
+        jaro_winkler(
+            &a.normalize_to_alphabet(&alphabet),
+            &b.normalize_to_alphabet(&alphabet),
+        )
+    };
+    assert_eq!(jw("martha", "martha"), 1.0);
+    assert_eq!(jw("abc", "xyz"), 0.0);
+    assert!((jw("martha", "marhta") - 0.9611).abs() < 0.0001);
+    assert!((jw("dwayne", "duane") - 0.84).abs() < 0.0001);
+    assert!((jw("dixon", "dicksonx") - 0.8133).abs() < 0.0001);
+    //symmetric
+    assert_eq!(jw("dixon", "dicksonx"), jw("dicksonx", "dixon"));
+}
+
+#[test]
+fn test0306_double_metaphone() {
+    assert_eq!(
+        double_metaphone("Smith"),
+        ("SM0".to_string(), "XMT".to_string())
+    );
+    assert_eq!(
+        double_metaphone("Schmidt"),
+        ("XMT".to_string(), "SMT".to_string())
+    );
+    assert_eq!(
+        double_metaphone("catherine"),
+        ("K0RN".to_string(), "KTRN".to_string())
+    );
+    assert_eq!(double_metaphone("Kathryn"), double_metaphone("catherine"));
+    assert_eq!(double_metaphone("Schutte"), double_metaphone("Schuttee"));
+    assert_eq!(
+        double_metaphone("Xavier"),
+        ("SF".to_string(), "SFR".to_string())
+    );
+    assert_eq!(
+        double_metaphone("Jankelowicz"),
+        ("JNKL".to_string(), "ANKL".to_string())
+    );
+    assert_eq!(double_metaphone("Knight").0, "NT");
+    assert_eq!(double_metaphone("Laugh").0, "LF");
+    assert_eq!(phonetic_codes("Caesar"), vec!["SSR".to_string()]);
+    assert!(phonetic_codes("").is_empty());
+}
+
+#[test]
+fn test0307_editops() {
+    let editscript = sesdiff::shortest_edit_script("huys", "huis", false, false, false);
+    assert_eq!(
+        EditOp::from_editscript(&editscript),
+        vec![EditOp {
+            optype: EditOpType::Substitution,
+            offset: 2,
+            end: 3,
+            input: "y".to_string(),
+            output: "i".to_string(),
+        }]
+    );
+    let editscript = sesdiff::shortest_edit_script("hous", "houses", false, false, false);
+    assert_eq!(
+        EditOp::from_editscript(&editscript),
+        vec![EditOp {
+            optype: EditOpType::Insertion,
+            offset: 4,
+            end: 4,
+            input: String::new(),
+            output: "es".to_string(),
+        }]
+    );
+    let editscript = sesdiff::shortest_edit_script("éénd", "één", false, false, false);
+    //offsets are in unicode points
+    assert_eq!(
+        EditOp::from_editscript(&editscript),
+        vec![EditOp {
+            optype: EditOpType::Deletion,
+            offset: 3,
+            end: 4,
+            input: "d".to_string(),
+            output: String::new(),
+        }]
+    );
+}
+
+#[test]
+fn test0308_weighted_damerau_levenshtein() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let norm = |s: &str| s.normalize_to_alphabet(&alphabet);
+    let mut costs = SubstitutionCosts::new();
+    //without custom costs, the result is the same as the unweighted distance
+    for (a, b) in [
+        ("hvis", "huis"),
+        ("abc", "bac"),
+        ("test", "tests"),
+        ("", "abc"),
+    ] {
+        assert_eq!(
+            weighted_damerau_levenshtein(&norm(a), &norm(b), 99.0, &costs),
+            damerau_levenshtein(&norm(a), &norm(b), 99).map(|d| d as f64)
+        );
+    }
+    costs.insert((norm("v")[0], norm("u")[0]), 0.25);
+    assert_eq!(
+        weighted_damerau_levenshtein(&norm("hvis"), &norm("huis"), 2.0, &costs),
+        Some(0.25)
+    );
+    //only in the specified direction
+    assert_eq!(
+        weighted_damerau_levenshtein(&norm("huis"), &norm("hvis"), 2.0, &costs),
+        Some(1.0)
+    );
+    assert_eq!(
+        weighted_damerau_levenshtein(&norm("hvvis"), &norm("huuis"), 2.0, &costs),
+        Some(0.5)
+    );
+    assert_eq!(
+        weighted_damerau_levenshtein(&norm("hvis"), &norm("haus"), 1.0, &costs),
+        None
+    );
+}
+
+#[test]
+fn test0309_case_similarity() {
+    assert_eq!(case_similarity("huis", "huis"), 1.0);
+    assert_eq!(case_similarity("Huis", "huis"), 0.75);
+    assert_eq!(case_similarity("HUIS", "huis"), 0.0);
+    assert_eq!(case_similarity("NATO", "NAVO"), 1.0);
+    assert_eq!(case_similarity("4U", "4u"), 0.5);
+    assert_eq!(case_similarity("", "huis"), 1.0);
+}
+
 #[test]
 fn test0400_model_load() {
     let (alphabet, _alphabet_size) = get_test_alphabet();
@@ -911,167 +1315,2249 @@ fn test0404_score_test() {
 }
 
 #[test]
-fn test0501_confusable_found_in() {
-    let confusable = Confusable::new("-[y]+[i]", 1.1).expect("valid script");
-    eprintln!("confusable: {:?}", confusable);
-    let huis_script = sesdiff::shortest_edit_script("huys", "huis", false, false, false);
-    eprintln!("huis_script: {:?}", huis_script);
-    let huls_script = sesdiff::shortest_edit_script("huys", "huls", false, false, false);
-    eprintln!("huls_script: {:?}", huls_script);
-    assert!(
-        confusable.found_in(&huis_script),
-        "confusable should be found in huys->huis"
-    );
-    assert!(
-        !confusable.found_in(&huls_script),
-        "confusable should not be found in huys->huls"
-    );
-}
-
-#[test]
-fn test0502_confusable_test() {
+fn test0405_tie_groups() {
     let (alphabet, _alphabet_size) = get_test_alphabet();
     let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
-    let lexicon: &[&str] = &["huis", "huls"];
+    let lexicon: &[&str] = &["huis", "huls", "huys"];
     for text in lexicon.iter() {
         model.add_to_vocabulary(text, None, &VocabParams::default());
     }
-    model
-        .add_to_confusables("-[y]+[i]", 1.1)
-        .expect("added to confusables");
     model.build();
     let results = model.find_variants("huys", &get_test_searchparams());
-    assert_eq!(
-        model
-            .decoder
-            .get(results.get(0).unwrap().vocab_id as usize)
-            .unwrap()
-            .text,
-        "huis"
-    );
-    assert_eq!(
-        model
-            .decoder
-            .get(results.get(1).unwrap().vocab_id as usize)
-            .unwrap()
-            .text,
-        "huls"
-    );
-    assert!(
-        results.get(0).unwrap().dist_score > results.get(1).unwrap().dist_score,
-        "score of huis should be greater than that of huls"
-    );
+    assert_eq!(results.len(), 3);
+    let groups = tie_groups(&results, 0.0);
+    assert_eq!(groups.len(), 3);
+    //the exact match comes first, huis and huls are tied after that
+    assert_eq!(groups[0], 1);
+    assert_eq!(groups[1], 2);
+    assert_eq!(groups[2], 2);
 }
 
 #[test]
-fn test0503_confusable_test2() {
+fn test0406_model_reload_lexicon() {
     let (alphabet, _alphabet_size) = get_test_alphabet();
     let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
-    let lexicon: &[&str] = &["huis", "huls"];
-    for text in lexicon.iter() {
-        model.add_to_vocabulary(text, None, &VocabParams::default());
-    }
-    model
-        .add_to_confusables("-[y]+[i]", 1.1)
-        .expect("added to confusables");
+    assert!(model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .is_ok());
+    assert!(model
+        .read_vocabulary(LEXICON_REPTILES, &VocabParams::default())
+        .is_ok());
     model.build();
-    let results = model.find_variants("Huys", &get_test_searchparams());
-    assert_eq!(
-        model
-            .decoder
-            .get(results.get(0).unwrap().vocab_id as usize)
-            .unwrap()
-            .text,
-        "huis"
-    );
+    assert!(model.has("salamander"));
+
+    let filename = std::env::temp_dir().join("analiticcl_test0406_amphibians.tsv");
+    std::fs::write(&filename, "frog\nnewt\ncaecilian\n").expect("writing temporary lexicon");
+    assert!(model
+        .reload_lexicon(0, filename.to_str().unwrap(), &VocabParams::default())
+        .is_ok());
+    std::fs::remove_file(&filename).ok();
+
+    assert_eq!(model.lexicons.len(), 2);
+    assert!(model.has("frog"));
+    assert!(model.has("caecilian"));
+    assert!(model.has("lizard"));
+    assert!(!model.has("salamander"));
+    assert!(!model.has("axolotl"));
+    assert!(model.get("caecilian").unwrap().in_lexicon(0));
+    let results = model.find_variants("caecilan", &get_test_searchparams());
+    assert!(!results.is_empty());
     assert_eq!(
-        model
-            .decoder
-            .get(results.get(1).unwrap().vocab_id as usize)
-            .unwrap()
-            .text,
-        "huls"
-    );
-    assert!(
-        results.get(0).unwrap().dist_score > results.get(1).unwrap().dist_score,
-        "score of huis should be greater than that of huls"
+        model.get_vocab(results[0].vocab_id).unwrap().text,
+        "caecilian"
     );
+    assert!(model
+        .find_variants("salamandr", &get_test_searchparams())
+        .is_empty());
 }
 
 #[test]
-fn test0504_confusable_nomatch() {
+fn test0407_score_combination_geometric() {
     let (alphabet, _alphabet_size) = get_test_alphabet();
     let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
-    let lexicon: &[&str] = &["huis", "huls"];
+    let lexicon: &[&str] = &["huis", "huls", "Huis"];
     for text in lexicon.iter() {
         model.add_to_vocabulary(text, None, &VocabParams::default());
     }
-    model
-        .add_to_confusables("-[y]+[p]", 1.1)
-        .expect("added to confusables");
     model.build();
-    let results = model.find_variants("Huys", &get_test_searchparams());
-    assert_eq!(results.len(), 2);
+    let arithmetic = model.find_variants("huys", &get_test_searchparams());
+    let geometric = model.find_variants(
+        "huys",
+        &get_test_searchparams().with_score_combination(ScoreCombination::GeometricMean),
+    );
+    assert_eq!(arithmetic.len(), geometric.len());
+    for (a, g) in arithmetic.iter().zip(geometric.iter()) {
+        //the geometric mean never exceeds the arithmetic mean
+        assert!(g.dist_score <= a.dist_score);
+        assert!(g.dist_score > 0.0);
+    }
+    //an exact match still scores 1.0
+    let exact = model.find_variants(
+        "huis",
+        &get_test_searchparams().with_score_combination(ScoreCombination::GeometricMean),
+    );
     assert_eq!(
-        results.get(0).unwrap().dist_score,
-        results.get(1).unwrap().dist_score,
-        "score of huis should be equal to that of huls"
+        model.decoder.get(exact[0].vocab_id as usize).unwrap().text,
+        "huis"
     );
+    assert!((exact[0].dist_score - 1.0).abs() < 1e-9);
 }
 
 #[test]
-fn test0601_find_boundaries() {
-    let text = "Hallo allemaal, ik zeg: \"Welkom in Aix-les-bains!\".";
-    let boundaries = find_boundaries(&text);
-    eprintln!("{:?}", boundaries);
-    assert_eq!(boundaries.len(), 9);
-    assert_eq!(boundaries.get(0).unwrap().offset.begin, 5);
-    assert_eq!(boundaries.get(0).unwrap().offset.end, 6);
-    assert_eq!(boundaries.get(0).unwrap().text, " ");
-    assert_eq!(boundaries.get(1).unwrap().text, ", ");
-    assert_eq!(boundaries.get(2).unwrap().text, " ");
-    assert_eq!(boundaries.get(3).unwrap().text, ": \"");
-    assert_eq!(boundaries.get(4).unwrap().text, " ");
-    assert_eq!(boundaries.get(5).unwrap().text, " ");
-    assert_eq!(boundaries.get(6).unwrap().text, "-");
-    assert_eq!(boundaries.get(7).unwrap().text, "-");
-    assert_eq!(boundaries.get(8).unwrap().text, "!\".");
+fn test0408_model_save_load() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet.clone(), Weights::default(), 1);
+    assert!(model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .is_ok());
+    assert!(model.add_to_confusables("-[y]+[i]", 1.1).is_ok());
+    model.build();
+
+    let filename = std::env::temp_dir().join("analiticcl_test0408.model");
+    assert!(model.save(filename.to_str().unwrap()).is_ok());
+    let loaded = VariantModel::load_with_alphabet(filename.to_str().unwrap(), alphabet, 1);
+
+    //loading with a different alphabet must fail
+    let mut otheralphabet = get_test_alphabet().0;
+    otheralphabet.pop();
+    let mismatch = VariantModel::load_with_alphabet(filename.to_str().unwrap(), otheralphabet, 1);
+    std::fs::remove_file(&filename).ok();
+    assert!(mismatch.is_err());
+
+    let loaded = loaded.expect("loading model");
+    assert_eq!(loaded.decoder.len(), model.decoder.len());
+    assert_eq!(loaded.index.len(), model.index.len());
+    assert_eq!(loaded.sortedindex, model.sortedindex);
+    assert_eq!(loaded.lexicons, model.lexicons);
+    assert_eq!(loaded.confusables.len(), 1);
+    assert!(loaded.has("salamander"));
+    let expected = model.find_variants("salamandre", &get_test_searchparams());
+    let results = loaded.find_variants("salamandre", &get_test_searchparams());
+    assert!(!results.is_empty());
+    assert_eq!(results, expected);
 }
 
 #[test]
-fn test0602_find_ngrams_unigram1() {
-    let text = "dit is een mooie test";
-    let boundaries = find_boundaries(&text);
-    let ngrams = find_match_ngrams(text, &boundaries, 1, 0, None);
-    assert_eq!(ngrams.len(), 5);
-    assert_eq!(ngrams.get(0).unwrap().text, "dit");
-    assert_eq!(ngrams.get(1).unwrap().text, "is");
-    assert_eq!(ngrams.get(2).unwrap().text, "een");
-    assert_eq!(ngrams.get(3).unwrap().text, "mooie");
-    assert_eq!(ngrams.get(4).unwrap().text, "test");
+fn test0409_model_load_invalid() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let filename = std::env::temp_dir().join("analiticcl_test0409.model");
+    std::fs::write(&filename, "not a model").expect("writing temporary file");
+    let result = VariantModel::load_with_alphabet(filename.to_str().unwrap(), alphabet, 1);
+    std::fs::remove_file(&filename).ok();
+    assert!(result.is_err());
 }
 
 #[test]
-fn test0603_find_ngrams_unigram2() {
-    let text = "dit is een mooie test.";
-    let boundaries = find_boundaries(&text);
-    let ngrams = find_match_ngrams(text, &boundaries, 1, 0, None);
-    assert_eq!(ngrams.len(), 5);
-    assert_eq!(ngrams.get(0).unwrap().text, "dit");
-    assert_eq!(ngrams.get(1).unwrap().text, "is");
-    assert_eq!(ngrams.get(2).unwrap().text, "een");
-    assert_eq!(ngrams.get(3).unwrap().text, "mooie");
-    assert_eq!(ngrams.get(4).unwrap().text, "test");
+fn test0410_read_vocabulary_gzip() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let filename = std::env::temp_dir().join("analiticcl_test0410.tsv.gz");
+    let output = std::process::Command::new("gzip")
+        .arg("-c")
+        .arg(LEXICON_AMPHIBIANS)
+        .output()
+        .expect("running gzip");
+    std::fs::write(&filename, &output.stdout).expect("writing compressed lexicon");
+    let result = model.read_vocabulary(filename.to_str().unwrap(), &VocabParams::default());
+    std::fs::remove_file(&filename).ok();
+    assert!(result.is_ok());
+    model.build();
+    assert!(model.has("salamander"));
+    assert!(model.has("axolotl"));
+
+    //a truncated file results in an error rather than being read partially (or forever)
+    let filename = std::env::temp_dir().join("analiticcl_test0410_truncated.tsv.gz");
+    std::fs::write(&filename, &output.stdout[..output.stdout.len() / 2])
+        .expect("writing compressed lexicon");
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let result = model.read_vocabulary(filename.to_str().unwrap(), &VocabParams::default());
+    std::fs::remove_file(&filename).ok();
+    assert!(result.is_err());
 }
 
 #[test]
-fn test0604_find_ngrams_unigram3() {
-    let text = "hello, world!";
-    let boundaries = find_boundaries(&text);
-    let ngrams = find_match_ngrams(text, &boundaries, 1, 0, None);
-    assert_eq!(ngrams.len(), 2);
-    assert_eq!(ngrams.get(0).unwrap().text, "hello");
-    assert_eq!(ngrams.get(1).unwrap().text, "world");
+fn test0411_model_insert_built() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    assert!(model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .is_ok());
+    model.build();
+    assert!(model
+        .find_variants("caecilan", &get_test_searchparams())
+        .is_empty());
+
+    let vocab_id = model.insert_built("caecilian", None, &VocabParams::default());
+    //inserting the same item again is harmless
+    assert_eq!(
+        model.insert_built("caecilian", None, &VocabParams::default()),
+        vocab_id
+    );
+    assert!(model.has("caecilian"));
+    let results = model.find_variants("caecilan", &get_test_searchparams());
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].vocab_id, vocab_id);
+    let anahash = "caecilian".anahash(&model.alphabet);
+    assert_eq!(model.index.get(&anahash).unwrap().instances, vec![vocab_id]);
+
+    //the secondary index must remain sorted
+    for keys in model.sortedindex.values() {
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+    }
+}
+
+#[test]
+fn test0412_model_unicode_normalization() {
+    let (mut alphabet, _alphabet_size) = get_test_alphabet();
+    alphabet.push(vec!["\u{e9}".to_string()]);
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.set_unicode_normalization(UnicodeNormalization::NFC);
+    //lexicon entry in decomposed form
+    model.add_to_vocabulary("cafe\u{301}", None, &VocabParams::default());
+    model.build();
+    assert!(model.has("caf\u{e9}"));
+    assert!(model.has("cafe\u{301}"));
+    for input in ["caf\u{e9}", "cafe\u{301}"] {
+        let results = model.find_variants(input, &get_test_searchparams());
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            model.decoder[results[0].vocab_id as usize].text,
+            "caf\u{e9}"
+        );
+        assert_eq!(results[0].dist_score, 1.0);
+    }
+}
+
+#[test]
+fn test0412_model_unicode_normalization_save_load() {
+    let (mut alphabet, _alphabet_size) = get_test_alphabet();
+    alphabet.push(vec!["\u{e9}".to_string()]);
+    let mut model = VariantModel::new_with_alphabet(alphabet.clone(), Weights::default(), 1);
+    //this normalizes the alphabet entry to its decomposed form
+    model.set_unicode_normalization(UnicodeNormalization::NFD);
+    model.add_to_vocabulary("caf\u{e9}", None, &VocabParams::default());
+    model.build();
+
+    let filename = std::env::temp_dir().join("analiticcl_test0412.model");
+    assert!(model.save(filename.to_str().unwrap()).is_ok());
+    //the provided alphabet is not normalized
+    let loaded = VariantModel::load_with_alphabet(filename.to_str().unwrap(), alphabet, 1);
+    std::fs::remove_file(&filename).ok();
+    let loaded = loaded.expect("loading model");
+    assert_eq!(loaded.unicode_normalization, UnicodeNormalization::NFD);
+    assert_eq!(loaded.alphabet, model.alphabet);
+    for input in ["caf\u{e9}", "cafe\u{301}"] {
+        let results = loaded.find_variants(input, &get_test_searchparams());
+        assert_eq!(
+            results,
+            model.find_variants(input, &get_test_searchparams())
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].dist_score, 1.0);
+    }
+}
+
+#[test]
+fn test0413_model_jarowinkler() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let weights = Weights {
+        jarowinkler: 0.5,
+        ..Weights::default()
+    };
+    let mut model = VariantModel::new_with_alphabet(alphabet, weights, 1);
+    assert!(model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .is_ok());
+    model.build();
+    let results = model.find_variants("salamander", &get_test_searchparams());
+    assert_eq!(
+        model.decoder[results[0].vocab_id as usize].text,
+        "salamander"
+    );
+    assert_eq!(results[0].dist_score, 1.0);
+    let results = model.find_variants("salamandre", &get_test_searchparams());
+    assert_eq!(
+        model.decoder[results[0].vocab_id as usize].text,
+        "salamander"
+    );
+    assert!(results[0].dist_score < 1.0);
+}
+
+#[test]
+fn test0414_model_phonetic() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let weights = Weights {
+        phonetic: 0.25,
+        ..Weights::default()
+    };
+    let mut model = VariantModel::new_with_alphabet(alphabet, weights, 1);
+    for name in ["catherine", "smith", "schutte", "johnson", "thompson"] {
+        model.add_to_vocabulary(name, None, &VocabParams::default());
+    }
+    model.build();
+    assert!(!model.phonetic_index.is_empty());
+
+    //kathryn is beyond the anagram distance of catherine
+    let results = model.find_variants("kathryn", &get_test_searchparams());
+    assert!(results.is_empty());
+    let results = model.find_variants("kathryn", &get_test_searchparams().with_phonetic(true));
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        model.decoder[results[0].vocab_id as usize].text,
+        "catherine"
+    );
+
+    let results = model.find_variants("schuttee", &get_test_searchparams().with_phonetic(true));
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "schutte");
+    //an exact match shares the phonetic code by definition, so still scores 1.0
+    let results = model.find_variants("smith", &get_test_searchparams().with_phonetic(true));
+    assert_eq!(results[0].dist_score, 1.0);
+
+    //items added to the built model are added to the phonetic index too
+    model.insert_built("katrien", None, &VocabParams::default());
+    assert!(model
+        .find_phonetic_matches("catherine")
+        .contains(model.encoder.get("katrien").unwrap()));
+}
+
+#[test]
+fn test0415_find_variants_edits() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let results = model.find_variants("huys", &get_test_searchparams());
+    assert!(results[0].edits.is_none());
+    let results = model.find_variants("huys", &get_test_searchparams().with_return_edits(true));
+    assert_eq!(results.len(), 2);
+    for result in results.iter() {
+        let edits = result.edits.as_ref().expect("edits must be returned");
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].optype, EditOpType::Substitution);
+        assert_eq!(edits[0].offset, 2);
+        assert_eq!(edits[0].input, "y");
+        assert_eq!(
+            edits[0].output,
+            &model.decoder[result.vocab_id as usize].text[2..3]
+        );
+    }
+}
+
+#[test]
+fn test0416_model_substitution_costs() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["hvid", "huis"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let results = model.find_variants("hvis", &get_test_searchparams());
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "hvid");
+    model
+        .add_substitution_cost("u", "v", 0.2)
+        .expect("valid characters");
+    assert!(model.add_substitution_cost("u", "vw", 0.2).is_err());
+    //the cheaper substitution changes the ranking
+    let results = model.find_variants("hvis", &get_test_searchparams());
+    assert_eq!(results.len(), 2);
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "huis");
+}
+
+#[test]
+fn test0417_model_casefold() {
+    //an alphabet without case equivalences
+    let alphabet: Alphabet = "abcdefghijklmnopqrstuvwxyzAMS"
+        .chars()
+        .map(|c| vec![c.to_string()])
+        .collect();
+    let mut model = VariantModel::new_with_alphabet(alphabet.clone(), Weights::default(), 1);
+    model.add_to_vocabulary("Amsterdam", None, &VocabParams::default());
+    model.build();
+    let results = model.find_variants("amsterdam", &get_test_searchparams());
+    assert!(results.is_empty() || results[0].dist_score < 1.0);
+
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.set_casefold(true);
+    model.add_to_vocabulary("Amsterdam", None, &VocabParams::default());
+    model.build();
+    for input in ["amsterdam", "AMSTERDAM"] {
+        let results = model.find_variants(input, &get_test_searchparams());
+        assert!(!results.is_empty());
+        //the lexicon casing is preserved
+        assert_eq!(
+            model.decoder[results[0].vocab_id as usize].text,
+            "Amsterdam"
+        );
+    }
+    //the case component still compares the original casing (of all aligned characters)
+    let results = model.find_variants("Amsterdam", &get_test_searchparams());
+    assert_eq!(results[0].dist_score, 1.0);
+    let lowercase = model.find_variants("amsterdam", &get_test_searchparams());
+    assert!(lowercase[0].dist_score < 1.0);
+    let uppercase = model.find_variants("AMSTERDAM", &get_test_searchparams());
+    assert!(uppercase[0].dist_score < lowercase[0].dist_score);
+    //or only of the first character
+    model.set_case_first_char(true);
+    let results = model.find_variants("AMSTERDAM", &get_test_searchparams());
+    assert_eq!(results[0].dist_score, 1.0);
+    let results = model.find_variants("amsterdam", &get_test_searchparams());
+    assert!(results[0].dist_score < 1.0);
+}
+
+#[test]
+fn test0418_read_vocabulary_metadata() {
+    let filename = std::env::temp_dir().join("analiticcl_test0418_gazetteer.tsv");
+    std::fs::write(
+        &filename,
+        "Amsterdam\t10\tcity\nNoord-Holland\t5\tprovince\nHaarlem\t3\t\n",
+    )
+    .expect("writing temporary lexicon");
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model
+        .read_vocabulary(
+            filename.to_str().expect("valid filename"),
+            &VocabParams::default().with_metadata_column(2),
+        )
+        .expect("reading lexicon");
+    std::fs::remove_file(&filename).ok();
+    model.build();
+    let results = model.find_variants("Amstrdam", &get_test_searchparams());
+    assert!(!results.is_empty());
+    let vocabvalue = model.get_vocab(results[0].vocab_id).expect("vocab item");
+    assert_eq!(vocabvalue.text, "Amsterdam");
+    assert_eq!(vocabvalue.metadata.as_deref(), Some("city"));
+    let vocab_id = model.encoder.get("Haarlem").expect("in vocabulary");
+    assert_eq!(
+        model.get_vocab(*vocab_id).expect("vocab item").metadata,
+        None
+    );
+}
+
+#[test]
+fn test0419_result_lexicons() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .expect("reading lexicon");
+    model
+        .read_vocabulary(LEXICON_REPTILES, &VocabParams::default())
+        .expect("reading lexicon");
+    model.build();
+    let results = model.find_variants("lizzard", &get_test_searchparams());
+    assert!(!results.is_empty());
+    assert_eq!(model.result_lexicons(&results[0]), vec![LEXICON_REPTILES]);
+}
+
+#[test]
+fn test0420_add_vocabulary_from_iter() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .expect("reading lexicon");
+    model.add_vocabulary_from_iter(
+        "reptiles",
+        vec![("lizard", 5), ("snake", 3), ("frog", 2)],
+        &VocabParams::default(),
+    );
+    model.build();
+    assert_eq!(model.lexicons, vec![LEXICON_AMPHIBIANS, "reptiles"]);
+    let results = model.find_variants("lizzard", &get_test_searchparams());
+    assert!(!results.is_empty());
+    assert_eq!(model.result_lexicons(&results[0]), vec!["reptiles"]);
+    //entries in multiple lexicons are registered in both
+    let results = model.find_variants("frog", &get_test_searchparams());
+    assert_eq!(
+        model.result_lexicons(&results[0]),
+        vec![LEXICON_AMPHIBIANS, "reptiles"]
+    );
+}
+
+struct CollectingLogger(std::sync::Arc<std::sync::Mutex<Vec<(LogLevel, String)>>>);
+
+impl Logger for CollectingLogger {
+    fn log(&self, level: LogLevel, message: std::fmt::Arguments) {
+        self.0.lock().unwrap().push((level, message.to_string()));
+    }
+}
+
+#[test]
+fn test0421_logger() {
+    let messages = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    set_logger(Box::new(CollectingLogger(messages.clone())));
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 0);
+    model.add_to_vocabulary("huis", None, &VocabParams::default());
+    model.build();
+    unset_logger();
+    let messages = messages.lock().unwrap();
+    assert!(messages
+        .iter()
+        .any(|(level, message)| *level == LogLevel::Info
+            && message.starts_with("Computing anagram values")));
+}
+
+#[test]
+fn test0422_build_with_progress() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls", "huys"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    let mut reports: Vec<(BuildStage, usize, usize)> = Vec::new();
+    model.build_with_progress(|stage, current, total| reports.push((stage, current, total)));
+    let mut stages: Vec<BuildStage> = reports.iter().map(|(stage, _, _)| *stage).collect();
+    stages.dedup();
+    assert_eq!(
+        stages,
+        vec![
+            BuildStage::Hashing,
+            BuildStage::Indexing,
+            BuildStage::Sorting,
+            BuildStage::LanguageModel
+        ]
+    );
+    //the last report of each stage is complete
+    for stage in stages {
+        let (_, current, total) = reports
+            .iter()
+            .rev()
+            .find(|(s, _, _)| *s == stage)
+            .expect("stage reported");
+        assert_eq!(current, total);
+    }
+    //the index covers the three entries and the special tokens are not indexed
+    assert!(reports.contains(&(BuildStage::Indexing, 3, 3)));
+}
+
+#[test]
+fn test0423_lm_smoothing() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let dit = model.add_to_vocabulary("dit", None, &VocabParams::default());
+    let is = model.add_to_vocabulary("is", None, &VocabParams::default());
+    model.add_ngram(NGram::UniGram(dit), 4);
+    model.add_ngram(NGram::BiGram(dit, is), 2);
+    let vocabsize = model.decoder.len() as f32;
+
+    //fixed smoothing for unseen and out-of-vocabulary transitions
+    let params = get_test_searchparams().with_lm_smoothing_logprob(-5.0);
+    let (logprob, _) = model.lm_score_tokens(&vec![Some(dit), Some(is)], &params);
+    assert!((logprob - 0.5_f32.ln()).abs() < 1e-6);
+    let (logprob, _) = model.lm_score_tokens(&vec![Some(is), Some(dit)], &params);
+    assert_eq!(logprob, -5.0);
+    let (logprob, _) = model.lm_score_tokens(&vec![Some(dit), None], &params);
+    assert_eq!(logprob, -5.0);
+
+    //add-k smoothing
+    let params = get_test_searchparams().with_lm_add_k(0.5);
+    let (logprob, _) = model.lm_score_tokens(&vec![Some(dit), Some(is)], &params);
+    assert!((logprob - (2.5 / (4.0 + 0.5 * vocabsize)).ln()).abs() < 1e-6);
+    let (logprob, _) = model.lm_score_tokens(&vec![Some(dit), None], &params);
+    assert!((logprob - (0.5 / (4.0 + 0.5 * vocabsize)).ln()).abs() < 1e-6);
+    let (logprob, _) = model.lm_score_tokens(&vec![None, None], &params);
+    assert!((logprob - (1.0 / vocabsize).ln()).abs() < 1e-6);
+}
+
+#[test]
+fn test0424_lm_interpolated_smoothing() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let dit = model.add_to_vocabulary("dit", None, &VocabParams::default());
+    let is = model.add_to_vocabulary("is", None, &VocabParams::default());
+    let een = model.add_to_vocabulary("een", None, &VocabParams::default());
+    model.add_ngram(NGram::UniGram(dit), 4);
+    model.add_ngram(NGram::UniGram(is), 3);
+    model.add_ngram(NGram::UniGram(een), 2);
+    model.add_ngram(NGram::BiGram(dit, is), 3);
+    model.add_ngram(NGram::BiGram(dit, een), 1);
+    model.add_ngram(NGram::BiGram(is, een), 2);
+    model.freq_sum[0] = 9;
+    assert_eq!(model.bigram_types, 3);
+    assert_eq!(
+        model.continuation_counts.get(&dit),
+        Some(&ContinuationCounts {
+            followers: 2,
+            predecessors: 0
+        })
+    );
+    assert_eq!(
+        model.continuation_counts.get(&een),
+        Some(&ContinuationCounts {
+            followers: 0,
+            predecessors: 2
+        })
+    );
+
+    let params = get_test_searchparams();
+    for smoothing in [LmSmoothing::WittenBell, LmSmoothing::KneserNey] {
+        model.set_lm_smoothing(smoothing);
+        //the transition probabilities from a token sum to one over the vocabulary
+        let total: f32 = (0..model.decoder.len() as VocabId)
+            .map(|next| {
+                model
+                    .lm_score_tokens(&vec![Some(dit), Some(next)], &params)
+                    .0
+                    .exp()
+            })
+            .sum();
+        assert!((total - 1.0).abs() < 1e-4, "{:?}: {}", smoothing, total);
+        //seen transitions are more likely than unseen ones
+        let (seen, _) = model.lm_score_tokens(&vec![Some(dit), Some(is)], &params);
+        let (unseen, _) = model.lm_score_tokens(&vec![Some(is), Some(dit)], &params);
+        assert!(seen > unseen, "{:?}", smoothing);
+        //out-of-vocabulary tokens get a finite score
+        let (oov, perplexity) = model.lm_score_tokens(&vec![Some(dit), None, Some(een)], &params);
+        assert!(oov.is_finite() && oov < unseen, "{:?}", smoothing);
+        assert!(perplexity > 0.0);
+    }
+}
+
+#[test]
+fn test0425_build_parallel_deterministic() {
+    //hashing is done in parallel, the resulting index must not depend on the number of threads
+    let words = [
+        "huis", "huls", "huys", "hui", "uis", "zijn", "zin", "tijd", "tyd", "dit", "is", "een",
+        "woord",
+    ];
+    let mut models: Vec<VariantModel> = Vec::new();
+    for num_threads in [1, 4] {
+        let (alphabet, _alphabet_size) = get_test_alphabet();
+        let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+        model.set_num_threads(num_threads).expect("thread pool");
+        for text in words {
+            model.add_to_vocabulary(text, None, &VocabParams::default());
+        }
+        model.build();
+        models.push(model);
+    }
+    assert_eq!(models[0].index.len(), models[1].index.len());
+    for (anahash, node) in models[0].index.iter() {
+        assert_eq!(
+            models[1].index.get(anahash).expect("anahash").instances,
+            node.instances
+        );
+    }
+    assert_eq!(models[0].sortedindex, models[1].sortedindex);
+}
+
+#[test]
+fn test0426_sortedindex_shares_index_keys() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls", "huys", "zijn", "tijd"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    //every key in the secondary index is the very same allocation as the key in the main index
+    let mut count = 0;
+    for keys in model.sortedindex.values() {
+        for key in keys {
+            let (indexkey, _) = model
+                .index
+                .get_key_value(key.as_ref())
+                .expect("key in index");
+            assert!(Arc::ptr_eq(key, indexkey));
+            count += 1;
+        }
+    }
+    assert_eq!(count, model.index.len());
+
+    let usage = model.memory_usage();
+    assert!(usage.vocabulary > 0);
+    assert!(usage.sortedindex > 0);
+    assert!(usage.sortedindex < usage.index);
+    assert_eq!(
+        usage.total(),
+        usage.vocabulary + usage.index + usage.sortedindex + usage.ngrams
+    );
+}
+
+#[test]
+fn test0427_neighbourhood_cache() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls", "huys", "zijn", "tijd"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let params = get_test_searchparams().with_neighbourhood_cache_size(2);
+    let ids = |results: Vec<VariantResult>| -> Vec<VocabId> {
+        results.iter().map(|result| result.vocab_id).collect()
+    };
+    let uncached = ids(model.find_variants("huijs", &get_test_searchparams()));
+    assert!(!uncached.is_empty());
+    assert_eq!(model.neighbourhood_cache().len(), 0);
+
+    //the second query (and an anagram of it) is served from the cache, with identical results
+    assert_eq!(ids(model.find_variants("huijs", &params)), uncached);
+    assert_eq!(ids(model.find_variants("huijs", &params)), uncached);
+    model.find_variants("sjiuh", &params);
+    assert_eq!(model.neighbourhood_cache().misses(), 1);
+    assert_eq!(model.neighbourhood_cache().hits(), 2);
+
+    //the least recently used neighbourhood is evicted
+    model.find_variants("tijt", &params);
+    model.find_variants("zijm", &params);
+    assert_eq!(model.neighbourhood_cache().len(), 2);
+    model.find_variants("huijs", &params);
+    assert_eq!(model.neighbourhood_cache().misses(), 4);
+
+    //changing the index invalidates the cache
+    model.insert_built("huijs", None, &VocabParams::default());
+    assert!(model.neighbourhood_cache().is_empty());
+    let results = model.find_variants("huijs", &params);
+    assert_eq!(
+        model.decoder[results[0].vocab_id as usize].text.as_str(),
+        "huijs"
+    );
+}
+
+#[test]
+fn test0428_find_insertions_matches_brute_force() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    assert!(model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .is_ok());
+    assert!(model
+        .read_vocabulary(LEXICON_REPTILES, &VocabParams::default())
+        .is_ok());
+    model.build();
+    let mut focuses: Vec<(AnaValue, u16)> = Vec::new();
+    for text in ["frog", "toad", "newt", "sala", "ake", "s", "crocodle", "x"] {
+        let anavalue = text.anahash(&model.alphabet);
+        let charcount = anavalue.char_count(model.alphabet_size());
+        focuses.push((anavalue, charcount));
+    }
+    let mut found = 0;
+    for (focus, charcount) in focuses.iter() {
+        for insertions in 1..=4 {
+            let search_charcount = charcount + insertions;
+            let mut expected: Vec<&AnaValue> = model
+                .sortedindex
+                .get(&search_charcount)
+                .map(|keys| {
+                    keys.iter()
+                        .filter(|key| key.contains(focus))
+                        .map(|key| key.as_ref())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let mut results = model.find_insertions(focus, search_charcount, insertions);
+            expected.sort();
+            results.sort();
+            assert_eq!(results, expected);
+            found += results.len();
+        }
+    }
+    assert!(found > 0);
+}
+
+#[test]
+fn test0429_max_candidates() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "suih", "huys", "hui", "huisje", "hus"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let params = get_test_searchparams()
+        .with_max_matches(0)
+        .with_score_threshold(0.0);
+    let texts = |results: Vec<VariantResult>| -> Vec<String> {
+        let mut texts: Vec<String> = results
+            .iter()
+            .map(|result| model.decoder[result.vocab_id as usize].text.clone())
+            .collect();
+        texts.sort();
+        texts
+    };
+    let unlimited = texts(model.find_variants("huis", &params));
+    assert_eq!(unlimited.len(), 6);
+
+    //only the exact anagram fits the budget
+    let capped = texts(model.find_variants("huis", &params.clone().with_max_candidates(1)));
+    assert_eq!(capped, vec!["huis", "suih"]);
+
+    //the nearest anagram values are kept first, and the truncation is deterministic
+    let capped = texts(model.find_variants("huis", &params.clone().with_max_candidates(3)));
+    assert_eq!(capped.len(), 4);
+    assert!(capped.contains(&"huis".to_string()));
+    assert!(!capped.contains(&"huisje".to_string()));
+    for _ in 0..5 {
+        assert_eq!(
+            texts(model.find_variants("huis", &params.clone().with_max_candidates(3))),
+            capped
+        );
+    }
+}
+
+#[test]
+fn test0430_deterministic_tie_breaking() {
+    let ranked = |entries: &[(&str, u32, u8)]| -> Vec<String> {
+        let (alphabet, _alphabet_size) = get_test_alphabet();
+        let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+        for (text, frequency, index) in entries {
+            let params = VocabParams {
+                index: *index,
+                ..VocabParams::default()
+            };
+            model.add_to_vocabulary(text, Some(*frequency), &params);
+        }
+        model.build();
+        model
+            .find_variants("huys", &get_test_searchparams())
+            .iter()
+            .map(|result| model.decoder[result.vocab_id as usize].text.clone())
+            .collect()
+    };
+    //ties are ordered lexicographically, regardless of the order in which the lexicon was loaded
+    assert_eq!(
+        ranked(&[("huls", 1, 0), ("huis", 1, 0)]),
+        vec!["huis", "huls"]
+    );
+    assert_eq!(
+        ranked(&[("huis", 1, 0), ("huls", 1, 0)]),
+        vec!["huis", "huls"]
+    );
+    //a higher frequency comes first
+    assert_eq!(
+        ranked(&[("huis", 1, 0), ("huls", 5, 0)]),
+        vec!["huls", "huis"]
+    );
+    //the lexicon loaded first takes precedence over frequency
+    assert_eq!(
+        ranked(&[("huis", 1, 1), ("huls", 5, 0)]),
+        vec!["huls", "huis"]
+    );
+    assert_eq!(
+        ranked(&[("huis", 5, 1), ("huls", 1, 0)]),
+        vec!["huls", "huis"]
+    );
+}
+
+#[test]
+fn test0431_vocab_lexicons() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    assert!(model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .is_ok());
+    assert!(model
+        .read_vocabulary(LEXICON_REPTILES, &VocabParams::default())
+        .is_ok());
+    let frog = model.get_vocab(model.encoder["frog"]).expect("vocab");
+    assert_eq!(model.vocab_lexicons(frog), vec![LEXICON_AMPHIBIANS]);
+    let bos = model.get_vocab(0).expect("vocab");
+    assert!(model.vocab_lexicons(bos).is_empty());
+}
+
+#[test]
+fn test0432_explain() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["huis", "huls"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model
+        .add_to_confusables("-[y]+[i]", 1.1)
+        .expect("added to confusables");
+    model.build();
+    let results = model.find_variants("huys", &get_test_searchparams());
+    assert!(results.iter().all(|result| result.explanation.is_none()));
+    let results = model.find_variants("huys", &get_test_searchparams().with_explain(true));
+    assert_eq!(results.len(), 2);
+    for result in results.iter() {
+        let explanation = result.explanation.as_ref().expect("explanation");
+        assert_eq!(explanation.distance.ld, 1);
+        assert!(
+            (explanation.combined_score * explanation.confusable_weight - result.dist_score).abs()
+                < 1e-9
+        );
+    }
+    let huis = results[0].explanation.as_ref().unwrap();
+    let huls = results[1].explanation.as_ref().unwrap();
+    assert_eq!(huis.confusable_weight, 1.1);
+    assert_eq!(huls.confusable_weight, 1.0);
+    assert!(huis.lm_perplexity.is_none());
+}
+
+#[test]
+fn test0433_min_length() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["on", "in", "think"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let params = get_test_searchparams().with_min_length(3);
+    assert!(!model
+        .find_variants("ni", &get_test_searchparams())
+        .is_empty());
+    assert!(model.find_variants("ni", &params).is_empty());
+    assert!(!model.find_variants("tink", &params).is_empty());
+    let matches = model.find_all_matches("ni tink", &params);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(model.match_to_str(&matches[0]), "ni");
+    assert_eq!(model.match_to_str(&matches[1]), "think");
+}
+
+#[test]
+fn test0434_freq_transform() {
+    let mut freqs = vec![1000.0, 10.0, 10.0, 1.0];
+    FrequencyTransform::Linear.normalize(&mut freqs);
+    assert_eq!(freqs, vec![1.0, 0.01, 0.01, 0.001]);
+    let mut freqs = vec![1000.0, 10.0, 10.0, 1.0];
+    FrequencyTransform::Rank.normalize(&mut freqs);
+    assert_eq!(freqs, vec![1.0, 2.0 / 3.0, 2.0 / 3.0, 1.0 / 3.0]);
+    let mut freqs = vec![1000.0, 10.0, 1.0];
+    FrequencyTransform::Log.normalize(&mut freqs);
+    assert_eq!(freqs[0], 1.0);
+    assert!(freqs[1] > 0.3 && freqs[1] < 0.4);
+    assert!(freqs[2] > freqs[1] / 4.0 && freqs[2] < freqs[1]);
+
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("huis", Some(1000), &VocabParams::default());
+    model.add_to_vocabulary("huls", Some(10), &VocabParams::default());
+    model.have_freq = true;
+    model.build();
+    let params = get_test_searchparams().with_freq_transform(FrequencyTransform::Rank);
+    let results = model.find_variants("huys", &params);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].freq_score, 1.0);
+    assert_eq!(results[1].freq_score, 0.5);
+}
+
+#[test]
+fn test0435_find_variants_prepared() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls", "house"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let prepared = model.prepare_query("huys");
+    assert_eq!(prepared.input, "huys");
+    assert_eq!(prepared.anahash, "huys".anahash(&model.alphabet));
+    let params = get_test_searchparams();
+    let expected = model.find_variants("huys", &params);
+    assert!(!expected.is_empty());
+    assert_eq!(model.find_variants_prepared(&prepared, &params), expected);
+    //a prepared query can be reused
+    assert_eq!(model.find_variants_prepared(&prepared, &params), expected);
+}
+
+#[test]
+fn test0436_disable_and_remove_lexicon() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    assert!(model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .is_ok());
+    assert!(model
+        .read_vocabulary(LEXICON_REPTILES, &VocabParams::default())
+        .is_ok());
+    model.build();
+    let params = get_test_searchparams();
+    let found = |model: &VariantModel, input: &str, text: &str| {
+        model
+            .find_variants(input, &params)
+            .iter()
+            .any(|result| model.decoder[result.vocab_id as usize].text == text)
+    };
+    assert!(found(&model, "frok", "frog"));
+    assert!(found(&model, "lizzard", "lizard"));
+
+    assert!(model.disable_lexicon(0).is_ok());
+    assert!(!model.lexicon_enabled(0));
+    assert!(!found(&model, "frok", "frog"));
+    assert!(found(&model, "lizzard", "lizard"));
+    assert!(model.enable_lexicon(0).is_ok());
+    assert!(found(&model, "frok", "frog"));
+    assert!(model.disable_lexicon(2).is_err());
+
+    assert!(model.remove_lexicon(0).is_ok());
+    assert!(!found(&model, "frok", "frog"));
+    assert!(found(&model, "lizzard", "lizard"));
+    assert_eq!(model.lexicons.len(), 2);
+    let frog = model.get_vocab(model.encoder["frog"]).expect("vocab");
+    assert_eq!(frog.lexindex, 0);
+}
+
+#[test]
+fn test0437_skip_exact() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    assert_eq!(
+        model.find_variants("huis", &get_test_searchparams()).len(),
+        2
+    );
+    let params = get_test_searchparams().with_skip_exact(true);
+    let results = model.find_variants("huis", &params);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].vocab_id, model.encoder["huis"]);
+    assert_eq!(results[0].dist_score, 1.0);
+    //inputs that are not exact matches are processed as usual
+    assert_eq!(model.find_variants("huys", &params).len(), 2);
+    let matches = model.find_all_matches("huis huys", &params);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].variants.as_ref().map(|v| v.len()), Some(1));
+    assert_eq!(model.match_to_str(&matches[0]), "huis");
+}
+
+#[test]
+fn test0438_find_variants_batch() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    assert!(model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .is_ok());
+    model.build();
+    let inputs = ["frok", "nwet", "toad", "salamandre"];
+    for params in [
+        get_test_searchparams(),
+        get_test_searchparams().with_single_thread(),
+    ] {
+        let results = model.find_variants_batch(&inputs, &params);
+        assert_eq!(results.len(), inputs.len());
+        for ((input, variants), expected) in results.iter().zip(inputs.iter()) {
+            assert_eq!(input, expected);
+            assert_eq!(variants, &model.find_variants(input, &params));
+        }
+    }
+}
+
+#[test]
+fn test0439_preserve_numeric() {
+    assert_eq!(numeric_ratio("1672"), 1.0);
+    assert_eq!(numeric_ratio("١٦٧٢"), 1.0); //arabic-indic digits
+    assert_eq!(numeric_ratio("16th"), 0.5);
+    assert_eq!(numeric_ratio("the"), 0.0);
+    assert_eq!(numeric_ratio(""), 0.0);
+
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["1670", "16th", "huis"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    assert!(!model
+        .find_variants("1672", &get_test_searchparams())
+        .is_empty());
+    let params = get_test_searchparams().with_preserve_numeric(1.0);
+    assert!(model.find_variants("1672", &params).is_empty());
+    assert!(!model.find_variants("17th", &params).is_empty());
+    let params = get_test_searchparams().with_preserve_numeric(0.5);
+    assert!(model.find_variants("17th", &params).is_empty());
+    assert!(!model.find_variants("huys", &params).is_empty());
+    //digits are boundaries by default, so they only end up in tokens if configured as such
+    let params =
+        params.with_boundary_config(BoundaryConfig::default().with_nonboundary("0123456789"));
+    let matches = model.find_all_matches("huys 1672", &params);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(model.match_to_str(&matches[0]), "huis");
+    assert_eq!(model.match_to_str(&matches[1]), "1672");
+}
+
+#[test]
+fn test0440_split_fields() {
+    let params = VocabParams::default();
+    assert_eq!(params.split_fields("frog\t12"), vec!["frog", "12"]);
+    let params = VocabParams::default().with_delimiter(',');
+    assert_eq!(params.split_fields("frog,12,"), vec!["frog", "12", ""]);
+    assert_eq!(params.split_fields("\"frog\",12"), vec!["\"frog\"", "12"]);
+    let params = params.with_quoting();
+    assert_eq!(params.split_fields("\"frog\",12"), vec!["frog", "12"]);
+    assert_eq!(
+        params.split_fields("\"tree, frog\",\"say \"\"ribbit\"\"\",\"\""),
+        vec!["tree, frog", "say \"ribbit\"", ""]
+    );
+    assert_eq!(params.split_fields("näive,\"a\tb\""), vec!["näive", "a\tb"]);
+}
+
+#[test]
+fn test0441_read_vocabulary_csv() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let filename = std::env::temp_dir().join("analiticcl_test0441_amphibians.csv");
+    std::fs::write(&filename, "frog,3\n\"frog, tree\",5\n").expect("writing temporary lexicon");
+    let params = VocabParams::default().with_delimiter(',').with_quoting();
+    assert!(model
+        .read_vocabulary(filename.to_str().unwrap(), &params)
+        .is_ok());
+    std::fs::remove_file(&filename).ok();
+    let frog = model.get_vocab(model.encoder["frog"]).expect("vocab");
+    assert_eq!(frog.frequency, 3);
+    let treefrog = model.get_vocab(model.encoder["frog, tree"]).expect("vocab");
+    assert_eq!(treefrog.frequency, 5);
+}
+
+#[test]
+fn test0442_alphabet_coverage_report() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    assert_eq!("h€u€s".unknown_chars(&alphabet), vec!['€', '€']);
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "hu€s", "€€€s"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.add_to_vocabulary(
+        "§§§",
+        None,
+        &VocabParams::default().with_vocab_type(VocabType::LM),
+    );
+    let report = model.alphabet_coverage_report();
+    assert_eq!(report.unknown_chars.len(), 1);
+    assert_eq!(report.unknown_chars[&'€'], 4);
+    assert_eq!(report.affected_entries, 2);
+    assert_eq!(report.dominated_entries, vec![model.encoder["€€€s"]]);
+}
+
+#[test]
+fn test0443_best_variant() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    assert!(model
+        .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+        .is_ok());
+    model.build();
+    let params = get_test_searchparams();
+    assert_eq!(
+        model
+            .best_variant("salamandre", &params)
+            .map(|v| v.text.as_str()),
+        Some("salamander")
+    );
+    assert!(model.best_variant("xyzzy", &params).is_none());
+    let mut params = get_test_searchparams();
+    params.score_threshold = 0.99;
+    assert!(model.best_variant("salamandre", &params).is_none());
+    assert_eq!(
+        model
+            .best_variant("salamander", &params)
+            .map(|v| v.text.as_str()),
+        Some("salamander")
+    );
+}
+
+#[test]
+fn test0444_score_pair() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls", "Huys"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let results = model.find_variants("huys", &get_test_searchparams());
+    for result in results.iter() {
+        let text = &model.get_vocab(result.vocab_id).unwrap().text;
+        assert_eq!(model.score_pair("huys", text), result.dist_score);
+    }
+    assert_eq!(model.score_pair("huis", "huis"), 1.0);
+    assert!(model.score_pair("huys", "huis") > model.score_pair("huys", "house"));
+    assert!(model.score_pair("huys", "huis") > 0.0);
+    assert_eq!(model.score_pair("", "huis"), 0.0);
+}
+
+#[test]
+fn test0445_hard_max() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls", "Huys", "hus", "huize"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let mut params = get_test_searchparams().with_max_matches(0);
+    params.score_threshold = 0.0;
+    params.cutoff_threshold = 0.0;
+    let unlimited = model.find_variants("huys", &params);
+    assert!(unlimited.len() > 2);
+    let results = model.find_variants("huys", &params.clone().with_hard_max(2));
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].vocab_id, unlimited[0].vocab_id);
+    assert_eq!(results[1].vocab_id, unlimited[1].vocab_id);
+    let results = model.find_variants("huys", &params.with_hard_max(100));
+    assert_eq!(results.len(), unlimited.len());
+}
+
+#[test]
+fn test0446_add_alphabet_entries() {
+    let (alphabet, alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("huis", None, &VocabParams::default());
+    model.add_to_vocabulary("λόγος", None, &VocabParams::default());
+    assert!(model
+        .add_alphabet_entries(vec![vec!["a".to_string()]])
+        .is_err());
+    assert!(model.add_alphabet_entries(vec![vec![]]).is_err());
+    let mut model = model
+        .with_additional_alphabet_entries(
+            ["λ", "ό", "γ", "ο", "ς"]
+                .iter()
+                .map(|c| vec![c.to_string()])
+                .collect(),
+        )
+        .expect("adding alphabet entries");
+    assert_eq!(model.alphabet.len(), alphabet_size as usize + 5);
+    let vocabitem = model.get_vocab(model.encoder["λόγος"]).unwrap();
+    assert_eq!(
+        vocabitem.norm,
+        "λόγος".normalize_to_alphabet(&model.alphabet)
+    );
+    assert!(vocabitem
+        .norm
+        .iter()
+        .all(|c| *c < model.alphabet.len() as CharIndexType));
+    model.build();
+    let results = model.find_variants("λόγοσ", &get_test_searchparams());
+    assert_eq!(model.get_vocab(results[0].vocab_id).unwrap().text, "λόγος");
+    assert!(model
+        .add_alphabet_entries(vec![vec!["σ".to_string()]])
+        .is_err());
+}
+
+#[test]
+fn test0446_add_alphabet_entries_unicode_normalization() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.set_unicode_normalization(UnicodeNormalization::NFC);
+    //a decomposed entry is composed like the input and the vocabulary
+    assert!(model
+        .add_alphabet_entries(vec![vec!["e\u{301}".to_string()]])
+        .is_ok());
+    assert_eq!(model.alphabet.last(), Some(&vec!["\u{e9}".to_string()]));
+    //so its composed form is already covered
+    assert!(model
+        .add_alphabet_entries(vec![vec!["\u{e9}".to_string()]])
+        .is_err());
+    model.add_to_vocabulary("caf\u{e9}", None, &VocabParams::default());
+    model.build();
+    for input in ["caf\u{e9}", "cafe\u{301}"] {
+        let results = model.find_variants(input, &get_test_searchparams());
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].dist_score, 1.0);
+    }
+}
+
+#[test]
+fn test0447_ngram_index() {
+    assert_eq!(
+        NGramIndex::ngrams(&[1, 2, 3, 1, 2, 3]),
+        vec![[1, 2, 3], [2, 3, 1], [3, 1, 2]]
+    );
+    assert!(NGramIndex::ngrams(&[1, 2]).is_empty());
+    let mut index = NGramIndex::default();
+    index.insert(5, &[1, 2, 3, 4]);
+    index.insert(6, &[1, 2, 3, 5]);
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.find(&[1, 2, 3, 4], 0.0), vec![(5, 1.0), (6, 0.5)]);
+    assert_eq!(index.find(&[1, 2, 3, 4], 0.6), vec![(5, 1.0)]);
+    index.remove(5, &[1, 2, 3, 4]);
+    assert_eq!(index.find(&[1, 2, 3, 4], 0.0), vec![(6, 0.5)]);
+}
+
+#[test]
+fn test0448_ngram_candidates() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in [
+        "the united states of america",
+        "the united kingdom",
+        "states",
+    ] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.set_ngram_index(true);
+    model.build();
+    assert!(!model.ngram_index.is_empty());
+    let input = "teh unitde staets of amercia";
+    let params = get_test_searchparams();
+    //too far away in terms of anagram and edit distance
+    assert!(model.find_variants(input, &params).is_empty());
+    let params = params.with_ngram_candidates(10, 0.3);
+    let results = model.find_variants(input, &params);
+    assert!(!results.is_empty());
+    assert_eq!(
+        model.get_vocab(results[0].vocab_id).unwrap().text,
+        "the united states of america"
+    );
+    //short inputs still use the anagram index
+    let results = model.find_variants("staets", &params);
+    assert_eq!(model.get_vocab(results[0].vocab_id).unwrap().text, "states");
+    //the setting is saved with the model and the index is rebuilt on load
+    let filename = std::env::temp_dir().join("analiticcl_test0448.model");
+    assert!(model.save(filename.to_str().unwrap()).is_ok());
+    let loaded =
+        VariantModel::load_with_alphabet(filename.to_str().unwrap(), get_test_alphabet().0, 1);
+    std::fs::remove_file(&filename).ok();
+    let loaded = loaded.expect("loading model");
+    assert_eq!(loaded.ngram_index.len(), model.ngram_index.len());
+    assert_eq!(
+        loaded.find_variants(input, &params),
+        model.find_variants(input, &params)
+    );
+    model.set_ngram_index(false);
+    assert!(model.ngram_index.is_empty());
+    assert!(model.find_variants(input, &params).is_empty());
+}
+
+#[test]
+fn test0449_edit_spans() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.set_unicode_normalization(UnicodeNormalization::NFC);
+    model.add_to_vocabulary("cafés", None, &VocabParams::default());
+    model.add_to_vocabulary("huis", None, &VocabParams::default());
+    model.build();
+    let params = get_test_searchparams().with_return_edits(true);
+    for input in ["hyis", "cafe\u{301}z", "caféz"] {
+        let results = model.find_variants(input, &params);
+        assert!(!results.is_empty());
+        let chars: Vec<char> = input.chars().collect();
+        for result in results.iter() {
+            for edit in result.edits.as_ref().expect("edits must be returned") {
+                //the span refers to the original input
+                let span: String = chars[edit.offset..edit.end].iter().collect();
+                assert_eq!(span, edit.input);
+            }
+        }
+    }
+    let results = model.find_variants("hyis", &params);
+    let edits = results[0].edits.as_ref().unwrap();
+    assert_eq!(edits.len(), 1);
+    assert_eq!((edits[0].offset, edits[0].end), (1, 2));
+}
+
+#[test]
+fn test0450_freq_cap() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("huis", Some(10), &VocabParams::default());
+    model.add_to_vocabulary("huls", Some(100000), &VocabParams::default());
+    model.have_freq = true;
+    model.build();
+    let params = get_test_searchparams().with_freq_weight(0.5);
+    let results = model.find_variants("huys", &params);
+    assert_eq!(results.len(), 2);
+    assert_eq!(model.get_vocab(results[0].vocab_id).unwrap().text, "huls");
+    assert_eq!(results[1].freq_score, 0.0001);
+    //with a cap, both variants are considered equally frequent
+    let params = params.with_freq_cap(10);
+    let results = model.find_variants("huys", &params);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].freq_score, 1.0);
+    assert_eq!(results[1].freq_score, 1.0);
+}
+
+#[test]
+fn test0451_alphabet_ignore_unk() {
+    let filename = std::env::temp_dir().join("analiticcl_test0451.alphabet.tsv");
+    let mut alphabet = String::new();
+    for c in 'a'..='z' {
+        alphabet.push(c);
+        alphabet.push('\n');
+    }
+    alphabet.push_str("\\ignore\t\\u00AD\t\u{200D}\n\\unk\t\u{2610}\n");
+    std::fs::write(&filename, alphabet).expect("writing temporary alphabet");
+    let mut model = VariantModel::new(filename.to_str().unwrap(), Weights::default(), 1);
+    std::fs::remove_file(&filename).ok();
+    assert_eq!(model.alphabet.len(), 26);
+    assert_eq!(model.alphabet_ignore, vec!["\u{00AD}", "\u{200D}"]);
+    assert_eq!(model.alphabet_unk, vec!["\u{2610}"]);
+
+    model.add_to_vocabulary("huis", None, &VocabParams::default());
+    model.add_to_vocabulary("hu\u{2610}s", None, &VocabParams::default());
+    model.add_to_vocabulary("ho\u{00FF}s", None, &VocabParams::default());
+    model.build();
+    //ignored characters are stripped prior to encoding
+    let prepared = model.prepare_query("hu\u{00AD}i\u{200D}s");
+    assert_eq!(prepared.normstring, model.prepare_query("huis").normstring);
+    assert_eq!(prepared.anahash, "huis".anahash(&model.alphabet));
+    let results = model.find_variants("hu\u{00AD}is", &get_test_searchparams());
+    assert_eq!(model.get_vocab(results[0].vocab_id).unwrap().text, "huis");
+    assert_eq!(results[0].dist_score, 1.0);
+    //explicitly unknown characters are not reported as missing from the alphabet
+    let report = model.alphabet_coverage_report();
+    assert_eq!(report.affected_entries, 1);
+    assert_eq!(report.unknown_chars.len(), 1);
+    assert!(report.unknown_chars.contains_key(&'\u{00FF}'));
+
+    //both are saved with the model, the explicitly passed alphabet does not carry them
+    let filename = std::env::temp_dir().join("analiticcl_test0451.model");
+    assert!(model.save(filename.to_str().unwrap()).is_ok());
+    let loaded =
+        VariantModel::load_with_alphabet(filename.to_str().unwrap(), model.alphabet.clone(), 1);
+    std::fs::remove_file(&filename).ok();
+    let loaded = loaded.expect("loading model");
+    assert_eq!(loaded.alphabet_ignore, model.alphabet_ignore);
+    assert_eq!(loaded.alphabet_unk, model.alphabet_unk);
+    let results = loaded.find_variants("hu\u{00AD}is", &get_test_searchparams());
+    assert_eq!(loaded.get_vocab(results[0].vocab_id).unwrap().text, "huis");
+    assert_eq!(results[0].dist_score, 1.0);
+    assert_eq!(loaded.alphabet_coverage_report().unknown_chars.len(), 1);
+}
+
+#[test]
+fn test0452_check_files() {
+    let dir = std::env::temp_dir();
+    let alphabet = dir.join("analiticcl_test0452.alphabet.tsv");
+    std::fs::write(&alphabet, "a\nb\nA\ta\n\\ignore\t\\u00AD\n").expect("writing");
+    let lexicon = dir.join("analiticcl_test0452.lexicon.tsv");
+    std::fs::write(&lexicon, "abba\t10\nbaba\tmany\n").expect("writing");
+    let variants = dir.join("analiticcl_test0452.variants.tsv");
+    std::fs::write(
+        &variants,
+        "abba\t3\taaba\t0.5\t2\nbaba\t1\tbaab\thigh\t1\tab\n",
+    )
+    .expect("writing");
+    let confusables = dir.join("analiticcl_test0452.confusables.tsv");
+    std::fs::write(&confusables, "-[b]+[a]\t1.1\n-[b]+[a]\t1.1\tfoo\n").expect("writing");
+
+    let problems = check_alphabet(alphabet.to_str().unwrap()).expect("check");
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].line, 3);
+    assert!(problems[0].message.starts_with("Duplicate entry"));
+
+    let problems =
+        check_vocabulary(lexicon.to_str().unwrap(), &VocabParams::default()).expect("check");
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].line, 2);
+
+    let problems =
+        check_variants(variants.to_str().unwrap(), &VocabParams::default()).expect("check");
+    assert_eq!(problems.len(), 2);
+    assert!(problems.iter().all(|problem| problem.line == 2));
+    assert!(problems[0]
+        .message
+        .starts_with("Inconsistent number of columns"));
+    assert!(problems[1].message.contains("column 4"));
+
+    let problems = check_confusablelist(confusables.to_str().unwrap()).expect("check");
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].line, 2);
+
+    assert!(check_alphabet("/nonexistent/analiticcl_test0452.tsv").is_err());
+    for filename in [alphabet, lexicon, variants, confusables] {
+        std::fs::remove_file(&filename).ok();
+    }
+}
+
+#[test]
+fn test0453_lexicon_weight() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_vocabulary_from_iter(
+        "noisy",
+        [("huls", 1), ("huis", 1)],
+        &VocabParams::default().with_weight(0.9),
+    );
+    model.add_vocabulary_from_iter(
+        "authoritative",
+        [("huis", 1)],
+        &VocabParams::default().with_weight(1.1),
+    );
+    model.build();
+    assert_eq!(model.lexicon_weights, vec![0.9, 1.1]);
+    let huis = model.get("huis").unwrap();
+    assert_eq!(model.lexicon_weight(huis), 1.1);
+    assert_eq!(model.lexicon_weight(model.get("huls").unwrap()), 0.9);
+
+    let params = get_test_searchparams().with_explain(true);
+    let results = model.find_variants("huys", &params);
+    assert_eq!(results.len(), 2);
+    assert_eq!(model.get_vocab(results[0].vocab_id).unwrap().text, "huis");
+    let explanation = results[0].explanation.as_ref().unwrap();
+    assert_eq!(explanation.lexicon_weight, 1.1);
+    assert_eq!(
+        results[0].dist_score,
+        explanation.combined_score * explanation.lexicon_weight
+    );
+    assert!(results[0].dist_score > results[1].dist_score);
+
+    //weights of disabled lexicons do not apply
+    model.disable_lexicon(1).unwrap();
+    let huis = model.get("huis").unwrap();
+    assert_eq!(model.lexicon_weight(huis), 0.9);
+}
+
+#[test]
+fn test0454_anagram_distance_derived_from_edit() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("house", None, &VocabParams::default());
+    model.build();
+    //a substitution counts double in the anagram distance
+    let params = get_test_searchparams()
+        .with_edit_distance(DistanceThreshold::Absolute(1))
+        .with_anagram_distance(DistanceThreshold::Absolute(1));
+    assert!(model.find_variants("hoose", &params).is_empty());
+    let params = params.with_anagram_distance("+0".parse().expect("parse"));
+    assert!(model.find_variants("hoose", &params).is_empty());
+    let params = params.with_anagram_distance("+1".parse().expect("parse"));
+    assert!(matches!(
+        params.max_anagram_distance,
+        DistanceThreshold::DerivedFromEdit(1)
+    ));
+    let results = model.find_variants("hoose", &params);
+    assert_eq!(results.len(), 1);
+    assert_eq!(model.get_vocab(results[0].vocab_id).unwrap().text, "house");
+}
+
+#[test]
+fn test0455_empty_input() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("separate", Some(100), &VocabParams::default());
+    model.build();
+    let params = get_test_searchparams();
+    let inputs = ["", " ", "   ", "\t\n", "!?.", "..."];
+    for input in inputs {
+        assert!(model.find_variants(input, &params).is_empty());
+        assert!(model.best_variant(input, &params).is_none());
+    }
+    let results = model.find_variants_batch(&inputs, &params);
+    assert!(results.iter().all(|(_, variants)| variants.is_empty()));
+}
+
+#[test]
+fn test0456_transfer_case() {
+    assert_eq!(CasePattern::of("huys"), CasePattern::Lowercase);
+    assert_eq!(CasePattern::of("Huys"), CasePattern::Titlecase);
+    assert_eq!(CasePattern::of("HUYS"), CasePattern::Uppercase);
+    assert_eq!(CasePattern::of("I"), CasePattern::Titlecase);
+    assert_eq!(CasePattern::of("hUys"), CasePattern::Mixed);
+    assert_eq!(CasePattern::of("1234"), CasePattern::Mixed);
+    assert_eq!(transfer_case("Huys", "huis"), "Huis");
+    assert_eq!(transfer_case("HUYS", "huis"), "HUIS");
+    assert_eq!(transfer_case("huys", "Huis"), "huis");
+    assert_eq!(transfer_case("hUys", "huis"), "huis");
+
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.set_casefold(true);
+    model.add_to_vocabulary("huis", Some(10), &VocabParams::default());
+    model.add_to_vocabulary("staat", Some(10), &VocabParams::default());
+    model.build();
+    let params = get_test_searchparams().with_transfer_case(true);
+    let results = model.find_variants("Huys", &params);
+    assert_eq!(model.variant_text(&results[0]), "Huis");
+    assert_eq!(results[0].cased.as_deref(), Some("Huis"));
+    let results = model.find_variants("huys", &params);
+    assert_eq!(model.variant_text(&results[0]), "huis");
+    assert_eq!(results[0].cased, None, "unchanged casing is not stored");
+    assert_eq!(
+        model.correct_text("Het HUYS staat.", &params),
+        "Het HUIS staat."
+    );
+    //disabled by default
+    let results = model.find_variants("Huys", &get_test_searchparams());
+    assert_eq!(model.variant_text(&results[0]), "huis");
+}
+
+#[test]
+fn test0457_deterministic_save() {
+    let build = || {
+        let (alphabet, _alphabet_size) = get_test_alphabet();
+        let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+        assert!(model
+            .read_vocabulary(LEXICON_AMPHIBIANS, &VocabParams::default())
+            .is_ok());
+        for (a, b) in [("u", "v"), ("i", "j"), ("a", "e"), ("o", "u")] {
+            model
+                .add_substitution_cost(a, b, 0.5)
+                .expect("added substitution cost");
+        }
+        model.build();
+        model
+    };
+    let mut saved = Vec::new();
+    for i in 0..2 {
+        //separately built models have hash maps with different iteration orders
+        let model = build();
+        let filename = std::env::temp_dir().join(format!("analiticcl_test0457_{}.model", i));
+        assert!(model.save(filename.to_str().unwrap()).is_ok());
+        saved.push(std::fs::read(&filename).expect("reading saved model"));
+        std::fs::remove_file(&filename).ok();
+    }
+    assert!(
+        saved[0] == saved[1],
+        "saved models should be byte-identical"
+    );
+}
+
+#[test]
+fn test0458_read_from_reader() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon = "huis\t10\nmuis\t5\n";
+    model
+        .read_vocabulary_from_reader(
+            "inline lexicon",
+            lexicon.as_bytes(),
+            &VocabParams::default(),
+        )
+        .expect("reading vocabulary");
+    let variants = "huis\thuys\t0.9\n";
+    model
+        .read_variants_from_reader("inline variants", variants.as_bytes(), None, true)
+        .expect("reading variants");
+    model.build();
+    assert_eq!(model.lexicons, vec!["inline lexicon", "inline variants"]);
+    assert!(model.has("huis"));
+    assert_eq!(
+        model.get_vocab(model.encoder["huis"]).unwrap().frequency,
+        10
+    );
+    let results = model.find_variants("huys", &get_test_searchparams());
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "huis");
+}
+
+#[test]
+fn test0459_corpus_lm() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let corpus = "het\t7\nhuis\t10\nmuis\t5\nhet huis\t3\n";
+    model
+        .read_vocabulary_from_reader(
+            "corpus",
+            corpus.as_bytes(),
+            &VocabParams::default().with_vocab_type(VocabType::CORPUS),
+        )
+        .expect("reading corpus");
+    //an entry from a lexicon that also occurs in a language model is used for both
+    model.add_to_vocabulary("kat", Some(2), &VocabParams::default());
+    model.add_to_vocabulary(
+        "kat",
+        Some(2),
+        &VocabParams::default().with_vocab_type(VocabType::LM),
+    );
+    model.build();
+    assert!(model.have_lm);
+    let huis = model.encoder["huis"];
+    let het = model.encoder["het"];
+    let kat = model.encoder["kat"];
+    assert_eq!(model.ngrams.get(&NGram::UniGram(huis)), Some(&10));
+    assert_eq!(model.ngrams.get(&NGram::BiGram(het, huis)), Some(&3));
+    assert!(model.ngrams.contains_key(&NGram::UniGram(kat)));
+    assert!(model.decoder[kat as usize]
+        .vocabtype
+        .check(VocabType::INDEXED | VocabType::LM));
+    let results = model.find_variants("huys", &get_test_searchparams());
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "huis");
+}
+
+#[test]
+fn test0460_distinct_unknowns() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet.clone(), Weights::default(), 1);
+    model.add_to_vocabulary("huisα", None, &VocabParams::default());
+    model.add_to_vocabulary("huisβ", None, &VocabParams::default());
+    model.build();
+    //by default, unknown characters are indistinguishable
+    let results = model.find_variants("huisα", &get_test_searchparams());
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].dist_score, results[1].dist_score);
+
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("huisα", None, &VocabParams::default());
+    model.add_to_vocabulary("huisβ", None, &VocabParams::default());
+    model.set_distinct_unknowns(true);
+    assert_ne!(
+        model.decoder[model.encoder["huisα"] as usize].norm,
+        model.decoder[model.encoder["huisβ"] as usize].norm
+    );
+    model.build();
+    let results = model.find_variants("huisα", &get_test_searchparams());
+    assert_eq!(results.len(), 2);
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "huisα");
+    assert_eq!(results[0].dist_score, 1.0);
+    assert!(results[1].dist_score < 1.0);
+    //both unknown characters still share the same anagram hash
+    assert_eq!(
+        "huisα".anahash(&model.alphabet),
+        "huisβ".anahash(&model.alphabet)
+    );
+    //distinct unknowns never collide with the plain UNK symbol
+    let unk = model.alphabet.len() as CharIndexType + 1;
+    assert_eq!("α".normalize_to_alphabet(&model.alphabet), vec![unk]);
+    for c in ('\u{80}'..'\u{2000}').chain(['α', 'β', '\u{10FFFF}']) {
+        assert!(unknown_symbol(c, model.alphabet.len()) > unk);
+    }
+    //unless the alphabet leaves no room at all
+    let full = CharIndexType::MAX as usize - 1;
+    assert_eq!(unknown_symbol('α', full), CharIndexType::MAX);
+}
+
+#[test]
+fn test0461_lemmas() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon = "lopen\t30\tlopen\nloopt\t12\tlopen\nslopen\t4\tslopen\nloper\t2\n";
+    model
+        .read_vocabulary_from_reader(
+            "lexicon",
+            lexicon.as_bytes(),
+            &VocabParams::default().with_lemma_column(2),
+        )
+        .expect("reading lexicon");
+    model.build();
+    assert_eq!(
+        model.decoder[model.encoder["loopt"] as usize]
+            .lemma
+            .as_deref(),
+        Some("lopen")
+    );
+    assert_eq!(model.decoder[model.encoder["loper"] as usize].lemma, None);
+    let params = get_test_searchparams()
+        .with_edit_distance(DistanceThreshold::Absolute(3))
+        .with_anagram_distance(DistanceThreshold::Absolute(4));
+    let results = model.find_variants("loopen", &params);
+    let lemmas: Vec<Option<&str>> = results
+        .iter()
+        .map(|result| model.variant_lemma(result))
+        .collect();
+    assert!(
+        lemmas
+            .iter()
+            .filter(|lemma| **lemma == Some("lopen"))
+            .count()
+            > 1
+    );
+    let collapsed = model.find_variants("loopen", &params.with_collapse_lemmas(true));
+    let lemmas: Vec<Option<&str>> = collapsed
+        .iter()
+        .map(|result| model.variant_lemma(result))
+        .collect();
+    assert_eq!(
+        lemmas
+            .iter()
+            .filter(|lemma| **lemma == Some("lopen"))
+            .count(),
+        1
+    );
+    //the best ranking inflection of the lemma is retained
+    let best = results
+        .iter()
+        .find(|result| model.variant_lemma(result) == Some("lopen"))
+        .unwrap();
+    assert!(collapsed
+        .iter()
+        .any(|result| result.vocab_id == best.vocab_id));
+    //variants without a lemma are retained
+    assert!(collapsed
+        .iter()
+        .any(|result| result.vocab_id == model.encoder["loper"]));
+}
+
+#[test]
+fn test0462_no_insertions() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "hus", "huys", "huiss"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.set_insertions(false);
+    model.build();
+    assert!(model.sortedindex.is_empty());
+    let params = get_test_searchparams()
+        .with_edit_distance(DistanceThreshold::Absolute(2))
+        .with_anagram_distance(DistanceThreshold::Absolute(2))
+        .with_max_matches(0);
+    let results = model.find_variants("huis", &params);
+    let texts: Vec<&str> = results
+        .iter()
+        .map(|result| model.decoder[result.vocab_id as usize].text.as_str())
+        .collect();
+    assert_eq!(texts[0], "huis");
+    assert!(texts.contains(&"hus"), "deletion is found");
+    assert!(texts.contains(&"huys"), "substitution is found");
+    assert!(!texts.contains(&"huiss"), "insertion is not found");
+
+    //enabling it again builds the secondary index immediately
+    model.set_insertions(true);
+    assert!(!model.sortedindex.is_empty());
+    let results = model.find_variants("huis", &params);
+    assert!(results
+        .iter()
+        .any(|result| model.decoder[result.vocab_id as usize].text == "huiss"));
+}
+
+#[test]
+fn test0463_precompute() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls", "huys", "zijn", "tijd"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let params = get_test_searchparams().with_neighbourhood_cache_size(10);
+    let uncached = model.find_variants("huijs", &get_test_searchparams());
+
+    //"sjiuh" is an anagram of "huijs" so shares its neighbourhood
+    let stats = model.precompute(&["huijs", "sjiuh", "tijt", " "], &params);
+    assert_eq!(stats.queries, 4);
+    assert_eq!(stats.computed, 2);
+    assert_eq!(stats.cached, 1);
+    assert_eq!(stats.skipped, 1);
+    assert_eq!(model.neighbourhood_cache().len(), 2);
+    assert_eq!(model.neighbourhood_cache().misses(), 0);
+
+    //queries are now served from the cache, with identical results
+    let results = model.find_variants("huijs", &params);
+    model.find_variants("tijt", &params);
+    assert_eq!(model.neighbourhood_cache().hits(), 2);
+    assert_eq!(model.neighbourhood_cache().misses(), 0);
+    assert_eq!(
+        results
+            .iter()
+            .map(|result| result.vocab_id)
+            .collect::<Vec<_>>(),
+        uncached
+            .iter()
+            .map(|result| result.vocab_id)
+            .collect::<Vec<_>>()
+    );
+
+    //precomputing again computes nothing new
+    let stats = model.precompute(&["huijs"], &params);
+    assert_eq!(stats.computed, 0);
+    assert_eq!(stats.cached, 1);
+}
+
+#[test]
+fn test0464_transposition_pruning() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["house", "hose", "horse", "mouse", "blouse", "hoes"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    //a wide anagram neighbourhood, most of which is pruned on character counts alone
+    let params = get_test_searchparams()
+        .with_edit_distance(DistanceThreshold::Absolute(1))
+        .with_anagram_distance(DistanceThreshold::Absolute(3))
+        .with_max_matches(0);
+    let results = model.find_variants("ohuse", &params);
+    let texts: Vec<&str> = results
+        .iter()
+        .map(|result| model.decoder[result.vocab_id as usize].text.as_str())
+        .collect();
+    assert_eq!(texts, vec!["house"]);
+
+    //anagrams of the input are never pruned, but only transpositions are within the edit distance
+    let results = model.find_variants("hsoe", &params);
+    let texts: Vec<&str> = results
+        .iter()
+        .map(|result| model.decoder[result.vocab_id as usize].text.as_str())
+        .collect();
+    assert_eq!(texts, vec!["hose"]);
+}
+
+#[test]
+fn test0501_confusable_found_in() {
+    let confusable = Confusable::new("-[y]+[i]", 1.1).expect("valid script");
+    eprintln!("confusable: {:?}", confusable);
+    let huis_script = sesdiff::shortest_edit_script("huys", "huis", false, false, false);
+    eprintln!("huis_script: {:?}", huis_script);
+    let huls_script = sesdiff::shortest_edit_script("huys", "huls", false, false, false);
+    eprintln!("huls_script: {:?}", huls_script);
+    assert!(
+        confusable.found_in(&huis_script),
+        "confusable should be found in huys->huis"
+    );
+    assert!(
+        !confusable.found_in(&huls_script),
+        "confusable should not be found in huys->huls"
+    );
+}
+
+#[test]
+fn test0502_confusable_test() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["huis", "huls"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model
+        .add_to_confusables("-[y]+[i]", 1.1)
+        .expect("added to confusables");
+    model.build();
+    let results = model.find_variants("huys", &get_test_searchparams());
+    assert_eq!(
+        model
+            .decoder
+            .get(results.get(0).unwrap().vocab_id as usize)
+            .unwrap()
+            .text,
+        "huis"
+    );
+    assert_eq!(
+        model
+            .decoder
+            .get(results.get(1).unwrap().vocab_id as usize)
+            .unwrap()
+            .text,
+        "huls"
+    );
+    assert!(
+        results.get(0).unwrap().dist_score > results.get(1).unwrap().dist_score,
+        "score of huis should be greater than that of huls"
+    );
+}
+
+#[test]
+fn test0503_confusable_test2() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["huis", "huls"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model
+        .add_to_confusables("-[y]+[i]", 1.1)
+        .expect("added to confusables");
+    model.build();
+    let results = model.find_variants("Huys", &get_test_searchparams());
+    assert_eq!(
+        model
+            .decoder
+            .get(results.get(0).unwrap().vocab_id as usize)
+            .unwrap()
+            .text,
+        "huis"
+    );
+    assert_eq!(
+        model
+            .decoder
+            .get(results.get(1).unwrap().vocab_id as usize)
+            .unwrap()
+            .text,
+        "huls"
+    );
+    assert!(
+        results.get(0).unwrap().dist_score > results.get(1).unwrap().dist_score,
+        "score of huis should be greater than that of huls"
+    );
+}
+
+#[test]
+fn test0504_confusable_nomatch() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["huis", "huls"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model
+        .add_to_confusables("-[y]+[p]", 1.1)
+        .expect("added to confusables");
+    model.build();
+    let results = model.find_variants("Huys", &get_test_searchparams());
+    assert_eq!(results.len(), 2);
+    assert_eq!(
+        results.get(0).unwrap().dist_score,
+        results.get(1).unwrap().dist_score,
+        "score of huis should be equal to that of huls"
+    );
+}
+
+#[test]
+fn test0505_keyboard_layout() {
+    let pairs = KeyboardLayout::Qwerty.adjacent_pairs();
+    assert_eq!(pairs.len(), 55);
+    assert!(pairs.contains(&('n', 'm')));
+    assert!(pairs.contains(&('i', 'o')));
+    assert!(pairs.contains(&('w', 'a')));
+    assert!(!pairs.contains(&('q', 'p')));
+    let pairs = KeyboardLayout::Azerty.adjacent_pairs();
+    assert!(pairs.contains(&('a', 'z')));
+    assert!(pairs.contains(&('l', 'm')));
+    assert_eq!(
+        KeyboardLayout::Custom(vec!["ab".to_string(), "cd".to_string()]).adjacent_pairs(),
+        vec![('a', 'b'), ('a', 'c'), ('b', 'c'), ('b', 'd'), ('c', 'd')]
+    );
+    assert_eq!(
+        "AZERTY".parse::<KeyboardLayout>().unwrap(),
+        KeyboardLayout::Azerty
+    );
+}
+
+#[test]
+fn test0506_keyboard_confusables() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["house", "horse"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model
+        .add_keyboard_confusables(KeyboardLayout::Qwerty, 1.1)
+        .expect("added keyboard confusables");
+    assert_eq!(model.confusables.len(), 110);
+    model.build();
+    //i is adjacent to u on a qwerty keyboard, but not to r
+    let results = model.find_variants("hoise", &get_test_searchparams());
+    assert_eq!(results.len(), 2);
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "house");
+    assert!(results[0].dist_score > results[1].dist_score);
+}
+
+#[test]
+fn test0507_confusable_casefold() {
+    let confusable = Confusable::new_with_casefold("-[Y]+[I]", 1.1, true).expect("valid script");
+    let huis_script = sesdiff::shortest_edit_script("huys", "huis", false, false, false);
+    assert!(confusable.found_in(&huis_script));
+
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model
+        .add_to_confusables("-[y]+[i]", 1.1)
+        .expect("added to confusables");
+    model.build();
+    //the case-sensitive confusable does not fire on uppercase input
+    let results = model.find_variants("HUYS", &get_test_searchparams());
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].dist_score, results[1].dist_score);
+
+    model.confusables.clear();
+    model
+        .add_to_confusables_casefold("-[y]+[i]", 1.1)
+        .expect("added to confusables");
+    let results = model.find_variants("HUYS", &get_test_searchparams());
+    assert_eq!(results.len(), 2);
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "huis");
+    assert!(results[0].dist_score > results[1].dist_score);
+}
+
+#[test]
+fn test0508_derive_confusables() {
+    let pairs = [
+        ("huis", "huys"),
+        ("kind", "kynd"),
+        ("wijn", "wyn"),
+        ("tijd", "tyd"),
+        ("mijn", "myn"),
+        ("kerk", "kcrk"),
+        ("ook", "oock"),
+    ];
+    let confusables = VariantModel::derive_confusables(pairs.into_iter(), 2);
+    assert_eq!(confusables.len(), 2);
+    assert_eq!(confusables[0].editscript.to_string(), "-[y]+[ij]");
+    assert_eq!(confusables[0].weight, DERIVED_CONFUSABLE_MAX_WEIGHT);
+    assert_eq!(confusables[1].editscript.to_string(), "-[y]+[i]");
+    assert!(confusables[1].weight > 1.0 && confusables[1].weight < confusables[0].weight);
+
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for confusable in confusables {
+        model
+            .add_to_confusables(&confusable.editscript.to_string(), confusable.weight)
+            .expect("added to confusables");
+    }
+    assert_eq!(model.confusables.len(), 2);
+}
+
+#[test]
+fn test0509_confusable_context() {
+    let script =
+        |input, candidate| sesdiff::shortest_edit_script(input, candidate, false, false, false);
+    let confusable = Confusable::new("(a|o)-[c]+[t]", 1.1).expect("valid script");
+    assert_eq!(confusable.leftcontext, vec!["a", "o"]);
+    assert!(confusable.found_in(&script("ace", "ate")));
+    assert!(confusable.found_in(&script("oce", "ote")));
+    assert!(!confusable.found_in(&script("ice", "ite")));
+    //the context may itself be part of an edit
+    assert!(confusable.found_in(&script("bace", "bte")));
+
+    let confusable = Confusable::new("-[c]+[t](h)", 1.1).expect("valid script");
+    assert_eq!(confusable.rightcontext, vec!["h"]);
+    assert!(confusable.found_in(&script("ach", "ath")));
+    assert!(!confusable.found_in(&script("ace", "ate")));
+
+    //the context must hold for the same occurrence of the edit
+    let confusable = Confusable::new("(a)-[c]+[t](e)", 1.1).expect("valid script");
+    assert!(confusable.found_in(&script("ocaace", "otaate")));
+    assert!(!confusable.found_in(&script("oce", "ote")));
+
+    assert_eq!(
+        Confusable::new("^(a|o)-[c]+[t](e)$", 1.1)
+            .expect("valid script")
+            .to_string(),
+        "^(a|o)-[c]+[t](e)$"
+    );
+}
+
+#[test]
+fn test0510_confusable_context_model() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["rate", "rare", "rote", "rore"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model
+        .add_to_confusables("(a)-[c]+[t]", 1.1)
+        .expect("added to confusables");
+    model.build();
+    let score = |results: &Vec<VariantResult>, text: &str| {
+        results
+            .iter()
+            .find(|result| model.decoder[result.vocab_id as usize].text == text)
+            .expect("result")
+            .dist_score
+    };
+    let results = model.find_variants("race", &get_test_searchparams());
+    assert!(score(&results, "rate") > score(&results, "rare"));
+    //the left context does not apply, so the confusable does not either
+    let results = model.find_variants("roce", &get_test_searchparams());
+    assert_eq!(score(&results, "rote"), score(&results, "rore"));
+}
+
+#[test]
+fn test0511_affixes() {
+    let rule = AffixRule::new("-jes", 0.9).expect("valid affix");
+    assert_eq!(rule.affixtype, AffixType::Suffix);
+    assert_eq!(rule.to_string(), "-jes");
+    assert_eq!(rule.strip("huisjes"), Some("huis"));
+    assert_eq!(rule.strip("jes"), None, "the stem may not be empty");
+    assert_eq!(
+        rule.strip("huis jes"),
+        None,
+        "affixes only apply within a word"
+    );
+    assert_eq!(rule.strip("huis"), None);
+    let rule = AffixRule::new("ge-", 0.9).expect("valid affix");
+    assert_eq!(rule.affixtype, AffixType::Prefix);
+    assert_eq!(rule.strip("gehuis"), Some("huis"));
+    assert!(AffixRule::new("jes", 0.9).is_err());
+    assert!(AffixRule::new("-", 0.9).is_err());
+    assert!(AffixRule::new("-jes", 1.5).is_err());
+
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huisje", "muis"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let params = get_test_searchparams();
+    let results = model.find_variants("huisjes", &params);
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "huisje");
+    let baseline = results
+        .iter()
+        .find(|result| model.decoder[result.vocab_id as usize].text == "huis")
+        .map(|result| result.dist_score)
+        .unwrap_or(0.0);
+    model.add_to_affixes("-jes", 0.9).expect("added to affixes");
+    let results = model.find_variants("huisjes", &params);
+    let huis = results
+        .iter()
+        .find(|result| model.decoder[result.vocab_id as usize].text == "huis")
+        .expect("stem found");
+    assert!(huis.dist_score > baseline);
+    assert!(huis.dist_score <= 0.9);
+    assert!(
+        results
+            .windows(2)
+            .all(|pair| pair[0].dist_score >= pair[1].dist_score),
+        "merged results are ranked"
+    );
+    //the affix is stripped at the end of the input only
+    let results = model.find_variants("jeshuis", &params);
+    assert!(results.iter().all(|result| result.dist_score < 0.9));
+}
+
+#[test]
+fn test0512_apply_confusables() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let results = model.find_variants("huys", &get_test_searchparams());
+    assert_eq!(results.len(), 2);
+    model
+        .add_to_confusables("-[y]+[i]", 1.1)
+        .expect("added to confusables");
+    let huis = model.encoder["huis"];
+    let huls = model.encoder["huls"];
+    assert!((model.compute_confusable_weight("huys", huis) - 1.1).abs() < 1e-9);
+    assert_eq!(model.compute_confusable_weight("huys", huls), 1.0);
+    let mut rescored = results.clone();
+    model.apply_confusables("huys", &mut rescored);
+    for (before, after) in results.iter().zip(rescored.iter()) {
+        assert_eq!(before.vocab_id, after.vocab_id);
+        let weight = if after.vocab_id == huis { 1.1 } else { 1.0 };
+        assert!((after.dist_score - before.dist_score * weight).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test0601_find_boundaries() {
+    let text = "Hallo allemaal, ik zeg: \"Welkom in Aix-les-bains!\".";
+    let boundaries = find_boundaries(&text);
+    eprintln!("{:?}", boundaries);
+    assert_eq!(boundaries.len(), 9);
+    assert_eq!(boundaries.get(0).unwrap().offset.begin, 5);
+    assert_eq!(boundaries.get(0).unwrap().offset.end, 6);
+    assert_eq!(boundaries.get(0).unwrap().text, " ");
+    assert_eq!(boundaries.get(1).unwrap().text, ", ");
+    assert_eq!(boundaries.get(2).unwrap().text, " ");
+    assert_eq!(boundaries.get(3).unwrap().text, ": \"");
+    assert_eq!(boundaries.get(4).unwrap().text, " ");
+    assert_eq!(boundaries.get(5).unwrap().text, " ");
+    assert_eq!(boundaries.get(6).unwrap().text, "-");
+    assert_eq!(boundaries.get(7).unwrap().text, "-");
+    assert_eq!(boundaries.get(8).unwrap().text, "!\".");
+}
+
+#[test]
+fn test0602_find_ngrams_unigram1() {
+    let text = "dit is een mooie test";
+    let boundaries = find_boundaries(&text);
+    let ngrams = find_match_ngrams(text, &boundaries, 1, 0, None);
+    assert_eq!(ngrams.len(), 5);
+    assert_eq!(ngrams.get(0).unwrap().text, "dit");
+    assert_eq!(ngrams.get(1).unwrap().text, "is");
+    assert_eq!(ngrams.get(2).unwrap().text, "een");
+    assert_eq!(ngrams.get(3).unwrap().text, "mooie");
+    assert_eq!(ngrams.get(4).unwrap().text, "test");
+}
+
+#[test]
+fn test0603_find_ngrams_unigram2() {
+    let text = "dit is een mooie test.";
+    let boundaries = find_boundaries(&text);
+    let ngrams = find_match_ngrams(text, &boundaries, 1, 0, None);
+    assert_eq!(ngrams.len(), 5);
+    assert_eq!(ngrams.get(0).unwrap().text, "dit");
+    assert_eq!(ngrams.get(1).unwrap().text, "is");
+    assert_eq!(ngrams.get(2).unwrap().text, "een");
+    assert_eq!(ngrams.get(3).unwrap().text, "mooie");
+    assert_eq!(ngrams.get(4).unwrap().text, "test");
+}
+
+#[test]
+fn test0604_find_ngrams_unigram3() {
+    let text = "hello, world!";
+    let boundaries = find_boundaries(&text);
+    let ngrams = find_match_ngrams(text, &boundaries, 1, 0, None);
+    assert_eq!(ngrams.len(), 2);
+    assert_eq!(ngrams.get(0).unwrap().text, "hello");
+    assert_eq!(ngrams.get(1).unwrap().text, "world");
 }
 
 #[test]
@@ -1116,6 +3602,141 @@ fn test0608_find_ngrams_bigrams4() {
     assert_eq!(ngrams.len(), 0); //no bigrams in this text
 }
 
+#[test]
+fn test0609_find_boundaries_protected() {
+    let text = "hello<lb/>world [unclear] foo";
+    let protected = vec![
+        regex::Regex::new("<[^>]+>").unwrap(),
+        regex::Regex::new(r"\[[^\]]+\]").unwrap(),
+    ];
+    let boundaries = find_boundaries_protected(text, &protected);
+    assert_eq!(boundaries.len(), 3);
+    assert_eq!(boundaries.get(0).unwrap().text, "<lb/>");
+    assert_eq!(boundaries.get(0).unwrap().offset.begin, 5);
+    assert_eq!(boundaries.get(0).unwrap().offset.end, 10);
+    assert_eq!(boundaries.get(1).unwrap().text, " [unclear] ");
+    assert_eq!(boundaries.get(2).unwrap().text, "");
+}
+
+#[test]
+fn test0610_find_boundaries_config() {
+    let text = "Welkom in Aix-les-bains!";
+    let config = BoundaryConfig::default().with_nonboundary("-");
+    let boundaries = find_boundaries_with_config(text, &[], &config);
+    assert_eq!(boundaries.len(), 3);
+    assert_eq!(boundaries.first().unwrap().text, " ");
+    assert_eq!(boundaries.get(1).unwrap().text, " ");
+    assert_eq!(boundaries.get(2).unwrap().text, "!");
+    let ngrams = find_match_ngrams(text, &boundaries, 1, 0, None);
+    assert_eq!(ngrams.get(2).unwrap().text, "Aix-les-bains");
+}
+
+#[test]
+fn test0611_classify_boundaries_config() {
+    let text = "foo-bar/baz qux.";
+    let boundaries = find_boundaries(text);
+    let strengths = classify_boundaries(&boundaries, &BoundaryConfig::default());
+    assert_eq!(
+        strengths,
+        vec![
+            BoundaryStrength::Weak,
+            BoundaryStrength::Normal,
+            BoundaryStrength::Normal,
+            BoundaryStrength::Hard
+        ]
+    );
+    let config = BoundaryConfig::default()
+        .with_hard("/")
+        .with_whitespace(BoundaryStrength::Weak);
+    let strengths = classify_boundaries(&boundaries, &config);
+    assert_eq!(
+        strengths,
+        vec![
+            BoundaryStrength::Weak,
+            BoundaryStrength::Hard,
+            BoundaryStrength::Weak,
+            BoundaryStrength::Hard
+        ]
+    );
+}
+
+#[test]
+fn test0612_intraword_contractions() {
+    let text = "I don't know, 'tis what they'd say.";
+    let ngrams = |config: &BoundaryConfig| -> Vec<&str> {
+        let boundaries = find_boundaries_with_config(text, &[], config);
+        find_match_ngrams(text, &boundaries, 1, 0, None)
+            .into_iter()
+            .map(|m| m.text)
+            .collect()
+    };
+    assert_eq!(
+        ngrams(&BoundaryConfig::default()),
+        vec!["I", "don", "t", "know", "tis", "what", "they", "d", "say"]
+    );
+    //the leading apostrophe of 'tis is not between two alphabetic characters
+    assert_eq!(
+        ngrams(&BoundaryConfig::default().with_intraword("'")),
+        vec!["I", "don't", "know", "tis", "what", "they'd", "say"]
+    );
+    //abbreviations are kept together, except for the final period
+    let text = "The U.S. army";
+    let config = BoundaryConfig::default().with_intraword("'.");
+    let boundaries = find_boundaries_with_config(text, &[], &config);
+    let ngrams: Vec<&str> = find_match_ngrams(text, &boundaries, 1, 0, None)
+        .into_iter()
+        .map(|m| m.text)
+        .collect();
+    assert_eq!(ngrams, vec!["The", "U.S", "army"]);
+
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["I", "don't", "know"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let mut params = get_test_searchparams();
+    params.boundary_config = BoundaryConfig::default().with_intraword("'");
+    let matches = model.find_all_matches("I dont't know", &params);
+    assert_eq!(
+        matches.iter().map(|m| m.text).collect::<Vec<_>>(),
+        vec!["I", "dont't", "know"]
+    );
+    assert_eq!(model.match_to_str(&matches[1]), "don't");
+}
+
+#[test]
+fn test0613_intraword_surnames() {
+    let text = "Mr O'Brien met 'Neill' and Ó'Súilleabháin.";
+    let config = BoundaryConfig::default().with_intraword("'");
+    let boundaries = find_boundaries_with_config(text, &[], &config);
+    let ngrams: Vec<&str> = find_match_ngrams(text, &boundaries, 1, 0, None)
+        .into_iter()
+        .map(|m| m.text)
+        .collect();
+    //quotes around a word remain boundaries, non-ASCII letters count as alphabetic
+    assert_eq!(
+        ngrams,
+        vec!["Mr", "O'Brien", "met", "Neill", "and", "Ó'Súilleabháin"]
+    );
+
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["O'Brien", "O'Neill", "met"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let mut params = get_test_searchparams();
+    params.boundary_config = config;
+    let matches = model.find_all_matches("O'Brein met O'Neil", &params);
+    assert_eq!(
+        matches.iter().map(|m| m.text).collect::<Vec<_>>(),
+        vec!["O'Brein", "met", "O'Neil"]
+    );
+    assert_eq!(model.match_to_str(&matches[0]), "O'Brien");
+    assert_eq!(model.match_to_str(&matches[2]), "O'Neill");
+}
+
 #[test]
 fn test0701_find_all_matches_unigram_only() {
     let (alphabet, _alphabet_size) = get_test_alphabet();
@@ -1361,14 +3982,328 @@ fn test0704_find_all_matches_two_batches() {
 }
 
 #[test]
-fn test0705_find_all_matches_context_only() {
+fn test0705_find_all_matches_context_only() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 3);
+    model.add_to_vocabulary("I", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("think", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("sink", Some(2), &VocabParams::default()); //language model will decide between think/sink
+    model.add_to_vocabulary("you", Some(2), &VocabParams::default());
+
+    model.add_to_vocabulary("are", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("right", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("are right", Some(2), &VocabParams::default());
+    model.add_to_vocabulary(
+        "<bos> I",
+        Some(2),
+        &VocabParams {
+            vocab_type: VocabType::LM,
+            ..VocabParams::default()
+        },
+    );
+    model.add_to_vocabulary(
+        "I think",
+        Some(2),
+        &VocabParams {
+            vocab_type: VocabType::LM,
+            ..VocabParams::default()
+        },
+    );
+    model.add_to_vocabulary(
+        "I sink",
+        Some(1),
+        &VocabParams {
+            vocab_type: VocabType::LM,
+            ..VocabParams::default()
+        },
+    );
+    model.add_to_vocabulary(
+        "you are",
+        Some(2),
+        &VocabParams {
+            vocab_type: VocabType::LM,
+            ..VocabParams::default()
+        },
+    );
+    model.add_to_vocabulary(
+        "right <eos>",
+        Some(2),
+        &VocabParams {
+            vocab_type: VocabType::LM,
+            ..VocabParams::default()
+        },
+    );
+    model.build();
+    let mut params = get_test_searchparams();
+    params.context_weight = 0.5; //half of the variant model
+    params.lm_weight = 0.0; //disable normal language model
+    let matches = model.find_all_matches("I tink you are rihgt", &params);
+    assert!(!matches.is_empty());
+    assert_eq!(matches.get(0).unwrap().text, "I");
+    assert_eq!(model.match_to_str(matches.get(0).unwrap()), "I");
+    assert_eq!(matches.get(1).unwrap().text, "tink");
+    assert_eq!(model.match_to_str(matches.get(1).unwrap()), "think");
+    assert_eq!(matches.get(2).unwrap().text, "you");
+    assert_eq!(model.match_to_str(matches.get(2).unwrap()), "you");
+    assert_eq!(matches.get(3).unwrap().text, "are rihgt"); //system opts for the bigram here
+    assert_eq!(model.match_to_str(matches.get(3).unwrap()), "are right");
+}
+
+#[test]
+fn test0706_find_all_matches_unicodeoffsets() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["I", "think", "you", "are", "right"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let matches = model.find_all_matches(
+        "I thиnk you are righт",
+        &get_test_searchparams()
+            .with_max_ngram(1)
+            .with_unicodeoffsets(),
+    );
+    assert!(!matches.is_empty());
+    assert_eq!(matches.get(0).unwrap().text, "I");
+    assert_eq!(matches.get(1).unwrap().text, "thиnk");
+    assert_eq!(matches.get(1).unwrap().offset.begin, 2);
+    assert_eq!(matches.get(1).unwrap().offset.end, 7);
+    assert_eq!(model.match_to_str(matches.get(1).unwrap()), "think");
+    assert_eq!(matches.get(2).unwrap().text, "you");
+    assert_eq!(matches.get(3).unwrap().text, "are");
+    assert_eq!(matches.get(4).unwrap().text, "righт");
+    assert_eq!(model.match_to_str(matches.get(4).unwrap()), "right");
+}
+
+#[test]
+fn test0707_find_all_matches_utf8offsets() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["I", "think", "you", "are", "right"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let matches = model.find_all_matches(
+        "I thиnk you are rihgt",
+        &get_test_searchparams().with_max_ngram(1),
+    );
+    assert!(!matches.is_empty());
+    assert_eq!(matches.get(0).unwrap().text, "I");
+    assert_eq!(matches.get(1).unwrap().text, "thиnk");
+    assert_eq!(matches.get(1).unwrap().offset.begin, 2);
+    assert_eq!(matches.get(1).unwrap().offset.end, 8);
+    assert_eq!(model.match_to_str(matches.get(1).unwrap()), "think");
+    assert_eq!(matches.get(2).unwrap().text, "you");
+    assert_eq!(matches.get(3).unwrap().text, "are");
+    assert_eq!(matches.get(4).unwrap().text, "rihgt");
+    assert_eq!(model.match_to_str(matches.get(4).unwrap()), "right");
+}
+
+#[test]
+fn test0708_find_all_matches_sentence_token_index() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["I", "think", "you", "are", "right", "it", "is", "true"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let matches = model.find_all_matches(
+        "I thnk you are rihgt. It is treu",
+        &get_test_searchparams().with_max_ngram(1),
+    );
+    assert_eq!(matches.len(), 8);
+    assert_eq!(matches.get(0).unwrap().text, "I");
+    assert_eq!(matches.get(0).unwrap().sentence_index, 0);
+    assert_eq!(matches.get(0).unwrap().token_index, 0);
+    assert_eq!(matches.get(4).unwrap().text, "rihgt");
+    assert_eq!(matches.get(4).unwrap().sentence_index, 0);
+    assert_eq!(matches.get(4).unwrap().token_index, 4);
+    assert_eq!(matches.get(5).unwrap().text, "It");
+    assert_eq!(matches.get(5).unwrap().sentence_index, 1);
+    assert_eq!(matches.get(5).unwrap().token_index, 0);
+    assert_eq!(matches.get(7).unwrap().text, "treu");
+    assert_eq!(matches.get(7).unwrap().sentence_index, 1);
+    assert_eq!(matches.get(7).unwrap().token_index, 2);
+}
+
+#[test]
+fn test0709_find_all_matches_protected() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["I", "think", "you", "are", "right", "lb", "unclear"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let text = "I thnk you<lb/>are [unclear] rihgt";
+    let matches = model.find_all_matches(
+        text,
+        &get_test_searchparams()
+            .with_max_ngram(1)
+            .with_protected_pattern(regex::Regex::new("<[^>]+>").unwrap())
+            .with_protected_pattern(regex::Regex::new(r"\[[^\]]+\]").unwrap()),
+    );
+    let texts: Vec<&str> = matches.iter().map(|m| m.text).collect();
+    assert_eq!(texts, vec!["I", "thnk", "you", "are", "rihgt"]);
+    assert_eq!(model.match_to_str(matches.get(1).unwrap()), "think");
+    assert_eq!(model.match_to_str(matches.get(4).unwrap()), "right");
+    let last = matches.get(4).unwrap();
+    assert_eq!(&text[last.offset.begin..last.offset.end], "rihgt");
+}
+
+#[test]
+fn test0710_find_all_matches_debug_normalization() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["I", "think", "you", "are", "right"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let matches =
+        model.find_all_matches("you are riçht", &get_test_searchparams().with_max_ngram(1));
+    assert!(matches.iter().all(|m| m.normalized.is_none()));
+    let matches = model.find_all_matches(
+        "you are riçht",
+        &get_test_searchparams()
+            .with_max_ngram(1)
+            .with_debug_normalization(true),
+    );
+    let normalized = matches.get(0).unwrap().normalized.as_ref().unwrap();
+    assert_eq!(model.decompose_normstring(normalized), vec!["y", "o", "u"]);
+    let normalized = matches.get(2).unwrap().normalized.as_ref().unwrap();
+    assert_eq!(
+        model.decompose_normstring(normalized),
+        vec!["r", "i", "<UNK>", "h", "t"]
+    );
+}
+
+#[test]
+fn test0711_find_all_matches_no_consolidation() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["you", "are", "right", "are right"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, Some(2), &VocabParams::default());
+    }
+    model.build();
+    let mut params = get_test_searchparams();
+    params.consolidate_matches = false;
+    let matches = model.find_all_matches("you are rihgt", &params);
+    let texts: Vec<&str> = matches.iter().map(|m| m.text).collect();
+    assert_eq!(texts, vec!["you", "are", "are rihgt", "rihgt"]); //"you are" is redundant
+    let m = matches.iter().find(|m| m.text == "are rihgt").unwrap();
+    assert_eq!(m.offset.begin, 4);
+    assert_eq!(m.offset.end, 13);
+    assert_eq!(model.match_to_str(m), "are right");
+    let m = matches.iter().find(|m| m.text == "rihgt").unwrap();
+    assert_eq!(model.match_to_str(m), "right");
+}
+
+#[test]
+fn test0712_find_all_matches_byte_and_char_offsets() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["I", "think", "you", "are", "right"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    for unicodeoffsets in [false, true] {
+        let mut params = get_test_searchparams().with_max_ngram(1);
+        params.unicodeoffsets = unicodeoffsets;
+        let matches = model.find_all_matches("I thиnk you are rihgt", &params);
+        let m = matches.get(2).unwrap();
+        assert_eq!(m.text, "you");
+        assert_eq!(m.byteoffset, Some(Offset { begin: 9, end: 12 }));
+        assert_eq!(m.charoffset, Some(Offset { begin: 8, end: 11 }));
+        if unicodeoffsets {
+            assert_eq!(m.offset, Offset { begin: 8, end: 11 });
+        } else {
+            assert_eq!(m.offset, Offset { begin: 9, end: 12 });
+        }
+    }
+}
+
+#[test]
+fn test0713_find_matches_streaming() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["I", "think", "you", "are", "right", "it", "is", "true"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let text = "I thnk you\nare rihgt. It is\ntreu\n";
+    let params = get_test_searchparams().with_max_ngram(1);
+    let mut streamed: Vec<(String, usize, usize, usize, String)> = Vec::new();
+    model
+        .find_matches_streaming(std::io::Cursor::new(text), &params, |m| {
+            streamed.push((
+                m.text.to_string(),
+                m.offset.begin,
+                m.offset.end,
+                m.sentence_index,
+                model.match_to_str(&m).to_string(),
+            ))
+        })
+        .expect("streaming");
+    let expected: Vec<(String, usize, usize, usize, String)> = model
+        .find_all_matches(text, &params)
+        .iter()
+        .map(|m| {
+            (
+                m.text.to_string(),
+                m.offset.begin,
+                m.offset.end,
+                m.sentence_index,
+                model.match_to_str(m).to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(streamed.len(), 8);
+    assert_eq!(streamed, expected);
+    assert_eq!(streamed[7].0, "treu");
+    assert_eq!(streamed[7].1, 28);
+    assert_eq!(streamed[7].3, 1);
+    assert_eq!(streamed[7].4, "true");
+}
+
+#[test]
+fn test0714_find_all_matches_phrase() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let params = VocabParams::default().with_vocab_type(VocabType::INDEXED | VocabType::PHRASE);
+    model.add_to_vocabulary("van aylva", None, &params);
+    model.add_to_vocabulary("huis", None, &VocabParams::default());
+    model.build();
+    let vocab_id = model.encoder.get("van aylva").expect("in vocabulary");
+    assert_eq!(
+        model.get_vocab(*vocab_id).expect("vocab item").tokencount,
+        1
+    );
+
+    let results = model.find_variants("van ailva", &get_test_searchparams());
+    assert!(!results.is_empty());
+    assert_eq!(results[0].vocab_id, *vocab_id);
+
+    let matches = model.find_all_matches("huis van ailva", &get_test_searchparams());
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[1].text, "van ailva");
+    assert_eq!(model.match_to_str(&matches[1]), "van aylva");
+}
+
+#[test]
+fn test0715_find_all_matches_crlf() {
     let (alphabet, _alphabet_size) = get_test_alphabet();
-    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 3);
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
     model.add_to_vocabulary("I", Some(2), &VocabParams::default());
     model.add_to_vocabulary("think", Some(2), &VocabParams::default());
-    model.add_to_vocabulary("sink", Some(2), &VocabParams::default()); //language model will decide between think/sink
+    model.add_to_vocabulary("sink", Some(2), &VocabParams::default());
     model.add_to_vocabulary("you", Some(2), &VocabParams::default());
-
     model.add_to_vocabulary("are", Some(2), &VocabParams::default());
     model.add_to_vocabulary("right", Some(2), &VocabParams::default());
     model.add_to_vocabulary("are right", Some(2), &VocabParams::default());
@@ -1413,10 +4348,7 @@ fn test0705_find_all_matches_context_only() {
         },
     );
     model.build();
-    let mut params = get_test_searchparams();
-    params.context_weight = 0.5; //half of the variant model
-    params.lm_weight = 0.0; //disable normal language model
-    let matches = model.find_all_matches("I tink you are rihgt", &params);
+    let matches = model.find_all_matches("I tink you are\r\nrihgt", &get_test_searchparams());
     assert!(!matches.is_empty());
     assert_eq!(matches.get(0).unwrap().text, "I");
     assert_eq!(model.match_to_str(matches.get(0).unwrap()), "I");
@@ -1424,60 +4356,13 @@ fn test0705_find_all_matches_context_only() {
     assert_eq!(model.match_to_str(matches.get(1).unwrap()), "think");
     assert_eq!(matches.get(2).unwrap().text, "you");
     assert_eq!(model.match_to_str(matches.get(2).unwrap()), "you");
-    assert_eq!(matches.get(3).unwrap().text, "are rihgt"); //system opts for the bigram here
+    assert_eq!(matches.get(3).unwrap().text, "are\r\nrihgt"); //system opts for the bigram here
     assert_eq!(model.match_to_str(matches.get(3).unwrap()), "are right");
-}
-
-#[test]
-fn test0706_find_all_matches_unicodeoffsets() {
-    let (alphabet, _alphabet_size) = get_test_alphabet();
-    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
-    let lexicon: &[&str] = &["I", "think", "you", "are", "right"];
-    for text in lexicon.iter() {
-        model.add_to_vocabulary(text, None, &VocabParams::default());
-    }
-    model.build();
-    let matches = model.find_all_matches(
-        "I thиnk you are righт",
-        &get_test_searchparams()
-            .with_max_ngram(1)
-            .with_unicodeoffsets(),
-    );
-    assert!(!matches.is_empty());
-    assert_eq!(matches.get(0).unwrap().text, "I");
-    assert_eq!(matches.get(1).unwrap().text, "thиnk");
-    assert_eq!(matches.get(1).unwrap().offset.begin, 2);
-    assert_eq!(matches.get(1).unwrap().offset.end, 7);
-    assert_eq!(model.match_to_str(matches.get(1).unwrap()), "think");
-    assert_eq!(matches.get(2).unwrap().text, "you");
-    assert_eq!(matches.get(3).unwrap().text, "are");
-    assert_eq!(matches.get(4).unwrap().text, "righт");
-    assert_eq!(model.match_to_str(matches.get(4).unwrap()), "right");
-}
 
-#[test]
-fn test0707_find_all_matches_utf8offsets() {
-    let (alphabet, _alphabet_size) = get_test_alphabet();
-    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
-    let lexicon: &[&str] = &["I", "think", "you", "are", "right"];
-    for text in lexicon.iter() {
-        model.add_to_vocabulary(text, None, &VocabParams::default());
-    }
-    model.build();
-    let matches = model.find_all_matches(
-        "I thиnk you are rihgt",
-        &get_test_searchparams().with_max_ngram(1),
-    );
-    assert!(!matches.is_empty());
-    assert_eq!(matches.get(0).unwrap().text, "I");
-    assert_eq!(matches.get(1).unwrap().text, "thиnk");
-    assert_eq!(matches.get(1).unwrap().offset.begin, 2);
-    assert_eq!(matches.get(1).unwrap().offset.end, 8);
-    assert_eq!(model.match_to_str(matches.get(1).unwrap()), "think");
-    assert_eq!(matches.get(2).unwrap().text, "you");
-    assert_eq!(matches.get(3).unwrap().text, "are");
-    assert_eq!(matches.get(4).unwrap().text, "rihgt");
-    assert_eq!(model.match_to_str(matches.get(4).unwrap()), "right");
+    //a stray carriage return is treated as a newline
+    let matches = model.find_all_matches("I tink you are\rrihgt", &get_test_searchparams());
+    assert_eq!(matches.get(3).unwrap().text, "are\rrihgt");
+    assert_eq!(model.match_to_str(matches.get(3).unwrap()), "are right");
 }
 
 #[test]
@@ -1509,6 +4394,142 @@ fn test0801_expand_variants() {
     );
 }
 
+#[test]
+fn test0802_learn_and_save_variants() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet.clone(), Weights::default(), 1);
+    model.add_to_vocabulary("afgescheid", None, &VocabParams::default());
+    model.build();
+    let input: Vec<String> = vec!["afgescheyd".to_string(), "afgescheid".to_string()];
+    let count = model.learn_variants(&input, &get_test_searchparams(), true, true);
+    assert_eq!(count, 1);
+
+    let filename = std::env::temp_dir().join("analiticcl_test0802.variants.tsv");
+    assert!(model.save_variants(filename.to_str().unwrap()).is_ok());
+    let output = std::fs::read_to_string(&filename).expect("reading saved variants");
+    assert!(output.starts_with("afgescheid\tafgescheyd\t"));
+
+    let mut model2 = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model2.add_to_vocabulary("afgescheid", None, &VocabParams::default());
+    assert!(model2
+        .read_variants(filename.to_str().unwrap(), None, true)
+        .is_ok());
+    std::fs::remove_file(&filename).ok();
+    model2.build();
+    assert!(model2.has("afgescheyd"));
+}
+
+#[test]
+fn test0803_num_threads() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let lexicon: &[&str] = &["I", "think", "you", "are", "right", "afgescheid"];
+    for text in lexicon.iter() {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let mut params = get_test_searchparams().with_max_ngram(1);
+    params.single_thread = false;
+    let expected: Vec<String> = model
+        .find_all_matches("I tink you are rihgt", &params)
+        .iter()
+        .map(|m| model.match_to_str(m).to_string())
+        .collect();
+    model.set_num_threads(2).expect("thread pool");
+    let matches = model.find_all_matches("I tink you are rihgt", &params);
+    let result: Vec<String> = matches
+        .iter()
+        .map(|m| model.match_to_str(m).to_string())
+        .collect();
+    assert_eq!(result, expected);
+    let input: Vec<String> = vec!["afgescheyd".to_string()];
+    assert_eq!(model.learn_variants(&input, &params, true, true), 1);
+}
+
+#[test]
+fn test0804_expand_variants_chain() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    let transparent =
+        VocabParams::default().with_vocab_type(VocabType::INDEXED | VocabType::TRANSPARENT);
+    let separate = model.add_to_vocabulary("separate", Some(100), &VocabParams::default());
+    model.add_variant(separate, "seperate", 0.9, Some(10), &transparent);
+    let seperate = model.encoder["seperate"];
+    //two hops: seperete -> seperate -> separate
+    model.add_variant(seperate, "seperete", 0.8, Some(2), &transparent);
+    let seperete = model.encoder["seperete"];
+    model.build();
+
+    let result = VariantResult {
+        vocab_id: seperete,
+        dist_score: 1.0,
+        freq_score: 2.0,
+        via: None,
+        edits: None,
+        cased: None,
+        explanation: None,
+    };
+    for (policy, freq_score) in [
+        (ExpansionFrequency::Minimum, 2.0),
+        (ExpansionFrequency::Product, 2000.0),
+        (ExpansionFrequency::Target, 100.0),
+    ] {
+        let results = model.expand_variants_with(vec![result.clone()], policy);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].vocab_id, separate);
+        assert_eq!(results[0].via, Some(seperete));
+        assert!((results[0].dist_score - 0.72).abs() < 1e-9);
+        assert_eq!(results[0].freq_score, freq_score);
+    }
+
+    let params = get_test_searchparams()
+        .with_edit_distance(DistanceThreshold::Absolute(1))
+        .with_anagram_distance(DistanceThreshold::Absolute(2));
+    let results = model.find_variants("seperete", &params);
+    assert!(!results.is_empty());
+    assert!(results.iter().all(|result| result.vocab_id == separate));
+    assert!(results.iter().any(|result| result.via == Some(seperete)));
+}
+
+#[test]
+fn test0805_variants_lexicon_column() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for name in ["lexicons/modern.tsv", "lexicons/historical.tsv"] {
+        model
+            .read_vocabulary_from_reader(name, "huis\t10\n".as_bytes(), &VocabParams::default())
+            .expect("reading vocabulary");
+    }
+    //the first row is attributed to a lexicon (by file name), the second to the variant list
+    let variants = "huis\thuys\t0.9\t@historical.tsv\nmuis\tmuys\t0.9\n";
+    model
+        .read_variants_from_reader("errors", variants.as_bytes(), None, true)
+        .expect("reading variants");
+    model.build();
+    assert_eq!(model.lexicon_index("historical.tsv"), Some(1));
+    assert_eq!(model.lexicon_index("lexicons/modern.tsv"), Some(0));
+    assert_eq!(model.lexicon_index("errors"), Some(2));
+    assert_eq!(
+        model.decoder[model.encoder["huys"] as usize].lexindex,
+        1 << 1
+    );
+    assert_eq!(model.decoder[model.encoder["huis"] as usize].lexindex, 0b11);
+    assert_eq!(
+        model.decoder[model.encoder["muys"] as usize].lexindex,
+        1 << 2
+    );
+    assert_eq!(
+        model.decoder[model.encoder["muis"] as usize].lexindex,
+        1 << 2
+    );
+
+    //an unknown lexicon is an error
+    let variants = "huis\thuys\t0.9\t@unknown.tsv\n";
+    assert!(model
+        .read_variants_from_reader("errors2", variants.as_bytes(), None, true)
+        .is_err());
+}
+
 #[test]
 fn test0901_find_all_matches_with_multiple_lexicons() {
     let (alphabet, _alphabet_size) = get_test_alphabet();
@@ -1726,3 +4747,383 @@ fn test0905_find_all_match_context_rules_multitag() {
     assert_eq!(matches.get(4).unwrap().text, "rihgt");
     assert_eq!(model.match_to_str(matches.get(4).unwrap()), "right");
 }
+
+#[test]
+fn test0906_match_tags() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 2);
+    for text in ["I", "think", "sink", "you", "are", "right"] {
+        model.add_to_vocabulary(text, Some(2), &VocabParams::default());
+    }
+    model.build();
+    model
+        .add_contextrule("I; think", 1.1, vec!["testtag", "testtag2"], vec![])
+        .expect("Adding context rule");
+    let mut params = get_test_searchparams();
+    params.lm_weight = 0.0;
+    params.max_ngram = 1;
+    let matches = model.find_all_matches("I tink you are rihgt", &params);
+    assert_eq!(
+        model.match_tags(&matches[0]),
+        vec![("testtag", 0), ("testtag2", 0)]
+    );
+    assert_eq!(
+        model.match_tags(&matches[1]),
+        vec![("testtag", 1), ("testtag2", 1)]
+    );
+    assert!(model.match_tags(&matches[2]).is_empty());
+}
+
+#[test]
+fn test0907_find_all_match_context_rules_regex() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 2);
+    for text in ["I", "think", "sink", "you", "are", "right"] {
+        model.add_to_vocabulary(text, Some(2), &VocabParams::default());
+    }
+    model.build();
+    model
+        .add_contextrule("I; regex:/th.*/", 1.1, vec![], vec![])
+        .expect("Adding context rule"); //bonus for think over sink
+    model
+        .add_contextrule("are; regex:/[A-Z][a-z]+/", 1.0, vec!["testtag"], vec![])
+        .expect("Adding context rule"); //out of vocabulary tokens are tested against the input text
+    assert!(model
+        .add_contextrule("regex:/[/", 1.0, vec![], vec![])
+        .is_err());
+
+    let mut params = get_test_searchparams();
+    params.lm_weight = 0.0; //disable normal language model
+    params.max_ngram = 1;
+    let matches = model.find_all_matches("I tink you are Zqxj", &params);
+    assert_eq!(model.match_to_str(&matches[1]), "think");
+    assert_eq!(matches[3].text, "are");
+    assert_eq!(matches[3].tag, vec!(0));
+    assert_eq!(matches[4].text, "Zqxj");
+    assert_eq!(matches[4].tag, vec!(0));
+    assert_eq!(matches[4].seqnr, vec!(1));
+}
+
+#[test]
+fn test0908_find_all_match_context_rules_global() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 2);
+    for text in ["I", "think", "you", "are", "right"] {
+        model.add_to_vocabulary(text, Some(2), &VocabParams::default());
+    }
+    model.build();
+    model
+        .add_contextrule("you; are", 1.0, vec!["testtag"], vec![])
+        .expect("Adding context rule");
+
+    let mut params = get_test_searchparams();
+    params.lm_weight = 0.0; //disable normal language model
+    params.max_ngram = 1;
+    //the rule spans a hard boundary, so it does not match within a batch
+    let matches = model.find_all_matches("I tink you. are rihgt", &params);
+    assert_eq!(matches[2].text, "you");
+    assert!(matches[2].tag.is_empty());
+    assert!(matches[3].tag.is_empty());
+
+    let params = params.with_global_context_rules(true);
+    let matches = model.find_all_matches("I tink you. are rihgt", &params);
+    assert_eq!(matches[2].text, "you");
+    assert_eq!(matches[2].tag, vec!(0));
+    assert_eq!(matches[2].seqnr, vec!(0));
+    assert_eq!(matches[3].text, "are");
+    assert_eq!(matches[3].tag, vec!(0));
+    assert_eq!(matches[3].seqnr, vec!(1));
+    assert_eq!(matches[3].sentence_index, 1);
+    assert!(matches[4].tag.is_empty());
+}
+
+#[test]
+fn test0716_find_all_matches_oov_policy() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("I", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("think", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("you", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("are", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("right", Some(2), &VocabParams::default());
+    model.build();
+    for max_ngram in [1, 3] {
+        let mut params = get_test_searchparams();
+        params.max_ngram = max_ngram;
+        let matches = model.find_all_matches("I tink qqqqqqqq are right", &params);
+        assert_eq!(matches.len(), 5);
+        assert!(!matches[1].oov);
+        assert_eq!(model.match_output(&matches[1], &params), Some("think"));
+        assert_eq!(matches[2].text, "qqqqqqqq");
+        assert!(matches[2].oov);
+        assert_eq!(matches[2].selected, None);
+        assert_eq!(model.match_output(&matches[2], &params), Some("qqqqqqqq"));
+        let params = params.with_oov_policy(OovPolicy::Empty);
+        assert_eq!(model.match_output(&matches[2], &params), None);
+        let params = params.with_oov_policy("placeholder".parse().unwrap());
+        assert_eq!(model.match_output(&matches[2], &params), Some("<unk>"));
+        let params = params.with_oov_policy("placeholder:???".parse().unwrap());
+        assert_eq!(model.match_output(&matches[2], &params), Some("???"));
+        assert_eq!(model.match_output(&matches[1], &params), Some("think"));
+    }
+}
+
+#[test]
+fn test0717_decode_sequence() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("I", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("sink", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("think", Some(2), &VocabParams::default());
+    model.build();
+    let vocab_id = |text: &str| model.encoder.get(text).copied();
+    let lattice = vec![
+        TokenHypotheses::new("I").with_candidate(vocab_id("I"), 0.0),
+        TokenHypotheses::new("tink")
+            .with_candidate(vocab_id("sink"), 0.2)
+            .with_candidate(vocab_id("think"), 0.2),
+        TokenHypotheses::new("qqq"),
+        TokenHypotheses::new("tink")
+            .with_candidate(vocab_id("sink"), 0.6)
+            .with_candidate(vocab_id("think"), 0.1)
+            .with_candidate(None, 0.9),
+    ];
+    let params = get_test_searchparams();
+    assert_eq!(model.decode_sequence(&lattice, &params), vec![0, 0, 0, 1]);
+    assert!(model.decode_sequence(&[], &params).is_empty());
+
+    //the context rule decides between the equally costly candidates
+    model
+        .add_contextrule("I; think", 1.1, vec![], vec![])
+        .expect("Adding context rule");
+    assert_eq!(model.decode_sequence(&lattice, &params), vec![0, 1, 0, 1]);
+}
+
+#[test]
+fn test0718_find_all_matches_skip_unknown_scripts() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("I", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("think", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("you", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("are", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("right", Some(2), &VocabParams::default());
+    model.build();
+    let text = "I tink Привет, мир you are rihgt";
+    let params = get_test_searchparams();
+    let matches = model.find_all_matches(text, &params);
+    assert!(matches.iter().any(|m| m.text == "мир"));
+
+    let params = params.with_skip_unknown_scripts(true);
+    let matches = model.find_all_matches(text, &params);
+    assert_eq!(
+        matches.iter().map(|m| m.text).collect::<Vec<_>>(),
+        vec!["I", "tink", "you", "are", "rihgt"]
+    );
+    assert_eq!(model.match_to_str(&matches[1]), "think");
+    assert_eq!(&text[matches[2].offset.begin..matches[2].offset.end], "you");
+    assert_eq!(model.match_to_str(&matches[4]), "right");
+}
+
+#[test]
+fn test0719_find_all_matches_nbest() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("I", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("sink", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("think", Some(2), &VocabParams::default());
+    model.build();
+    let params = get_test_searchparams().with_max_ngram(1);
+    let matches = model.find_all_matches("I tink. I tink", &params);
+    assert!(matches.iter().all(|m| m.alternatives.is_empty()));
+
+    let params = params.with_nbest(2);
+    let matches = model.find_all_matches("I tink. I tink", &params);
+    assert_eq!(matches.len(), 4);
+    for i in [0, 2] {
+        assert_eq!(matches[i].text, "I");
+        let alternatives = &matches[i].alternatives;
+        assert_eq!(alternatives.len(), 2);
+        assert_eq!(alternatives[0].text, "I think");
+        assert_eq!(alternatives[1].text, "I sink");
+        assert!(alternatives[0].score > alternatives[1].score);
+        assert!(alternatives[0].lm_perplexity.is_none());
+    }
+    assert!(matches[1].alternatives.is_empty());
+    assert_eq!(model.match_to_str(&matches[1]), "think");
+}
+
+#[test]
+fn test0720_correct_text() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("I", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("think", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("you", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("are", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("right", Some(2), &VocabParams::default());
+    model.build();
+    let params = get_test_searchparams().with_unicodeoffsets();
+    assert_eq!(
+        model.correct_text("“I tink,  you are rihgt!”\n", &params),
+        "“I think,  you are right!”\n"
+    );
+    assert_eq!(model.correct_text("", &params), "");
+    let params = params.with_oov_policy(OovPolicy::Placeholder("?".to_string()));
+    assert_eq!(
+        model.correct_text("I tink qqqqqqqq are rihgt", &params),
+        "I think ? are right"
+    );
+}
+
+#[test]
+fn test0721_tiny_input_single_char() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["a", "b", "bbb"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    for threshold in [
+        DistanceThreshold::Ratio(0.25),
+        DistanceThreshold::RatioWithLimit(0.25, 2),
+        DistanceThreshold::Absolute(2),
+    ] {
+        let params = get_test_searchparams()
+            .with_edit_distance(threshold)
+            .with_anagram_distance(threshold);
+        let results = model.find_variants("a", &params);
+        assert!(!results.is_empty(), "exact match for {:?}", threshold);
+        assert_eq!(model.decoder[results[0].vocab_id as usize].text, "a");
+        assert_eq!(results[0].dist_score, 1.0);
+        let results = model.find_variants("c", &params);
+        let texts: Vec<&str> = results
+            .iter()
+            .map(|result| model.decoder[result.vocab_id as usize].text.as_str())
+            .collect();
+        assert!(texts.contains(&"a"), "distance-1 match for {:?}", threshold);
+        assert!(texts.contains(&"b"), "distance-1 match for {:?}", threshold);
+        assert!(
+            !texts.contains(&"bbb"),
+            "no distance-3 match for {:?}",
+            threshold
+        );
+    }
+    //an explicit absolute threshold of zero still only allows exact matches
+    let params = get_test_searchparams()
+        .with_edit_distance(DistanceThreshold::Absolute(0))
+        .with_anagram_distance(DistanceThreshold::Absolute(0));
+    assert!(model.find_variants("c", &params).is_empty());
+    assert_eq!(model.find_variants("a", &params).len(), 1);
+}
+
+#[test]
+fn test0722_tiny_input_two_chars() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["ab", "ac", "xyz"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    let params = get_test_searchparams()
+        .with_edit_distance(DistanceThreshold::Ratio(0.3))
+        .with_anagram_distance(DistanceThreshold::Ratio(0.3));
+    let results = model.find_variants("ab", &params);
+    assert_eq!(model.decoder[results[0].vocab_id as usize].text, "ab");
+    assert_eq!(results[0].dist_score, 1.0);
+    let results = model.find_variants("ad", &params);
+    let texts: Vec<&str> = results
+        .iter()
+        .map(|result| model.decoder[result.vocab_id as usize].text.as_str())
+        .collect();
+    assert!(texts.contains(&"ab"));
+    assert!(texts.contains(&"ac"));
+    assert!(!texts.contains(&"xyz"));
+}
+
+#[test]
+fn test0722_tiny_input_ratio_floor() {
+    //the floor at distance 1 only applies to inputs of one or two characters
+    assert_eq!(DistanceThreshold::Ratio(0.2).effective(1, 3), 1);
+    assert_eq!(DistanceThreshold::Ratio(0.2).effective(2, 3), 1);
+    assert_eq!(DistanceThreshold::Ratio(0.2).effective(4, 3), 0);
+    assert_eq!(DistanceThreshold::RatioWithLimit(0.2, 2).effective(4, 3), 0);
+    assert_eq!(DistanceThreshold::Ratio(0.2).effective(5, 3), 1);
+    assert_eq!(DistanceThreshold::Ratio(0.0).effective(1, 3), 0);
+}
+
+#[test]
+fn test0723_eval_hook() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    for text in ["huis", "huls", "hui", "huiss"] {
+        model.add_to_vocabulary(text, None, &VocabParams::default());
+    }
+    model.build();
+    //input, candidates, whether all candidates are explained
+    type Dumped = Vec<(String, Vec<VocabId>, bool)>;
+    let dumped: Arc<Mutex<Dumped>> = Arc::new(Mutex::new(Vec::new()));
+    let sink = dumped.clone();
+    model.set_eval_hook(Some(Box::new(move |eval: &EvalCandidates| {
+        sink.lock().unwrap().push((
+            eval.input.to_string(),
+            eval.candidates
+                .iter()
+                .map(|result| result.vocab_id)
+                .collect(),
+            eval.candidates
+                .iter()
+                .all(|result| result.explanation.is_some()),
+        ));
+    })));
+    let mut params = get_test_searchparams();
+    params.max_matches = 1;
+    let results = model.find_variants("huis", &params);
+    assert_eq!(results.len(), 1);
+    assert!(results[0].explanation.is_none());
+    let dumped = dumped.lock().unwrap();
+    assert_eq!(dumped.len(), 1);
+    let (input, candidates, explained) = &dumped[0];
+    assert_eq!(input, "huis");
+    assert_eq!(candidates.len(), 4);
+    assert_eq!(candidates[0], results[0].vocab_id);
+    assert!(explained);
+}
+
+#[test]
+fn test0724_sentence_confidence() {
+    let (alphabet, _alphabet_size) = get_test_alphabet();
+    let mut model = VariantModel::new_with_alphabet(alphabet, Weights::default(), 1);
+    model.add_to_vocabulary("I", Some(2), &VocabParams::default());
+    model.add_to_vocabulary("think", Some(2), &VocabParams::default());
+    model.build();
+    let params = get_test_searchparams().with_max_ngram(1);
+    let matches = model.find_all_matches("I think. I tink. I qqqqqqq", &params);
+    assert!(matches.iter().all(|m| m.confidence.is_none()));
+
+    let params = params.with_sentence_confidence(true);
+    let matches = model.find_all_matches("I think. I tink. I qqqqqqq", &params);
+    assert_eq!(matches.len(), 6);
+    let scores: Vec<f64> = [0, 2, 4]
+        .iter()
+        .map(|i| matches[*i].confidence.as_ref().expect("confidence").score)
+        .collect();
+    assert!(matches
+        .iter()
+        .enumerate()
+        .all(|(i, m)| i % 2 == 0 || m.confidence.is_none()));
+    assert!((scores[0] - 1.0).abs() < 1e-9, "exact matches only");
+    assert!(scores[1] < 1.0 && scores[1] > 0.5, "one variant");
+    assert!((scores[2] - 0.5).abs() < 1e-9, "one oov token out of two");
+    assert!(matches[0]
+        .confidence
+        .as_ref()
+        .unwrap()
+        .lm_perplexity
+        .is_none());
+
+    //also when the sequence is decoded
+    let matches = model.find_all_matches("I tink", &params.with_nbest(2));
+    let confidence = matches[0].confidence.as_ref().expect("confidence");
+    assert!(confidence.score < 1.0 && confidence.score > 0.5);
+}